@@ -0,0 +1,19 @@
+/**
+ * The server is ESM (`"type": "module"`, NodeNext), so tests run through ts-jest's ESM preset.
+ * Jest needs `--experimental-vm-modules` for that, which `npm test` passes.
+ *
+ * @type {import('ts-jest').JestConfigWithTsJest}
+ */
+export default {
+  preset: 'ts-jest/presets/default-esm',
+  testEnvironment: 'node',
+  roots: ['<rootDir>/src'],
+  testMatch: ['**/__tests__/**/*.spec.ts'],
+  // Sources import each other with the `.js` extension they have once compiled
+  moduleNameMapper: {
+    '^(\\.{1,2}/.*)\\.js$': '$1',
+  },
+  transform: {
+    '^.+\\.ts$': ['ts-jest', { useESM: true }],
+  },
+};
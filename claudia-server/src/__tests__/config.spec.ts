@@ -0,0 +1,40 @@
+import { configFromEnv } from '../config.js';
+import { ClaudiaServer } from '../server.js';
+
+describe('configFromEnv', () => {
+  it('reads CLAUDIA_ variables with the field type', () => {
+    const config = configFromEnv({
+      CLAUDIA_MAX_CONCURRENT_SESSIONS: '5',
+      CLAUDIA_KILL_ON_DROP: 'false',
+      CLAUDIA_CORS_ORIGIN: 'https://a.example, https://b.example',
+      CLAUDIA_MODEL_FALLBACK: '{"opus":"sonnet"}',
+    });
+
+    expect(config).toEqual({
+      max_concurrent_sessions: 5,
+      kill_on_drop: false,
+      cors_origin: ['https://a.example', 'https://b.example'],
+      model_fallback: { opus: 'sonnet' },
+    });
+  });
+
+  it('overrides the defaults and is overridden by explicit options', () => {
+    const fromEnv = configFromEnv({ CLAUDIA_MAX_CONCURRENT_SESSIONS: '3', CLAUDIA_PORT: '4100' });
+
+    expect(new ClaudiaServer(fromEnv).getConfig().max_concurrent_sessions).toBe(3);
+    expect(new ClaudiaServer({ ...fromEnv, port: 4200 }).getConfig().port).toBe(4200);
+  });
+
+  it('prefers prefixed variables over the legacy names', () => {
+    expect(configFromEnv({ PORT: '4000', CLAUDIA_PORT: '4001', CLAUDE_HOME: '/h' })).toEqual({
+      port: 4001,
+      claude_home_dir: '/h',
+    });
+    expect(configFromEnv({ CLAUDIA_TEE_OUTPUT: '1' })).toEqual({ tee_output_to_stdout: true });
+  });
+
+  it('names the variable when a value does not parse', () => {
+    expect(() => configFromEnv({ CLAUDIA_MAX_BODY_BYTES: 'lots' })).toThrow('CLAUDIA_MAX_BODY_BYTES');
+    expect(() => configFromEnv({ CLAUDIA_SKIP_PERMISSIONS: 'maybe' })).toThrow('CLAUDIA_SKIP_PERMISSIONS');
+  });
+});
@@ -0,0 +1,2659 @@
+import { execFile } from 'child_process';
+import { createServer, get as httpGet } from 'http';
+import { promises as fs } from 'fs';
+import { connect, type AddressInfo } from 'net';
+import { join } from 'path';
+import { promisify } from 'util';
+
+import { ClaudiaServer } from '../server.js';
+import {
+  connectWebSocket,
+  createFakeClaude,
+  createTempProject,
+  fetchUntil,
+  postJson,
+  startTestServer,
+  waitFor,
+} from '../testing/helpers.js';
+
+describe('ClaudiaServer', () => {
+  describe('multiple listen addresses', () => {
+    it('serves /api/status/health on every bound address', async () => {
+      const server = new ClaudiaServer({
+        listen: [
+          { host: '127.0.0.1', port: 0 },
+          { host: '127.0.0.1', port: 0 },
+        ],
+      });
+      await server.start();
+
+      try {
+        const addresses = server.getAddresses();
+        expect(addresses).toHaveLength(2);
+        expect(addresses[0].port).not.toBe(addresses[1].port);
+
+        for (const address of addresses) {
+          const res = await fetch(`http://${address.host}:${address.port}/api/status/health`);
+          expect(res.status).toBe(200);
+          const body = (await res.json()) as any;
+          expect(body.data.status).toBe('healthy');
+        }
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('serves the API and /ws on separate addresses when ws_listen is set', async () => {
+      const { server, baseUrl, wsUrl } = await startTestServer({ ws_listen: [{ host: '127.0.0.1', port: 0 }] });
+
+      try {
+        const [api] = server.getAddresses();
+        const [ws] = server.getWebSocketAddresses();
+        expect(ws.port).not.toBe(api.port);
+
+        expect((await fetch(`${baseUrl}/api/status/health`)).status).toBe(200);
+        const apiOnWs = await fetch(`http://${ws.host}:${ws.port}/api/status/health`);
+        expect(apiOnWs.status).toBe(404);
+        expect(((await apiOnWs.json()) as any).code).toBe('NOT_FOUND');
+
+        const { ws: client, messages } = await connectWebSocket(wsUrl);
+        await waitFor(() => messages.some((message) => message.data?.status === 'connected'));
+        client.close();
+        await expect(connectWebSocket(`ws://${api.host}:${api.port}/ws`)).rejects.toThrow();
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('fails startup naming the address that could not be bound', async () => {
+      const blocker = createServer();
+      await new Promise<void>((resolve) => blocker.listen(0, '127.0.0.1', resolve));
+      const takenPort = (blocker.address() as AddressInfo).port;
+
+      const server = new ClaudiaServer({
+        listen: [
+          { host: '127.0.0.1', port: 0 },
+          { host: '127.0.0.1', port: takenPort },
+        ],
+      });
+
+      try {
+        await expect(server.start()).rejects.toThrow(`127.0.0.1:${takenPort}`);
+      } finally {
+        await new Promise<void>((resolve) => blocker.close(() => resolve()));
+      }
+    });
+  });
+
+  describe('server-wide events', () => {
+    it('delivers lifecycle events for every session to subscribe_all clients', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"done"}'`);
+      const project = await createTempProject();
+      const { server, baseUrl, wsUrl } = await startTestServer({ claude_binary_path: claude });
+      const { ws, messages } = await connectWebSocket(wsUrl);
+
+      try {
+        ws.send(JSON.stringify({ type: 'subscribe_all' }));
+        await waitFor(() => messages.some((m) => m.data?.status === 'subscribed_all'));
+
+        const request = { project_path: project, prompt: 'hi', model: 'sonnet' };
+        const first = await postJson(`${baseUrl}/api/claude/execute`, request);
+        const second = await postJson(`${baseUrl}/api/claude/execute`, request);
+        const ids = [first.body.data.session_id, second.body.data.session_id];
+
+        const events = () => messages.filter((m) => m.type === 'session_event').map((m) => m.data);
+        await waitFor(() => events().filter((e) => e.event === 'completed').length === 2);
+
+        for (const id of ids) {
+          const lifecycle = events().filter((e) => e.session_id === id).map((e) => e.event);
+          expect(lifecycle).toEqual(['started', 'completed']);
+        }
+      } finally {
+        ws.close();
+        await server.stop();
+      }
+    });
+  });
+
+  describe('session result', () => {
+    it('returns the parsed result of a completed session', async () => {
+      const claude = await createFakeClaude(
+        `echo '{"type":"assistant","message":{"content":"working"}}'\n` +
+          `echo '{"type":"result","result":"All done","is_error":false,"total_cost_usd":0.02,"usage":{"input_tokens":10,"output_tokens":5}}'`
+      );
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+
+        const { status, body } = await fetchUntil(
+          `${baseUrl}/api/claude/sessions/${id}/result`,
+          (code) => code !== 409
+        );
+
+        expect(status).toBe(200);
+        expect(body.data.text).toBe('All done');
+        expect(body.data.parsed).toBe(true);
+        expect(body.data.cost_usd).toBe(0.02);
+        expect(body.data.usage.output_tokens).toBe(5);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('returns 409 while running and falls back to output lines', async () => {
+      const claude = await createFakeClaude(`echo 'plain text answer'\nsleep 1`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const url = `${baseUrl}/api/claude/sessions/${started.body.data.session_id}/result`;
+
+        expect((await fetch(url)).status).toBe(409);
+
+        const { body } = await fetchUntil(url, (code) => code === 200);
+        expect(body.data.parsed).toBe(false);
+        expect(body.data.text).toBe('plain text answer');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('shutdown', () => {
+    it('sends WebSocket clients a close frame with a reason', async () => {
+      const { server, wsUrl } = await startTestServer();
+      const { ws } = await connectWebSocket(wsUrl);
+
+      const closed = new Promise<{ code: number; reason: string }>((resolve) => {
+        ws.once('close', (code, reason) => resolve({ code, reason: reason.toString() }));
+      });
+
+      await server.stop();
+
+      expect(await closed).toEqual({ code: 1001, reason: 'server shutting down' });
+    });
+  });
+
+  describe('working directory', () => {
+    it('runs Claude in working_dir while keeping project_path', async () => {
+      const claude = await createFakeClaude(`echo "{\\"type\\":\\"result\\",\\"result\\":\\"$(pwd)\\"}"`);
+      const project = await createTempProject();
+      const workingDir = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, working_dir: workingDir, prompt: 'hi', model: 'sonnet',
+        });
+        expect(started.status).toBe(200);
+        const id = started.body.data.session_id;
+
+        const { body } = await fetchUntil(
+          `${baseUrl}/api/claude/sessions/${id}/result`,
+          (code) => code === 200
+        );
+        expect(body.data.text).toBe(await fs.realpath(workingDir));
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('rejects a working_dir that is not a directory', async () => {
+      const claude = await createFakeClaude('true');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, working_dir: join(project, 'missing'), prompt: 'hi', model: 'sonnet',
+        });
+        expect(res.status).toBe(400);
+        expect(res.body.code).toBe('VALIDATION_ERROR');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('output encoding', () => {
+    it('tags invalid UTF-8 lines and keeps their raw bytes', async () => {
+      const claude = await createFakeClaude(`printf 'caf\\303\\251\\n'\nprintf 'caf\\351\\n'`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+
+        const res = await fetch(`${baseUrl}/api/claude/sessions/${id}/output`);
+        const { lines } = ((await res.json()) as any).data;
+
+        expect(lines[0]).toEqual({ stream: 'stdout', content: 'café', timestamp: expect.any(String) });
+        expect(lines[1].invalid_utf8).toBe(true);
+        expect(lines[1].content).toBe('caf\uFFFD');
+        expect(Buffer.from(lines[1].raw_base64, 'base64')).toEqual(Buffer.from([0x63, 0x61, 0x66, 0xe9]));
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('preconditions', () => {
+    it('starts the session when the precondition passes', async () => {
+      const claude = await createFakeClaude('true');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+          precondition: { command: 'test -d .', expect_exit_code: 0 },
+        });
+        expect(res.status).toBe(200);
+        expect(res.body.data.session_id).toBeDefined();
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('returns 412 with the command output when the precondition fails', async () => {
+      const claude = await createFakeClaude('true');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+          precondition: { command: 'echo dirty tree; exit 3' },
+        });
+        expect(res.status).toBe(412);
+        expect(res.body.code).toBe('PRECONDITION_FAILED');
+        expect(res.body.details.exit_code).toBe(3);
+        expect(res.body.details.output).toContain('dirty tree');
+
+        const running = await fetch(`${baseUrl}/api/claude/sessions/running`);
+        expect(((await running.json()) as any).data).toHaveLength(0);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('ping', () => {
+    it('answers quickly with pong', async () => {
+      const { server, baseUrl } = await startTestServer();
+
+      try {
+        const started = Date.now();
+        const res = await fetch(`${baseUrl}/api/status/ping`);
+        const elapsed = Date.now() - started;
+
+        expect(res.status).toBe(200);
+        expect(((await res.json()) as any).data).toEqual({ pong: true });
+        expect(elapsed).toBeLessThan(1000);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('request limits', () => {
+    it('rejects a body over the configured limit', async () => {
+      const { server, baseUrl } = await startTestServer({ max_body_bytes: 1024 });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: '/tmp', prompt: 'x'.repeat(4096), model: 'sonnet',
+        });
+        expect(res.status).toBe(413);
+        expect(res.body.code).toBe('PAYLOAD_TOO_LARGE');
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('drops a connection that stalls in the middle of a WebSocket upgrade', async () => {
+      const { server, baseUrl, wsUrl } = await startTestServer({
+        request_timeout_ms: 0,
+        handshake_timeout_ms: 200,
+      });
+      const { hostname, port } = new URL(baseUrl);
+      const socket = connect(Number(port), hostname);
+      const { ws } = await connectWebSocket(wsUrl);
+
+      try {
+        const closed = new Promise<number>((resolve) => socket.on('close', () => resolve(Date.now())));
+        const sentAt = Date.now();
+        socket.write(
+          'GET /ws HTTP/1.1\r\n' +
+            `Host: ${hostname}\r\n` +
+            'Upgrade: websocket\r\n'
+        );
+
+        expect((await closed) - sentAt).toBeLessThan(2000);
+        // Upgrades that completed are left alone
+        await new Promise((resolve) => setTimeout(resolve, 300));
+        expect(ws.readyState).toBe(ws.OPEN);
+      } finally {
+        socket.destroy();
+        ws.close();
+        await server.stop();
+      }
+    });
+
+    it('times out a request whose body never arrives', async () => {
+      const { server, baseUrl } = await startTestServer({ request_timeout_ms: 200 });
+      const { hostname, port } = new URL(baseUrl);
+      const socket = connect(Number(port), hostname);
+
+      try {
+        const response = new Promise<string>((resolve) => {
+          let received = '';
+          socket.on('data', (chunk) => (received += chunk.toString()));
+          socket.on('close', () => resolve(received));
+        });
+
+        socket.write(
+          'POST /api/claude/execute HTTP/1.1\r\n' +
+            `Host: ${hostname}\r\n` +
+            'Content-Type: application/json\r\n' +
+            'Content-Length: 100\r\n\r\n{"prompt":'
+        );
+
+        expect(await response).toMatch(/^HTTP\/1\.1 408/);
+      } finally {
+        socket.destroy();
+        await server.stop();
+      }
+    });
+  });
+
+  describe('session settings', () => {
+    it('passes a temporary settings.json and removes it when the session ends', async () => {
+      const claude = await createFakeClaude(
+        `while [ "$#" -gt 0 ]; do [ "$1" = "--settings" ] && file="$2"; shift; done\n` +
+          `cat "$file" >&2\n` +
+          `echo "{\\"type\\":\\"result\\",\\"result\\":\\"$file\\"}"`
+      );
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+      const settings = { permissions: { deny: ['Bash(rm:*)'] } };
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', settings,
+        });
+        const id = started.body.data.session_id;
+
+        const { body } = await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+        const settingsFile = body.data.text;
+        expect(settingsFile).toMatch(/settings\.json$/);
+
+        const res = await fetch(`${baseUrl}/api/claude/sessions/${id}/output`);
+        const { lines } = ((await res.json()) as any).data;
+        const stderr = lines.filter((l: any) => l.stream === 'stderr').map((l: any) => l.content).join('');
+        expect(JSON.parse(stderr)).toEqual(settings);
+
+        await waitFor(async () => !(await fs.access(settingsFile).then(() => true, () => false)));
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('rejects settings that are not an object', async () => {
+      const claude = await createFakeClaude('true');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', settings: ['nope'],
+        });
+        expect(res.status).toBe(400);
+        expect(res.body.code).toBe('VALIDATION_ERROR');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('persistence', () => {
+    it('writes session records to the data directory', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"saved"}'`);
+      const project = await createTempProject();
+      const dataDir = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, data_dir: dataDir });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        const file = join(dataDir, 'sessions', `${id}.json`);
+
+        await waitFor(async () => {
+          const saved = await fs.readFile(file, 'utf8').catch(() => '{}');
+          return JSON.parse(saved).status === 'completed';
+        });
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('appends every output line to disk in batches by the time the session completes', async () => {
+      const claude = await createFakeClaude(
+        `i=1; while [ $i -le 250 ]; do echo "{\\"type\\":\\"assistant\\",\\"n\\":$i}"; i=$((i+1)); done`
+      );
+      const project = await createTempProject();
+      const dataDir = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        data_dir: dataDir,
+        output_flush_batch_lines: 64,
+        output_flush_interval_ms: 60000,
+      });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        const readLines = async () => {
+          const saved = await fs.readFile(join(dataDir, 'sessions', `${id}.output.jsonl`), 'utf8').catch(() => '');
+          return saved.split('\n').filter(Boolean).map((line) => JSON.parse(line));
+        };
+
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+        await waitFor(async () => (await readLines()).length >= 250, 2000);
+
+        const lines = await readLines();
+        expect(lines.map((line) => JSON.parse(line.content).n)).toEqual(
+          Array.from({ length: 250 }, (_, i) => i + 1)
+        );
+        expect(lines.every((line) => line.stream === 'stdout' && typeof line.timestamp === 'string')).toBe(true);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('reads output lines that scrolled out of memory back from disk', async () => {
+      const claude = await createFakeClaude(
+        `i=1; while [ $i -le 20 ]; do echo "{\\"type\\":\\"assistant\\",\\"n\\":$i}"; i=$((i+1)); done`
+      );
+      const project = await createTempProject();
+      const dataDir = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        data_dir: dataDir,
+        max_output_lines: 5,
+        disk_backed_output: true,
+      });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+
+        const res = await fetch(`${baseUrl}/api/claude/sessions/${id}/output`);
+        const body = await res.json();
+        expect(res.status).toBe(200);
+        expect(body.data.lines.map((line: { content: string }) => JSON.parse(line.content).n)).toEqual(
+          Array.from({ length: 20 }, (_, i) => i + 1)
+        );
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('keeps sessions running and reports degraded health when writes fail', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"still fine"}'`);
+      const project = await createTempProject();
+      // A regular file cannot hold the sessions directory, so every write fails (even as root)
+      const dataDir = join(await createTempProject(), 'not-a-directory');
+      await fs.writeFile(dataDir, '');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, data_dir: dataDir });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        expect(started.status).toBe(200);
+
+        const { body } = await fetchUntil(
+          `${baseUrl}/api/claude/sessions/${started.body.data.session_id}/result`,
+          (code) => code === 200
+        );
+        expect(body.data.status).toBe('completed');
+        expect(body.data.text).toBe('still fine');
+
+        const health = (await (await fetch(`${baseUrl}/api/status/health`)).json()) as any;
+        expect(health.data.status).toBe('degraded');
+        expect(health.data.persistence.degraded).toBe(true);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('nests a session\'s files under its output_subdir', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"nested"}'`);
+      const project = await createTempProject();
+      const dataDir = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, data_dir: dataDir });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', output_subdir: 'team-a/nightly',
+        });
+        expect(started.status).toBe(200);
+        const id = started.body.data.session_id;
+        const dir = join(dataDir, 'sessions', 'team-a', 'nightly');
+
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+        await waitFor(async () => {
+          const saved = await fs.readFile(join(dir, `${id}.json`), 'utf8').catch(() => '{}');
+          return JSON.parse(saved).status === 'completed';
+        });
+        const output = await fs.readFile(join(dir, `${id}.output.jsonl`), 'utf8');
+        expect(output).toContain('nested');
+        await expect(fs.access(join(dataDir, 'sessions', `${id}.json`))).rejects.toThrow();
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('rejects an output_subdir that escapes the sessions directory', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"never"}'`);
+      const project = await createTempProject();
+      const dataDir = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, data_dir: dataDir });
+
+      try {
+        for (const outputSubdir of ['../escape', 'a/../../b', '/tmp/abs', '', 42]) {
+          const res = await postJson(`${baseUrl}/api/claude/execute`, {
+            project_path: project, prompt: 'hi', model: 'sonnet', output_subdir: outputSubdir,
+          });
+          expect(res.status).toBe(400);
+          expect(res.body.code).toBe('VALIDATION_ERROR');
+        }
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('multi-session subscriptions', () => {
+    it('streams output from several sessions over one WebSocket', async () => {
+      const claude = await createFakeClaude(
+        `for i in 1 2 3; do echo "{\\"type\\":\\"assistant\\",\\"n\\":$i}"; sleep 0.1; done`
+      );
+      const project = await createTempProject();
+      const { server, baseUrl, wsUrl } = await startTestServer({ claude_binary_path: claude });
+      const { ws, messages } = await connectWebSocket(wsUrl);
+
+      try {
+        const request = { project_path: project, prompt: 'hi', model: 'sonnet' };
+        const first = (await postJson(`${baseUrl}/api/claude/execute`, request)).body.data.session_id;
+        const second = (await postJson(`${baseUrl}/api/claude/execute`, request)).body.data.session_id;
+
+        ws.send(JSON.stringify({ type: 'subscribe_many', session_ids: [first, second] }));
+        await waitFor(() => messages.some((m) => m.data?.subscriptions?.length === 2));
+
+        const streamed = () => messages.filter((m) => m.type === 'claude_stream' && m.data.type === 'assistant');
+        await waitFor(() => new Set(streamed().map((m) => m.session_id)).size === 2);
+
+        ws.send(JSON.stringify({ type: 'unsubscribe_many', session_ids: [first] }));
+        await waitFor(() => messages.some((m) => m.data?.status === 'unsubscribed'));
+        const last = messages.filter((m) => m.type === 'status').pop();
+        expect(last?.data.subscriptions).toEqual([second]);
+      } finally {
+        ws.close();
+        await server.stop();
+      }
+    });
+  });
+
+  describe('process groups', () => {
+    const unixIt = process.platform === 'win32' ? it.skip : it;
+
+    // Zombies still answer kill(pid, 0), so look at the process state where /proc exists
+    async function isAlive(pid: number): Promise<boolean> {
+      try {
+        const stat = await fs.readFile(`/proc/${pid}/stat`, 'utf8');
+        return stat.slice(stat.lastIndexOf(')') + 2)[0] !== 'Z';
+      } catch {
+        try {
+          process.kill(pid, 0);
+          return true;
+        } catch {
+          return false;
+        }
+      }
+    }
+
+    unixIt('kills subprocesses spawned by Claude when the session is cancelled', async () => {
+      const claude = await createFakeClaude(`sleep 30 &\necho $! > child.pid\nwait`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        const pidFile = join(project, 'child.pid');
+        await waitFor(async () => (await fs.readFile(pidFile, 'utf8').catch(() => '')).trim() !== '');
+        const childPid = parseInt(await fs.readFile(pidFile, 'utf8'), 10);
+
+        const info = (await (await fetch(`${baseUrl}/api/claude/sessions/${id}`)).json()) as any;
+        const claudePid = info.data.pid;
+
+        await postJson(`${baseUrl}/api/claude/cancel/${id}`, {});
+
+        await waitFor(async () => !(await isAlive(claudePid)) && !(await isAlive(childPid)));
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('output download', () => {
+    it('serves the whole log and honours byte ranges', async () => {
+      const claude = await createFakeClaude(`echo 'first line'\necho 'second line'`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+        const url = `${baseUrl}/api/claude/sessions/${id}/output/download`;
+
+        const full = await fetch(url);
+        expect(full.status).toBe(200);
+        expect(full.headers.get('accept-ranges')).toBe('bytes');
+        expect(await full.text()).toBe('first line\nsecond line\n');
+
+        const partial = await fetch(url, { headers: { Range: 'bytes=6-15' } });
+        expect(partial.status).toBe(206);
+        expect(partial.headers.get('content-range')).toBe('bytes 6-15/23');
+        expect(await partial.text()).toBe('line\nsecon');
+
+        const beyond = await fetch(url, { headers: { Range: 'bytes=100-' } });
+        expect(beyond.status).toBe(416);
+        expect(beyond.headers.get('content-range')).toBe('bytes */23');
+
+        const several = await fetch(url, { headers: { Range: 'bytes=0-4,6-9' } });
+        expect(several.status).toBe(200);
+        expect(several.headers.get('content-length')).toBe('23');
+        expect(await several.text()).toBe('first line\nsecond line\n');
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('serves the persisted log, including lines beyond the buffer', async () => {
+      const claude = await createFakeClaude(`i=1; while [ $i -le 20 ]; do echo "line $i"; i=$((i+1)); done`);
+      const project = await createTempProject();
+      const dataDir = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        data_dir: dataDir,
+        max_output_lines: 5,
+      });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+        const url = `${baseUrl}/api/claude/sessions/${id}/output/download`;
+        const expected = Array.from({ length: 20 }, (_, i) => `line ${i + 1}\n`).join('');
+
+        const full = await fetch(url);
+        expect(full.status).toBe(200);
+        expect(await full.text()).toBe(expected);
+
+        const partial = await fetch(url, { headers: { Range: 'bytes=0-6' } });
+        expect(partial.status).toBe(206);
+        expect(partial.headers.get('content-range')).toBe(`bytes 0-6/${expected.length}`);
+        expect(await partial.text()).toBe('line 1\n');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('estimate', () => {
+    it('estimates tokens and cost without running Claude', async () => {
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: '/nonexistent/claude' });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/estimate`, {
+          prompt: 'The quick brown fox jumps over the lazy dog.',
+          model: 'claude-sonnet-4-5',
+          expected_output_tokens: 1000,
+        });
+
+        expect(res.status).toBe(200);
+        // The sentence is about ten tokens with Claude's tokenizer
+        expect(res.body.data.input_tokens).toBeGreaterThanOrEqual(8);
+        expect(res.body.data.input_tokens).toBeLessThanOrEqual(16);
+        expect(res.body.data.pricing).toEqual({ input_per_mtok: 3, output_per_mtok: 15 });
+        expect(res.body.data.estimated_cost_usd).toBeCloseTo(
+          (res.body.data.input_tokens * 3 + 1000 * 15) / 1_000_000
+        );
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('counts context files and reports null cost for unknown models', async () => {
+      const project = await createTempProject();
+      await fs.writeFile(join(project, 'notes.md'), 'x'.repeat(350));
+      const { server, baseUrl } = await startTestServer();
+
+      try {
+        const res = await postJson(`${baseUrl}/api/estimate`, {
+          prompt: '', model: 'mystery-model', project_path: project, context_files: ['notes.md'],
+        });
+
+        expect(res.body.data.context_files[0].tokens).toBe(100);
+        expect(res.body.data.input_tokens).toBe(100);
+        expect(res.body.data.estimated_cost_usd).toBeNull();
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('session watchdogs', () => {
+    it('cancels sooner after update_session lowers the timeout', async () => {
+      const claude = await createFakeClaude('sleep 30');
+      const project = await createTempProject();
+      const { server, baseUrl, wsUrl } = await startTestServer({ claude_binary_path: claude });
+      const { ws, messages } = await connectWebSocket(wsUrl);
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', timeout_ms: 60000,
+        });
+        const id = started.body.data.session_id;
+
+        ws.send(JSON.stringify({ type: 'update_session', session_id: id, data: { timeout_ms: 300 } }));
+        await waitFor(() => messages.some((m) => m.data?.status === 'session_updated'));
+
+        const { body } = await fetchUntil(
+          `${baseUrl}/api/claude/sessions/${id}/result`,
+          (code) => code === 200,
+          5000
+        );
+        expect(body.data.status).toBe('cancelled');
+        expect(body.data.terminated_by).toBe('timeout');
+      } finally {
+        ws.close();
+        await server.stop();
+      }
+    });
+
+    it('kills a process that closes stdout but keeps running', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"assistant","message":{"content":"bye"}}'\nexec 1>&-\nsleep 30`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, stdout_eof_grace_ms: 300 });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+
+        const { body } = await fetchUntil(
+          `${baseUrl}/api/claude/sessions/${id}/result`,
+          (code) => code === 200,
+          5000
+        );
+        expect(body.data.status).toBe('cancelled');
+        expect(body.data.terminated_by).toBe('stdout_closed');
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('rejects an invalid abort pattern and leaves the session running', async () => {
+      const claude = await createFakeClaude('sleep 30');
+      const project = await createTempProject();
+      const { server, baseUrl, wsUrl } = await startTestServer({ claude_binary_path: claude });
+      const { ws, messages } = await connectWebSocket(wsUrl);
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+
+        ws.send(JSON.stringify({ type: 'update_session', session_id: id, data: { abort_patterns: ['(unclosed'] } }));
+        await waitFor(() => messages.some((m) => m.type === 'error'));
+
+        const info = (await (await fetch(`${baseUrl}/api/claude/sessions/${id}`)).json()) as any;
+        expect(info.data.abort_patterns).toBeUndefined();
+      } finally {
+        ws.close();
+        await server.stop();
+      }
+    });
+  });
+
+  describe('deep health check', () => {
+    it('reports a data directory that cannot be written', async () => {
+      // A regular file cannot hold the sessions directory, so the probe fails (even as root)
+      const dataDir = join(await createTempProject(), 'not-a-directory');
+      await fs.writeFile(dataDir, '');
+      const claude = await createFakeClaude('echo "claude 1.0.30"');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, data_dir: dataDir });
+
+      try {
+        const shallow = (await (await fetch(`${baseUrl}/api/status/health`)).json()) as any;
+        expect(shallow.data.status).toBe('healthy');
+        expect(shallow.data.checks).toBeUndefined();
+
+        const deep = (await (await fetch(`${baseUrl}/api/status/health?deep=true`)).json()) as any;
+        expect(deep.data.status).toBe('degraded');
+        expect(deep.data.checks.data_dir.ok).toBe(false);
+        expect(deep.data.reasons[0]).toContain('data directory not writable');
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('passes for a writable data directory and leaves no probe file behind', async () => {
+      const dataDir = await createTempProject();
+      const claude = await createFakeClaude('echo "claude 1.0.30"');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, data_dir: dataDir });
+
+      try {
+        const deep = (await (await fetch(`${baseUrl}/api/status/health?deep=1`)).json()) as any;
+        expect(deep.data.status).toBe('healthy');
+        expect(deep.data.checks.data_dir.ok).toBe(true);
+        expect(await fs.readdir(join(dataDir, 'sessions'))).toEqual([]);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('reports every subsystem check and takes the worst as the overall status', async () => {
+      const claude = await createFakeClaude('echo "claude 1.0.30"');
+      const webhook = createServer((req, res) => res.end());
+      await new Promise<void>((resolve) => webhook.listen(0, '127.0.0.1', resolve));
+      const { port } = webhook.address() as AddressInfo;
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        data_dir: await createTempProject(),
+        webhooks: [{ url: `http://127.0.0.1:${port}/hook` }],
+      });
+
+      try {
+        const res = await fetch(`${baseUrl}/api/status/health?deep=true`);
+        const healthy = (await res.json()) as any;
+        expect(res.status).toBe(200);
+        expect(healthy.data.status).toBe('healthy');
+        expect(Object.keys(healthy.data.checks).sort()).toEqual(
+          ['claude_binary', 'concurrency', 'data_dir', 'persistence', 'webhooks']
+        );
+        for (const check of Object.values(healthy.data.checks) as any[]) {
+          expect(check.status).toBe('ok');
+          expect(typeof check.message).toBe('string');
+        }
+        expect(healthy.data.checks.claude_binary.version).toBe('1.0.30');
+
+        await new Promise<void>((resolve) => webhook.close(() => resolve()));
+        const degraded = (await (await fetch(`${baseUrl}/api/status/health?deep=true`)).json()) as any;
+        expect(degraded.data.status).toBe('degraded');
+        expect(degraded.data.checks.webhooks.status).toBe('degraded');
+        expect(degraded.data.reasons[0]).toContain('Unreachable webhooks');
+      } finally {
+        webhook.close();
+        await server.stop();
+      }
+    });
+
+    it('is unhealthy with 503 when the Claude binary cannot run', async () => {
+      const claude = await createFakeClaude('echo "cannot load" >&2\nexit 1');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const res = await fetch(`${baseUrl}/api/status/health?deep=true`);
+        const body = (await res.json()) as any;
+        expect(res.status).toBe(503);
+        expect(body.data.status).toBe('unhealthy');
+        expect(body.data.checks.claude_binary.status).toBe('fail');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('WebSocket request ids', () => {
+    it('echoes request_id on replies and errors', async () => {
+      const { server, wsUrl } = await startTestServer();
+      const { ws, messages } = await connectWebSocket(wsUrl);
+
+      try {
+        ws.send(JSON.stringify({ type: 'get_sessions', request_id: 'req-1' }));
+        ws.send(JSON.stringify({ type: 'subscribe', request_id: 'req-2' }));
+
+        await waitFor(() => messages.some((m) => m.request_id === 'req-2'));
+        const sessions = messages.find((m) => m.request_id === 'req-1');
+        expect(sessions?.type).toBe('sessions');
+        expect(sessions?.data).toEqual([]);
+        expect(messages.find((m) => m.request_id === 'req-2')?.type).toBe('error');
+      } finally {
+        ws.close();
+        await server.stop();
+      }
+    });
+  });
+
+  describe('claude help', () => {
+    it('parses flags from the CLI help and caches the result', async () => {
+      const project = await createTempProject();
+      const calls = join(project, 'help-calls');
+      const claude = await createFakeClaude(`
+case "$1" in
+  --version) echo "1.0.42 (Claude Code)" ;;
+  --help)
+    echo x >> ${calls}
+    cat <<'EOF'
+Usage: claude [options] [command] [prompt]
+
+Options:
+  -d, --debug [filter]            Enable debug mode
+  -p, --print                     Print response and exit (useful for pipes).
+  --output-format <format>        Output format (only works with --print):
+                                  "text" (default) or "stream-json"
+  --max-thinking-tokens <tokens>  Maximum thinking tokens
+  -h, --help                      Display help for command
+
+Commands:
+  config                          Manage configuration
+EOF
+    ;;
+esac`);
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const first = await fetch(`${baseUrl}/api/claude/help`).then((res) => res.json());
+        const second = await fetch(`${baseUrl}/api/claude/help`).then((res) => res.json());
+
+        expect(first.data.version).toBe('1.0.42');
+        expect(first.data.raw).toContain('Usage: claude');
+        expect(first.data.flags.map((flag: any) => flag.name)).toEqual([
+          '--debug', '--print', '--output-format', '--max-thinking-tokens', '--help',
+        ]);
+        expect(first.data.flags[0]).toEqual({
+          name: '--debug', short: '-d', argument: '[filter]', description: 'Enable debug mode',
+        });
+        expect(first.data.flags[2].description).toBe(
+          'Output format (only works with --print): "text" (default) or "stream-json"'
+        );
+        expect(second.data).toEqual(first.data);
+        expect((await fs.readFile(calls, 'utf8')).trim().split('\n')).toHaveLength(1);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('session debug', () => {
+    it('returns the argv and a redacted environment', async () => {
+      const claude = await createFakeClaude(`echo "line one"\necho "line two" >&2\nexit 3`);
+      const project = await createTempProject();
+      process.env.CLAUDIA_TEST_API_KEY = 'sk-very-secret';
+      process.env.CLAUDIA_TEST_PLAIN = 'visible';
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'debug me', model: 'sonnet',
+        });
+        const url = `${baseUrl}/api/claude/sessions/${started.body.data.session_id}/debug`;
+
+        await waitFor(async () => (await fetch(url).then((res) => res.json())).data.exit_code === 3);
+        const debug = (await fetch(url).then((res) => res.json())).data;
+
+        expect(debug.binary).toBe(claude);
+        expect(debug.args).toEqual(expect.arrayContaining(['-p', 'debug me', '--model', 'sonnet']));
+        expect(debug.cwd).toBe(project);
+        expect(debug.env.CLAUDIA_TEST_API_KEY).toBe('[REDACTED]');
+        expect(debug.env.CLAUDIA_TEST_PLAIN).toBe('visible');
+        expect(debug.exit_signal).toBeNull();
+        expect(debug.output_tail.map((line: any) => line.content.trim())).toEqual(
+          expect.arrayContaining(['line one', 'line two'])
+        );
+        expect((await fetch(`${baseUrl}/api/claude/sessions/missing/debug`)).status).toBe(404);
+      } finally {
+        delete process.env.CLAUDIA_TEST_API_KEY;
+        delete process.env.CLAUDIA_TEST_PLAIN;
+        await server.stop();
+      }
+    });
+  });
+
+  describe('read-only mode', () => {
+    it('refuses new sessions while listings keep working', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"ok"}'`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+      const start = () => postJson(`${baseUrl}/api/claude/execute`, { project_path: project, prompt: 'hi', model: 'sonnet' });
+
+      try {
+        const toggled = await postJson(`${baseUrl}/api/admin/readonly`, { read_only: true });
+        expect(toggled.body.data.read_only).toBe(true);
+
+        const refused = await start();
+        expect(refused.status).toBe(503);
+        expect(refused.body.code).toBe('READ_ONLY');
+        expect((await fetch(`${baseUrl}/api/claude/sessions/running`)).status).toBe(200);
+        const health = await fetch(`${baseUrl}/api/status/health`).then((res) => res.json());
+        expect(health.data.read_only).toBe(true);
+
+        await postJson(`${baseUrl}/api/admin/readonly`, { read_only: false });
+        expect((await start()).status).toBe(200);
+        expect((await postJson(`${baseUrl}/api/admin/readonly`, { read_only: 'yes' })).status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('version probe timeout', () => {
+    it('gives up on a hanging binary within the timeout', async () => {
+      const claude = await createFakeClaude('exec sleep 30');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, version_timeout_ms: 200 });
+
+      try {
+        const started = Date.now();
+        const res = await fetch(`${baseUrl}/api/claude/version`).then((r) => r.json());
+
+        expect(Date.now() - started).toBeLessThan(2000);
+        expect(res.data.is_installed).toBe(false);
+        expect(res.data.version).toBeUndefined();
+        expect(res.data.output).toMatch(/timed out after 200ms/);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('binary discovery', () => {
+    it('keeps serving health checks while a slow version probe runs', async () => {
+      const claude = await createFakeClaude('sleep 1\necho "1.0.0 (Claude Code)"');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const probes = Promise.all([1, 2, 3].map(() => fetch(`${baseUrl}/api/claude/version`)));
+
+        const started = Date.now();
+        const health = await fetch(`${baseUrl}/api/status/health`);
+        expect(health.status).toBe(200);
+        expect(Date.now() - started).toBeLessThan(500);
+
+        const versions = await Promise.all((await probes).map((res) => res.json()));
+        expect(versions.every((res) => res.data.version === '1.0.0')).toBe(true);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('late subscriptions', () => {
+    it('replays the output and completion of a session that already finished', async () => {
+      const claude = await createFakeClaude(
+        `echo '{"type":"assistant","message":{"content":"first"}}'\n` +
+          `echo 'plain line'\n` +
+          `echo '{"type":"result","result":"done"}'`
+      );
+      const project = await createTempProject();
+      const { server, baseUrl, wsUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+
+        const { ws, messages } = await connectWebSocket(wsUrl);
+        ws.send(JSON.stringify({ type: 'subscribe', session_id: id }));
+        const stream = () => messages.filter((m) => m.type === 'claude_stream' && m.session_id === id);
+        await waitFor(() => stream().some((m) => m.data.type === 'complete'));
+        ws.close();
+
+        expect(stream().map((m) => m.data.type)).toEqual(['assistant', 'output', 'result', 'complete']);
+        expect(stream()[1].data.content).toBe('plain line');
+        expect(stream()[3].data.content).toBe('Process exited with code 0');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('result file', () => {
+    it('writes the final result text to output_result_to on completion', async () => {
+      const claude = await createFakeClaude(
+        `echo '{"type":"assistant","message":{"content":"thinking"}}'\n` +
+          `echo '{"type":"result","result":"The answer is 42"}'`
+      );
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', output_result_to: 'out/answer.txt',
+        });
+        expect(started.status).toBe(200);
+
+        const target = join(project, 'out', 'answer.txt');
+        await waitFor(async () => fs.access(target).then(() => true, () => false));
+        expect(await fs.readFile(target, 'utf8')).toBe('The answer is 42');
+        expect(await fs.readdir(join(project, 'out'))).toEqual(['answer.txt']);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('rejects paths outside the project', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"x"}'`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', output_result_to: '../escape.txt',
+        });
+        expect(res.status).toBe(400);
+        expect(res.body.code).toBe('VALIDATION_ERROR');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('session messages stream', () => {
+    it('labels each SSE event with its message type', async () => {
+      const claude = await createFakeClaude(
+        `sleep 0.3\n` +
+          `echo '{"type":"assistant","message":{"content":[{"type":"text","text":"Reading"},{"type":"tool_use","id":"t1","name":"Read","input":{"file_path":"a.ts"}}]}}'\n` +
+          `echo '{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1","content":"file body"}]}}'\n` +
+          `echo 'not json'\n` +
+          `echo '{"type":"result","result":"done"}'`
+      );
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const res = await fetch(`${baseUrl}/api/claude/sessions/${started.body.data.session_id}/messages`);
+        expect(res.headers.get('content-type')).toMatch(/text\/event-stream/);
+
+        // The stream closes after the terminal lifecycle event
+        const events = (await res.text())
+          .split('\n\n')
+          .filter(Boolean)
+          .map((chunk) => {
+            const [eventLine, dataLine] = chunk.split('\n');
+            return { event: eventLine.slice('event: '.length), data: JSON.parse(dataLine.slice('data: '.length)) };
+          });
+
+        expect(events.map((e) => e.event)).toEqual(['assistant', 'tool_use', 'tool_result', 'raw', 'result', 'completed']);
+        expect(events[0].data.message.content).toEqual([{ type: 'text', text: 'Reading' }]);
+        expect(events[1].data).toEqual({ id: 't1', name: 'Read', input: { file_path: 'a.ts' } });
+        expect(events[2].data).toEqual({ tool_use_id: 't1', content: 'file body', is_error: false });
+        expect(events[3].data).toEqual({ stream: 'stdout', content: 'not json', timestamp: expect.any(String) });
+        expect(events[4].data.result).toBe('done');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('compare installations', () => {
+    it('runs the prompt against both binaries and returns both results', async () => {
+      const oldClaude = await createFakeClaude(`echo '{"type":"result","result":"old answer"}'`);
+      const newClaude = await createFakeClaude(`sleep 0.2\necho '{"type":"result","result":"new answer"}'`);
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: oldClaude,
+        compare_binaries: [oldClaude, newClaude],
+      });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/compare`, {
+          binaries: [oldClaude, newClaude], prompt: 'hi', model: 'sonnet',
+        });
+
+        expect(res.status).toBe(200);
+        const [first, second] = res.body.data.runs;
+        expect(first.binary).toBe(oldClaude);
+        expect(first.status).toBe('completed');
+        expect(first.result.text).toBe('old answer');
+        expect(second.binary).toBe(newClaude);
+        expect(second.result.text).toBe('new answer');
+        expect(second.duration_ms).toBeGreaterThanOrEqual(200);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('refuses binaries that are not allowed', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"x"}'`);
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, compare_binaries: [claude] });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/compare`, {
+          binaries: [claude, '/bin/sh'], prompt: 'hi', model: 'sonnet',
+        });
+        expect(res.status).toBe(403);
+        expect(res.body.details.binaries).toEqual(['/bin/sh']);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('webhooks', () => {
+    it('posts an intervention with its reason when an abort pattern kills a session', async () => {
+      const received: any[] = [];
+      const receiver = createServer((req, res) => {
+        let body = '';
+        req.on('data', (chunk) => { body += chunk; });
+        req.on('end', () => {
+          received.push(JSON.parse(body));
+          res.end();
+        });
+      });
+      await new Promise<void>((resolve) => receiver.listen(0, '127.0.0.1', resolve));
+      const url = `http://127.0.0.1:${(receiver.address() as AddressInfo).port}/hook`;
+
+      const claude = await createFakeClaude('echo "FATAL: out of tokens"\nsleep 30');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        webhooks: [{ url, events: ['intervention'] }],
+      });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', abort_patterns: ['FATAL'],
+        });
+        const id = started.body.data.session_id;
+
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200, 5000);
+        await waitFor(() => received.length > 0);
+
+        expect(received).toHaveLength(1);
+        expect(received[0]).toMatchObject({
+          event: 'intervention',
+          session_id: id,
+          reason: 'abort_pattern',
+          project_path: project,
+        });
+        expect(received[0].message).toContain('FATAL');
+      } finally {
+        await server.stop();
+        await new Promise<void>((resolve) => receiver.close(() => resolve()));
+      }
+    });
+  });
+
+  describe('session list', () => {
+    it('streams every session as NDJSON, honoring the filters', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"done"}'`);
+      const project = await createTempProject();
+      const other = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const ids: string[] = [];
+        for (const projectPath of [project, project, project, other]) {
+          const started = await postJson(`${baseUrl}/api/claude/execute`, {
+            project_path: projectPath, prompt: 'hi', model: 'sonnet',
+          });
+          ids.push(started.body.data.session_id);
+        }
+        for (const id of ids) {
+          await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200, 5000);
+        }
+
+        const res = await fetch(`${baseUrl}/api/claude/sessions?format=ndjson&project_path=${encodeURIComponent(project)}`);
+        expect(res.status).toBe(200);
+        expect(res.headers.get('content-type')).toContain('application/x-ndjson');
+
+        const text = await res.text();
+        expect(text.endsWith('\n')).toBe(true);
+        const sessions = text.trimEnd().split('\n').map((line) => JSON.parse(line));
+        expect(sessions).toHaveLength(3);
+        expect(sessions.map((s) => s.process_type.ClaudeSession.session_id)).toEqual(ids.slice(0, 3));
+        expect(sessions.every((s) => s.status === 'completed' && s.project_path === project)).toBe(true);
+
+        const page = await (await fetch(`${baseUrl}/api/claude/sessions?format=ndjson&offset=1&limit=2`)).text();
+        expect(page.trimEnd().split('\n')).toHaveLength(2);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('rejects an unknown status filter', async () => {
+      const { server, baseUrl } = await startTestServer();
+
+      try {
+        const res = await fetch(`${baseUrl}/api/claude/sessions?status=paused`);
+        expect(res.status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('session command', () => {
+    it('returns a command line that reruns the session with the same argv', async () => {
+      // The fake binary records its argv, NUL-separated, in its working directory
+      const claude = await createFakeClaude(`printf '%s\\0' "$@" > argv.bin`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+      const prompt = `it's a "quoted" $HOME \\ prompt; with * and \\n`;
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt, model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200, 5000);
+        const original = await fs.readFile(join(project, 'argv.bin'), 'utf8');
+
+        const res = await fetch(`${baseUrl}/api/claude/sessions/${id}/command`);
+        const command = ((await res.json()) as any).data;
+        expect(command.cwd).toBe(project);
+        expect(command.argv[0]).toBe(claude);
+        expect(command.argv).toContain(prompt);
+
+        await fs.unlink(join(project, 'argv.bin'));
+        await promisify(execFile)('/bin/sh', ['-c', command.command]);
+        const rerun = await fs.readFile(join(project, 'argv.bin'), 'utf8');
+
+        expect(rerun).toBe(original);
+        expect(rerun.split('\0').slice(0, -1)).toEqual(command.argv.slice(1));
+        expect((await fetch(`${baseUrl}/api/claude/sessions/missing/command`)).status).toBe(404);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('soft cancel', () => {
+    it('closes stdin and lets Claude exit on its own', async () => {
+      const claude = await createFakeClaude(`cat > /dev/null\necho '{"type":"result","result":"stopped cleanly"}'`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+
+        const res = await postJson(`${baseUrl}/api/claude/cancel/${id}`, { soft: true, grace_ms: 5000 });
+        expect(res.body.data.cancelled).toBe(true);
+
+        const { body } = await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200, 3000);
+        expect(body.data.status).toBe('cancelled');
+        expect(body.data.text).toBe('stopped cleanly');
+
+        const debug = (await fetch(`${baseUrl}/api/claude/sessions/${id}/debug`).then((r) => r.json())) as any;
+        expect(debug.data.exit_code).toBe(0);
+        expect(debug.data.exit_signal).toBeNull();
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('kills Claude when it is still running after the grace period', async () => {
+      const claude = await createFakeClaude('sleep 30');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+
+        await postJson(`${baseUrl}/api/claude/cancel/${id}`, { soft: true, grace_ms: 200 });
+
+        const { body } = await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200, 5000);
+        expect(body.data.status).toBe('cancelled');
+        const debug = (await fetch(`${baseUrl}/api/claude/sessions/${id}/debug`).then((r) => r.json())) as any;
+        expect(debug.data.exit_signal).toBe('SIGTERM');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('examples', () => {
+    const getWithHost = (url: string, host: string) =>
+      new Promise<any>((resolve, reject) => {
+        httpGet(url, { headers: { host } }, (res) => {
+          let body = '';
+          res.on('data', (chunk) => { body += chunk; });
+          res.on('end', () => resolve(JSON.parse(body)));
+        }).on('error', reject);
+      });
+
+    it('renders snippets for the address the server is reached on', async () => {
+      const { server, baseUrl } = await startTestServer();
+
+      try {
+        const list = (await (await fetch(`${baseUrl}/api/examples`)).json()) as any;
+        const running = list.data.find((example: any) => example.name === 'running-sessions');
+        expect(running.curl).toBe(`curl ${baseUrl}/api/claude/sessions/running`);
+        expect(running.javascript).toContain(`fetch('${baseUrl}/api/claude/sessions/running')`);
+
+        const proxied = await getWithHost(`${baseUrl}/api/example/stream-session`, 'claudia.internal:8443');
+        expect(proxied.data.curl).toContain('http://claudia.internal:8443/api/claude/sessions/');
+        expect(proxied.data.javascript).toContain(`new WebSocket('ws://claudia.internal:8443/ws')`);
+
+        // A Host header that would break out of the snippet falls back to the bound address
+        const hostile = await getWithHost(`${baseUrl}/api/example/health`, "evil'$(id)");
+        expect(hostile.data.curl).toBe(`curl ${baseUrl}/api/status/health`);
+
+        expect((await fetch(`${baseUrl}/api/example/missing`)).status).toBe(404);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('session names', () => {
+    it('looks sessions up by name and keeps names unique among running sessions', async () => {
+      const claude = await createFakeClaude('echo "working on it"\nsleep 30');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+      const start = () => postJson(`${baseUrl}/api/claude/execute`, {
+        project_path: project, prompt: 'hi', model: 'sonnet', name: 'my-refactor',
+      });
+
+      try {
+        const started = await start();
+        const id = started.body.data.session_id;
+
+        const info = (await (await fetch(`${baseUrl}/api/claude/sessions/my-refactor`)).json()) as any;
+        expect(info.data.process_type.ClaudeSession.session_id).toBe(id);
+        expect(info.data.name).toBe('my-refactor');
+        const outputUrl = `${baseUrl}/api/claude/sessions/my-refactor/output`;
+        await waitFor(async () => ((await (await fetch(outputUrl)).json()) as any).data.lines.length > 0);
+        const output = (await (await fetch(outputUrl)).json()) as any;
+        expect(output.data.lines[0].content).toBe('working on it');
+
+        const duplicate = await start();
+        expect(duplicate.status).toBe(409);
+        expect(duplicate.body.code).toBe('SESSION_NAME_TAKEN');
+        expect(duplicate.body.details.session_id).toBe(id);
+
+        await postJson(`${baseUrl}/api/claude/cancel/my-refactor`, {});
+        await fetchUntil(`${baseUrl}/api/claude/sessions/my-refactor/result`, (code) => code === 200, 5000);
+
+        const reused = await start();
+        expect(reused.status).toBe(200);
+        const next = (await (await fetch(`${baseUrl}/api/claude/sessions/my-refactor`)).json()) as any;
+        expect(next.data.process_type.ClaudeSession.session_id).toBe(reused.body.data.session_id);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('subscribes WebSocket clients to a session by name', async () => {
+      const claude = await createFakeClaude('sleep 0.5\necho "{\\"type\\":\\"assistant\\"}"\nsleep 30');
+      const project = await createTempProject();
+      const { server, baseUrl, wsUrl } = await startTestServer({ claude_binary_path: claude });
+      const { ws, messages } = await connectWebSocket(wsUrl);
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', name: 'named-stream',
+        });
+        const id = started.body.data.session_id;
+
+        ws.send(JSON.stringify({ type: 'subscribe', session_id: 'named-stream' }));
+        await waitFor(() => messages.some((m) => m.data?.status === 'subscribed'));
+        const subscribed = messages.find((m) => m.data?.status === 'subscribed');
+        expect(subscribed?.data.session_id).toBe(id);
+        expect(subscribed?.data.subscriptions).toEqual([id]);
+
+        await waitFor(() => messages.some((m) => m.type === 'claude_stream' && m.data.type === 'assistant'));
+        expect(messages.find((m) => m.type === 'claude_stream')?.session_id).toBe(id);
+
+        ws.send(JSON.stringify({ type: 'unsubscribe', session_id: 'named-stream' }));
+        await waitFor(() => messages.some((m) => m.data?.status === 'unsubscribed'));
+        expect(messages.find((m) => m.data?.status === 'unsubscribed')?.data.subscriptions).toEqual([]);
+      } finally {
+        ws.close();
+        await server.stop();
+      }
+    });
+
+    it('rejects names that look like session ids', async () => {
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer();
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', name: '550e8400-e29b-41d4-a716-446655440000',
+        });
+        expect(res.status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('session input files', () => {
+    it('streams a file into stdin line by line', async () => {
+      const claude = await createFakeClaude('while IFS= read -r line; do echo "echo: $line"; done');
+      const project = await createTempProject();
+      await fs.writeFile(join(project, 'turns.txt'), 'first turn\nsecond turn\nthird turn\n');
+      const outside = await createTempProject();
+      await fs.writeFile(join(outside, 'secret.txt'), 'nope\n');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        const inputUrl = `${baseUrl}/api/claude/sessions/${id}/input/file`;
+
+        const res = await postJson(inputUrl, { path: 'turns.txt' });
+        expect(res.status).toBe(200);
+        expect(res.body.data).toMatchObject({ lines: 3, bytes: 34 });
+
+        const outputUrl = `${baseUrl}/api/claude/sessions/${id}/output`;
+        const echoed = async () => ((await (await fetch(outputUrl)).json()) as any).data.lines
+          .map((line: any) => line.content)
+          .filter((content: string) => content.startsWith('echo: '));
+        await waitFor(async () => (await echoed()).length === 3);
+        expect(await echoed()).toEqual(['echo: first turn', 'echo: second turn', 'echo: third turn']);
+
+        const escaped = await postJson(inputUrl, { path: join(outside, 'secret.txt') });
+        expect(escaped.status).toBe(403);
+        expect((await postJson(inputUrl, { path: 'missing.txt' })).status).toBe(404);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('parsed session output', () => {
+    it('returns the typed messages of a running session so far', async () => {
+      const claude = await createFakeClaude(
+        `echo '{"type":"assistant","message":{"content":[{"type":"text","text":"Reading"},` +
+          `{"type":"tool_use","id":"toolu_1","name":"Read","input":{"file_path":"a.ts"}}]}}'\n` +
+          `echo 'not json'\n` +
+          `sleep 5`
+      );
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const url = `${baseUrl}/api/claude/sessions/${started.body.data.session_id}/output?parse=true`;
+        const parsed = async () => ((await (await fetch(url)).json()) as any).data;
+        await waitFor(async () => (await parsed()).events.length === 3);
+
+        const data = await parsed();
+        expect(data.status).toBe('running');
+        expect(data.lines).toBeUndefined();
+        expect(data.events.map((e: any) => e.event)).toEqual(['assistant', 'tool_use', 'raw']);
+        expect(data.events[0].data.message.content).toEqual([{ type: 'text', text: 'Reading' }]);
+        expect(data.events[1].data).toEqual({ id: 'toolu_1', name: 'Read', input: { file_path: 'a.ts' } });
+        expect(data.events[2].data).toEqual({ stream: 'stdout', content: 'not json', timestamp: expect.any(String) });
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('session labels', () => {
+    it('derives labels from the prompt and merges the requested ones over them', async () => {
+      const claude = await createFakeClaude('sleep 5');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        label_rules: [
+          { pattern: '\\btests?\\b', flags: 'i', labels: { category: 'testing', priority: 'low' } },
+          { pattern: 'deploy', labels: { category: 'ops' } },
+        ],
+      });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'Write Tests for the parser', model: 'sonnet', labels: { priority: 'high' },
+        });
+        expect(started.status).toBe(200);
+
+        const info = (await (await fetch(`${baseUrl}/api/claude/sessions/${started.body.data.session_id}`)).json()) as any;
+        expect(info.data.labels).toEqual({ category: 'testing', priority: 'high' });
+
+        const invalid = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', labels: { priority: 1 },
+        });
+        expect(invalid.status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('log tail', () => {
+    const admin_token = 'test-admin-token';
+    const authorization = { Authorization: `Bearer ${admin_token}` };
+
+    it('streams lines appended to the log file after connecting', async () => {
+      const logFile = join(await createTempProject(), 'server.log');
+      await fs.writeFile(logFile, 'before connecting\n');
+      const { server, baseUrl } = await startTestServer({ log_file: logFile, admin_token });
+      const abort = new AbortController();
+
+      try {
+        const res = await fetch(`${baseUrl}/api/logs/tail`, { headers: authorization, signal: abort.signal });
+        expect(res.headers.get('content-type')).toMatch(/text\/event-stream/);
+
+        await fs.appendFile(logFile, 'first appended\nsecond ');
+        await fs.appendFile(logFile, 'appended\n');
+
+        const reader = res.body!.getReader();
+        const decoder = new TextDecoder();
+        let text = '';
+        while (!text.includes('second appended')) {
+          const { value, done } = await reader.read();
+          if (done) {
+            break;
+          }
+          text += decoder.decode(value, { stream: true });
+        }
+
+        const lines = text
+          .split('\n\n')
+          .filter((chunk) => chunk.startsWith('event: line'))
+          .map((chunk) => JSON.parse(chunk.split('\n')[1].slice('data: '.length)).line);
+        expect(lines).toEqual(['first appended', 'second appended']);
+      } finally {
+        abort.abort();
+        await server.stop();
+      }
+    });
+
+    it('answers 409 without a log file', async () => {
+      const { server, baseUrl } = await startTestServer({ admin_token });
+
+      try {
+        const res = await fetch(`${baseUrl}/api/logs/tail`, { headers: authorization });
+        expect(res.status).toBe(409);
+        expect(((await res.json()) as any).code).toBe('LOG_FILE_DISABLED');
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('stays off without an admin token and refuses a wrong one', async () => {
+      const logFile = join(await createTempProject(), 'server.log');
+      await fs.writeFile(logFile, 'secret prompt\n');
+      const unconfigured = await startTestServer({ log_file: logFile });
+      const configured = await startTestServer({ log_file: logFile, admin_token });
+
+      try {
+        const off = await fetch(`${unconfigured.baseUrl}/api/logs/tail`, { headers: authorization });
+        expect(off.status).toBe(403);
+        expect(((await off.json()) as any).code).toBe('ADMIN_TOKEN_NOT_CONFIGURED');
+
+        for (const headers of [{}, { Authorization: 'Bearer wrong-token' }]) {
+          const res = await fetch(`${configured.baseUrl}/api/logs/tail`, { headers });
+          expect(res.status).toBe(401);
+          expect(res.headers.get('www-authenticate')).toBe('Bearer');
+          expect(((await res.json()) as any).code).toBe('ADMIN_TOKEN_INVALID');
+        }
+      } finally {
+        await unconfigured.server.stop();
+        await configured.server.stop();
+      }
+    });
+  });
+
+  describe('additional directories', () => {
+    it('passes each additional directory as an --add-dir flag', async () => {
+      const claude = await createFakeClaude('sleep 5');
+      const project = await createTempProject();
+      const shared = await fs.realpath(await createTempProject());
+      await fs.mkdir(join(project, 'vendor'));
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', additional_dirs: [shared, 'vendor'],
+        });
+        expect(started.status).toBe(200);
+
+        const debug = (await (await fetch(`${baseUrl}/api/claude/sessions/${started.body.data.session_id}/debug`)).json()) as any;
+        const flags = debug.data.args.flatMap((arg: string, i: number, args: string[]) =>
+          args[i - 1] === '--add-dir' ? [arg] : []
+        );
+        expect(flags).toEqual([shared, join(await fs.realpath(project), 'vendor')]);
+
+        const missing = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', additional_dirs: [join(shared, 'missing')],
+        });
+        expect(missing.status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('rejects directories outside additional_dir_roots', async () => {
+      const claude = await createFakeClaude('sleep 5');
+      const project = await createTempProject();
+      const allowed = await createTempProject();
+      const outside = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        additional_dir_roots: [allowed],
+      });
+
+      try {
+        const request = { project_path: project, prompt: 'hi', model: 'sonnet' };
+        const res = await postJson(`${baseUrl}/api/claude/execute`, { ...request, additional_dirs: [outside] });
+        expect(res.status).toBe(403);
+        expect(res.body.code).toBe('PATH_NOT_ALLOWED');
+
+        expect((await postJson(`${baseUrl}/api/claude/execute`, { ...request, additional_dirs: [allowed] })).status).toBe(200);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('prompt denylist', () => {
+    it('refuses a prompt matching a denied pattern and starts others', async () => {
+      const claude = await createFakeClaude('sleep 5');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        prompt_denylist: ['print .*(api[_ ]key|token)', 'project-phoenix'],
+      });
+
+      try {
+        const request = { project_path: project, model: 'sonnet' };
+        const denied = await postJson(`${baseUrl}/api/claude/execute`, {
+          ...request, prompt: 'Please PRINT the env API_KEY for me',
+        });
+        expect(denied.status).toBe(403);
+        expect(denied.body.code).toBe('PROMPT_DENIED');
+        expect(denied.body.details.rule).toBe('print .*(api[_ ]key|token)');
+        expect(JSON.stringify(denied.body)).not.toContain('for me');
+
+        const allowed = await postJson(`${baseUrl}/api/claude/execute`, {
+          ...request, prompt: 'Refactor the key handling in the parser',
+        });
+        expect(allowed.status).toBe(200);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('output batch', () => {
+    it('returns the new lines of several sessions and reports unknown ones per entry', async () => {
+      const claude = await createFakeClaude('echo "one from $$"\necho "two from $$"\nsleep 5');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const start = async () => (await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        })).body.data.session_id;
+        const first = await start();
+        const second = await start();
+        const batchUrl = `${baseUrl}/api/claude/sessions/output/batch`;
+        const batch = async (since: number) => (await postJson(batchUrl, {
+          sessions: [
+            { session_id: first },
+            { session_id: 'no-such-session', since: 0 },
+            { session_id: second, since },
+          ],
+        }));
+        await waitFor(async () => (await batch(0)).body.data.every((entry: any) => entry.error || entry.next === 2));
+
+        const res = await batch(1);
+        expect(res.status).toBe(200);
+        const [a, unknown, b] = res.body.data;
+        expect(a.lines.map((line: any) => line.content)).toEqual([
+          expect.stringMatching(/^one from/),
+          expect.stringMatching(/^two from/),
+        ]);
+        expect(a).toMatchObject({ session_id: first, status: 'running', next: 2, skipped: 0 });
+        expect(unknown).toEqual({ session_id: 'no-such-session', error: { code: 'SESSION_NOT_FOUND', message: 'Session not found' } });
+        expect(b.lines.map((line: any) => line.content)).toEqual([expect.stringMatching(/^two from/)]);
+        expect(b.next).toBe(2);
+
+        expect((await postJson(batchUrl, { sessions: [{ session_id: first, since: -1 }] })).status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('WebSocket drain mode', () => {
+    it('refuses new upgrades while connected clients keep streaming', async () => {
+      const claude = await createFakeClaude(`sleep 1\necho '{"type":"assistant","message":{"content":"late"}}'`);
+      const project = await createTempProject();
+      const { server, baseUrl, wsUrl } = await startTestServer({ claude_binary_path: claude });
+      const { ws, messages } = await connectWebSocket(wsUrl);
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        ws.send(JSON.stringify({ type: 'subscribe', session_id: id }));
+        const stream = () => messages.filter((m) => m.type === 'claude_stream' && m.session_id === id);
+        await waitFor(() => messages.some((m) => m.data?.status === 'subscribed'));
+
+        const toggled = await postJson(`${baseUrl}/api/admin/drain-ws`, { draining: true });
+        expect(toggled.body.data).toEqual({ draining: true, connections: 1 });
+        await expect(connectWebSocket(wsUrl)).rejects.toThrow(/503/);
+
+        await waitFor(() => stream().some((m) => m.data.type === 'complete'));
+        expect(stream().some((m) => m.data.message?.content === 'late')).toBe(true);
+
+        await postJson(`${baseUrl}/api/admin/drain-ws`, { draining: false });
+        const { ws: second } = await connectWebSocket(wsUrl);
+        second.close();
+        expect((await postJson(`${baseUrl}/api/admin/drain-ws`, { draining: 'yes' })).status).toBe(400);
+      } finally {
+        ws.close();
+        await server.stop();
+      }
+    });
+  });
+
+  describe('WebSocket connection limit', () => {
+    it('refuses upgrades at max_ws_connections until a client disconnects', async () => {
+      const { server, baseUrl, wsUrl } = await startTestServer({ max_ws_connections: 2 });
+      const connections = async () =>
+        ((await (await fetch(`${baseUrl}/api/admin/drain-ws`)).json()) as any).data.connections;
+      const { ws: first } = await connectWebSocket(wsUrl);
+      const { ws: second } = await connectWebSocket(wsUrl);
+
+      try {
+        await expect(connectWebSocket(wsUrl)).rejects.toThrow(/503/);
+
+        first.close();
+        await waitFor(async () => (await connections()) === 1);
+        const { ws: third } = await connectWebSocket(wsUrl);
+        expect(await connections()).toBe(2);
+        third.close();
+      } finally {
+        first.close();
+        second.close();
+        await server.stop();
+      }
+    });
+  });
+
+  describe('WebSocket idle timeout', () => {
+    it('closes a silent unsubscribed client but keeps a subscribed one', async () => {
+      const { server, wsUrl } = await startTestServer({ ws_idle_timeout_seconds: 1 });
+      const { ws: idle } = await connectWebSocket(wsUrl);
+      const { ws: subscribed, messages } = await connectWebSocket(wsUrl);
+
+      try {
+        const closed = new Promise<{ code: number; reason: string }>((resolve) => {
+          idle.once('close', (code, reason) => resolve({ code, reason: reason.toString() }));
+        });
+        subscribed.send(JSON.stringify({ type: 'subscribe', session_id: 'some-session' }));
+        await waitFor(() => messages.some((m) => m.data?.status === 'subscribed'));
+
+        const { code, reason } = await closed;
+        expect(code).toBe(4000);
+        expect(reason).toMatch(/idle/);
+        await new Promise((resolve) => setTimeout(resolve, 600));
+        expect(subscribed.readyState).toBe(subscribed.OPEN);
+      } finally {
+        subscribed.close();
+        await server.stop();
+      }
+    });
+  });
+
+  describe('sessions sharing a project', () => {
+    it('warns about, or rejects, a second session in a busy project', async () => {
+      const claude = await createFakeClaude('sleep 5');
+      const project = await createTempProject();
+      const request = { project_path: project, prompt: 'hi', model: 'sonnet' };
+
+      const warning = await startTestServer({ claude_binary_path: claude });
+      try {
+        const first = await postJson(`${warning.baseUrl}/api/claude/execute`, request);
+        expect(first.body.data.warnings).toBeUndefined();
+
+        const second = await postJson(`${warning.baseUrl}/api/claude/execute`, request);
+        expect(second.status).toBe(200);
+        expect(second.body.data.warnings).toEqual([expect.stringContaining(project)]);
+      } finally {
+        await warning.server.stop();
+      }
+
+      const rejecting = await startTestServer({ claude_binary_path: claude, reject_concurrent_same_project: true });
+      try {
+        const first = await postJson(`${rejecting.baseUrl}/api/claude/execute`, request);
+        expect(first.status).toBe(200);
+
+        const second = await postJson(`${rejecting.baseUrl}/api/claude/execute`, request);
+        expect(second.status).toBe(409);
+        expect(second.body.code).toBe('PROJECT_BUSY');
+        expect(second.body.details.session_ids).toEqual([first.body.data.session_id]);
+
+        const elsewhere = await postJson(`${rejecting.baseUrl}/api/claude/execute`, {
+          ...request, project_path: await createTempProject(),
+        });
+        expect(elsewhere.status).toBe(200);
+      } finally {
+        await rejecting.server.stop();
+      }
+    });
+  });
+
+  describe('ready file', () => {
+    it('appears once the server is bound and is removed on shutdown', async () => {
+      const dataDir = await createTempProject();
+      const readyFile = join(dataDir, 'ready');
+      const server = new ClaudiaServer({ listen: [{ host: '127.0.0.1', port: 0 }], data_dir: dataDir });
+
+      await expect(fs.access(readyFile)).rejects.toThrow();
+      await server.start();
+
+      try {
+        const ready = JSON.parse(await fs.readFile(readyFile, 'utf8'));
+        expect(ready.pid).toBe(process.pid);
+        expect(ready.addresses).toEqual(server.getAddresses());
+      } finally {
+        await server.stop();
+      }
+
+      await expect(fs.access(readyFile)).rejects.toThrow();
+    });
+  });
+
+  describe('symlinked project_path', () => {
+    it('accepts a link to a directory and rejects links to files and broken links', async () => {
+      const claude = await createFakeClaude('true');
+      const links = await createTempProject();
+      const project = await createTempProject();
+      const file = join(links, 'file.txt');
+      await fs.writeFile(file, '');
+      await fs.symlink(project, join(links, 'to-dir'));
+      await fs.symlink(file, join(links, 'to-file'));
+      await fs.symlink(join(links, 'missing'), join(links, 'broken'));
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+      const start = (name: string) => postJson(`${baseUrl}/api/claude/execute`, {
+        project_path: join(links, name), prompt: 'hi', model: 'sonnet',
+      });
+
+      try {
+        expect((await start('to-dir')).status).toBe(200);
+
+        const toFile = await start('to-file');
+        expect(toFile.status).toBe(400);
+        expect(toFile.body.code).toBe('VALIDATION_ERROR');
+        expect(toFile.body.error).toContain('symlink to a file');
+
+        const broken = await start('broken');
+        expect(broken.status).toBe(400);
+        expect(broken.body.error).toContain('broken symlink');
+        expect(broken.body.details.target).toBe(join(links, 'missing'));
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('per-key session limit', () => {
+    it('refuses a key at its limit while another key still has capacity', async () => {
+      const claude = await createFakeClaude('sleep 5');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, per_key_session_limit: 1 });
+      const start = async (apiKey: string) => {
+        const res = await fetch(`${baseUrl}/api/claude/execute`, {
+          method: 'POST',
+          headers: { 'Content-Type': 'application/json', 'X-API-Key': apiKey },
+          body: JSON.stringify({ project_path: await createTempProject(), prompt: 'hi', model: 'sonnet' }),
+        });
+        return { status: res.status, body: (await res.json()) as any };
+      };
+
+      try {
+        const first = await start('key-a');
+        expect(first.status).toBe(200);
+
+        const second = await start('key-a');
+        expect(second.status).toBe(429);
+        expect(second.body.code).toBe('MAX_KEY_SESSIONS_REACHED');
+
+        expect((await start('key-b')).status).toBe(200);
+
+        await postJson(`${baseUrl}/api/claude/cancel/${first.body.data.session_id}`, {});
+        await waitFor(async () => (await start('key-a')).status === 200);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('refuses starts without a key while the limit is set', async () => {
+      const claude = await createFakeClaude('sleep 5');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, per_key_session_limit: 1 });
+
+      try {
+        const res = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: await createTempProject(), prompt: 'hi', model: 'sonnet',
+        });
+        expect(res.status).toBe(401);
+        expect(res.body.code).toBe('API_KEY_REQUIRED');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('markdown output', () => {
+    it('renders the transcript with fenced tool calls and results', async () => {
+      const messages = [
+        { type: 'system', subtype: 'init' },
+        {
+          type: 'assistant',
+          message: { content: [
+            { type: 'text', text: 'Let me look.' },
+            { type: 'tool_use', id: 't1', name: 'Read', input: { file_path: 'src/index.ts' } },
+          ] },
+        },
+        { type: 'user', message: { content: [{ type: 'tool_result', tool_use_id: 't1', content: 'export {}' }] } },
+        { type: 'result', result: 'Nothing to do.' },
+      ];
+      const claude = await createFakeClaude(messages.map((m) => `echo '${JSON.stringify(m)}'`).join('\n'));
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+
+        const res = await fetch(`${baseUrl}/api/claude/sessions/${id}/output?convert=markdown`);
+        expect(res.headers.get('content-type')).toMatch(/^text\/markdown/);
+        const markdown = await res.text();
+        expect(markdown).toContain('**Assistant:**\n\nLet me look.');
+        expect(markdown).toContain('**Tool call: Read**\n\n```json\n{\n  "file_path": "src/index.ts"\n}\n```');
+        expect(markdown).toContain('**Tool result:**\n\n```\nexport {}\n```');
+        expect(markdown).toContain('**Result:**\n\nNothing to do.');
+        expect(markdown).not.toContain('init');
+
+        const invalid = await fetch(`${baseUrl}/api/claude/sessions/${id}/output?convert=html`);
+        expect(invalid.status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('returns only the lines of the requested stream', async () => {
+      const claude = await createFakeClaude(
+        `echo '{"type":"assistant","message":{"content":"content"}}'\necho 'warning: slow disk' >&2\n` +
+        `sleep 0.1\necho '{"type":"result","result":"done"}'`
+      );
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+
+        const stderr = (await (await fetch(`${baseUrl}/api/claude/sessions/${id}/output?stream=stderr`)).json()) as any;
+        expect(stderr.data.lines.map((line: any) => line.stream)).toEqual(['stderr']);
+        expect(stderr.data.lines[0].content).toContain('warning: slow disk');
+
+        const stdout = (await (await fetch(`${baseUrl}/api/claude/sessions/${id}/output?stream=stdout`)).json()) as any;
+        expect(stdout.data.lines).toHaveLength(2);
+        expect(stdout.data.lines.every((line: any) => line.stream === 'stdout')).toBe(true);
+
+        const invalid = await fetch(`${baseUrl}/api/claude/sessions/${id}/output?stream=both`);
+        expect(invalid.status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('git diff capture', () => {
+    it('records the changes a session made in a git repository', async () => {
+      const claude = await createFakeClaude(`echo changed >> tracked.txt\necho '{"type":"result","result":"edited"}'`);
+      const project = await createTempProject();
+      const git = (...args: string[]) => promisify(execFile)('git', args, { cwd: project });
+      await git('init', '-q');
+      await git('config', 'user.email', 'test@example.com');
+      await git('config', 'user.name', 'Test');
+      await fs.writeFile(join(project, 'tracked.txt'), 'original\n');
+      await git('add', 'tracked.txt');
+      await git('commit', '-q', '-m', 'initial');
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', capture_git_diff: true,
+        });
+        const id = started.body.data.session_id;
+
+        const { body } = await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/diff`, (code) => code === 200);
+        expect(body.data.diff).toContain('+changed');
+        expect(body.data.status).toContain('tracked.txt');
+      } finally {
+        await server.stop();
+      }
+    });
+
+    it('captures nothing outside a git repository', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"done"}'`);
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet', capture_git_diff: true,
+        });
+        const id = started.body.data.session_id;
+
+        const { body } = await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/diff`, (code) => code === 404);
+        expect(body.code).toBe('GIT_DIFF_UNAVAILABLE');
+        const result = await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+        expect(result.body.data.status).toBe('completed');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('cancelling without keeping output', () => {
+    it('clears the buffer and output log only when keep_output is false', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"assistant","message":{"content":"partial"}}'\nsleep 30`);
+      const project = await createTempProject();
+      const dataDir = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        data_dir: dataDir,
+        output_flush_interval_ms: 10,
+      });
+      const logOf = (id: string) => join(dataDir, 'sessions', `${id}.output.jsonl`);
+      const linesOf = async (id: string) =>
+        ((await (await fetch(`${baseUrl}/api/claude/sessions/${id}/output`)).json()) as any).data.lines;
+
+      try {
+        const ids: string[] = [];
+        for (let i = 0; i < 2; i++) {
+          const started = await postJson(`${baseUrl}/api/claude/execute`, {
+            project_path: project, prompt: 'hi', model: 'sonnet',
+          });
+          ids.push(started.body.data.session_id);
+        }
+        const [kept, discarded] = ids;
+        for (const id of ids) {
+          await waitFor(async () => (await linesOf(id)).length === 1);
+          await waitFor(async () => (await fs.readFile(logOf(id), 'utf8').catch(() => '')).includes('partial'));
+        }
+
+        expect((await postJson(`${baseUrl}/api/claude/cancel/${kept}`, {})).body.data.cancelled).toBe(true);
+        expect((await postJson(`${baseUrl}/api/claude/cancel/${discarded}`, { keep_output: false })).body.data.cancelled)
+          .toBe(true);
+        for (const id of ids) {
+          await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+        }
+
+        expect(await linesOf(kept)).toHaveLength(1);
+        expect(await fs.readFile(logOf(kept), 'utf8')).toContain('partial');
+        expect(await linesOf(discarded)).toEqual([]);
+        await waitFor(async () => !(await fs.access(logOf(discarded)).then(() => true, () => false)));
+
+        const invalid = await postJson(`${baseUrl}/api/claude/cancel/${kept}`, { keep_output: 'no' });
+        expect(invalid.status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('minimum Claude version', () => {
+    it('refuses to start with an older Claude, or only warns when configured to', async () => {
+      const claude = await createFakeClaude('echo "0.9.5 (Claude Code)"');
+      const listen = [{ host: '127.0.0.1', port: 0 }];
+
+      const strict = new ClaudiaServer({ listen, claude_binary_path: claude, min_claude_version: '1.0.0' });
+      await expect(strict.start()).rejects.toThrow(/0\.9\.5 is older than the minimum supported version 1\.0\.0/);
+
+      const { server } = await startTestServer({
+        claude_binary_path: claude,
+        min_claude_version: '1.0.0',
+        min_claude_version_action: 'warn',
+      });
+      await server.stop();
+
+      const { server: current } = await startTestServer({ claude_binary_path: claude, min_claude_version: '0.9.5' });
+      await current.stop();
+    });
+  });
+
+  describe('WebSocket stats feed', () => {
+    it('sends each stats subscriber snapshots at its own interval', async () => {
+      const { server, wsUrl } = await startTestServer();
+      const fast = await connectWebSocket(wsUrl);
+      const slow = await connectWebSocket(wsUrl);
+      const stats = (messages: any[]) => messages.filter((m) => m.type === 'stats');
+
+      try {
+        fast.ws.send(JSON.stringify({ type: 'subscribe_stats', interval_seconds: 0.2, request_id: 'fast' }));
+        slow.ws.send(JSON.stringify({ type: 'subscribe_stats', interval_seconds: 1 }));
+        await waitFor(() => stats(fast.messages).length >= 6);
+
+        expect(fast.messages.find((m) => m.request_id === 'fast').data).toEqual({
+          status: 'subscribed_stats',
+          interval_seconds: 0.2,
+        });
+        const snapshots = stats(fast.messages);
+        expect(snapshots[0].data).toMatchObject({
+          running: 0,
+          queue_depth: 0,
+          sessions: { total: 0, total_cost_usd: 0 },
+        });
+        expect(snapshots[0].data.memory.rss).toBeGreaterThan(0);
+
+        // The first snapshot is immediate; the rest follow the requested cadence
+        const times = snapshots.map((m) => Date.parse(m.timestamp));
+        for (let i = 2; i < times.length; i++) {
+          expect(times[i] - times[i - 1]).toBeGreaterThanOrEqual(150);
+          expect(times[i] - times[i - 1]).toBeLessThan(600);
+        }
+        expect(stats(slow.messages).length).toBeLessThanOrEqual(3);
+
+        fast.ws.send(JSON.stringify({ type: 'unsubscribe_stats' }));
+        await waitFor(() => fast.messages.some((m) => m.data?.status === 'unsubscribed_stats'));
+        const count = stats(fast.messages).length;
+        await new Promise((resolve) => setTimeout(resolve, 500));
+        expect(stats(fast.messages)).toHaveLength(count);
+
+        fast.ws.send(JSON.stringify({ type: 'subscribe_stats', interval_seconds: 0, request_id: 'bad' }));
+        await waitFor(() => fast.messages.some((m) => m.type === 'error' && m.request_id === 'bad'));
+      } finally {
+        fast.ws.close();
+        slow.ws.close();
+        await server.stop();
+      }
+    });
+  });
+
+  describe('POST /api/claude/sessions/:sessionId/reset', () => {
+    it('cancels the running session and starts a distinct one with the same parameters', async () => {
+      const claude = await createFakeClaude('echo "run $$ $*"\nsleep 30');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+      const outputOf = async (id: string) =>
+        ((await (await fetch(`${baseUrl}/api/claude/sessions/${id}/output`)).json()) as any).data.lines;
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project,
+          prompt: 'do the thing',
+          model: 'sonnet',
+          labels: { team: 'infra' },
+        });
+        const oldId = started.body.data.session_id;
+        await waitFor(async () => (await outputOf(oldId)).length > 0);
+
+        const reset = await postJson(`${baseUrl}/api/claude/sessions/${oldId}/reset`, {});
+        expect(reset.status).toBe(200);
+        const newId = reset.body.data.session_id;
+        expect(newId).not.toBe(oldId);
+        expect(reset.body.data.reset_from).toBe(oldId);
+
+        const cancelled = (await (await fetch(`${baseUrl}/api/claude/sessions?status=cancelled`)).json()) as any;
+        expect(cancelled.data.map((entry: any) => entry.process_type.ClaudeSession.session_id)).toEqual([oldId]);
+
+        const running = (await (await fetch(`${baseUrl}/api/claude/sessions/running`)).json()) as any;
+        expect(running.data.map((info: any) => info.process_type.ClaudeSession.session_id)).toEqual([newId]);
+        expect(running.data[0]).toMatchObject({ project_path: project, model: 'sonnet', labels: { team: 'infra' } });
+
+        await waitFor(async () => (await outputOf(newId)).length > 0);
+        const [oldLine] = await outputOf(oldId);
+        const [newLine] = await outputOf(newId);
+        expect(newLine.content).toContain('do the thing');
+        expect(newLine.content).not.toBe(oldLine.content);
+
+        const again = await postJson(`${baseUrl}/api/claude/sessions/${oldId}/reset`, {});
+        expect(again.status).toBe(404);
+
+        await postJson(`${baseUrl}/api/claude/cancel/${newId}`, {});
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('output_filters', () => {
+    it('drops matching lines before they are buffered and counts them', async () => {
+      const claude = await createFakeClaude(
+        [
+          `echo '{"type":"system","subtype":"heartbeat"}'`,
+          `echo '{"type":"assistant","message":{"content":[{"type":"text","text":"hello"}]}}'`,
+          `echo 'progress: 50%'`,
+          `echo 'progress: 100%' >&2`,
+          `echo '{"type":"result","result":"done"}'`,
+        ].join('\n')
+      );
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        output_filters: ['"subtype":"heartbeat"', '^progress: '],
+        count_filtered_output: true,
+      });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+
+        const { data } = (await (await fetch(`${baseUrl}/api/claude/sessions/${id}/output`)).json()) as any;
+        expect(data.lines.map((line: any) => JSON.parse(line.content).type)).toEqual(['assistant', 'result']);
+        expect(data.filtered).toBe(3);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('GET /api/claude/sessions/:sessionId/timeline', () => {
+    it('records each status transition with its time and reason', async () => {
+      const claude = await createFakeClaude('case "$*" in *slow*) sleep 30;; esac\nsleep 0.2\necho \'{"type":"result","result":"ok"}\'');
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+      const timelineOf = async (id: string) =>
+        ((await (await fetch(`${baseUrl}/api/claude/sessions/${id}/timeline`)).json()) as any).data;
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+
+        const timeline = await timelineOf(id);
+        expect(timeline.status).toBe('completed');
+        expect(timeline.status_history.map((entry: any) => entry.status)).toEqual(['starting', 'running', 'completed']);
+        const times = timeline.status_history.map((entry: any) => Date.parse(entry.at));
+        expect(times[1]).toBeGreaterThanOrEqual(times[0]);
+        expect(times[2] - times[1]).toBeGreaterThanOrEqual(150);
+        expect(timeline.status_history[2].reason).toBeUndefined();
+
+        const timedOut = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'slow', model: 'sonnet', timeout_ms: 300,
+        });
+        const timedOutId = timedOut.body.data.session_id;
+        await waitFor(async () => (await timelineOf(timedOutId)).status !== 'running');
+        const history = (await timelineOf(timedOutId)).status_history;
+        expect(history[history.length - 1].reason).toBe('terminated by timeout');
+
+        const missing = await fetch(`${baseUrl}/api/claude/sessions/no-such-session/timeline`);
+        expect(missing.status).toBe(404);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('GET /api/usage', () => {
+    it('totals tokens and cost per project, model and label within a time range', async () => {
+      const claude = await createFakeClaude(
+        'case "$*" in *big*) tokens=300; cost=0.3;; *) tokens=100; cost=0.1;; esac\n' +
+          'echo "{\\"type\\":\\"result\\",\\"result\\":\\"ok\\",\\"total_cost_usd\\":$cost,' +
+          '\\"usage\\":{\\"input_tokens\\":$tokens,\\"output_tokens\\":10}}"'
+      );
+      const alpha = await createTempProject();
+      const beta = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+      const usage = async (query: string) =>
+        (await (await fetch(`${baseUrl}/api/usage?${query}`)).json()) as any;
+
+      try {
+        const before = new Date().toISOString();
+        const runs = [
+          { project_path: alpha, prompt: 'small', model: 'sonnet', labels: { tenant: 'acme' } },
+          { project_path: alpha, prompt: 'big', model: 'opus', labels: { tenant: 'acme' } },
+          { project_path: beta, prompt: 'big', model: 'sonnet' },
+        ];
+        for (const run of runs) {
+          const started = await postJson(`${baseUrl}/api/claude/execute`, run);
+          const id = started.body.data.session_id;
+          await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+        }
+
+        const byProject = await usage('group_by=project');
+        expect(byProject.data.groups).toEqual([
+          expect.objectContaining({ key: alpha, sessions: 2, input_tokens: 400, output_tokens: 20 }),
+          expect.objectContaining({ key: beta, sessions: 1, input_tokens: 300, output_tokens: 10 }),
+        ]);
+        expect(byProject.data.groups[0].cost_usd).toBeCloseTo(0.4);
+        expect(byProject.data.groups[1].cost_usd).toBeCloseTo(0.3);
+
+        const byModel = await usage('group_by=model');
+        expect(byModel.data.groups.map((group: any) => [group.key, group.input_tokens])).toEqual([
+          ['sonnet', 400],
+          ['opus', 300],
+        ]);
+
+        const byTenant = await usage('group_by=tenant');
+        expect(byTenant.data.groups.map((group: any) => [group.key, group.sessions])).toEqual([
+          ['acme', 2],
+          [null, 1],
+        ]);
+
+        const inRange = await usage(`from=${encodeURIComponent(before)}`);
+        expect(inRange.data.groups).toHaveLength(2);
+        const beforeRange = await usage(`to=${encodeURIComponent(before)}`);
+        expect(beforeRange.data.groups).toEqual([]);
+
+        const invalid = await fetch(`${baseUrl}/api/usage?group_by=label`);
+        expect(invalid.status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('GET /api/claude/sessions/:sessionId/output?ansi=', () => {
+    it('strips ANSI escape codes by default and keeps them with ansi=preserve', async () => {
+      const claude = await createFakeClaude(
+        "printf '\\033[1;31mFAIL\\033[0m src/app.test.ts\\n'\n" +
+          "printf '\\033]8;;https://example.com\\007link\\033]8;;\\007 done\\n'"
+      );
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude });
+      const contentOf = async (id: string, query: string) =>
+        ((await (await fetch(`${baseUrl}/api/claude/sessions/${id}/output${query}`)).json()) as any).data.lines.map(
+          (line: any) => line.content
+        );
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await waitFor(async () => (await contentOf(id, '')).length === 2);
+
+        expect(await contentOf(id, '')).toEqual(['FAIL src/app.test.ts', 'link done']);
+        expect(await contentOf(id, '?ansi=strip')).toEqual(['FAIL src/app.test.ts', 'link done']);
+        expect(await contentOf(id, '?ansi=preserve')).toEqual([
+          '\x1b[1;31mFAIL\x1b[0m src/app.test.ts',
+          '\x1b]8;;https://example.com\x07link\x1b]8;;\x07 done',
+        ]);
+
+        const invalid = await fetch(`${baseUrl}/api/claude/sessions/${id}/output?ansi=html`);
+        expect(invalid.status).toBe(400);
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('git info', () => {
+    it('records the branch and HEAD commit of a git project at start', async () => {
+      const claude = await createFakeClaude(`echo '{"type":"result","result":"done"}'`);
+      const project = await createTempProject();
+      const git = (...args: string[]) => promisify(execFile)('git', args, { cwd: project });
+      await git('init', '-q');
+      await git('config', 'user.email', 'test@example.com');
+      await git('config', 'user.name', 'Test');
+      await git('checkout', '-q', '-b', 'feature/traceable');
+      await fs.writeFile(join(project, 'tracked.txt'), 'original\n');
+      await git('add', 'tracked.txt');
+      await git('commit', '-q', '-m', 'initial');
+      const { stdout: head } = await git('rev-parse', 'HEAD');
+      const plain = await createTempProject();
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: claude, record_git_info: true });
+
+      try {
+        const inRepo = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const outside = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: plain, prompt: 'hi', model: 'sonnet',
+        });
+
+        const { body } = await fetchUntil(`${baseUrl}/api/claude/sessions`, (code) => code === 200);
+        const infoOf = (id: string) =>
+          body.data.find((entry: any) => entry.process_type.ClaudeSession.session_id === id);
+        expect(infoOf(inRepo.body.data.session_id)).toMatchObject({
+          git_branch: 'feature/traceable',
+          git_commit: head.trim(),
+        });
+        const plainInfo = infoOf(outside.body.data.session_id);
+        expect(plainInfo).toBeDefined();
+        expect(plainInfo).not.toHaveProperty('git_branch');
+        expect(plainInfo).not.toHaveProperty('git_commit');
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+
+  describe('reselecting the Claude installation', () => {
+    it('switches new sessions to a newer installation that appeared on PATH', async () => {
+      const old = await createFakeClaude(
+        `case "$1" in --version) echo "claude 1.0.0";; *) echo '{"type":"result","result":"old"}';; esac`
+      );
+      const newer = await createFakeClaude(
+        `case "$1" in --version) echo "claude 99.0.0";; *) echo '{"type":"result","result":"new"}';; esac`
+      );
+      const binDir = await createTempProject();
+      await fs.copyFile(newer, join(binDir, 'claude'));
+      await fs.chmod(join(binDir, 'claude'), 0o755);
+      const project = await createTempProject();
+      const originalPath = process.env.PATH;
+      const { server, baseUrl } = await startTestServer({ claude_binary_path: old });
+      const resultOf = async () => {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'hi', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        const { body } = await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+        return body.data.text;
+      };
+
+      try {
+        expect(await resultOf()).toBe('old');
+
+        process.env.PATH = `${binDir}:${originalPath}`;
+        const reselected = await postJson(`${baseUrl}/api/claude/reselect`, {});
+        expect(reselected.status).toBe(200);
+        expect(reselected.body.data).toMatchObject({
+          previous: old,
+          selected: join(binDir, 'claude'),
+          version: '99.0.0',
+          changed: true,
+        });
+        expect(reselected.body.data.installations).toContainEqual({ path: old, version: '1.0.0' });
+        expect(await resultOf()).toBe('new');
+
+        const again = await postJson(`${baseUrl}/api/claude/reselect`, {});
+        expect(again.body.data).toMatchObject({ selected: join(binDir, 'claude'), changed: false });
+      } finally {
+        process.env.PATH = originalPath;
+        await server.stop();
+      }
+    });
+  });
+
+  describe('prompt wrapping', () => {
+    it('passes the configured prefix and suffix around the prompt to Claude', async () => {
+      const claude = await createFakeClaude(
+        `while [ $# -gt 0 ]; do [ "$1" = "-p" ] && prompt="$2"; shift; done\n` +
+          `printf '%s' "$prompt" > prompt.txt\n` +
+          `echo '{"type":"result","result":"done"}'`
+      );
+      const project = await createTempProject();
+      const { server, baseUrl } = await startTestServer({
+        claude_binary_path: claude,
+        prompt_prefix: 'Follow the style guide.',
+        prompt_suffix: 'Reply in English.',
+      });
+
+      try {
+        const started = await postJson(`${baseUrl}/api/claude/execute`, {
+          project_path: project, prompt: 'Fix the bug', model: 'sonnet',
+        });
+        const id = started.body.data.session_id;
+        await fetchUntil(`${baseUrl}/api/claude/sessions/${id}/result`, (code) => code === 200);
+
+        expect(await fs.readFile(join(project, 'prompt.txt'), 'utf8')).toBe(
+          'Follow the style guide.\n\nFix the bug\n\nReply in English.'
+        );
+      } finally {
+        await server.stop();
+      }
+    });
+  });
+});
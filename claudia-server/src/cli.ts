@@ -34,6 +34,11 @@ serverCmd
   .option('-h, --host <host>', 'Server host', '0.0.0.0')
   .option('--claude-binary <path>', 'Path to Claude binary')
   .option('--claude-home <path>', 'Path to Claude home directory')
+  .option('--no-skip-permissions', 'Do not pass --dangerously-skip-permissions by default')
+  .option('--no-kill-on-drop', 'Keep sessions running across restarts; requires --data-dir')
+  .option('--no-verbose', 'Do not pass --verbose to Claude by default')
+  .option('--reap-orphans', 'Terminate Claude processes left over from an earlier run')
+  .option('--read-only', 'Refuse new sessions until read-only mode is turned off')
   .option('-d, --daemon', 'Run as daemon (background process)')
   .action(async (options) => {
     try {
@@ -42,6 +47,11 @@ serverCmd
         host: options.host,
         claude_binary_path: options.claudeBinary,
         claude_home_dir: options.claudeHome,
+        skip_permissions: options.skipPermissions,
+        kill_on_drop: options.killOnDrop,
+        verbose: options.verbose,
+        reap_orphans_on_start: options.reapOrphans,
+        read_only: options.readOnly,
       };
 
       const server = new ClaudiaServer(config);
@@ -0,0 +1,156 @@
+import type { ServerConfig } from './types/index.js';
+
+/**
+ * How an environment variable value is converted to a ServerConfig field
+ */
+type EnvKind = 'string' | 'number' | 'boolean' | 'list' | 'json';
+
+/**
+ * ServerConfig fields that can be set with a `CLAUDIA_<FIELD>` environment variable
+ */
+const ENV_FIELDS: { [K in keyof ServerConfig]?: EnvKind } = {
+  port: 'number',
+  host: 'string',
+  listen: 'json',
+  ws_listen: 'json',
+  cors_origin: 'list',
+  max_concurrent_sessions: 'number',
+  max_concurrent_per_project: 'number',
+  per_key_session_limit: 'number',
+  max_ws_connections: 'number',
+  ws_idle_timeout_seconds: 'number',
+  max_stored_sessions: 'number',
+  spawn_rate_limit: 'number',
+  max_queue_depth: 'number',
+  session_timeout_ms: 'number',
+  preview_interval_min_ms: 'number',
+  preview_interval_max_ms: 'number',
+  claude_binary_path: 'string',
+  claude_home_dir: 'string',
+  wrapper_command: 'json',
+  skip_permissions: 'boolean',
+  verbose: 'boolean',
+  read_only: 'boolean',
+  reject_concurrent_same_project: 'boolean',
+  kill_on_drop: 'boolean',
+  reap_orphans_on_start: 'boolean',
+  output_charset_fallback: 'string',
+  tee_output_to_stdout: 'boolean',
+  output_filters: 'json',
+  count_filtered_output: 'boolean',
+  request_timeout_ms: 'number',
+  handshake_timeout_ms: 'number',
+  version_timeout_ms: 'number',
+  max_body_bytes: 'number',
+  max_line_bytes: 'number',
+  soft_cancel_grace_ms: 'number',
+  stdout_eof_grace_ms: 'number',
+  max_output_bytes_per_sec: 'number',
+  data_dir: 'string',
+  output_flush_interval_ms: 'number',
+  output_flush_batch_lines: 'number',
+  max_output_lines: 'number',
+  disk_backed_output: 'boolean',
+  log_file: 'string',
+  admin_token: 'string',
+  ready_file: 'string',
+  memory_limit_mb: 'number',
+  memory_soft_limit_mb: 'number',
+  cgroup_root: 'string',
+  record_git_info: 'boolean',
+  result_output_roots: 'list',
+  input_file_roots: 'list',
+  max_input_file_bytes: 'number',
+  additional_dir_roots: 'list',
+  resume_running: 'string',
+  compare_binaries: 'list',
+  min_claude_version: 'string',
+  min_claude_version_action: 'string',
+  allowed_extra_args: 'list',
+  model_fallback: 'json',
+  model_pricing: 'json',
+  webhooks: 'json',
+  label_rules: 'json',
+  label_arg_rules: 'json',
+  prompt_denylist: 'json',
+  prompt_prefix: 'string',
+  prompt_suffix: 'string',
+};
+
+/**
+ * Older variable names accepted for backwards compatibility; the CLAUDIA_<FIELD> form wins
+ */
+const LEGACY_ENV: Record<string, keyof ServerConfig> = {
+  PORT: 'port',
+  HOST: 'host',
+  CLAUDE_BINARY: 'claude_binary_path',
+  CLAUDE_HOME: 'claude_home_dir',
+  CLAUDIA_TEE_OUTPUT: 'tee_output_to_stdout',
+};
+
+const TRUE_VALUES = ['1', 'true', 'yes', 'on'];
+const FALSE_VALUES = ['0', 'false', 'no', 'off'];
+
+function parseEnvValue(name: string, kind: EnvKind, raw: string): unknown {
+  switch (kind) {
+    case 'string':
+      return raw;
+    case 'number': {
+      const value = Number(raw);
+      if (raw.trim() === '' || !Number.isFinite(value)) {
+        throw new Error(`Invalid value for ${name}: expected a number, got "${raw}"`);
+      }
+      return value;
+    }
+    case 'boolean': {
+      const value = raw.trim().toLowerCase();
+      if (TRUE_VALUES.includes(value)) {
+        return true;
+      }
+      if (FALSE_VALUES.includes(value)) {
+        return false;
+      }
+      throw new Error(`Invalid value for ${name}: expected true or false, got "${raw}"`);
+    }
+    case 'list':
+      return raw.split(',').map((entry) => entry.trim()).filter(Boolean);
+    case 'json':
+      try {
+        return JSON.parse(raw);
+      } catch {
+        throw new Error(`Invalid value for ${name}: expected JSON, got "${raw}"`);
+      }
+  }
+}
+
+/**
+ * Read ServerConfig overrides from environment variables.
+ *
+ * Every field listed in ENV_FIELDS can be set as `CLAUDIA_` followed by the field name in
+ * upper case, e.g. `CLAUDIA_MAX_CONCURRENT_SESSIONS=5` or `CLAUDIA_KILL_ON_DROP=false`.
+ * Lists are comma-separated and object fields (`listen`, `ws_listen`, `model_fallback`,
+ * `model_pricing`, `webhooks`, `label_rules`, `label_arg_rules`, `prompt_denylist`,
+ * `output_filters`, `wrapper_command`) take JSON. Empty variables are ignored.
+ *
+ * @throws Error naming the variable when a value cannot be parsed.
+ */
+export function configFromEnv(env: NodeJS.ProcessEnv = process.env): Partial<ServerConfig> {
+  const config: Record<string, unknown> = {};
+
+  for (const [name, field] of Object.entries(LEGACY_ENV)) {
+    const raw = env[name];
+    if (raw) {
+      config[field] = parseEnvValue(name, ENV_FIELDS[field]!, raw);
+    }
+  }
+
+  for (const [field, kind] of Object.entries(ENV_FIELDS) as [keyof ServerConfig, EnvKind][]) {
+    const name = `CLAUDIA_${field.toUpperCase()}`;
+    const raw = env[name];
+    if (raw) {
+      config[field] = parseEnvValue(name, kind, raw);
+    }
+  }
+
+  return config as Partial<ServerConfig>;
+}
@@ -1,16 +1,127 @@
 #!/usr/bin/env node
 
 import { ClaudiaServer } from './server.js';
-import type { ServerConfig } from './types/index.js';
+import { configFromEnv } from './config.js';
+import { teeConsoleToFile } from './utils/log-file.js';
+import type { ServerConfig, ListenAddress } from './types/index.js';
+
+/**
+ * Pair up host and port values into listen addresses.
+ *
+ * Equal-length lists are zipped pairwise. A single host is combined with every port
+ * and a single port with every host, so `--host 127.0.0.1 --port 3000,3001` and
+ * `--host 127.0.0.1,::1 --port 3000` both work. Any other length mismatch is an error.
+ *
+ * @returns The listen addresses, or undefined when neither hosts nor ports were given.
+ */
+export function resolveListenAddresses(
+  hosts: string[],
+  ports: number[]
+): ListenAddress[] | undefined {
+  if (hosts.length === 0 && ports.length === 0) {
+    return undefined;
+  }
+
+  const hostList = hosts.length > 0 ? hosts : ['0.0.0.0'];
+  const portList = ports.length > 0 ? ports : [3000];
+
+  if (hostList.length === portList.length) {
+    return hostList.map((host, i) => ({ host, port: portList[i] }));
+  }
+  if (hostList.length === 1) {
+    return portList.map((port) => ({ host: hostList[0], port }));
+  }
+  if (portList.length === 1) {
+    return hostList.map((host) => ({ host, port: portList[0] }));
+  }
+
+  throw new Error(
+    `Cannot pair ${hostList.length} hosts with ${portList.length} ports; give equal counts or a single host/port`
+  );
+}
+
+/**
+ * Parse a `host:port` listen address; an IPv6 host is given in brackets, e.g. `[::1]:3001`
+ *
+ * @throws Error when the value has no host or no valid port.
+ */
+export function parseListenAddress(value: string): ListenAddress {
+  const separator = value.lastIndexOf(':');
+  const host = value.slice(0, Math.max(separator, 0)).replace(/^\[(.*)\]$/, '$1');
+  const portText = value.slice(separator + 1);
+  const port = Number(portText);
+  if (separator <= 0 || !host || !/^\d+$/.test(portText) || port > 65535) {
+    throw new Error(`Invalid listen address "${value}"; expected host:port`);
+  }
+  return { host, port };
+}
+
+/**
+ * Split a comma-separated option value into trimmed, non-empty entries
+ */
+function splitList(value: string): string[] {
+  return value.split(',').map((entry) => entry.trim()).filter(Boolean);
+}
 
 /**
  * Parse command-line arguments into a partial ServerConfig.
  *
  * Recognized options:
- * - `--port`, `-p <number>` — sets `port`
- * - `--host`, `-h <host>` — sets `host`
+ * - `--port`, `-p <number>` — sets `port`; repeatable or comma-separated
+ * - `--host`, `-h <host>` — sets `host`; repeatable or comma-separated
+ * - `--ws-listen <host:port>` — adds to `ws_listen`; repeatable or comma-separated
  * - `--claude-binary <path>` — sets `claude_binary_path`
  * - `--claude-home <path>` — sets `claude_home_dir`
+ * - `--wrapper <command>` — sets `wrapper_command`, split on whitespace
+ * - `--no-skip-permissions` — sets `skip_permissions` to false
+ * - `--no-kill-on-drop` — sets `kill_on_drop` to false
+ * - `--no-verbose` — sets `verbose` to false
+ * - `--read-only` — sets `read_only` to true
+ * - `--reap-orphans` — sets `reap_orphans_on_start` to true
+ * - `--tee-output` — sets `tee_output_to_stdout` to true
+ * - `--output-filter <regex>` — adds to `output_filters`; repeatable
+ * - `--count-filtered-output` — sets `count_filtered_output` to true
+ * - `--max-sessions <number>` — sets `max_concurrent_sessions`
+ * - `--max-per-project <number>` — sets `max_concurrent_per_project`
+ * - `--reject-same-project` — sets `reject_concurrent_same_project` to true
+ * - `--max-per-key <number>` — sets `per_key_session_limit`
+ * - `--max-ws-connections <number>` — sets `max_ws_connections`
+ * - `--ws-idle-timeout <seconds>` — sets `ws_idle_timeout_seconds`
+ * - `--max-stored-sessions <number>` — sets `max_stored_sessions`
+ * - `--spawn-rate-limit <number>` — sets `spawn_rate_limit`
+ * - `--max-queue-depth <number>` — sets `max_queue_depth`
+ * - `--memory-limit <mb>` — sets `memory_limit_mb`
+ * - `--memory-soft-limit <mb>` — sets `memory_soft_limit_mb`
+ * - `--cgroup-root <path>` — sets `cgroup_root`
+ * - `--record-git-info` — sets `record_git_info` to true
+ * - `--result-output-root <path>` — adds to `result_output_roots`; repeatable or comma-separated
+ * - `--input-file-root <path>` — adds to `input_file_roots`; repeatable or comma-separated
+ * - `--max-input-file-bytes <number>` — sets `max_input_file_bytes`
+ * - `--add-dir-root <path>` — adds to `additional_dir_roots`; repeatable or comma-separated
+ * - `--resume-running <reject|attach>` — sets `resume_running`
+ * - `--compare-binary <path>` — adds to `compare_binaries`; repeatable or comma-separated
+ * - `--min-claude-version <version>` — sets `min_claude_version`
+ * - `--min-version-action <refuse|warn>` — sets `min_claude_version_action`
+ * - `--request-timeout <ms>` — sets `request_timeout_ms`
+ * - `--handshake-timeout <ms>` — sets `handshake_timeout_ms`
+ * - `--version-timeout <ms>` — sets `version_timeout_ms`
+ * - `--max-body-bytes <number>` — sets `max_body_bytes`
+ * - `--max-line-bytes <number>` — sets `max_line_bytes`
+ * - `--soft-cancel-grace <ms>` — sets `soft_cancel_grace_ms`
+ * - `--stdout-eof-grace <ms>` — sets `stdout_eof_grace_ms`
+ * - `--max-output-rate <bytes>` — sets `max_output_bytes_per_sec`
+ * - `--data-dir <path>` — sets `data_dir`
+ * - `--flush-interval <ms>` — sets `output_flush_interval_ms`
+ * - `--flush-batch <lines>` — sets `output_flush_batch_lines`
+ * - `--max-output-lines <lines>` — sets `max_output_lines`
+ * - `--disk-backed-output` — sets `disk_backed_output` to true
+ * - `--log-file <path>` — sets `log_file`
+ * - `--ready-file <path>` — sets `ready_file`
+ * - `--model-fallback <model=fallback>` — adds an entry to `model_fallback`; repeatable
+ * - `--allowed-extra-args <flags>` — sets `allowed_extra_args`; repeatable or comma-separated
+ * - `--deny-prompt <regex>` — adds to `prompt_denylist`; repeatable
+ * - `--prompt-prefix <text>` — sets `prompt_prefix`
+ * - `--prompt-suffix <text>` — sets `prompt_suffix`
  * - `--help` — prints help and exits (0)
  * - `--version` — prints the version and exits (0)
  *
@@ -21,11 +132,16 @@ import type { ServerConfig } from './types/index.js';
  * Note: this function may call `process.exit()` as a side effect for help,
  * version, or unrecognized option handling.
  *
+ * When more than one host or port is given, the pairs are resolved with
+ * `resolveListenAddresses` and returned as `listen`.
+ *
  * @returns A Partial<ServerConfig> populated with any recognized CLI options.
  */
 function parseArgs(): Partial<ServerConfig> {
   const args = process.argv.slice(2);
   const config: Partial<ServerConfig> = {};
+  const hosts: string[] = [];
+  const ports: number[] = [];
 
   for (let i = 0; i < args.length; i++) {
     const arg = args[i];
@@ -35,14 +151,25 @@ function parseArgs(): Partial<ServerConfig> {
       case '--port':
       case '-p':
         if (nextArg && !nextArg.startsWith('-')) {
-          config.port = parseInt(nextArg, 10);
+          ports.push(...splitList(nextArg).map((port) => parseInt(port, 10)));
           i++;
         }
         break;
       case '--host':
       case '-h':
         if (nextArg && !nextArg.startsWith('-')) {
-          config.host = nextArg;
+          hosts.push(...splitList(nextArg));
+          i++;
+        }
+        break;
+      case '--ws-listen':
+        if (nextArg && !nextArg.startsWith('-')) {
+          try {
+            config.ws_listen = [...(config.ws_listen ?? []), ...splitList(nextArg).map(parseListenAddress)];
+          } catch (error) {
+            console.error((error as Error).message);
+            process.exit(1);
+          }
           i++;
         }
         break;
@@ -58,6 +185,291 @@ function parseArgs(): Partial<ServerConfig> {
           i++;
         }
         break;
+      case '--wrapper':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.wrapper_command = nextArg.split(/\s+/).filter(Boolean);
+          i++;
+        }
+        break;
+      case '--no-skip-permissions':
+        config.skip_permissions = false;
+        break;
+      case '--no-kill-on-drop':
+        config.kill_on_drop = false;
+        break;
+      case '--no-verbose':
+        config.verbose = false;
+        break;
+      case '--read-only':
+        config.read_only = true;
+        break;
+      case '--reap-orphans':
+        config.reap_orphans_on_start = true;
+        break;
+      case '--tee-output':
+        config.tee_output_to_stdout = true;
+        break;
+      case '--output-filter':
+        // A regular expression may contain commas, so the value is not split
+        if (nextArg) {
+          config.output_filters = [...(config.output_filters ?? []), nextArg];
+          i++;
+        }
+        break;
+      case '--count-filtered-output':
+        config.count_filtered_output = true;
+        break;
+      case '--max-sessions':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_concurrent_sessions = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--max-per-project':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_concurrent_per_project = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--max-per-key':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.per_key_session_limit = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--max-ws-connections':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_ws_connections = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--ws-idle-timeout':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.ws_idle_timeout_seconds = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--reject-same-project':
+        config.reject_concurrent_same_project = true;
+        break;
+      case '--max-stored-sessions':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_stored_sessions = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--spawn-rate-limit':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.spawn_rate_limit = parseFloat(nextArg);
+          i++;
+        }
+        break;
+      case '--max-queue-depth':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_queue_depth = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--memory-soft-limit':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.memory_soft_limit_mb = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--memory-limit':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.memory_limit_mb = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--cgroup-root':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.cgroup_root = nextArg;
+          i++;
+        }
+        break;
+      case '--record-git-info':
+        config.record_git_info = true;
+        break;
+      case '--result-output-root':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.result_output_roots = [...(config.result_output_roots ?? []), ...splitList(nextArg)];
+          i++;
+        }
+        break;
+      case '--input-file-root':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.input_file_roots = [...(config.input_file_roots ?? []), ...splitList(nextArg)];
+          i++;
+        }
+        break;
+      case '--max-input-file-bytes':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_input_file_bytes = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--add-dir-root':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.additional_dir_roots = [...(config.additional_dir_roots ?? []), ...splitList(nextArg)];
+          i++;
+        }
+        break;
+      case '--resume-running':
+        if (nextArg === 'reject' || nextArg === 'attach') {
+          config.resume_running = nextArg;
+          i++;
+        } else {
+          console.error(`Invalid --resume-running value: ${nextArg} (expected reject or attach)`);
+          process.exit(1);
+        }
+        break;
+      case '--min-claude-version':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.min_claude_version = nextArg;
+          i++;
+        }
+        break;
+      case '--min-version-action':
+        if (nextArg === 'refuse' || nextArg === 'warn') {
+          config.min_claude_version_action = nextArg;
+          i++;
+        } else {
+          console.error(`Invalid --min-version-action value: ${nextArg} (expected refuse or warn)`);
+          process.exit(1);
+        }
+        break;
+      case '--compare-binary':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.compare_binaries = [...(config.compare_binaries ?? []), ...splitList(nextArg)];
+          i++;
+        }
+        break;
+      case '--request-timeout':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.request_timeout_ms = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--handshake-timeout':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.handshake_timeout_ms = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--version-timeout':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.version_timeout_ms = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--max-line-bytes':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_line_bytes = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--soft-cancel-grace':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.soft_cancel_grace_ms = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--stdout-eof-grace':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.stdout_eof_grace_ms = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--max-output-rate':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_output_bytes_per_sec = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--max-body-bytes':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_body_bytes = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--data-dir':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.data_dir = nextArg;
+          i++;
+        }
+        break;
+      case '--flush-interval':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.output_flush_interval_ms = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--flush-batch':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.output_flush_batch_lines = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--max-output-lines':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.max_output_lines = parseInt(nextArg, 10);
+          i++;
+        }
+        break;
+      case '--disk-backed-output':
+        config.disk_backed_output = true;
+        break;
+      case '--log-file':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.log_file = nextArg;
+          i++;
+        }
+        break;
+      case '--ready-file':
+        if (nextArg && !nextArg.startsWith('-')) {
+          config.ready_file = nextArg;
+          i++;
+        }
+        break;
+      case '--model-fallback':
+        if (nextArg && !nextArg.startsWith('-')) {
+          const [model, fallback] = nextArg.split('=').map((part) => part.trim());
+          if (!model || !fallback) {
+            console.error(`Invalid --model-fallback value: ${nextArg} (expected model=fallback)`);
+            process.exit(1);
+          }
+          config.model_fallback = { ...config.model_fallback, [model]: fallback };
+          i++;
+        }
+        break;
+      case '--allowed-extra-args':
+        // The value is itself a list of flags, so it is expected to start with a dash
+        if (nextArg) {
+          config.allowed_extra_args = [...(config.allowed_extra_args ?? []), ...splitList(nextArg)];
+          i++;
+        }
+        break;
+      case '--deny-prompt':
+        // A regular expression may contain commas, so the value is not split
+        if (nextArg) {
+          config.prompt_denylist = [...(config.prompt_denylist ?? []), nextArg];
+          i++;
+        }
+        break;
+      case '--prompt-prefix':
+        // Prompt text may well start with a dash, e.g. a bullet list
+        if (nextArg !== undefined) {
+          config.prompt_prefix = nextArg;
+          i++;
+        }
+        break;
+      case '--prompt-suffix':
+        if (nextArg !== undefined) {
+          config.prompt_suffix = nextArg;
+          i++;
+        }
+        break;
       case '--help':
         printHelp();
         process.exit(0);
@@ -74,6 +486,21 @@ function parseArgs(): Partial<ServerConfig> {
     }
   }
 
+  if (hosts.length > 1 || ports.length > 1) {
+    try {
+      config.listen = resolveListenAddresses(hosts, ports);
+    } catch (error) {
+      console.error((error as Error).message);
+      process.exit(1);
+    }
+  }
+  if (hosts.length > 0) {
+    config.host = hosts[0];
+  }
+  if (ports.length > 0) {
+    config.port = ports[0];
+  }
+
   return config;
 }
 
@@ -90,10 +517,61 @@ Claudia Server - Standalone TypeScript server for Claude Code integration
 Usage: claudia-server [options]
 
 Options:
-  -p, --port <port>           Server port (default: 3000)
-  -h, --host <host>           Server host (default: 0.0.0.0)
+  -p, --port <port>           Server port (default: 3000); repeat or comma-separate to bind several
+  -h, --host <host>           Server host (default: 0.0.0.0); repeat or comma-separate to bind several
+  --ws-listen <host:port>     Serve /ws only on <host:port>, and only the API on --host/--port; repeatable
   --claude-binary <path>      Path to Claude binary (auto-detected if not specified)
   --claude-home <path>        Path to Claude home directory (default: ~/.claude)
+  --wrapper <command>         Run sessions under <command>, e.g. "nice -n 10"; Claude and its args follow
+  --no-skip-permissions       Do not pass --dangerously-skip-permissions by default
+  --no-kill-on-drop           Keep sessions running across restarts; requires --data-dir
+  --no-verbose                Do not pass --verbose to Claude by default
+  --read-only                 Start refusing new sessions (toggle with POST /api/admin/readonly)
+  --reap-orphans              Terminate session processes an earlier run recorded in --data-dir
+  --tee-output                Mirror session output to stdout, prefixed with the session ID
+  --output-filter <regex>     Drop session output lines matching <regex> before buffering; repeatable
+  --count-filtered-output     Count the lines --output-filter dropped in each session's output_filtered
+  --max-sessions <n>          Maximum concurrent sessions (default: 10)
+  --max-per-project <n>       Maximum concurrent sessions per project (default: unlimited)
+  --reject-same-project       Refuse a session whose project already has one running (default: warn)
+  --max-per-key <n>           Maximum concurrent sessions per X-API-Key header, which becomes required (default: unlimited)
+  --max-ws-connections <n>    Maximum connected WebSocket clients; more get 503 (default: unlimited)
+  --ws-idle-timeout <s>       Close WebSocket clients silent and unsubscribed this long (default: never)
+  --max-stored-sessions <n>   Session records kept; oldest finished are evicted (default: unlimited)
+  --spawn-rate-limit <n>      Claude processes started per second; others wait (default: unlimited)
+  --max-queue-depth <n>       Starts that may wait for the spawn rate limit; more get 429
+  --memory-limit <mb>         Per-session memory limit via cgroup v2, Linux only (default: none)
+  --memory-soft-limit <mb>    Shed output of the oldest finished sessions above this server RSS (default: off)
+  --cgroup-root <path>        Parent cgroup for session groups (default: /sys/fs/cgroup/claudia)
+  --record-git-info           Record each session's project git branch and HEAD commit at start
+  --result-output-root <dir>  Allow output_result_to files under <dir>; repeatable (default: project_path)
+  --input-file-root <dir>     Allow feeding files under <dir> to session stdin; repeatable (default: project_path)
+  --max-input-file-bytes <n>  Largest file fed to a session's stdin (default: 1048576)
+  --add-dir-root <dir>        Only allow additional_dirs under <dir>; repeatable (default: any directory)
+  --resume-running <mode>     Resuming a running session: reject (409) or attach (default: reject)
+  --compare-binary <path>     Allow POST /api/claude/compare to run this binary; repeatable
+  --min-claude-version <v>    Refuse to start with an older or unidentifiable Claude (default: any version)
+  --min-version-action <a>    Below --min-claude-version: refuse to start or warn (default: refuse)
+  --request-timeout <ms>      Timeout for non-streaming API requests, 0 to disable (default: 30000)
+  --handshake-timeout <ms>    Drop connections stalled before their first request or WS upgrade (default: 10000)
+  --version-timeout <ms>      Kill a stalled claude --version or --help probe after this long (default: 5000)
+  --max-body-bytes <n>        Largest accepted request body (default: 10485760)
+  --max-line-bytes <n>        Longest Claude output line kept; longer ones are truncated (default: 10485760)
+  --soft-cancel-grace <ms>    How long a soft cancel waits before killing Claude (default: 10000)
+  --stdout-eof-grace <ms>     How long Claude may run after closing stdout before it is killed (default: 10000)
+  --max-output-rate <n>       Cancel sessions averaging more stdout bytes/sec over 10s (default: unlimited)
+  --data-dir <path>           Persist session records under <path>/sessions (default: off)
+  --flush-interval <ms>       Append persisted output to disk at least this often (default: 1000)
+  --flush-batch <n>           Append a session's persisted output once n lines are buffered (default: 100)
+  --max-output-lines <n>      Keep the last n output lines of each session in memory (default: 1000)
+  --disk-backed-output        Read output older than the in-memory lines back from <data-dir> (needs --data-dir)
+  --log-file <path>           Also append the server log to <path>, readable via GET /api/logs/tail
+  --ready-file <path>         Create <path> once listening, remove it on shutdown (default: <data-dir>/ready)
+  --model-fallback <m=f>      Retry a session once with model f when model m is overloaded; repeatable
+  --allowed-extra-args <f,..> Only accept these flags in a request's extra_args (default: any unreserved)
+  --deny-prompt <regex>       Refuse sessions whose prompt matches <regex>, ignoring case; repeatable
+  --prompt-prefix <text>      Put <text> and a blank line before every prompt unless a request opts out
+  --prompt-suffix <text>      Put a blank line and <text> after every prompt unless a request opts out
   --help                      Show this help message
   --version                   Show version number
 
@@ -101,6 +579,7 @@ Examples:
   claudia-server                                    # Start with default settings
   claudia-server --port 8080                       # Start on port 8080
   claudia-server --host localhost --port 3001      # Start on localhost:3001
+  claudia-server --host 127.0.0.1,::1 --port 3000  # Dual-stack loopback
   claudia-server --claude-binary /usr/bin/claude   # Use specific Claude binary
 
 Environment Variables:
@@ -108,15 +587,24 @@ Environment Variables:
   HOST                        Server host (overridden by --host)
   CLAUDE_BINARY               Claude binary path (overridden by --claude-binary)
   CLAUDE_HOME                 Claude home directory (overridden by --claude-home)
+  CLAUDIA_<SETTING>           Any configuration field in upper case, e.g. CLAUDIA_MAX_CONCURRENT_SESSIONS=5
+                              or CLAUDIA_KILL_ON_DROP=false (overridden by the matching option)
+  CLAUDIA_ADMIN_TOKEN         Bearer token for GET /api/logs/tail, which is off without one
 
 API Endpoints:
   GET  /                      Server info
+  GET  /api/status/ping       Latency probe
   GET  /api/status/health     Health check
   GET  /api/status/info       Detailed server info
   GET  /api/claude/version    Claude version info
+  GET  /api/claude/help       Claude CLI help and supported flags
   POST /api/claude/execute    Execute Claude Code
   POST /api/claude/continue   Continue Claude conversation
   POST /api/claude/resume     Resume Claude session
+  POST /api/estimate          Estimate prompt tokens and cost
+  POST /api/admin/readonly    Toggle read-only mode
+  POST /api/admin/drain-ws    Toggle refusing new WebSocket connections
+  GET  /api/logs/tail         Follow the server log file (SSE)
   GET  /api/projects          List projects
   POST /api/projects          Create project
   WS   /ws                    WebSocket for real-time streaming
@@ -140,13 +628,16 @@ async function main(): Promise<void> {
     // Parse command line arguments
     const cliConfig = parseArgs();
 
-    // Merge with environment variables
-    const config: Partial<ServerConfig> = {
-      port: cliConfig.port || (process.env.PORT ? parseInt(process.env.PORT, 10) : undefined),
-      host: cliConfig.host || process.env.HOST,
-      claude_binary_path: cliConfig.claude_binary_path || process.env.CLAUDE_BINARY,
-      claude_home_dir: cliConfig.claude_home_dir || process.env.CLAUDE_HOME,
-    };
+    // Command-line options override environment variables, which override defaults
+    const config: Partial<ServerConfig> = { ...configFromEnv(), ...cliConfig };
+    if (cliConfig.host || cliConfig.port) {
+      // Explicit --host/--port replace a listen list from the environment
+      config.listen = cliConfig.listen;
+    }
+
+    if (config.log_file) {
+      teeConsoleToFile(config.log_file);
+    }
 
     // Create and start server
     const server = new ClaudiaServer(config);
@@ -155,13 +646,24 @@ async function main(): Promise<void> {
     // Log configuration
     const serverConfig = server.getConfig();
     console.log('Server Configuration:');
-    console.log(`  Port: ${serverConfig.port}`);
-    console.log(`  Host: ${serverConfig.host}`);
+    console.log(`  Listen: ${server.getAddresses().map((a) => `${a.host}:${a.port}`).join(', ')}`);
     console.log(`  CORS Origins: ${serverConfig.cors_origin.join(', ')}`);
     console.log(`  Max Concurrent Sessions: ${serverConfig.max_concurrent_sessions}`);
+    if (serverConfig.max_concurrent_per_project) {
+      console.log(`  Max Concurrent Per Project: ${serverConfig.max_concurrent_per_project}`);
+    }
+    if (serverConfig.per_key_session_limit) {
+      console.log(`  Max Concurrent Per API Key: ${serverConfig.per_key_session_limit}`);
+    }
+    if (serverConfig.max_ws_connections) {
+      console.log(`  Max WebSocket Connections: ${serverConfig.max_ws_connections}`);
+    }
     if (serverConfig.claude_binary_path) {
       console.log(`  Claude Binary: ${serverConfig.claude_binary_path}`);
     }
+    if (serverConfig.wrapper_command?.length) {
+      console.log(`  Wrapper Command: ${serverConfig.wrapper_command.join(' ')}`);
+    }
     if (serverConfig.claude_home_dir) {
       console.log(`  Claude Home: ${serverConfig.claude_home_dir}`);
     }
@@ -0,0 +1,103 @@
+import { Router } from 'express';
+import type { ClaudeService } from '../services/claude.js';
+import type { WebSocketService } from '../services/websocket.js';
+import type { SuccessResponse, ErrorResponse } from '../types/index.js';
+
+/**
+ * Create an Express Router with operator endpoints.
+ *
+ * - GET  /readonly — whether new sessions are currently refused
+ * - POST /readonly — body `{ "read_only": true | false }`; toggles read-only mode at runtime.
+ *   While enabled, starting sessions fails with 503 and code `READ_ONLY`, but listing,
+ *   output, and cancel keep working, so running sessions can be drained before shutdown.
+ * - GET  /drain-ws — whether new WebSocket connections are refused, and how many are open
+ * - POST /drain-ws — body `{ "draining": true | false }`; while draining, new `/ws` upgrades are
+ *   answered with 503 but connected clients keep streaming until they disconnect.
+ *
+ * @returns An Express Router configured with the admin endpoints.
+ */
+export function createAdminRoutes(claudeService: ClaudeService, wsService: WebSocketService): Router {
+  const router = Router();
+
+  /**
+   * Get the read-only flag
+   */
+  router.get('/readonly', (req, res) => {
+    const response: SuccessResponse = {
+      success: true,
+      data: { read_only: claudeService.isReadOnly() },
+      timestamp: new Date().toISOString(),
+    };
+
+    res.json(response);
+  });
+
+  /**
+   * Set the read-only flag
+   */
+  router.post('/readonly', (req, res) => {
+    const readOnly = req.body?.read_only;
+    if (typeof readOnly !== 'boolean') {
+      const errorResponse: ErrorResponse = {
+        error: 'read_only must be a boolean',
+        code: 'VALIDATION_ERROR',
+        timestamp: new Date().toISOString(),
+      };
+      return res.status(400).json(errorResponse);
+    }
+
+    claudeService.setReadOnly(readOnly);
+    console.log(readOnly ? '🔒 Read-only mode enabled; new sessions are refused' : '🔓 Read-only mode disabled');
+
+    const response: SuccessResponse = {
+      success: true,
+      data: { read_only: readOnly },
+      timestamp: new Date().toISOString(),
+    };
+
+    res.json(response);
+  });
+
+  /**
+   * Get the WebSocket drain flag
+   */
+  router.get('/drain-ws', (req, res) => {
+    const response: SuccessResponse = {
+      success: true,
+      data: { draining: wsService.isDraining(), connections: wsService.getConnectedClientsCount() },
+      timestamp: new Date().toISOString(),
+    };
+
+    res.json(response);
+  });
+
+  /**
+   * Set the WebSocket drain flag
+   */
+  router.post('/drain-ws', (req, res) => {
+    const draining = req.body?.draining;
+    if (typeof draining !== 'boolean') {
+      const errorResponse: ErrorResponse = {
+        error: 'draining must be a boolean',
+        code: 'VALIDATION_ERROR',
+        timestamp: new Date().toISOString(),
+      };
+      return res.status(400).json(errorResponse);
+    }
+
+    wsService.setDraining(draining);
+    console.log(
+      draining ? '🚰 Draining WebSocket connections; new upgrades are refused' : '🔌 Accepting WebSocket connections again'
+    );
+
+    const response: SuccessResponse = {
+      success: true,
+      data: { draining, connections: wsService.getConnectedClientsCount() },
+      timestamp: new Date().toISOString(),
+    };
+
+    res.json(response);
+  });
+
+  return router;
+}
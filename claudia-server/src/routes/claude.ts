@@ -1,26 +1,66 @@
-import { Router } from 'express';
+import { Router, type Request, type Response } from 'express';
+import { promises as fs, createReadStream } from 'fs';
+import { pipeline } from 'stream/promises';
 import type { ClaudeService } from '../services/claude.js';
 import type { ProjectService } from '../services/project.js';
 import type { 
   ExecuteClaudeRequest, 
   ContinueClaudeRequest, 
   ResumeClaudeRequest,
+  CancelClaudeRequest,
+  RestartSessionRequest,
+  CompareRequest,
   SuccessResponse,
-  ErrorResponse
+  ErrorResponse,
+  SessionEvent,
+  SessionListFilter,
+  SessionStatus
 } from '../types/index.js';
+import { ServiceError, toErrorResponse } from '../utils/errors.js';
+import { openEventStream } from '../utils/sse.js';
+import type { EventStream } from '../utils/sse.js';
+import { markdownOfOutput, typedEventsOf, typedEventsOfOutput } from '../utils/stream-json.js';
+import { stripAnsi } from '../utils/ansi.js';
 
 /**
  * Creates an Express Router with endpoints for managing and interacting with Claude code executions.
  *
  * The router exposes these routes:
  * - GET  /version                        — check Claude code version/installation status
+ * - GET  /help                           — installed CLI's help text and parsed flags (cached per version)
  * - POST /execute                        — start a new Claude execution (requires project_path, prompt, model)
  * - POST /continue                       — continue an existing conversation (requires project_path, prompt, model)
  * - POST /resume                         — resume a session (requires project_path, session_id, prompt, model)
- * - POST /cancel/:sessionId              — cancel a running execution
+ * - POST /compare                        — run one prompt against two allowed binaries and return both outcomes
+ * - POST /cancel/:sessionId              — cancel a running execution; `{soft: true}` lets it finish the turn first
+ * - POST /sessions/terminate-older-than — cancel running sessions started more than `?minutes=` ago
+ * - POST /sessions/:sessionId/restart    — resume a finished session's conversation as a new session
+ * - POST /sessions/:sessionId/reset      — cancel a running session and start it afresh as a new session
+ * - GET  /sessions                       — list running and finished sessions; `format=ndjson` streams
+ *                                          one per line (filters: status, project_path, offset, limit)
  * - GET  /sessions/running               — list running Claude sessions
+ * - GET  /processes/stats                — running session counts and spawn queue depth
+ * - GET  /orphans                        — list untracked Claude processes from earlier runs
  * - GET  /sessions/:sessionId            — get session information
  * - GET  /sessions/:sessionId/history    — load session history/output
+ * - GET  /sessions/:sessionId/result     — final result text (and usage/cost) of a finished session
+ * - GET  /sessions/:sessionId/diff       — `git diff` and `git status` captured when the session ended
+ * - GET  /sessions/:sessionId/timeline   — every status the session went through, with timestamps
+ * - GET  /sessions/:sessionId/debug      — binary, argv, cwd, redacted env, exit status and output excerpts
+ * - GET  /sessions/:sessionId/command    — the launch as a shell-quoted command line to rerun by hand
+ * - GET  /sessions/:sessionId/tools      — tools the session called, with call counts
+ * - GET  /sessions/:sessionId/messages   — SSE feed of typed messages (`assistant`, `tool_use`,
+ *                                          `tool_result`, `result`, `raw`), then the lifecycle event
+ * - POST /sessions/output/batch         — new output lines of several sessions after a cursor each
+ * - GET  /sessions/:sessionId/output     — buffered output lines of a running or finished session;
+ *                                          `?parse=true` returns the typed messages rebuilt so far,
+ *                                          `?convert=markdown` a Markdown transcript,
+ *                                          `?stream=stdout|stderr` only that stream's lines;
+ *                                          ANSI escapes are removed unless `?ansi=preserve`
+ * - POST /sessions/:sessionId/input/file — stream a file under the allowed roots into the session's stdin
+ * - GET  /sessions/:sessionId/output/download — the buffered output as a log file, with Range support
+ *
+ * Wherever a route takes `:sessionId`, the `name` given when the session was started works too.
  *
  * All endpoints return a standardized SuccessResponse or ErrorResponse object with a timestamp and appropriate HTTP status codes for validation, not-found, and internal errors.
  *
@@ -32,6 +72,12 @@ export function createClaudeRoutes(
 ): Router {
   const router = Router();
 
+  // Every :sessionId also accepts the name a session was started with
+  router.param('sessionId', (req, res, next, value: string) => {
+    req.params.sessionId = claudeService.resolveSessionId(value);
+    next();
+  });
+
   /**
    * Check Claude Code version and installation status
    */
@@ -56,6 +102,75 @@ export function createClaudeRoutes(
     }
   });
 
+  /**
+   * Discover the Claude installations again and switch new sessions to the newest one
+   */
+  router.post('/reselect', async (req, res) => {
+    try {
+      const response: SuccessResponse = {
+        success: true,
+        data: await claudeService.reselectClaudeBinary(),
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'CLAUDE_RESELECT_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Run a prompt against two Claude installations side by side
+   */
+  router.post('/compare', async (req, res) => {
+    try {
+      const request = req.body as CompareRequest;
+
+      if (!request.binaries || !request.prompt || !request.model) {
+        const errorResponse: ErrorResponse = {
+          error: 'Missing required fields: binaries, prompt, model',
+          code: 'VALIDATION_ERROR',
+          timestamp: new Date().toISOString(),
+        };
+        return res.status(400).json(errorResponse);
+      }
+
+      const runs = await claudeService.compareInstallations(request, apiKeyOf(req));
+
+      const response: SuccessResponse = {
+        success: true,
+        data: { prompt: request.prompt, model: request.model, runs },
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'COMPARE_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Get the installed Claude CLI's help text and the flags it supports
+   */
+  router.get('/help', async (req, res) => {
+    try {
+      const help = await claudeService.getClaudeHelp();
+
+      const response: SuccessResponse = {
+        success: true,
+        data: help,
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'CLAUDE_HELP_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
   /**
    * Execute Claude Code with new prompt
    */
@@ -73,22 +188,18 @@ export function createClaudeRoutes(
         return res.status(400).json(errorResponse);
       }
 
-      const sessionId = await claudeService.executeClaudeCode(request);
+      const sessionId = await claudeService.executeClaudeCode(request, apiKeyOf(req));
       
       const response: SuccessResponse = {
         success: true,
-        data: { session_id: sessionId },
+        data: startedSession(claudeService, sessionId),
         timestamp: new Date().toISOString(),
       };
       
       res.json(response);
     } catch (error) {
-      const errorResponse: ErrorResponse = {
-        error: error instanceof Error ? error.message : 'Unknown error',
-        code: 'EXECUTION_ERROR',
-        timestamp: new Date().toISOString(),
-      };
-      res.status(500).json(errorResponse);
+      const { status, body } = toErrorResponse(error, 'EXECUTION_ERROR');
+      res.status(status).json(body);
     }
   });
 
@@ -109,22 +220,18 @@ export function createClaudeRoutes(
         return res.status(400).json(errorResponse);
       }
 
-      const sessionId = await claudeService.continueClaudeCode(request);
+      const sessionId = await claudeService.continueClaudeCode(request, apiKeyOf(req));
       
       const response: SuccessResponse = {
         success: true,
-        data: { session_id: sessionId },
+        data: startedSession(claudeService, sessionId),
         timestamp: new Date().toISOString(),
       };
       
       res.json(response);
     } catch (error) {
-      const errorResponse: ErrorResponse = {
-        error: error instanceof Error ? error.message : 'Unknown error',
-        code: 'EXECUTION_ERROR',
-        timestamp: new Date().toISOString(),
-      };
-      res.status(500).json(errorResponse);
+      const { status, body } = toErrorResponse(error, 'EXECUTION_ERROR');
+      res.status(status).json(body);
     }
   });
 
@@ -145,22 +252,18 @@ export function createClaudeRoutes(
         return res.status(400).json(errorResponse);
       }
 
-      const sessionId = await claudeService.resumeClaudeCode(request);
+      const sessionId = await claudeService.resumeClaudeCode(request, apiKeyOf(req));
       
       const response: SuccessResponse = {
         success: true,
-        data: { session_id: sessionId },
+        data: startedSession(claudeService, sessionId),
         timestamp: new Date().toISOString(),
       };
       
       res.json(response);
     } catch (error) {
-      const errorResponse: ErrorResponse = {
-        error: error instanceof Error ? error.message : 'Unknown error',
-        code: 'EXECUTION_ERROR',
-        timestamp: new Date().toISOString(),
-      };
-      res.status(500).json(errorResponse);
+      const { status, body } = toErrorResponse(error, 'EXECUTION_ERROR');
+      res.status(status).json(body);
     }
   });
 
@@ -170,7 +273,20 @@ export function createClaudeRoutes(
   router.post('/cancel/:sessionId', async (req, res) => {
     try {
       const { sessionId } = req.params;
-      const cancelled = await claudeService.cancelClaudeExecution(sessionId);
+      const { soft, grace_ms, keep_output } = (req.body ?? {}) as CancelClaudeRequest;
+
+      if ((soft !== undefined && typeof soft !== 'boolean') ||
+          (keep_output !== undefined && typeof keep_output !== 'boolean') ||
+          (grace_ms !== undefined && (typeof grace_ms !== 'number' || !(grace_ms >= 0)))) {
+        const errorResponse: ErrorResponse = {
+          error: 'soft and keep_output must be booleans and grace_ms a non-negative number',
+          code: 'VALIDATION_ERROR',
+          timestamp: new Date().toISOString(),
+        };
+        return res.status(400).json(errorResponse);
+      }
+
+      const cancelled = await claudeService.cancelClaudeExecution(sessionId, { soft, grace_ms, keep_output });
       
       const response: SuccessResponse = {
         success: true,
@@ -189,6 +305,127 @@ export function createClaudeRoutes(
     }
   });
 
+  /**
+   * Cancel every running session older than `?minutes=`, returning the cancelled IDs
+   */
+  router.post('/sessions/terminate-older-than', async (req, res) => {
+    try {
+      const minutes = req.query.minutes === undefined ? undefined : Number(req.query.minutes);
+      const terminated = await claudeService.terminateOlderThan(minutes);
+
+      const response: SuccessResponse = {
+        success: true,
+        data: { terminated },
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'CANCELLATION_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Start a new session resuming a finished one's conversation with its captured Claude session id
+   */
+  router.post('/sessions/:sessionId/restart', async (req, res) => {
+    try {
+      const { sessionId } = req.params;
+      const request = (req.body ?? {}) as RestartSessionRequest;
+      const newSessionId = await claudeService.restartSession(sessionId, request, apiKeyOf(req));
+
+      const response: SuccessResponse = {
+        success: true,
+        data: { ...startedSession(claudeService, newSessionId), restarted_from: sessionId },
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'EXECUTION_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Cancel a running session and start a new one with the same parameters
+   */
+  router.post('/sessions/:sessionId/reset', async (req, res) => {
+    try {
+      const { sessionId } = req.params;
+      const newSessionId = await claudeService.resetSession(sessionId, apiKeyOf(req));
+
+      const response: SuccessResponse = {
+        success: true,
+        data: { ...startedSession(claudeService, newSessionId), reset_from: sessionId },
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'EXECUTION_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * List running and finished sessions, as one JSON array or streamed as NDJSON
+   */
+  router.get('/sessions', async (req, res) => {
+    let filter: SessionListFilter;
+    try {
+      filter = parseSessionListQuery(req.query);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'SESSIONS_ERROR');
+      return res.status(status).json(body);
+    }
+
+    const sessions = claudeService.listSessions(filter);
+
+    if (req.query.format !== 'ndjson') {
+      const response: SuccessResponse = {
+        success: true,
+        data: Array.from(sessions),
+        timestamp: new Date().toISOString(),
+      };
+      return res.json(response);
+    }
+
+    res.status(200);
+    res.setHeader('Content-Type', 'application/x-ndjson');
+    res.setHeader('X-Accel-Buffering', 'no');
+
+    let closed = false;
+    req.on('close', () => {
+      closed = true;
+    });
+
+    for (const session of sessions) {
+      if (closed) {
+        return;
+      }
+      const drained = res.write(`${JSON.stringify(session)}\n`);
+      // flush() comes from the compression middleware, which would otherwise hold lines back;
+      // without it there is nothing buffered to push out
+      if (typeof res.flush === 'function') {
+        res.flush();
+      }
+      if (!drained) {
+        await new Promise<void>((resolve) => {
+          const resume = () => {
+            res.off('drain', resume);
+            req.off('close', resume);
+            resolve();
+          };
+          res.on('drain', resume);
+          req.on('close', resume);
+        });
+      }
+    }
+    res.end();
+  });
+
   /**
    * Get list of running Claude sessions
    */
@@ -213,6 +450,39 @@ export function createClaudeRoutes(
     }
   });
 
+  /**
+   * Running session counts and how many starts wait for the spawn rate limit
+   */
+  router.get('/processes/stats', (req, res) => {
+    const response: SuccessResponse = {
+      success: true,
+      data: claudeService.getProcessStats(),
+      timestamp: new Date().toISOString(),
+    };
+
+    res.json(response);
+  });
+
+  /**
+   * List Claude processes that look like sessions but are not tracked by this server
+   */
+  router.get('/orphans', async (req, res) => {
+    try {
+      const orphans = await claudeService.findOrphans();
+      
+      const response: SuccessResponse = {
+        success: true,
+        data: orphans,
+        timestamp: new Date().toISOString(),
+      };
+      
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'ORPHANS_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
   /**
    * Get session info by ID
    */
@@ -232,7 +502,7 @@ export function createClaudeRoutes(
       
       const response: SuccessResponse = {
         success: true,
-        data: sessionInfo,
+        data: { ...sessionInfo, throughput: claudeService.getSessionThroughput(sessionId) },
         timestamp: new Date().toISOString(),
       };
       
@@ -247,6 +517,437 @@ export function createClaudeRoutes(
     }
   });
 
+  /**
+   * Get the tools a session called
+   */
+  router.get('/sessions/:sessionId/tools', async (req, res) => {
+    try {
+      const { sessionId } = req.params;
+      const tools = claudeService.getSessionTools(sessionId);
+
+      if (!tools) {
+        const errorResponse: ErrorResponse = {
+          error: 'Session not found',
+          code: 'SESSION_NOT_FOUND',
+          timestamp: new Date().toISOString(),
+        };
+        return res.status(404).json(errorResponse);
+      }
+
+      const response: SuccessResponse = {
+        success: true,
+        data: {
+          session_id: sessionId,
+          tools,
+          total: tools.reduce((sum, tool) => sum + tool.count, 0),
+        },
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'SESSION_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Stream a session's messages as typed Server-Sent Events. Output buffered before the client
+   * connected is sent first; the stream ends with the session's terminal lifecycle event.
+   */
+  router.get('/sessions/:sessionId/messages', (req, res) => {
+    const { sessionId } = req.params;
+    const record = claudeService.getSessionRecord(sessionId);
+
+    if (!record) {
+      const errorResponse: ErrorResponse = {
+        error: 'Session not found',
+        code: 'SESSION_NOT_FOUND',
+        timestamp: new Date().toISOString(),
+      };
+      return res.status(404).json(errorResponse);
+    }
+
+    const sendMessage = (stream: EventStream, message: any) => {
+      for (const { event, data } of typedEventsOf(message)) {
+        stream.send(event, data);
+      }
+    };
+    const onStream = (data: { session_id: string; message: any }) => {
+      if (data.session_id === sessionId) {
+        sendMessage(stream, data.message);
+      }
+    };
+    const onOutput = (data: { session_id: string; data: string; timestamp: string }) => {
+      if (data.session_id === sessionId) {
+        stream.send('raw', { stream: 'stdout', content: data.data, timestamp: data.timestamp });
+      }
+    };
+    const onError = (data: { session_id: string; error: string; timestamp?: string }) => {
+      if (data.session_id === sessionId) {
+        const timestamp = data.timestamp ?? new Date().toISOString();
+        stream.send('raw', { stream: 'stderr', content: data.error, timestamp });
+      }
+    };
+    const onSessionEvent = (event: SessionEvent) => {
+      if (event.session_id === sessionId && event.event !== 'started') {
+        stream.send(event.event, event);
+        stream.close();
+      }
+    };
+
+    const stream = openEventStream(req, res, () => {
+      claudeService.off('claude_stream', onStream);
+      claudeService.off('claude_output', onOutput);
+      claudeService.off('claude_error', onError);
+      claudeService.off('session_event', onSessionEvent);
+    });
+
+    for (const { event, data } of typedEventsOfOutput(claudeService.visibleOutput(record))) {
+      stream.send(event, data);
+    }
+
+    if (record.status !== 'running') {
+      const event: SessionEvent = {
+        event: record.status,
+        session_id: sessionId,
+        project_path: record.info.project_path,
+        model: record.info.model,
+        exit_code: record.exit_code,
+        timestamp: record.ended_at ?? new Date().toISOString(),
+      };
+      stream.send(event.event, event);
+      stream.close();
+      return;
+    }
+
+    claudeService.on('claude_stream', onStream);
+    claudeService.on('claude_output', onOutput);
+    claudeService.on('claude_error', onError);
+    claudeService.on('session_event', onSessionEvent);
+  });
+
+  /**
+   * Get launch details and exit status for debugging a session
+   */
+  router.get('/sessions/:sessionId/debug', async (req, res) => {
+    try {
+      const { sessionId } = req.params;
+      const debug = claudeService.getSessionDebug(sessionId);
+
+      if (!debug) {
+        const errorResponse: ErrorResponse = {
+          error: 'Session not found',
+          code: 'SESSION_NOT_FOUND',
+          timestamp: new Date().toISOString(),
+        };
+        return res.status(404).json(errorResponse);
+      }
+
+      const response: SuccessResponse = {
+        success: true,
+        data: debug,
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'SESSION_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Get the launch command of a session as a line to paste into a shell
+   */
+  router.get('/sessions/:sessionId/command', async (req, res) => {
+    try {
+      const { sessionId } = req.params;
+      const command = claudeService.getSessionCommand(sessionId);
+
+      if (!command) {
+        const errorResponse: ErrorResponse = {
+          error: 'Session not found',
+          code: 'SESSION_NOT_FOUND',
+          timestamp: new Date().toISOString(),
+        };
+        return res.status(404).json(errorResponse);
+      }
+
+      const response: SuccessResponse = {
+        success: true,
+        data: command,
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'SESSION_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Feed a file to a running session's stdin
+   */
+  router.post('/sessions/:sessionId/input/file', async (req, res) => {
+    try {
+      const { sessionId } = req.params;
+      const result = await claudeService.streamFileToSession(sessionId, req.body?.path);
+
+      const response: SuccessResponse = {
+        success: true,
+        data: result,
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'INPUT_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Get the new output of several sessions in one round-trip
+   */
+  router.post('/sessions/output/batch', async (req, res) => {
+    try {
+      const response: SuccessResponse = {
+        success: true,
+        data: claudeService.getOutputBatch(req.body?.sessions),
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'SESSION_OUTPUT_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Get buffered output lines, or with `?parse=true` the typed events rebuilt from them so far
+   * (the same events the messages feed sends), for clients that poll instead of streaming.
+   * With disk_backed_output the lines older than the buffer are read back from the output log.
+   */
+  router.get('/sessions/:sessionId/output', async (req, res) => {
+    try {
+      const { sessionId } = req.params;
+      const record = claudeService.getSessionRecord(sessionId);
+      
+      if (!record) {
+        const errorResponse: ErrorResponse = {
+          error: 'Session not found',
+          code: 'SESSION_NOT_FOUND',
+          timestamp: new Date().toISOString(),
+        };
+        return res.status(404).json(errorResponse);
+      }
+      
+      const convert = req.query.convert ?? 'json';
+      if (convert !== 'json' && convert !== 'markdown') {
+        throw new ServiceError('convert must be json or markdown', 'VALIDATION_ERROR', 400);
+      }
+      const stream = req.query.stream;
+      if (stream !== undefined && stream !== 'stdout' && stream !== 'stderr') {
+        throw new ServiceError('stream must be stdout or stderr', 'VALIDATION_ERROR', 400);
+      }
+      const ansi = req.query.ansi ?? 'strip';
+      if (ansi !== 'strip' && ansi !== 'preserve') {
+        throw new ServiceError('ansi must be strip or preserve', 'VALIDATION_ERROR', 400);
+      }
+      const lines = (await claudeService.readSessionOutput(sessionId)) ?? claudeService.visibleOutput(record);
+      const selected = stream ? lines.filter((line) => line.stream === stream) : lines;
+      const output = ansi === 'strip'
+        ? selected.map((line) => ({ ...line, content: stripAnsi(line.content) }))
+        : selected;
+
+      if (convert === 'markdown') {
+        res.type('text/markdown; charset=utf-8');
+        return res.send(markdownOfOutput(output));
+      }
+
+      const filtered = record.output_filtered !== undefined ? { filtered: record.output_filtered } : {};
+      const data = req.query.parse === 'true'
+        ? { session_id: sessionId, status: record.status, events: typedEventsOfOutput(output), ...filtered }
+        : { session_id: sessionId, lines: output, ...filtered };
+      const response: SuccessResponse = {
+        success: true,
+        data,
+        timestamp: new Date().toISOString(),
+      };
+      
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'SESSION_OUTPUT_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Download a session's output as a log file. Honours a single-range `Range` header so
+   * interrupted downloads of large transcripts can be resumed; without one the log is streamed.
+   */
+  router.get('/sessions/:sessionId/output/download', async (req, res) => {
+    const { sessionId } = req.params;
+    const notFound = () => {
+      const errorResponse: ErrorResponse = {
+        error: 'Session not found',
+        code: 'SESSION_NOT_FOUND',
+        timestamp: new Date().toISOString(),
+      };
+      res.status(404).json(errorResponse);
+    };
+    const headers = {
+      'Content-Type': 'text/plain; charset=utf-8',
+      'Content-Disposition': `attachment; filename="${sessionId}.log"`,
+      'Accept-Ranges': 'bytes',
+      // Keep compression from rewriting byte ranges
+      'Cache-Control': 'no-transform',
+    };
+
+    try {
+      // A range needs the size of the log, so the log is written to a file and the range read
+      // from there; a persisted transcript can be far too large to hold in memory
+      if (req.headers.range !== undefined) {
+        const log = await claudeService.writeSessionLog(sessionId);
+        if (!log) {
+          return notFound();
+        }
+        try {
+          await sendLogRange(req, res.set(headers), log.path);
+        } finally {
+          await log.remove();
+        }
+        return;
+      }
+
+      const chunks = await claudeService.getSessionLog(sessionId);
+      if (!chunks) {
+        return notFound();
+      }
+      res.set(headers);
+      res.status(200);
+      for await (const chunk of chunks) {
+        if (!res.write(chunk)) {
+          await new Promise<void>((resolve) => {
+            const resume = () => {
+              res.off('drain', resume);
+              req.off('close', resume);
+              resolve();
+            };
+            res.on('drain', resume);
+            req.on('close', resume);
+          });
+        }
+        if (req.destroyed) {
+          return;
+        }
+      }
+      res.end();
+    } catch (error) {
+      if (res.headersSent) {
+        // The log failed partway through streaming; cut the download short rather than end it cleanly
+        return res.destroy();
+      }
+      const { status, body } = toErrorResponse(error, 'SESSION_OUTPUT_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Get the final result of a finished session
+   */
+  router.get('/sessions/:sessionId/result', async (req, res) => {
+    try {
+      const { sessionId } = req.params;
+      const record = claudeService.getSessionRecord(sessionId);
+      
+      if (!record) {
+        const errorResponse: ErrorResponse = {
+          error: 'Session not found',
+          code: 'SESSION_NOT_FOUND',
+          timestamp: new Date().toISOString(),
+        };
+        return res.status(404).json(errorResponse);
+      }
+
+      if (record.status === 'running') {
+        const errorResponse: ErrorResponse = {
+          error: 'Session is still running',
+          code: 'SESSION_RUNNING',
+          timestamp: new Date().toISOString(),
+        };
+        return res.status(409).json(errorResponse);
+      }
+      
+      const response: SuccessResponse = {
+        success: true,
+        data: {
+          session_id: sessionId,
+          status: record.status,
+          ...(record.terminated_by ? { terminated_by: record.terminated_by } : {}),
+          ...claudeService.getSessionResult(sessionId),
+        },
+        timestamp: new Date().toISOString(),
+      };
+      
+      res.json(response);
+    } catch (error) {
+      const errorResponse: ErrorResponse = {
+        error: error instanceof Error ? error.message : 'Unknown error',
+        code: 'SESSION_RESULT_ERROR',
+        timestamp: new Date().toISOString(),
+      };
+      res.status(500).json(errorResponse);
+    }
+  });
+
+  /**
+   * Get the project changes captured when a session started with capture_git_diff ended
+   */
+  router.get('/sessions/:sessionId/diff', (req, res) => {
+    try {
+      const { sessionId } = req.params;
+      const response: SuccessResponse = {
+        success: true,
+        data: { session_id: sessionId, ...claudeService.getGitDiff(sessionId) },
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'GIT_DIFF_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  /**
+   * Get the statuses a running or finished session went through, oldest first
+   */
+  router.get('/sessions/:sessionId/timeline', (req, res) => {
+    const { sessionId } = req.params;
+    const record = claudeService.getSessionRecord(sessionId);
+
+    if (!record) {
+      const errorResponse: ErrorResponse = {
+        error: 'Session not found',
+        code: 'SESSION_NOT_FOUND',
+        timestamp: new Date().toISOString(),
+      };
+      return res.status(404).json(errorResponse);
+    }
+
+    const response: SuccessResponse = {
+      success: true,
+      data: { session_id: sessionId, status: record.status, status_history: record.status_history ?? [] },
+      timestamp: new Date().toISOString(),
+    };
+
+    res.json(response);
+  });
+
   /**
    * Get session history/output
    */
@@ -273,4 +974,85 @@ export function createClaudeRoutes(
   });
 
   return router;
-}
\ No newline at end of file
+}
+
+const SESSION_STATUSES: SessionStatus[] = ['running', 'completed', 'cancelled', 'failed'];
+
+/**
+ * Validate the query of GET /sessions
+ */
+function parseSessionListQuery(query: Record<string, unknown>): SessionListFilter {
+  const { format, status, project_path, offset, limit } = query;
+
+  if (format !== undefined && format !== 'json' && format !== 'ndjson') {
+    throw new ServiceError('format must be json or ndjson', 'VALIDATION_ERROR', 400);
+  }
+  if (status !== undefined && !SESSION_STATUSES.includes(status as SessionStatus)) {
+    throw new ServiceError(`status must be one of ${SESSION_STATUSES.join(', ')}`, 'VALIDATION_ERROR', 400);
+  }
+  if (project_path !== undefined && typeof project_path !== 'string') {
+    throw new ServiceError('project_path must be a single path', 'VALIDATION_ERROR', 400);
+  }
+
+  const count = (name: string, value: unknown): number | undefined => {
+    if (value === undefined) {
+      return undefined;
+    }
+    if (typeof value !== 'string' || !/^\d+$/.test(value)) {
+      throw new ServiceError(`${name} must be a non-negative integer`, 'VALIDATION_ERROR', 400);
+    }
+    return parseInt(value, 10);
+  };
+
+  return {
+    status: status as SessionStatus | undefined,
+    project_path: project_path as string | undefined,
+    offset: count('offset', offset),
+    limit: count('limit', limit),
+  };
+}
+
+/**
+ * Response data for a started session: its id, plus any warnings raised while starting it
+ */
+function startedSession(claudeService: ClaudeService, sessionId: string): Record<string, unknown> {
+  const warnings = claudeService.getSessionRecord(sessionId)?.info.warnings;
+  return warnings ? { session_id: sessionId, warnings } : { session_id: sessionId };
+}
+
+/**
+ * Answer a request with a `Range` header from a log file: the single requested range with 206,
+ * 416 when it lies beyond the end, and the whole file for a malformed header or several ranges.
+ * The file is streamed, never read into memory.
+ */
+async function sendLogRange(req: Request, res: Response, path: string): Promise<void> {
+  const { size } = await fs.stat(path);
+  const ranges = req.range(size);
+  if (ranges === -1) {
+    res.set('Content-Range', `bytes */${size}`).status(416).end();
+    return;
+  }
+
+  let start = 0;
+  let end = size - 1;
+  if (Array.isArray(ranges) && ranges.type === 'bytes' && ranges.length === 1) {
+    ({ start, end } = ranges[0]);
+    res.status(206).set('Content-Range', `bytes ${start}-${end}/${size}`);
+  } else {
+    res.status(200);
+  }
+  res.set('Content-Length', String(end - start + 1));
+  if (end < start) {
+    // An empty log; createReadStream has no empty range
+    res.end();
+    return;
+  }
+  await pipeline(createReadStream(path, { start, end }), res);
+}
+
+/**
+ * The API key a client identified itself with in the `X-API-Key` header, if any
+ */
+function apiKeyOf(req: Request): string | undefined {
+  return req.get('x-api-key') || undefined;
+}
@@ -0,0 +1,78 @@
+import { Router } from 'express';
+import { promises as fs } from 'fs';
+import { resolve } from 'path';
+import type { EstimateRequest, ModelPricing, SuccessResponse, ErrorResponse } from '../types/index.js';
+import { toErrorResponse, ServiceError } from '../utils/errors.js';
+import { estimateTokens, pricingFor } from '../utils/tokens.js';
+
+/**
+ * Create an Express Router that estimates the size and cost of a prompt without running Claude.
+ *
+ * - POST / — takes `prompt`, `model`, and optional `context_files` (resolved against
+ *   `project_path`) and `expected_output_tokens`; returns approximate input tokens and the
+ *   estimated cost in USD from `pricing`, or null cost for models without pricing.
+ *
+ * @returns An Express Router configured with the estimate endpoint.
+ */
+export function createEstimateRoutes(pricing: Record<string, ModelPricing>): Router {
+  const router = Router();
+
+  /**
+   * Estimate tokens and cost for a prompt
+   */
+  router.post('/', async (req, res) => {
+    try {
+      const request = req.body as EstimateRequest;
+
+      if (typeof request.prompt !== 'string' || !request.model) {
+        const errorResponse: ErrorResponse = {
+          error: 'Missing required fields: prompt, model',
+          code: 'VALIDATION_ERROR',
+          timestamp: new Date().toISOString(),
+        };
+        return res.status(400).json(errorResponse);
+      }
+
+      const files = [];
+      for (const file of request.context_files ?? []) {
+        const path = resolve(request.project_path ?? process.cwd(), file);
+        let content: string;
+        try {
+          content = await fs.readFile(path, 'utf8');
+        } catch {
+          throw new ServiceError(`Cannot read context file: ${file}`, 'VALIDATION_ERROR', 400, { path });
+        }
+        files.push({ path, tokens: estimateTokens(content) });
+      }
+
+      const promptTokens = estimateTokens(request.prompt);
+      const inputTokens = files.reduce((sum, file) => sum + file.tokens, promptTokens);
+      const outputTokens = request.expected_output_tokens ?? 0;
+      const modelPricing = pricingFor(request.model, pricing);
+      const cost = modelPricing
+        ? (inputTokens * modelPricing.input_per_mtok + outputTokens * modelPricing.output_per_mtok) / 1_000_000
+        : null;
+
+      const response: SuccessResponse = {
+        success: true,
+        data: {
+          model: request.model,
+          prompt_tokens: promptTokens,
+          context_files: files,
+          input_tokens: inputTokens,
+          output_tokens: outputTokens,
+          estimated_cost_usd: cost,
+          pricing: modelPricing ?? null,
+        },
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'ESTIMATE_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  return router;
+}
@@ -0,0 +1,31 @@
+import { Router } from 'express';
+import type { ClaudeService } from '../services/claude.js';
+import type { SessionEvent } from '../types/index.js';
+import { openEventStream } from '../utils/sse.js';
+
+/**
+ * Create an Express Router serving the server-wide event stream.
+ *
+ * - GET / — Server-Sent Events feed of lifecycle events (`started`, `completed`,
+ *   `cancelled`, `failed`) for every session. Each SSE `event:` is the lifecycle
+ *   name and `data:` is the SessionEvent JSON. Output lines are not included.
+ *
+ * @returns An Express Router configured with the event stream endpoint.
+ */
+export function createEventRoutes(claudeService: ClaudeService): Router {
+  const router = Router();
+
+  /**
+   * Stream lifecycle events for all sessions
+   */
+  router.get('/', (req, res) => {
+    const listener = (event: SessionEvent) => stream.send(event.event, event);
+    const stream = openEventStream(req, res, () => {
+      claudeService.off('session_event', listener);
+    });
+
+    claudeService.on('session_event', listener);
+  });
+
+  return router;
+}
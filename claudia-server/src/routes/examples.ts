@@ -0,0 +1,149 @@
+import { Router } from 'express';
+import type { Request } from 'express';
+import type { ApiExample, ListenAddress, SuccessResponse, ErrorResponse } from '../types/index.js';
+
+/**
+ * A Host header that is safe to paste into a shell or JavaScript string
+ */
+const HOST_HEADER = /^[A-Za-z0-9.\-]+(:\d+)?$|^\[[0-9A-Fa-f:.]+\](:\d+)?$/;
+
+/**
+ * Usage examples; `base` is the server's HTTP origin and `wsBase` its WebSocket origin
+ */
+const EXAMPLES: {
+  name: string;
+  description: string;
+  render: (base: string, wsBase: string) => Pick<ApiExample, 'curl' | 'javascript'>;
+}[] = [
+  {
+    name: 'health',
+    description: 'Check that the server is up',
+    render: (base) => ({
+      curl: `curl ${base}/api/status/health`,
+      javascript: `const response = await fetch('${base}/api/status/health');
+console.log(await response.json());`,
+    }),
+  },
+  {
+    name: 'execute',
+    description: 'Start a new Claude session',
+    render: (base) => ({
+      curl: `curl -X POST ${base}/api/claude/execute \\
+  -H "Content-Type: application/json" \\
+  -d '{"project_path": "/path/to/project", "prompt": "Help me write a REST API", "model": "sonnet"}'`,
+      javascript: `const response = await fetch('${base}/api/claude/execute', {
+  method: 'POST',
+  headers: { 'Content-Type': 'application/json' },
+  body: JSON.stringify({ project_path: '/path/to/project', prompt: 'Help me write a REST API', model: 'sonnet' }),
+});
+const { data } = await response.json();
+console.log(data.session_id);`,
+    }),
+  },
+  {
+    name: 'running-sessions',
+    description: 'List running sessions',
+    render: (base) => ({
+      curl: `curl ${base}/api/claude/sessions/running`,
+      javascript: `const response = await fetch('${base}/api/claude/sessions/running');
+console.log((await response.json()).data);`,
+    }),
+  },
+  {
+    name: 'stream-session',
+    description: 'Follow the messages of a running session',
+    render: (base, wsBase) => ({
+      curl: `curl -N ${base}/api/claude/sessions/SESSION_ID/messages`,
+      javascript: `const ws = new WebSocket('${wsBase}/ws');
+ws.onopen = () => ws.send(JSON.stringify({ type: 'subscribe', session_id: 'SESSION_ID' }));
+ws.onmessage = (event) => console.log(JSON.parse(event.data));`,
+    }),
+  },
+];
+
+/**
+ * Create an Express Router serving copy-pasteable curl and JavaScript examples.
+ *
+ * - GET /examples      — every example
+ * - GET /example/:name — one example by name
+ *
+ * Snippets use the origin the client reached the server on (its Host header), falling back
+ * to `fallbackAddress` when the header is missing or unusable. When `/ws` is served on its own
+ * address, `wsAddress` gives it and WebSocket snippets use its port.
+ *
+ * @returns An Express Router configured with the example endpoints.
+ */
+export function createExampleRoutes(
+  fallbackAddress: () => ListenAddress,
+  wsAddress: () => ListenAddress | undefined = () => undefined
+): Router {
+  const router = Router();
+
+  const renderAll = (req: Request): ApiExample[] => {
+    const origin = originOf(req, fallbackAddress());
+    const wsUrl = new URL(origin.replace(/^http/, 'ws'));
+    const ws = wsAddress();
+    if (ws) {
+      wsUrl.port = String(ws.port);
+    }
+    const wsOrigin = wsUrl.origin;
+    return EXAMPLES.map(({ name, description, render }) => ({
+      name,
+      description,
+      ...render(origin, wsOrigin),
+    }));
+  };
+
+  /**
+   * List all examples
+   */
+  router.get('/examples', (req, res) => {
+    const response: SuccessResponse = {
+      success: true,
+      data: renderAll(req),
+      timestamp: new Date().toISOString(),
+    };
+
+    res.json(response);
+  });
+
+  /**
+   * Get one example by name
+   */
+  router.get('/example/:name', (req, res) => {
+    const example = renderAll(req).find((candidate) => candidate.name === req.params.name);
+
+    if (!example) {
+      const errorResponse: ErrorResponse = {
+        error: 'Example not found',
+        code: 'EXAMPLE_NOT_FOUND',
+        timestamp: new Date().toISOString(),
+        details: { available: EXAMPLES.map(({ name }) => name) },
+      };
+      return res.status(404).json(errorResponse);
+    }
+
+    const response: SuccessResponse = {
+      success: true,
+      data: example,
+      timestamp: new Date().toISOString(),
+    };
+
+    res.json(response);
+  });
+
+  return router;
+}
+
+/**
+ * The HTTP origin a request was made to, or the bound address when its Host header cannot be used
+ */
+function originOf(req: Request, fallback: ListenAddress): string {
+  const host = req.get('host');
+  if (host && HOST_HEADER.test(host)) {
+    return `${req.protocol}://${host}`;
+  }
+
+  const hostname = fallback.host === '0.0.0.0' || fallback.host === '::' ? 'localhost' : fallback.host;
+  return `http://${hostname.includes(':') ? `[${hostname}]` : hostname}:${fallback.port}`;
+}
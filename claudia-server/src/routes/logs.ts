@@ -0,0 +1,45 @@
+import { Router } from 'express';
+import type { ErrorResponse } from '../types/index.js';
+import { requireAdminToken } from '../utils/admin-token.js';
+import { followFile } from '../utils/log-file.js';
+import { openEventStream } from '../utils/sse.js';
+
+/**
+ * Create an Express Router for reading the server's own log. The log can hold prompts and
+ * paths, so every endpoint requires the admin token.
+ *
+ * - GET /tail — Server-Sent Events feed of lines appended to `log_file` from the time of the
+ *   request on, one `line` event per line with `{ "line": "..." }` as data. Answers 409 when
+ *   the server has no log file configured.
+ *
+ * @returns An Express Router configured with the log endpoints.
+ */
+export function createLogRoutes(logFile: string | undefined, adminToken: string | undefined): Router {
+  const router = Router();
+  router.use(requireAdminToken(adminToken));
+
+  /**
+   * Follow the log file
+   */
+  router.get('/tail', async (req, res) => {
+    if (!logFile) {
+      const errorResponse: ErrorResponse = {
+        error: 'The server is not writing a log file',
+        code: 'LOG_FILE_DISABLED',
+        timestamp: new Date().toISOString(),
+      };
+      return res.status(409).json(errorResponse);
+    }
+
+    // Find the end of the file before answering, so lines appended once the client sees the
+    // response are never missed
+    const stop = await followFile(logFile, (line) => stream.send('line', { line }));
+    if (req.socket.destroyed) {
+      stop();
+      return;
+    }
+    const stream = openEventStream(req, res, stop);
+  });
+
+  return router;
+}
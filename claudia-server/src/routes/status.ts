@@ -1,37 +1,78 @@
 import { Router } from 'express';
-import type { SuccessResponse } from '../types/index.js';
+import type { HealthCheck, SuccessResponse } from '../types/index.js';
+import type { ClaudeService } from '../services/claude.js';
+import type { WebhookService } from '../services/webhooks.js';
+import { runHealthChecks, worstStatus } from '../services/health.js';
 import { homedir } from 'os';
 import { join } from 'path';
 
 /**
  * Create an Express Router with status-related endpoints.
  *
- * Exposes three GET endpoints:
- * - GET /health: returns runtime health data (status, uptime, memory usage, Node version) and a timestamp.
+ * Exposes four GET endpoints:
+ * - GET /ping: trivially cheap liveness/latency probe that does no IO.
+ * - GET /health: returns runtime health data (status, uptime, memory usage, Node version, persistence) and a timestamp. Status is `degraded` when session persistence writes are failing, and `read_only` reports whether new sessions are refused. With `?deep=true` it also runs every subsystem check (Claude binary, data directory, persistence, concurrency, webhooks), reports each as `ok`, `degraded` or `fail` under `checks`, and takes the worst as the overall status, answering 503 when it is `unhealthy`.
  * - GET /info: returns server metadata (name, version, description) and runtime/environment details (node version, platform, architecture, pid, cwd, claude_home) with a timestamp.
  * - GET /home: returns the current user's home directory and the server's Claude-specific directory path with a timestamp.
  *
  * @returns An Express Router configured with the above endpoints.
  */
-export function createStatusRoutes(): Router {
+export function createStatusRoutes(claudeService: ClaudeService, webhookService?: WebhookService): Router {
   const router = Router();
 
+  /**
+   * Latency probe; deliberately does no work beyond building the response
+   */
+  router.get('/ping', (req, res) => {
+    const response: SuccessResponse = {
+      success: true,
+      data: { pong: true },
+      timestamp: new Date().toISOString(),
+    };
+
+    res.json(response);
+  });
+
   /**
    * Health check endpoint
    */
-  router.get('/health', (req, res) => {
+  router.get('/health', async (req, res) => {
+    const persistence = claudeService.getPersistenceStatus();
+    const reasons: string[] = [];
+    if (persistence.degraded) {
+      reasons.push(`persistence writes failing: ${persistence.last_error}`);
+    }
+
+    let status = reasons.length > 0 ? 'degraded' : 'healthy';
+    let checks: Record<string, HealthCheck> | undefined;
+    if (req.query.deep === 'true' || req.query.deep === '1') {
+      checks = await runHealthChecks(claudeService, webhookService);
+      // A persistence failure is already among the reasons
+      for (const [name, check] of Object.entries(checks)) {
+        if (name !== 'persistence' && !check.ok) {
+          reasons.push(check.message);
+        }
+      }
+      const worst = worstStatus(Object.values(checks));
+      status = worst === 'fail' ? 'unhealthy' : reasons.length > 0 ? 'degraded' : 'healthy';
+    }
+
     const response: SuccessResponse = {
       success: true,
       data: {
-        status: 'healthy',
+        status,
+        ...(reasons.length > 0 ? { reasons } : {}),
         uptime: process.uptime(),
         memory: process.memoryUsage(),
         version: process.version,
+        persistence,
+        read_only: claudeService.isReadOnly(),
+        ...(checks ? { checks } : {}),
       },
       timestamp: new Date().toISOString(),
     };
     
-    res.json(response);
+    res.status(status === 'unhealthy' ? 503 : 200).json(response);
   });
 
   /**
@@ -0,0 +1,75 @@
+import { Router } from 'express';
+import type { ClaudeService } from '../services/claude.js';
+import type { SuccessResponse, UsageGroupBy, UsageQuery } from '../types/index.js';
+import { ServiceError, toErrorResponse } from '../utils/errors.js';
+
+const GROUP_BY: UsageGroupBy[] = ['project', 'model', 'label', 'tenant'];
+
+/**
+ * Create an Express Router reporting token usage and cost across sessions.
+ *
+ * - GET / — totals of input, output and cache tokens and cost in USD per `group_by` value
+ *   (`project`, `model`, `label` with `label=<name>`, or `tenant`), over the stored sessions
+ *   started between the optional ISO 8601 `from` (inclusive) and `to` (exclusive).
+ *
+ * @returns An Express Router configured with the usage endpoint.
+ */
+export function createUsageRoutes(claudeService: ClaudeService): Router {
+  const router = Router();
+
+  /**
+   * Grouped usage totals
+   */
+  router.get('/', (req, res) => {
+    try {
+      const query = parseUsageQuery(req.query);
+      const response: SuccessResponse = {
+        success: true,
+        data: {
+          group_by: query.group_by,
+          ...(query.label ? { label: query.label } : {}),
+          ...(query.from ? { from: query.from.toISOString() } : {}),
+          ...(query.to ? { to: query.to.toISOString() } : {}),
+          groups: claudeService.getUsage(query),
+        },
+        timestamp: new Date().toISOString(),
+      };
+
+      res.json(response);
+    } catch (error) {
+      const { status, body } = toErrorResponse(error, 'USAGE_ERROR');
+      res.status(status).json(body);
+    }
+  });
+
+  return router;
+}
+
+function parseUsageQuery(query: Record<string, unknown>): UsageQuery {
+  const { group_by = 'project', label, from, to } = query;
+
+  if (!GROUP_BY.includes(group_by as UsageGroupBy)) {
+    throw new ServiceError(`group_by must be one of ${GROUP_BY.join(', ')}`, 'VALIDATION_ERROR', 400);
+  }
+  if (group_by === 'label' && (typeof label !== 'string' || !label)) {
+    throw new ServiceError('label is required with group_by=label', 'VALIDATION_ERROR', 400);
+  }
+
+  const time = (name: string, value: unknown): Date | undefined => {
+    if (value === undefined) {
+      return undefined;
+    }
+    const date = typeof value === 'string' ? new Date(value) : undefined;
+    if (!date || Number.isNaN(date.getTime())) {
+      throw new ServiceError(`${name} must be an ISO 8601 time`, 'VALIDATION_ERROR', 400);
+    }
+    return date;
+  };
+
+  return {
+    group_by: group_by as UsageGroupBy,
+    ...(group_by === 'label' ? { label: label as string } : {}),
+    from: time('from', from),
+    to: time('to', to),
+  };
+}
@@ -3,44 +3,202 @@ import cors from 'cors';
 import helmet from 'helmet';
 import compression from 'compression';
 import morgan from 'morgan';
-import { createServer } from 'http';
+import { promises as fs } from 'fs';
+import { createServer, Server } from 'http';
+import type { IncomingMessage } from 'http';
+import type { AddressInfo, Socket } from 'net';
+import { dirname, join } from 'path';
+import type { Duplex } from 'stream';
 import { ClaudeService } from './services/claude.js';
 import { ProjectService } from './services/project.js';
 import { WebSocketService } from './services/websocket.js';
+import { WebhookService } from './services/webhooks.js';
+import { MemoryGuard } from './services/memory-guard.js';
 import { createClaudeRoutes } from './routes/claude.js';
 import { createProjectRoutes } from './routes/projects.js';
 import { createStatusRoutes } from './routes/status.js';
-import type { ServerConfig, ErrorResponse } from './types/index.js';
+import { createEventRoutes } from './routes/events.js';
+import { createEstimateRoutes } from './routes/estimate.js';
+import { createUsageRoutes } from './routes/usage.js';
+import { createAdminRoutes } from './routes/admin.js';
+import { createLogRoutes } from './routes/logs.js';
+import { createExampleRoutes } from './routes/examples.js';
+import { DEFAULT_MODEL_PRICING } from './utils/tokens.js';
+import type { ServerConfig, ErrorResponse, ListenAddress } from './types/index.js';
+
+/**
+ * Long-lived streaming endpoints that must not be cut off by the request timeout
+ */
+const STREAMING_PATHS = [
+  /^\/api\/events(\/|\?|$)/,
+  /^\/api\/claude\/sessions\/[^/]+\/messages(\?|$)/,
+  /^\/api\/claude\/sessions\?(.*&)?format=ndjson(&|$)/,
+  /^\/api\/logs\/tail(\?|$)/,
+];
 
 /**
  * Main Claudia Server class
  */
 export class ClaudiaServer {
   private app: express.Application;
-  private server: any;
+  private servers: Server[];
+  private wsServers: Server[];
   private config: ServerConfig;
   private claudeService: ClaudeService;
   private projectService: ProjectService;
   private wsService: WebSocketService;
+  private memoryGuard?: MemoryGuard;
+  private webhookService: WebhookService;
 
   constructor(config: Partial<ServerConfig> = {}) {
+    const port = config.listen?.[0]?.port ?? (config.port || 3000);
+    const host = config.listen?.[0]?.host || config.host || '0.0.0.0';
+
     this.config = {
-      port: config.port || 3000,
-      host: config.host || '0.0.0.0',
+      port,
+      host,
+      listen: config.listen && config.listen.length > 0 ? config.listen : [{ host, port }],
+      ws_listen: config.ws_listen ?? [],
       cors_origin: config.cors_origin || ['http://localhost:3000'],
       max_concurrent_sessions: config.max_concurrent_sessions || 10,
+      max_concurrent_per_project: config.max_concurrent_per_project || 0,
       session_timeout_ms: config.session_timeout_ms || 300000, // 5 minutes
       claude_binary_path: config.claude_binary_path,
       claude_home_dir: config.claude_home_dir,
+      wrapper_command: config.wrapper_command ?? [],
+      skip_permissions: config.skip_permissions ?? true,
+      kill_on_drop: config.kill_on_drop ?? true,
+      reap_orphans_on_start: config.reap_orphans_on_start ?? false,
+      output_charset_fallback: config.output_charset_fallback ?? 'replace',
+      tee_output_to_stdout: config.tee_output_to_stdout ?? false,
+      output_filters: config.output_filters ?? [],
+      count_filtered_output: config.count_filtered_output ?? false,
+      request_timeout_ms: config.request_timeout_ms ?? 30000,
+      handshake_timeout_ms: config.handshake_timeout_ms ?? 10000,
+      max_body_bytes: config.max_body_bytes || 10 * 1024 * 1024,
+      data_dir: config.data_dir,
+      output_flush_interval_ms: config.output_flush_interval_ms || 1000,
+      output_flush_batch_lines: config.output_flush_batch_lines || 100,
+      max_output_lines: config.max_output_lines || 1000,
+      disk_backed_output: config.disk_backed_output ?? false,
+      log_file: config.log_file,
+      admin_token: config.admin_token,
+      ready_file: config.ready_file ?? (config.data_dir ? join(config.data_dir, 'ready') : undefined),
+      model_fallback: config.model_fallback ?? {},
+      model_pricing: config.model_pricing ?? DEFAULT_MODEL_PRICING,
+      max_stored_sessions: config.max_stored_sessions || 0,
+      verbose: config.verbose ?? true,
+      preview_interval_min_ms: config.preview_interval_min_ms || 1000,
+      preview_interval_max_ms: config.preview_interval_max_ms || 16000,
+      allowed_extra_args: config.allowed_extra_args,
+      spawn_rate_limit: config.spawn_rate_limit || 0,
+      max_queue_depth: config.max_queue_depth || 0,
+      read_only: config.read_only ?? false,
+      reject_concurrent_same_project: config.reject_concurrent_same_project ?? false,
+      per_key_session_limit: config.per_key_session_limit || 0,
+      max_ws_connections: config.max_ws_connections || 0,
+      ws_idle_timeout_seconds: config.ws_idle_timeout_seconds || 0,
+      version_timeout_ms: config.version_timeout_ms || 5000,
+      memory_limit_mb: config.memory_limit_mb || 0,
+      memory_soft_limit_mb: config.memory_soft_limit_mb || 0,
+      cgroup_root: config.cgroup_root,
+      record_git_info: config.record_git_info ?? false,
+      result_output_roots: config.result_output_roots,
+      input_file_roots: config.input_file_roots,
+      max_input_file_bytes: config.max_input_file_bytes || 1024 * 1024,
+      additional_dir_roots: config.additional_dir_roots,
+      resume_running: config.resume_running || 'reject',
+      min_claude_version: config.min_claude_version,
+      min_claude_version_action: config.min_claude_version_action || 'refuse',
+      compare_binaries: config.compare_binaries || [],
+      max_line_bytes: config.max_line_bytes || 10 * 1024 * 1024,
+      soft_cancel_grace_ms: config.soft_cancel_grace_ms || 10000,
+      stdout_eof_grace_ms: config.stdout_eof_grace_ms || 10000,
+      max_output_bytes_per_sec: config.max_output_bytes_per_sec || 0,
+      webhooks: config.webhooks ?? [],
+      label_rules: config.label_rules ?? [],
+      label_arg_rules: config.label_arg_rules ?? [],
+      prompt_denylist: config.prompt_denylist ?? [],
+      prompt_prefix: config.prompt_prefix,
+      prompt_suffix: config.prompt_suffix,
     };
 
     this.app = express();
-    this.server = createServer(this.app);
+    this.servers = this.config.listen.map(() => this.createListener());
+    this.wsServers = this.config.ws_listen.map(() => this.createWebSocketListener());
 
     // Initialize services
-    this.claudeService = new ClaudeService(this.config.claude_binary_path);
+    this.claudeService = new ClaudeService(this.config.claude_binary_path, {
+      skip_permissions: this.config.skip_permissions,
+      verbose: this.config.verbose,
+      kill_on_drop: this.config.kill_on_drop,
+      output_charset_fallback: this.config.output_charset_fallback,
+      tee_output_to_stdout: this.config.tee_output_to_stdout,
+      wrapper_command: this.config.wrapper_command,
+      output_filters: this.config.output_filters,
+      count_filtered_output: this.config.count_filtered_output,
+      max_concurrent_sessions: this.config.max_concurrent_sessions,
+      max_concurrent_per_project: this.config.max_concurrent_per_project,
+      data_dir: this.config.data_dir,
+      output_flush_interval_ms: this.config.output_flush_interval_ms,
+      output_flush_batch_lines: this.config.output_flush_batch_lines,
+      max_output_lines: this.config.max_output_lines,
+      disk_backed_output: this.config.disk_backed_output,
+      model_fallback: this.config.model_fallback,
+      max_stored_sessions: this.config.max_stored_sessions,
+      preview_interval_min_ms: this.config.preview_interval_min_ms,
+      preview_interval_max_ms: this.config.preview_interval_max_ms,
+      allowed_extra_args: this.config.allowed_extra_args,
+      spawn_rate_limit: this.config.spawn_rate_limit,
+      max_queue_depth: this.config.max_queue_depth,
+      read_only: this.config.read_only,
+      reject_concurrent_same_project: this.config.reject_concurrent_same_project,
+      per_key_session_limit: this.config.per_key_session_limit,
+      version_timeout_ms: this.config.version_timeout_ms,
+      memory_limit_mb: this.config.memory_limit_mb,
+      cgroup_root: this.config.cgroup_root,
+      record_git_info: this.config.record_git_info,
+      result_output_roots: this.config.result_output_roots,
+      input_file_roots: this.config.input_file_roots,
+      max_input_file_bytes: this.config.max_input_file_bytes,
+      additional_dir_roots: this.config.additional_dir_roots,
+      resume_running: this.config.resume_running,
+      compare_binaries: this.config.compare_binaries,
+      max_line_bytes: this.config.max_line_bytes,
+      soft_cancel_grace_ms: this.config.soft_cancel_grace_ms,
+      stdout_eof_grace_ms: this.config.stdout_eof_grace_ms,
+      max_output_bytes_per_sec: this.config.max_output_bytes_per_sec,
+      label_rules: this.config.label_rules,
+      label_arg_rules: this.config.label_arg_rules,
+      prompt_denylist: this.config.prompt_denylist,
+      prompt_prefix: this.config.prompt_prefix,
+      prompt_suffix: this.config.prompt_suffix,
+    });
+    this.webhookService = new WebhookService(this.config.webhooks);
+    this.webhookService.attach(this.claudeService);
+    if (this.config.memory_soft_limit_mb > 0) {
+      this.memoryGuard = new MemoryGuard(this.claudeService, this.config.memory_soft_limit_mb);
+    }
     this.projectService = new ProjectService(this.config.claude_home_dir);
-    this.wsService = new WebSocketService(this.server);
+    this.wsService = new WebSocketService();
+    this.wsService.setMaxConnections(this.config.max_ws_connections);
+    this.wsService.setIdleTimeout(this.config.ws_idle_timeout_seconds);
+    // With separate WebSocket addresses, the API listeners refuse upgrades
+    if (this.wsServers.length > 0) {
+      for (const server of this.wsServers) {
+        this.wsService.attach(server);
+      }
+      for (const server of this.servers) {
+        server.on('upgrade', (req: IncomingMessage, socket: Duplex) => {
+          socket.write('HTTP/1.1 404 Not Found\r\nConnection: close\r\n\r\n');
+          socket.destroy();
+        });
+      }
+    } else {
+      for (const server of this.servers) {
+        this.wsService.attach(server);
+      }
+    }
 
     this.setupMiddleware();
     this.setupRoutes();
@@ -66,22 +224,132 @@ export class ClaudiaServer {
     // Logging
     this.app.use(morgan('combined'));
 
+    // Request timeout (registered before body parsing so slow uploads count against it)
+    if (this.config.request_timeout_ms > 0) {
+      this.app.use('/api', this.requestTimeout());
+    }
+
     // Body parsing
-    this.app.use(express.json({ limit: '10mb' }));
-    this.app.use(express.urlencoded({ extended: true, limit: '10mb' }));
+    this.app.use(express.json({ limit: this.config.max_body_bytes }));
+    this.app.use(express.urlencoded({ extended: true, limit: this.config.max_body_bytes }));
+  }
 
-    // Request timeout
-    this.app.use((req, res, next) => {
-      req.setTimeout(30000); // 30 seconds
-      next();
+  /**
+   * Create an HTTP listener whose request timeout matches the configured one,
+   * so clients that trickle headers or bodies are disconnected
+   */
+  private createListener(): Server {
+    const timeout = this.config.request_timeout_ms;
+    if (timeout <= 0) {
+      return this.limitHandshake(createServer(this.app));
+    }
+
+    const server = createServer({ connectionsCheckingInterval: Math.min(timeout, 30000) }, this.app);
+    server.headersTimeout = Math.min(server.headersTimeout, timeout);
+    server.requestTimeout = timeout;
+    return this.limitHandshake(server);
+  }
+
+  /**
+   * Create a listener for a ws_listen address: it accepts `/ws` upgrades, which are attached
+   * separately, and answers every plain HTTP request with 404, so the API is not reachable there
+   */
+  private createWebSocketListener(): Server {
+    return this.limitHandshake(
+      createServer((req, res) => {
+        const errorResponse: ErrorResponse = {
+          error: 'Not Found',
+          code: 'NOT_FOUND',
+          timestamp: new Date().toISOString(),
+        };
+        res.writeHead(404, { 'Content-Type': 'application/json' });
+        res.end(JSON.stringify(errorResponse));
+      })
+    );
+  }
+
+  /**
+   * Destroy connections that do not finish sending their first request headers, such as a
+   * WebSocket upgrade, within handshake_timeout_ms. Node only checks its own header timeout
+   * periodically, so a stalled client could otherwise hold the socket much longer.
+   */
+  private limitHandshake(server: Server): Server {
+    const timeout = this.config.handshake_timeout_ms;
+    if (timeout <= 0) {
+      return server;
+    }
+
+    const pending = new WeakMap<Socket, NodeJS.Timeout>();
+    server.on('connection', (socket: Socket) => {
+      const timer = setTimeout(() => socket.destroy(), timeout);
+      timer.unref();
+      pending.set(socket, timer);
+      socket.once('close', () => clearTimeout(timer));
     });
+
+    const handshakeDone = (req: IncomingMessage) => {
+      const timer = pending.get(req.socket);
+      if (timer) {
+        clearTimeout(timer);
+        pending.delete(req.socket);
+      }
+    };
+    server.on('request', handshakeDone);
+    server.on('upgrade', handshakeDone);
+    return server;
+  }
+
+  /**
+   * Answer 408 when a non-streaming API request takes longer than the configured timeout
+   */
+  private requestTimeout(): express.RequestHandler {
+    return (req, res, next) => {
+      if (STREAMING_PATHS.some((pattern) => pattern.test(req.originalUrl))) {
+        return next();
+      }
+
+      const timer = setTimeout(() => {
+        if (res.headersSent) {
+          return;
+        }
+
+        const errorResponse: ErrorResponse = {
+          error: 'Request Timeout',
+          code: 'REQUEST_TIMEOUT',
+          timestamp: new Date().toISOString(),
+          details: { timeout_ms: this.config.request_timeout_ms },
+        };
+        res.set('Connection', 'close');
+        res.status(408).json(errorResponse);
+
+        // The handler may still finish later; drop its response instead of
+        // failing on headers that were already sent
+        res.json = () => res;
+        res.send = () => res;
+      }, this.config.request_timeout_ms);
+
+      res.on('close', () => clearTimeout(timer));
+      next();
+    };
   }
 
   private setupRoutes(): void {
     // API routes
     this.app.use('/api/claude', createClaudeRoutes(this.claudeService, this.projectService));
     this.app.use('/api/projects', createProjectRoutes(this.projectService));
-    this.app.use('/api/status', createStatusRoutes());
+    this.app.use('/api/status', createStatusRoutes(this.claudeService, this.webhookService));
+    this.app.use('/api/events', createEventRoutes(this.claudeService));
+    this.app.use('/api/estimate', createEstimateRoutes(this.config.model_pricing));
+    this.app.use('/api/usage', createUsageRoutes(this.claudeService));
+    this.app.use('/api/admin', createAdminRoutes(this.claudeService, this.wsService));
+    this.app.use('/api/logs', createLogRoutes(this.config.log_file, this.config.admin_token));
+    this.app.use(
+      '/api',
+      createExampleRoutes(
+        () => this.getAddresses()[0],
+        () => (this.wsServers.length > 0 ? this.getWebSocketAddresses()[0] : undefined)
+      )
+    );
 
     // Root endpoint
     this.app.get('/', (req, res) => {
@@ -94,6 +362,10 @@ export class ClaudiaServer {
           websocket: '/ws',
           health: '/api/status/health',
           info: '/api/status/info',
+          events: '/api/events',
+          estimate: '/api/estimate',
+          usage: '/api/usage',
+          examples: '/api/examples',
         },
         timestamp: new Date().toISOString(),
       });
@@ -112,6 +384,18 @@ export class ClaudiaServer {
   }
 
   private setupWebSocketEvents(): void {
+    this.wsService.setSessionUpdateHandler((sessionId, update) =>
+      this.claudeService.updateSession(sessionId, update)
+    );
+    this.wsService.setSessionListProvider(() => this.claudeService.getRunningClaudeSessions());
+    this.wsService.setSessionRecordProvider((sessionId) => this.claudeService.getSessionRecord(sessionId));
+    this.wsService.setSessionIdResolver((idOrName) => this.claudeService.resolveSessionId(idOrName));
+    this.wsService.setStatsProvider(() => ({
+      ...this.claudeService.getProcessStats(),
+      sessions: this.claudeService.getSessionTotals(),
+      memory: process.memoryUsage(),
+    }));
+
     // Forward Claude service events to WebSocket clients
     this.claudeService.on('claude_stream', (data) => {
       this.wsService.broadcastClaudeStream(data.session_id, data.message);
@@ -121,18 +405,23 @@ export class ClaudiaServer {
       this.wsService.broadcastClaudeStream(data.session_id, {
         type: 'output',
         content: data.data,
-        timestamp: new Date().toISOString(),
+        timestamp: data.timestamp,
       });
     });
 
     this.claudeService.on('claude_error', (data) => {
+      // Errors raised by the server itself, such as watchdog messages, carry no read time
       this.wsService.broadcastClaudeStream(data.session_id, {
         type: 'error',
         content: data.error,
-        timestamp: new Date().toISOString(),
+        timestamp: data.timestamp ?? new Date().toISOString(),
       });
     });
 
+    this.claudeService.on('session_event', (event) => {
+      this.wsService.broadcastSessionEvent(event);
+    });
+
     this.claudeService.on('claude_exit', (data) => {
       this.wsService.broadcastClaudeStream(data.session_id, {
         type: 'complete',
@@ -145,6 +434,17 @@ export class ClaudiaServer {
   private setupErrorHandling(): void {
     // Global error handler
     this.app.use((error: Error, req: express.Request, res: express.Response, next: express.NextFunction) => {
+      if ((error as { type?: string }).type === 'entity.too.large') {
+        const errorResponse: ErrorResponse = {
+          error: 'Payload Too Large',
+          code: 'PAYLOAD_TOO_LARGE',
+          timestamp: new Date().toISOString(),
+          details: { limit_bytes: this.config.max_body_bytes },
+        };
+        res.status(413).json(errorResponse);
+        return;
+      }
+
       console.error('Unhandled error:', error);
 
       const errorResponse: ErrorResponse = {
@@ -185,36 +485,202 @@ export class ClaudiaServer {
   }
 
   /**
-   * Start the server
+   * Start the server, binding a listener for every configured address and WebSocket address.
+   * If any address fails to bind, listeners that did bind are closed and
+   * startup fails with an error naming the address.
    */
   async start(): Promise<void> {
+    await this.checkClaudeVersion();
+
+    try {
+      for (let i = 0; i < this.servers.length; i++) {
+        await this.listen(this.servers[i], this.config.listen[i]);
+      }
+      for (let i = 0; i < this.wsServers.length; i++) {
+        await this.listen(this.wsServers[i], this.config.ws_listen[i]);
+      }
+    } catch (error) {
+      await Promise.all(
+        [...this.servers, ...this.wsServers]
+          .filter((server) => server.listening)
+          .map((server) => this.closeServer(server))
+      );
+      throw error;
+    }
+
+    for (const address of this.getAddresses()) {
+      console.log(`🚀 Claudia Server started on http://${address.host}:${address.port}`);
+    }
+    for (const address of this.getWebSocketAddresses()) {
+      console.log(`📡 WebSocket endpoint: ws://${address.host}:${address.port}/ws`);
+    }
+    console.log(`🏠 Claude home directory: ${this.claudeService.getClaudeHomeDir()}`);
+
+    await this.adoptDetachedSessions();
+    await this.reportOrphans();
+    this.memoryGuard?.start();
+
+    if (this.config.skip_permissions) {
+      console.warn('⚠️  WARNING: sessions run with --dangerously-skip-permissions by default.');
+      console.warn('⚠️  Claude will edit files and run commands without asking for approval.');
+      console.warn('⚠️  Pass skip_permissions: false per request, or start with --no-skip-permissions.');
+    }
+
+    await this.writeReadyFile();
+  }
+
+  /**
+   * Enforce min_claude_version: throw so the server does not start, or only warn when
+   * min_claude_version_action is `warn`
+   */
+  private async checkClaudeVersion(): Promise<void> {
+    const minVersion = this.config.min_claude_version;
+    if (!minVersion) {
+      return;
+    }
+
+    const problem = await this.claudeService.checkMinimumVersion(minVersion);
+    if (!problem) {
+      return;
+    }
+    if (this.config.min_claude_version_action === 'warn') {
+      console.warn(`⚠️  ${problem}; some features may not work`);
+      return;
+    }
+    throw new Error(`Refusing to start: ${problem}`);
+  }
+
+  /**
+   * Write ready_file with the pid and bound addresses, so supervisors can poll for it during boot
+   */
+  private async writeReadyFile(): Promise<void> {
+    const path = this.config.ready_file;
+    if (!path) {
+      return;
+    }
+
+    const ready = { pid: process.pid, addresses: this.getAddresses(), ready_at: new Date().toISOString() };
+    try {
+      await fs.mkdir(dirname(path), { recursive: true });
+      await fs.writeFile(path, `${JSON.stringify(ready)}\n`);
+    } catch (error) {
+      console.warn(`⚠️  Cannot write ready file ${path}:`, error);
+    }
+  }
+
+  /**
+   * Remove ready_file, so a supervisor stops treating this process as ready
+   */
+  private async removeReadyFile(): Promise<void> {
+    if (this.config.ready_file) {
+      await fs.rm(this.config.ready_file, { force: true }).catch((error) => {
+        console.warn(`⚠️  Cannot remove ready file ${this.config.ready_file}:`, error);
+      });
+    }
+  }
+
+  /**
+   * Take back the detached sessions an earlier run left running, when kill_on_drop is off
+   */
+  private async adoptDetachedSessions(): Promise<void> {
+    try {
+      const adopted = await this.claudeService.adoptDetachedSessions();
+      if (adopted.length > 0) {
+        console.log(`🔗 Adopted ${adopted.length} detached session(s): ${adopted.join(', ')}`);
+      }
+    } catch (error) {
+      console.warn('Adopting detached sessions failed:', error);
+    }
+  }
+
+  /**
+   * Log Claude processes left behind by an earlier run, reaping them when configured
+   */
+  private async reportOrphans(): Promise<void> {
+    try {
+      const orphans = this.config.reap_orphans_on_start
+        ? await this.claudeService.reapOrphans()
+        : await this.claudeService.findOrphans();
+
+      if (orphans.length > 0) {
+        const action = this.config.reap_orphans_on_start ? 'Reaped' : 'Found';
+        console.warn(`🧟 ${action} ${orphans.length} orphaned Claude process(es): ${orphans.map((o) => `${o.pid} (session ${o.session_id})`).join(', ')}`);
+      }
+    } catch (error) {
+      console.warn('Orphaned process scan failed:', error);
+    }
+  }
+
+  /**
+   * Bind a single listener
+   */
+  private listen(server: Server, address: ListenAddress): Promise<void> {
     return new Promise((resolve, reject) => {
-      this.server.listen(this.config.port, this.config.host, (error?: Error) => {
-        if (error) {
-          reject(error);
-        } else {
-          console.log(`🚀 Claudia Server started on http://${this.config.host}:${this.config.port}`);
-          console.log(`📡 WebSocket endpoint: ws://${this.config.host}:${this.config.port}/ws`);
-          console.log(`🏠 Claude home directory: ${this.claudeService.getClaudeHomeDir()}`);
-          resolve();
-        }
+      const onError = (error: Error) => {
+        reject(new Error(`Failed to bind ${address.host}:${address.port}: ${error.message}`));
+      };
+
+      server.once('error', onError);
+      server.listen(address.port, address.host, () => {
+        server.off('error', onError);
+        resolve();
       });
     });
   }
 
+  /**
+   * Close a single listener
+   */
+  private closeServer(server: Server): Promise<void> {
+    return new Promise((resolve) => {
+      server.close(() => resolve());
+    });
+  }
+
+  /**
+   * Get the addresses the server is actually bound to (resolves port 0 to the assigned port)
+   */
+  getAddresses(): ListenAddress[] {
+    return this.servers.map((server, i) => {
+      const address = server.address() as AddressInfo | null;
+      return address
+        ? { host: this.config.listen[i].host, port: address.port }
+        : { ...this.config.listen[i] };
+    });
+  }
+
+  /**
+   * Get the addresses `/ws` is served on: the ws_listen addresses when configured, otherwise
+   * the same ones as the API
+   */
+  getWebSocketAddresses(): ListenAddress[] {
+    if (this.wsServers.length === 0) {
+      return this.getAddresses();
+    }
+    return this.wsServers.map((server, i) => {
+      const address = server.address() as AddressInfo | null;
+      return address
+        ? { host: this.config.ws_listen[i].host, port: address.port }
+        : { ...this.config.ws_listen[i] };
+    });
+  }
+
   /**
    * Stop the server gracefully
    */
   async stop(): Promise<void> {
     return new Promise((resolve) => {
       console.log('Stopping Claudia Server...');
+      const readyFileRemoved = this.removeReadyFile();
 
       // Cleanup services
+      this.memoryGuard?.stop();
       this.claudeService.cleanup();
       this.wsService.close();
 
-      // Close server
-      this.server.close(() => {
+      // Close listeners
+      const listeners = [...this.servers, ...this.wsServers];
+      Promise.all([readyFileRemoved, ...listeners.map((server) => this.closeServer(server))]).then(() => {
         console.log('Claudia Server stopped');
         resolve();
       });
@@ -239,7 +705,13 @@ export class ClaudiaServer {
    * Get server configuration
    */
   getConfig(): ServerConfig {
-    return { ...this.config };
+    return {
+      ...this.config,
+      listen: this.config.listen.map((address) => ({ ...address })),
+      ws_listen: this.config.ws_listen.map((address) => ({ ...address })),
+      model_fallback: { ...this.config.model_fallback },
+      model_pricing: { ...this.config.model_pricing },
+    };
   }
 
   /**
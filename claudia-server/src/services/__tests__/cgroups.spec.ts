@@ -0,0 +1,62 @@
+import { jest } from '@jest/globals';
+import { promises as fs } from 'fs';
+import { tmpdir } from 'os';
+import { join } from 'path';
+import type { SpiedFunction } from 'jest-mock';
+
+import { MemoryCgroups } from '../cgroups.js';
+
+describe('MemoryCgroups', () => {
+  let hierarchy: string;
+  let warn: SpiedFunction<typeof console.warn>;
+
+  // A plain directory stands in for the cgroup v2 mount; the files the kernel would
+  // provide are created by hand
+  beforeEach(async () => {
+    hierarchy = await fs.mkdtemp(join(tmpdir(), 'claudia-cgroup-'));
+    await fs.writeFile(join(hierarchy, 'cgroup.controllers'), 'cpuset cpu io memory pids\n');
+    warn = jest.spyOn(console, 'warn').mockImplementation(() => undefined);
+  });
+
+  afterEach(async () => {
+    warn.mockRestore();
+    await fs.rm(hierarchy, { recursive: true, force: true });
+  });
+
+  it('creates a session group with the memory limit and enables the controller', async () => {
+    const cgroups = new MemoryCgroups(join(hierarchy, 'claudia'), 'linux');
+
+    const path = await cgroups.create('abc', 512);
+    await cgroups.addProcess(path!, 4242);
+
+    expect(path).toBe(join(hierarchy, 'claudia', 'session-abc'));
+    expect(await fs.readFile(join(path!, 'memory.max'), 'utf8')).toBe(String(512 * 1024 * 1024));
+    expect(await fs.readFile(join(path!, 'memory.swap.max'), 'utf8')).toBe('0');
+    expect(await fs.readFile(join(path!, 'cgroup.procs'), 'utf8')).toBe('4242');
+    expect(await fs.readFile(join(hierarchy, 'cgroup.subtree_control'), 'utf8')).toBe('+memory');
+    expect(await fs.readFile(join(hierarchy, 'claudia', 'cgroup.subtree_control'), 'utf8')).toBe('+memory');
+  });
+
+  it('reports OOM kills from memory.events', async () => {
+    const cgroups = new MemoryCgroups(join(hierarchy, 'claudia'), 'linux');
+    const path = (await cgroups.create('abc', 64))!;
+
+    await fs.writeFile(join(path, 'memory.events'), 'low 0\nhigh 0\nmax 3\noom 1\noom_kill 0\n');
+    expect(await cgroups.wasOomKilled(path)).toBe(false);
+
+    await fs.writeFile(join(path, 'memory.events'), 'low 0\nhigh 0\nmax 9\noom 2\noom_kill 1\n');
+    expect(await cgroups.wasOomKilled(path)).toBe(true);
+  });
+
+  it('falls back to no limit with a single warning when cgroups are unavailable', async () => {
+    await fs.writeFile(join(hierarchy, 'cgroup.controllers'), 'cpu io pids\n');
+    const cgroups = new MemoryCgroups(join(hierarchy, 'claudia'), 'linux');
+
+    expect(await cgroups.create('a', 64)).toBeUndefined();
+    expect(await cgroups.create('b', 64)).toBeUndefined();
+    expect(warn).toHaveBeenCalledTimes(1);
+    expect(warn.mock.calls[0][0]).toMatch(/memory controller is not available/);
+
+    expect(await new MemoryCgroups(join(hierarchy, 'claudia'), 'darwin').create('c', 64)).toBeUndefined();
+  });
+});
@@ -1,15 +1,44 @@
+import { jest } from '@jest/globals';
+import type { ChildProcess } from 'child_process';
 import { EventEmitter } from 'events';
-
-// We mock child_process.spawn, fs.promises.access, and os.homedir to ensure deterministic behavior
-import * as childProcess from 'child_process';
-import { promises as fs } from 'fs';
-import * as os from 'os';
 import * as path from 'path';
-
-// Import the class under test
-// The provided file is named `claude.test.ts` but contains the service implementation.
-// We import it directly as that's where the ClaudeService is exported.
-import { ClaudeService } from '../claude.test';
+import { setImmediate as nextIteration } from 'timers/promises';
+import type { Mock, SpiedFunction } from 'jest-mock';
+import type { SessionRecord } from '../../types/index.js';
+
+// We mock child_process.spawn, fs.promises.access, and os.homedir to ensure deterministic
+// behavior. ES modules are mocked before they are imported, so the modules that use them,
+// and the service under test, are imported dynamically afterwards.
+type AnyMock = Mock<(...args: any[]) => any>;
+
+const actualChildProcess = await import('child_process');
+const actualFs = await import('fs');
+const actualOs = await import('os');
+
+jest.unstable_mockModule('child_process', () => ({
+  ...actualChildProcess,
+  spawn: jest.fn(),
+}));
+
+jest.unstable_mockModule('fs', () => ({
+  ...actualFs,
+  promises: {
+    ...actualFs.promises,
+    access: jest.fn(),
+  },
+}));
+
+jest.unstable_mockModule('os', () => ({
+  ...actualOs,
+  homedir: jest.fn(),
+}));
+
+const childProcess = await import('child_process');
+const { promises: fs } = await import('fs');
+const os = await import('os');
+const { ClaudeService } = await import('../claude.js');
+const { MemoryGuard } = await import('../memory-guard.js');
+const { SessionStore } = await import('../session-store.js');
 
 // Test helpers to craft controllable fake ChildProcess instances
 class FakeChildProcess extends EventEmitter {
@@ -17,6 +46,8 @@ class FakeChildProcess extends EventEmitter {
   public stdout?: EventEmitter;
   public stderr?: EventEmitter;
   public killed = false;
+  public exitCode: number | null = null;
+  public signalCode: NodeJS.Signals | null = null;
 
   constructor(withPid = true) {
     super();
@@ -30,46 +61,27 @@ class FakeChildProcess extends EventEmitter {
     // No-op; tests will assert on flags or trigger "close" as needed
     return true;
   };
-}
 
-// Jest-compatible mocks (works in Vitest with vi.mock as well)
-jest.mock('child_process', () => {
-  const actual = jest.requireActual('child_process');
-  return {
-    ...actual,
-    spawn: jest.fn(),
-  };
-});
-
-jest.mock('fs', () => {
-  const actual = jest.requireActual('fs');
-  return {
-    ...actual,
-    promises: {
-      ...(actual.promises || {}),
-      access: jest.fn(),
-    },
-  };
-});
-
-jest.mock('os', () => {
-  const actual = jest.requireActual('os');
-  return {
-    ...actual,
-    homedir: jest.fn(),
-  };
-});
+  unref = jest.fn();
+}
 
 describe('ClaudeService', () => {
-  const mockedSpawn = childProcess.spawn as unknown as jest.Mock;
-  const mockedFsAccess = fs.access as unknown as jest.Mock;
-  const mockedHomedir = os.homedir as unknown as jest.Mock;
+  const mockedSpawn = childProcess.spawn as unknown as AnyMock;
+  const mockedFsAccess = fs.access as unknown as AnyMock;
+  const mockedHomedir = os.homedir as unknown as AnyMock;
 
   const CLAUDE_BIN = '/fake/path/claude';
 
+  // Fake PIDs must never reach real process groups. By default group signals fail as if
+  // the group did not exist, so the service falls back to FakeChildProcess.kill.
+  let killSpy: SpiedFunction<typeof process.kill>;
+
   beforeEach(() => {
     jest.useFakeTimers();
     jest.spyOn(global, 'setTimeout');
+    killSpy = jest.spyOn(process, 'kill').mockImplementation(() => {
+      throw Object.assign(new Error('kill ESRCH'), { code: 'ESRCH' });
+    });
 
     mockedHomedir.mockReturnValue('/home/testuser');
     mockedFsAccess.mockResolvedValue(undefined);
@@ -79,6 +91,7 @@ describe('ClaudeService', () => {
   afterEach(() => {
     jest.useRealTimers();
     jest.clearAllMocks();
+    killSpy.mockRestore();
   });
 
   function setupSpawnForVersion(output: string, exitCode: number) {
@@ -94,7 +107,7 @@ describe('ClaudeService', () => {
         // stderr is unused for success path; keep empty
         fake.emit('close', exitCode);
       });
-      return fake as unknown as childProcess.ChildProcess;
+      return fake as unknown as ChildProcess;
     });
     return fake;
   }
@@ -107,7 +120,7 @@ describe('ClaudeService', () => {
         if (stderrOutput) fake.stderr?.emit('data', Buffer.from(stderrOutput));
         fake.emit('close', exitCode);
       });
-      return fake as unknown as childProcess.ChildProcess;
+      return fake as unknown as ChildProcess;
     });
     return fake;
   }
@@ -131,7 +144,7 @@ describe('ClaudeService', () => {
           }
         });
       }
-      return fake as unknown as childProcess.ChildProcess;
+      return fake as unknown as ChildProcess;
     });
     return fake;
   }
@@ -344,15 +357,15 @@ describe('ClaudeService', () => {
       // The process that will be stored/registered; we need it to not exit immediately
       const isExecuteArgs = (args: string[]) => args.includes('-p') && args.includes('cancel test');
       const fake = new FakeChildProcess(true);
-      (childProcess.spawn as jest.Mock).mockImplementation((_cmd: string, args: string[]) => {
+      (childProcess.spawn as unknown as AnyMock).mockImplementation((_cmd: string, args: string[]) => {
         if (isExecuteArgs(args)) {
           // keep it alive; do not emit close immediately
-          return fake as unknown as childProcess.ChildProcess;
+          return fake as unknown as ChildProcess;
         }
         // For binary validation
         const ver = new FakeChildProcess(true);
         setImmediate(() => ver.emit('close', 0));
-        return ver as unknown as childProcess.ChildProcess;
+        return ver as unknown as ChildProcess;
       });
 
       const svc = new ClaudeService(CLAUDE_BIN);
@@ -426,11 +439,11 @@ describe('ClaudeService', () => {
       const fake = new FakeChildProcess(true);
       mockedSpawn.mockImplementation((_cmd: string, args: string[]) => {
         if (isExecuteArgs(args)) {
-          return fake as unknown as childProcess.ChildProcess;
+          return fake as unknown as ChildProcess;
         }
         const ver = new FakeChildProcess(true);
         setImmediate(() => ver.emit('close', 0));
-        return ver as unknown as childProcess.ChildProcess;
+        return ver as unknown as ChildProcess;
       });
 
       const svc = new ClaudeService(CLAUDE_BIN);
@@ -467,7 +480,7 @@ describe('ClaudeService', () => {
           fake.stderr?.emit('data', Buffer.from('boom'));
           fake.emit('close', 2);
         });
-        return fake as unknown as childProcess.ChildProcess;
+        return fake as unknown as ChildProcess;
       });
 
       const svc = new ClaudeService(CLAUDE_BIN);
@@ -486,12 +499,12 @@ describe('ClaudeService', () => {
       mockedSpawn.mockImplementation((_cmd: string, args: string[]) => {
         // for the execution spawn (not version check), return bad child
         if (args.includes('--output-format')) {
-          return badChild as unknown as childProcess.ChildProcess;
+          return badChild as unknown as ChildProcess;
         }
         // for version checks, return normal that closes immediately
         const ver = new FakeChildProcess(true);
         setImmediate(() => ver.emit('close', 0));
-        return ver as unknown as childProcess.ChildProcess;
+        return ver as unknown as ChildProcess;
       });
 
       const svc = new ClaudeService(CLAUDE_BIN);
@@ -502,4 +515,1261 @@ describe('ClaudeService', () => {
       })).rejects.toThrow(/Failed to start Claude process/);
     });
   });
-});
\ No newline at end of file
+
+  describe('permissions skipping', () => {
+    function keepAliveSpawn(): AnyMock {
+      mockedSpawn.mockImplementation((_cmd: string, args: string[]) => {
+        const child = new FakeChildProcess(true);
+        if (!args.includes('--output-format')) {
+          setImmediate(() => child.emit('close', 0));
+        }
+        return child as unknown as ChildProcess;
+      });
+      return mockedSpawn;
+    }
+
+    it('flags sessions launched with --dangerously-skip-permissions', async () => {
+      keepAliveSpawn();
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      const id = await svc.executeClaudeCode({
+        prompt: 'skip', model: 'claude-3', project_path: '/tmp/p',
+      });
+
+      const args = mockedSpawn.mock.calls[mockedSpawn.mock.calls.length - 1][1] as string[];
+      expect(args).toContain('--dangerously-skip-permissions');
+      expect(svc.getSessionInfo(id)?.permissions_skipped).toBe(true);
+    });
+
+    it('leaves the flag unset when the request opts out', async () => {
+      keepAliveSpawn();
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      const id = await svc.executeClaudeCode({
+        prompt: 'ask', model: 'claude-3', project_path: '/tmp/p', skip_permissions: false,
+      });
+
+      const args = mockedSpawn.mock.calls[mockedSpawn.mock.calls.length - 1][1] as string[];
+      expect(args).not.toContain('--dangerously-skip-permissions');
+      expect(svc.getSessionInfo(id)?.permissions_skipped).toBeUndefined();
+    });
+
+    it('honours a server default of not skipping', async () => {
+      keepAliveSpawn();
+      const svc = new ClaudeService(CLAUDE_BIN, { skip_permissions: false });
+
+      const id = await svc.executeClaudeCode({
+        prompt: 'ask', model: 'claude-3', project_path: '/tmp/p',
+      });
+
+      expect(svc.getSessionInfo(id)?.permissions_skipped).toBeUndefined();
+    });
+  });
+
+  describe('kill on drop', () => {
+    function spawnKeepingExecAlive(): FakeChildProcess {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation((_cmd: string, args: string[]) => {
+        if (args.includes('--output-format')) {
+          return exec as unknown as ChildProcess;
+        }
+        const ver = new FakeChildProcess(true);
+        setImmediate(() => ver.emit('close', 0));
+        return ver as unknown as ChildProcess;
+      });
+      return exec;
+    }
+
+    it('kills on cleanup by default', async () => {
+      const exec = spawnKeepingExecAlive();
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      const id = await svc.executeClaudeCode({ prompt: 'a', model: 'claude-3', project_path: '/tmp/p' });
+
+      const options = mockedSpawn.mock.calls[mockedSpawn.mock.calls.length - 1][2];
+      // Still a process group leader on Unix, but not reported as surviving shutdown
+      expect(options.detached).toBe(process.platform !== 'win32');
+      expect(svc.getSessionInfo(id)?.detached).toBeUndefined();
+
+      svc.cleanup();
+      expect(exec.killed).toBe(true);
+    });
+
+    it('requires a data directory when disabled', () => {
+      expect(() => new ClaudeService(CLAUDE_BIN, { kill_on_drop: false })).toThrow(/requires data_dir/);
+    });
+
+    describe('when disabled', () => {
+      let dataDir: string;
+
+      beforeEach(async () => {
+        dataDir = await fs.mkdtemp(path.join(os.tmpdir(), 'claudia-detached-'));
+      });
+
+      afterEach(async () => {
+        await fs.rm(dataDir, { recursive: true, force: true });
+      });
+
+      it('spawns detached with output going to files, and leaves processes running', async () => {
+        const exec = spawnKeepingExecAlive();
+        const svc = new ClaudeService(CLAUDE_BIN, { kill_on_drop: false, data_dir: dataDir });
+
+        const id = await svc.executeClaudeCode({ prompt: 'b', model: 'claude-3', project_path: '/tmp/p' });
+
+        const options = mockedSpawn.mock.calls[mockedSpawn.mock.calls.length - 1][2];
+        expect(options.detached).toBe(true);
+        expect(options.stdio).toEqual(['ignore', expect.any(Number), expect.any(Number)]);
+        expect(exec.unref).toHaveBeenCalled();
+        expect(svc.getSessionInfo(id)?.detached).toBe(true);
+        const store = new SessionStore(dataDir);
+        expect(await store.listProcesses()).toEqual([
+          expect.objectContaining({ session_id: id, pid: exec.pid, server_pid: process.pid, detached: true }),
+        ]);
+
+        svc.cleanup();
+        expect(exec.killed).toBe(false);
+        expect(killSpy).not.toHaveBeenCalled();
+        // Kept for the next run to adopt
+        expect(await store.listProcesses()).toHaveLength(1);
+      });
+
+      it('adopts the sessions an earlier run left behind', async () => {
+        const store = new SessionStore(dataDir);
+        const startedAt = '2026-10-16T07:30:00.000Z';
+        const result = JSON.stringify({ type: 'result', subtype: 'success', result: 'done', is_error: false });
+        for (const [id, pid] of [['alive', 4321], ['gone', 4322]] as const) {
+          const saved: SessionRecord = {
+            info: {
+              run_id: 1,
+              process_type: { ClaudeSession: { session_id: id } },
+              pid,
+              started_at: startedAt,
+              project_path: '/tmp/p',
+              task: 'b',
+              model: 'claude-3',
+              detached: true,
+            },
+            status: 'running',
+            output: [],
+          };
+          await store.save(id, saved);
+          await store.recordProcess({ session_id: id, pid, started_at: startedAt, server_pid: 1, detached: true });
+          await fs.writeFile(store.processOutputPaths(id).stdout, `${result}\n`);
+        }
+
+        const svc = new ClaudeService(CLAUDE_BIN, { kill_on_drop: false, data_dir: dataDir });
+        const adopted = await svc.adoptDetachedSessions(async () => [
+          { pid: 4321, ppid: 1, started_at: Date.parse(startedAt), command: 'claude -p b' },
+          // The PID of the second session now belongs to a process started later
+          { pid: 4322, ppid: 1, started_at: Date.parse(startedAt) + 3_600_000, command: 'vim' },
+        ]);
+
+        expect(adopted.sort()).toEqual(['alive', 'gone']);
+        expect(mockedSpawn).not.toHaveBeenCalled();
+        expect(svc.getSessionInfo('alive')?.pid).toBe(4321);
+        expect(svc.getSessionRecord('alive')).toMatchObject({ status: 'running', result: { text: 'done' } });
+        // Gone before the server came back: finished from the output it left
+        expect(svc.getSessionInfo('gone')).toBeUndefined();
+        expect(svc.getSessionRecord('gone')).toMatchObject({ status: 'completed', exit_code: null });
+
+        svc.cleanup();
+      });
+    });
+  });
+
+  describe('output preview', () => {
+    it('publishes a bounded preview periodically while running', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      const longText = 'x'.repeat(400);
+      const assistant = (text: string) =>
+        JSON.stringify({ type: 'assistant', message: { content: [{ type: 'text', text }] } }) + '\n';
+      exec.stdout?.emit('data', Buffer.from(JSON.stringify({ type: 'system', subtype: 'init' }) + '\n'));
+      exec.stdout?.emit('data', Buffer.from(assistant(longText)));
+      exec.stdout?.emit('data', Buffer.from(assistant(longText)));
+
+      // Not refreshed on every line
+      expect(svc.getSessionInfo(id)?.output_preview).toBeUndefined();
+
+      jest.advanceTimersByTime(1000);
+
+      const preview = svc.getSessionInfo(id)?.output_preview ?? '';
+      expect(preview.startsWith(longText)).toBe(true);
+      expect(preview.length).toBe(500);
+    });
+
+    it('backs off while the session is quiet and speeds up with output', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+
+      const svc = new ClaudeService(CLAUDE_BIN, { preview_interval_min_ms: 100, preview_interval_max_ms: 800 });
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      const preview = () => svc.getSessionInfo(id)?.output_preview;
+
+      // Quiet refreshes at 100, 300, 700 and 1500ms leave the interval at its 800ms maximum
+      jest.advanceTimersByTime(1500);
+      exec.stdout?.emit('data', Buffer.from('first output\n'));
+
+      jest.advanceTimersByTime(700);
+      expect(preview()).toBeUndefined();
+      jest.advanceTimersByTime(100);
+      expect(preview()).toBe('first output');
+
+      // Output during each interval halves it again: 400ms, 200ms, then 100ms
+      exec.stdout?.emit('data', Buffer.from('more\n'));
+      jest.advanceTimersByTime(400);
+      exec.stdout?.emit('data', Buffer.from('more\n'));
+      jest.advanceTimersByTime(200);
+      exec.stdout?.emit('data', Buffer.from('more\n'));
+      jest.advanceTimersByTime(100);
+      expect(preview()).toBe('first output\nmore\nmore\nmore');
+    });
+  });
+
+  describe('concurrency limits', () => {
+    beforeEach(() => {
+      mockedSpawn.mockImplementation(() => new FakeChildProcess(true) as unknown as ChildProcess);
+    });
+
+    it('caps sessions per project while other projects keep capacity', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { max_concurrent_per_project: 1 });
+
+      await svc.executeClaudeCode({ prompt: 'a', model: 'claude-3', project_path: '/tmp/busy' });
+      await expect(
+        svc.executeClaudeCode({ prompt: 'b', model: 'claude-3', project_path: '/tmp/busy' })
+      ).rejects.toMatchObject({ code: 'MAX_PROJECT_SESSIONS_REACHED', status: 429 });
+
+      await expect(
+        svc.executeClaudeCode({ prompt: 'c', model: 'claude-3', project_path: '/tmp/quiet' })
+      ).resolves.toEqual(expect.any(String));
+    });
+
+    it('frees the project slot when a session exits', async () => {
+      const first = new FakeChildProcess(true);
+      mockedSpawn.mockImplementationOnce(() => first as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN, { max_concurrent_per_project: 1 });
+
+      await svc.executeClaudeCode({ prompt: 'a', model: 'claude-3', project_path: '/tmp/busy' });
+      first.emit('close', 0);
+
+      await expect(
+        svc.executeClaudeCode({ prompt: 'b', model: 'claude-3', project_path: '/tmp/busy' })
+      ).resolves.toEqual(expect.any(String));
+    });
+
+    it('enforces the global limit with a distinct code', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { max_concurrent_sessions: 1 });
+
+      await svc.executeClaudeCode({ prompt: 'a', model: 'claude-3', project_path: '/tmp/one' });
+      await expect(
+        svc.executeClaudeCode({ prompt: 'b', model: 'claude-3', project_path: '/tmp/two' })
+      ).rejects.toMatchObject({ code: 'MAX_SESSIONS_REACHED', status: 429 });
+    });
+  });
+
+  describe('stdout tee', () => {
+    let written: string[];
+    let writeSpy: SpiedFunction<typeof process.stdout.write>;
+
+    beforeEach(() => {
+      written = [];
+      writeSpy = jest.spyOn(process.stdout, 'write').mockImplementation((chunk: any) => {
+        written.push(chunk.toString());
+        return true;
+      });
+    });
+
+    afterEach(() => writeSpy.mockRestore());
+
+    async function runWithOutput(options: { tee_output_to_stdout?: boolean }): Promise<string> {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN, options);
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      exec.stdout?.emit('data', Buffer.from('hello from claude\n'));
+      exec.stderr?.emit('data', Buffer.from('a warning\n'));
+      return id;
+    }
+
+    it('writes prefixed lines to stdout when enabled', async () => {
+      const id = await runWithOutput({ tee_output_to_stdout: true });
+
+      expect(written).toContain(`[${id}] hello from claude\n`);
+      expect(written).toContain(`[${id}:stderr] a warning\n`);
+    });
+
+    it('stays quiet when disabled', async () => {
+      const id = await runWithOutput({});
+
+      expect(written.some((chunk) => chunk.includes(id))).toBe(false);
+    });
+  });
+
+  describe('model fallback', () => {
+    const request = { prompt: 'p', model: 'opus', project_path: '/tmp/p' };
+
+    it('retries once with the fallback model after an overload error', async () => {
+      const first = new FakeChildProcess(true);
+      const retry = new FakeChildProcess(true);
+      mockedSpawn
+        .mockImplementationOnce(() => first as unknown as ChildProcess)
+        .mockImplementationOnce(() => retry as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN, { model_fallback: { opus: 'sonnet' } });
+
+      const id = await svc.executeClaudeCode(request);
+      first.stderr?.emit('data', Buffer.from('API Error: 529 {"type":"overloaded_error"}\n'));
+      first.emit('close', 1);
+      await Promise.resolve();
+      await Promise.resolve();
+
+      expect(mockedSpawn).toHaveBeenCalledTimes(2);
+      const retryArgs: string[] = mockedSpawn.mock.calls[1][1];
+      expect(retryArgs[retryArgs.indexOf('--model') + 1]).toBe('sonnet');
+      expect(svc.getSessionInfo(id)).toMatchObject({ model: 'sonnet', fallback_from: 'opus', pid: retry.pid });
+
+      // The fallback only triggers once
+      retry.stderr?.emit('data', Buffer.from('overloaded_error\n'));
+      retry.emit('close', 1);
+      await Promise.resolve();
+
+      expect(mockedSpawn).toHaveBeenCalledTimes(2);
+      expect(svc.getSessionRecord(id)?.status).toBe('failed');
+    });
+
+    it('does not retry failures that are not overloads', async () => {
+      const first = new FakeChildProcess(true);
+      mockedSpawn.mockImplementationOnce(() => first as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN, { model_fallback: { opus: 'sonnet' } });
+
+      const id = await svc.executeClaudeCode(request);
+      first.stderr?.emit('data', Buffer.from('Error: invalid API key\n'));
+      first.emit('close', 1);
+
+      expect(mockedSpawn).toHaveBeenCalledTimes(1);
+      expect(svc.getSessionRecord(id)?.status).toBe('failed');
+    });
+  });
+
+  describe('process groups', () => {
+    const unixIt = process.platform === 'win32' ? it.skip : it;
+
+    unixIt('signals the whole process group on cancel', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+      killSpy.mockImplementation(() => true);
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      await svc.cancelClaudeExecution(id);
+
+      expect(mockedSpawn.mock.calls[0][2].detached).toBe(true);
+      expect(killSpy).toHaveBeenCalledWith(-exec.pid!, 'SIGTERM');
+      expect(exec.killed).toBe(false);
+
+      jest.advanceTimersByTime(5000);
+      expect(killSpy).toHaveBeenCalledWith(-exec.pid!, 'SIGKILL');
+    });
+
+    unixIt('skips SIGKILL when the process already exited', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+      killSpy.mockImplementation(() => true);
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      await svc.cancelClaudeExecution(id);
+      exec.signalCode = 'SIGTERM';
+      jest.advanceTimersByTime(5000);
+
+      expect(killSpy).not.toHaveBeenCalledWith(-exec.pid!, 'SIGKILL');
+    });
+  });
+
+  describe('cancel and completion races', () => {
+    let exec: FakeChildProcess;
+    const request = { prompt: 'p', model: 'claude-3', project_path: '/tmp/p' };
+
+    beforeEach(() => {
+      exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+    });
+
+    it('ignores a cancel for a process that exited before its close event', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const events: any[] = [];
+      svc.on('session_event', (event) => events.push(event));
+      const id = await svc.executeClaudeCode(request);
+
+      exec.exitCode = 0;
+      expect(await svc.cancelClaudeExecution(id)).toBe(false);
+      exec.emit('close', 0, null);
+
+      expect(exec.killed).toBe(false);
+      expect(svc.getSessionRecord(id)?.status).toBe('completed');
+      expect(events.map((event) => event.event)).toEqual(['started', 'completed']);
+      expect(svc.getProcessStats().running).toBe(0);
+    });
+
+    it('finishes a cancelled session once, however late its completion is reported', async () => {
+      const other = new FakeChildProcess(true);
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const events: any[] = [];
+      svc.on('session_event', (event) => events.push(event));
+      const id = await svc.executeClaudeCode(request);
+      mockedSpawn.mockImplementation(() => other as unknown as ChildProcess);
+      await svc.executeClaudeCode(request);
+
+      expect(await svc.cancelClaudeExecution(id)).toBe(true);
+      exec.emit('close', 0, null);
+      expect((svc as any).finishSession(id, 'completed', 0)).toBe(false);
+
+      const record = svc.getSessionRecord(id)!;
+      expect(record.status).toBe('cancelled');
+      expect(record.status_history!.map((change) => change.status)).toEqual(['starting', 'running', 'cancelled']);
+      expect(events.filter((event) => event.session_id === id).map((event) => event.event)).toEqual([
+        'started',
+        'cancelled',
+      ]);
+      expect(svc.getProcessStats()).toMatchObject({ running: 1, running_per_project: { '/tmp/p': 1 } });
+    });
+  });
+
+  describe('stored session cap', () => {
+    it('evicts the oldest finished records and keeps running ones', async () => {
+      const children: FakeChildProcess[] = [];
+      mockedSpawn.mockImplementation(() => {
+        const child = new FakeChildProcess(true);
+        children.push(child);
+        return child as unknown as ChildProcess;
+      });
+      const svc = new ClaudeService(CLAUDE_BIN, { max_stored_sessions: 2 });
+      const start = () => svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      const running = await start();
+      const first = await start();
+      const second = await start();
+      const third = await start();
+
+      children[1].emit('close', 0);
+      jest.advanceTimersByTime(1000);
+      children[2].emit('close', 0);
+      jest.advanceTimersByTime(1000);
+      children[3].emit('close', 0);
+
+      expect(svc.getSessionRecord(running)?.status).toBe('running');
+      expect(svc.getSessionRecord(first)).toBeUndefined();
+      expect(svc.getSessionRecord(second)).toBeUndefined();
+      expect(svc.getSessionRecord(third)?.status).toBe('completed');
+    });
+  });
+
+  describe('session watchdogs', () => {
+    let exec: FakeChildProcess;
+
+    beforeEach(() => {
+      exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+    });
+
+    it('cancels when output matches an abort pattern added while running', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      exec.stderr?.emit('data', Buffer.from('FATAL: before the update\n'));
+      expect(exec.killed).toBe(false);
+
+      svc.updateSession(id, { abort_patterns: ['^FATAL:'] });
+      exec.stderr?.emit('data', Buffer.from('FATAL: disk full\n'));
+
+      expect(exec.killed).toBe(true);
+      expect(svc.getSessionRecord(id)?.terminated_by).toBe('abort_pattern');
+    });
+
+    it('reports the rolling output rate of a session', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      exec.stdout?.emit('data', Buffer.from(`${'x'.repeat(19)}\n`.repeat(50)));
+
+      expect(svc.getSessionThroughput(id)).toEqual({
+        lines_per_sec: 5,
+        bytes_per_sec: 95,
+        total_lines: 50,
+        total_bytes: 950,
+        window_ms: 10000,
+      });
+
+      jest.advanceTimersByTime(11000);
+      expect(svc.getSessionThroughput(id)).toMatchObject({ lines_per_sec: 0, bytes_per_sec: 0, total_lines: 50 });
+    });
+
+    it('cancels a session whose sustained output rate exceeds the limit', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { max_output_bytes_per_sec: 100 });
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      const line = Buffer.from(`${'x'.repeat(200)}\n`);
+
+      // A burst over the limit is tolerated until the rate holds for a whole window
+      for (let second = 0; second < 10; second++) {
+        exec.stdout?.emit('data', line);
+        jest.advanceTimersByTime(1000);
+      }
+      expect(exec.killed).toBe(false);
+
+      exec.stdout?.emit('data', line);
+      expect(exec.killed).toBe(true);
+      expect(svc.getSessionRecord(id)?.terminated_by).toBe('output_rate');
+    });
+
+    it('rejects invalid patterns at start', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      await expect(
+        svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p', abort_patterns: ['[a-'] })
+      ).rejects.toMatchObject({ code: 'VALIDATION_ERROR', status: 400 });
+      expect(mockedSpawn).not.toHaveBeenCalled();
+    });
+  });
+
+  describe('verbose flag', () => {
+    const lastArgs = () => mockedSpawn.mock.calls[mockedSpawn.mock.calls.length - 1][1] as string[];
+
+    beforeEach(() => {
+      mockedSpawn.mockImplementation(() => new FakeChildProcess(true) as unknown as ChildProcess);
+    });
+
+    it('passes --verbose by default and drops it when the request opts out', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      expect(lastArgs()).toContain('--verbose');
+
+      await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p', verbose: false });
+      expect(lastArgs()).not.toContain('--verbose');
+    });
+
+    it('lets a request turn --verbose on over a quiet server default', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { verbose: false });
+
+      await svc.continueClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      expect(lastArgs()).not.toContain('--verbose');
+
+      await svc.continueClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p', verbose: true });
+      expect(lastArgs()).toContain('--verbose');
+    });
+  });
+
+  describe('extra args', () => {
+    const lastArgs = () => mockedSpawn.mock.calls[mockedSpawn.mock.calls.length - 1][1] as string[];
+
+    beforeEach(() => {
+      mockedSpawn.mockImplementation(() => new FakeChildProcess(true) as unknown as ChildProcess);
+    });
+
+    it('appends extra args but refuses flags the server sets', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      await svc.executeClaudeCode({
+        prompt: 'p',
+        model: 'claude-3',
+        project_path: '/tmp/p',
+        extra_args: ['--max-turns', '5'],
+      });
+      expect(lastArgs().slice(-2)).toEqual(['--max-turns', '5']);
+
+      await expect(
+        svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p', extra_args: ['--model=opus'] })
+      ).rejects.toMatchObject({ code: 'ARG_NOT_ALLOWED', status: 400 });
+      expect(mockedSpawn).toHaveBeenCalledTimes(1);
+    });
+
+    it('only accepts allowlisted flags when an allowlist is configured', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { allowed_extra_args: ['--max-turns'] });
+
+      await svc.executeClaudeCode({
+        prompt: 'p',
+        model: 'claude-3',
+        project_path: '/tmp/p',
+        extra_args: ['--max-turns', '5'],
+      });
+      await expect(
+        svc.executeClaudeCode({
+          prompt: 'p',
+          model: 'claude-3',
+          project_path: '/tmp/p',
+          extra_args: ['--add-dir', '/etc'],
+        })
+      ).rejects.toMatchObject({ code: 'ARG_NOT_ALLOWED', details: { flag: '--add-dir' } });
+    });
+
+    it('adds the args of label rules whose labels the session carries', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, {
+        label_rules: [{ pattern: 'deploy', labels: { env: 'prod' } }],
+        label_arg_rules: [
+          { labels: { env: 'prod' }, args: ['--max-turns', '3'] },
+          { labels: { env: 'prod', team: 'infra' }, args: ['--append-system-prompt', 'Be careful'] },
+        ],
+      });
+
+      await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p', labels: { env: 'prod' } });
+      expect(lastArgs().slice(-2)).toEqual(['--max-turns', '3']);
+
+      await svc.executeClaudeCode({ prompt: 'deploy it', model: 'claude-3', project_path: '/tmp/p', labels: { team: 'infra' } });
+      expect(lastArgs().slice(-4)).toEqual(['--max-turns', '3', '--append-system-prompt', 'Be careful']);
+
+      await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p', labels: { env: 'dev' } });
+      expect(lastArgs()).not.toContain('--max-turns');
+
+      expect(() => new ClaudeService(CLAUDE_BIN, { label_arg_rules: [{ labels: {}, args: ['--model', 'opus'] }] }))
+        .toThrow(/--model is set by the server/);
+    });
+  });
+
+  describe('spawn rate limit', () => {
+    beforeEach(() => {
+      mockedSpawn.mockImplementation(() => new FakeChildProcess(true) as unknown as ChildProcess);
+    });
+
+    it('spaces simultaneous starts according to the limit', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { spawn_rate_limit: 2 });
+
+      const starts = [1, 2, 3].map((n) =>
+        svc.executeClaudeCode({ prompt: `p${n}`, model: 'claude-3', project_path: '/tmp/p' })
+      );
+
+      await jest.advanceTimersByTimeAsync(0);
+      expect(mockedSpawn).toHaveBeenCalledTimes(1);
+
+      await jest.advanceTimersByTimeAsync(499);
+      expect(mockedSpawn).toHaveBeenCalledTimes(1);
+      await jest.advanceTimersByTimeAsync(1);
+      expect(mockedSpawn).toHaveBeenCalledTimes(2);
+
+      await jest.advanceTimersByTimeAsync(500);
+      expect(mockedSpawn).toHaveBeenCalledTimes(3);
+      await expect(Promise.all(starts)).resolves.toHaveLength(3);
+    });
+
+    it('rejects starts beyond max_queue_depth and reports the depth', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { spawn_rate_limit: 1, max_queue_depth: 2 });
+      const start = (n: number) =>
+        svc.executeClaudeCode({ prompt: `p${n}`, model: 'claude-3', project_path: '/tmp/p' });
+
+      const first = start(1);
+      const queued = [start(2), start(3)];
+      await jest.advanceTimersByTimeAsync(0);
+      expect(mockedSpawn).toHaveBeenCalledTimes(1);
+      expect(svc.getProcessStats()).toMatchObject({ running: 3, queue_depth: 2, max_queue_depth: 2 });
+
+      await expect(start(4)).rejects.toMatchObject({ code: 'SPAWN_QUEUE_FULL', status: 429 });
+      expect(svc.getProcessStats()).toMatchObject({ running: 3, queue_depth: 2 });
+
+      await jest.advanceTimersByTimeAsync(2000);
+      await expect(Promise.all([first, ...queued])).resolves.toHaveLength(3);
+      expect(svc.getProcessStats().queue_depth).toBe(0);
+    });
+  });
+
+  describe('tool usage', () => {
+    it('aggregates tool_use blocks from the stream by name', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      const toolUse = (...names: string[]) =>
+        JSON.stringify({
+          type: 'assistant',
+          message: { content: names.map((name, i) => ({ type: 'tool_use', id: `t${i}`, name, input: {} })) },
+        }) + '\n';
+      exec.stdout?.emit('data', Buffer.from(toolUse('Read', 'Grep')));
+      exec.stdout?.emit('data', Buffer.from('{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t0"}]}}\n'));
+      exec.stdout?.emit('data', Buffer.from(toolUse('Read') + toolUse('Edit', 'Read')));
+
+      expect(svc.getSessionTools(id)).toEqual([
+        { name: 'Read', count: 3 },
+        { name: 'Grep', count: 1 },
+        { name: 'Edit', count: 1 },
+      ]);
+      expect(svc.getSessionTools('missing')).toBeUndefined();
+    });
+  });
+
+  describe('resuming a running session', () => {
+    const request = { session_id: 'sess-1', prompt: 'more', model: 'claude-3', project_path: '/tmp/p' };
+
+    beforeEach(() => {
+      mockedSpawn.mockImplementation(() => new FakeChildProcess(true) as unknown as ChildProcess);
+    });
+
+    it('rejects the resume with 409 by default', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      await svc.resumeClaudeCode(request);
+
+      await expect(svc.resumeClaudeCode(request)).rejects.toMatchObject({
+        code: 'SESSION_ALREADY_RUNNING',
+        status: 409,
+      });
+      expect(mockedSpawn).toHaveBeenCalledTimes(1);
+    });
+
+    it('attaches to the running session when configured', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { resume_running: 'attach' });
+      await svc.resumeClaudeCode(request);
+
+      await expect(svc.resumeClaudeCode(request)).resolves.toBe('sess-1');
+      expect(mockedSpawn).toHaveBeenCalledTimes(1);
+    });
+
+    it('resumes a session by name with the Claude session id it reported', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const first = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => first as unknown as ChildProcess);
+      const original = await svc.executeClaudeCode({ ...request, name: 'nightly' });
+      first.stdout?.emit('data', Buffer.from('{"type":"system","subtype":"init","session_id":"claude-xyz"}\n'));
+      first.emit('close', 0);
+
+      await expect(svc.resumeClaudeCode({ ...request, session_id: 'nightly' })).resolves.toBe(original);
+      const [, args] = mockedSpawn.mock.calls[mockedSpawn.mock.calls.length - 1];
+      expect(args.slice(0, 2)).toEqual(['--resume', 'claude-xyz']);
+      expect(args).not.toContain('nightly');
+    });
+  });
+
+  describe('long output lines', () => {
+    it('truncates a line beyond the cap and resumes at the next newline', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN, { max_line_bytes: 1024 });
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      const warn = jest.spyOn(console, 'warn').mockImplementation(() => undefined);
+
+      // Three megabytes without a newline, delivered in chunks like a pipe would
+      const chunk = Buffer.alloc(64 * 1024, 'x');
+      for (let i = 0; i < 48; i++) {
+        exec.stdout?.emit('data', chunk);
+      }
+      exec.stdout?.emit('data', Buffer.from('xxxx\n{"type":"result","result":"still reading"}\nshort line\n'));
+      const warnings = warn.mock.calls.map(([message]) => message);
+      warn.mockRestore();
+
+      expect(warnings).toEqual([`Session ${id}: output line exceeded the 1024-byte cap and was truncated`]);
+
+      const output = svc.getSessionOutput(id)!;
+      expect(output).toHaveLength(3);
+      expect(output[0]).toMatchObject({ stream: 'stdout', truncated: true });
+      expect(output[0].content).toBe('x'.repeat(1024));
+      expect(output[1].truncated).toBeUndefined();
+      expect(output[2].content).toBe('short line');
+      expect(svc.getSessionRecord(id)?.result?.text).toBe('still reading');
+    });
+
+    it('truncates a long line that arrives complete in one chunk', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN, { max_line_bytes: 16 });
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      exec.stdout?.emit('data', Buffer.from(`${'y'.repeat(100)}\nok\n`));
+
+      expect(svc.getSessionOutput(id)!.map((line) => [line.content, line.truncated])).toEqual([
+        ['y'.repeat(16), true],
+        ['ok', undefined],
+      ]);
+    });
+  });
+
+  describe('output handler failures', () => {
+    it('fails the session instead of losing it when handling output throws', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      const events: any[] = [];
+      svc.on('session_event', (event) => events.push(event));
+      const errorSpy = jest.spyOn(console, 'error').mockImplementation(() => undefined);
+      jest.spyOn(svc as any, 'handleStdoutLine').mockImplementationOnce(() => {
+        throw new Error('reader blew up');
+      });
+
+      expect(() => exec.stdout?.emit('data', Buffer.from('{"type":"assistant"}\n'))).not.toThrow();
+      expect(exec.killed).toBe(true);
+      expect(svc.getSessionRecord(id)?.failure_reason).toContain('reader blew up');
+
+      // Output after the failure is dropped, and the session fails even if Claude exits cleanly
+      exec.stdout?.emit('data', Buffer.from('more\n'));
+      expect(svc.getSessionOutput(id)).toEqual([]);
+      exec.emit('close', 0, null);
+
+      expect(svc.getSessionRecord(id)?.status).toBe('failed');
+      expect(events).toEqual([
+        expect.objectContaining({ event: 'failed', session_id: id, error: expect.stringContaining('reader blew up') }),
+      ]);
+      errorSpy.mockRestore();
+    });
+  });
+
+  describe('output timestamps', () => {
+    it('stamps buffered and emitted lines with the time they were read', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      const streamed: any[] = [];
+      svc.on('claude_stream', (data) => streamed.push(data.message));
+      svc.on('claude_output', (data) => streamed.push(data));
+
+      const readAt = new Date('2024-01-01T12:00:00.000Z');
+      jest.setSystemTime(readAt);
+      exec.stdout?.emit('data', Buffer.from('{"type":"assistant","message":{"content":"hi"}}\nplain'));
+      jest.setSystemTime(new Date('2024-01-01T12:00:05.000Z'));
+      exec.stdout?.emit('data', Buffer.from(' text\n'));
+      exec.stderr?.emit('data', Buffer.from('warning'));
+
+      // Reading the buffer later does not change when its lines were produced
+      jest.setSystemTime(new Date('2024-01-01T12:01:00.000Z'));
+      expect(svc.getSessionOutput(id)!.map((line) => [line.content, line.timestamp])).toEqual([
+        ['{"type":"assistant","message":{"content":"hi"}}', '2024-01-01T12:00:00.000Z'],
+        ['plain text', '2024-01-01T12:00:05.000Z'],
+        ['warning', '2024-01-01T12:00:05.000Z'],
+      ]);
+      expect(streamed.map((message) => message.timestamp)).toEqual([
+        '2024-01-01T12:00:00.000Z',
+        '2024-01-01T12:00:05.000Z',
+      ]);
+    });
+  });
+
+  describe('error detail', () => {
+    it('captures the subtype and message of an error result', async () => {
+      const exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const events: any[] = [];
+      svc.on('session_event', (event) => events.push(event));
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      exec.stdout?.emit('data', Buffer.from('{"type":"assistant","message":{"content":"working"}}\n'));
+      expect(svc.getSessionInfo(id)?.error_detail).toBeUndefined();
+
+      exec.stdout?.emit(
+        'data',
+        Buffer.from('{"type":"result","subtype":"error_max_turns","is_error":true,"result":"Reached maximum number of turns (5)"}\n')
+      );
+      exec.emit('close', 1);
+
+      const detail = { subtype: 'error_max_turns', message: 'Reached maximum number of turns (5)' };
+      expect(svc.getSessionRecord(id)?.info.error_detail).toEqual(detail);
+      expect(Array.from(svc.listSessions())[0].error_detail).toEqual(detail);
+      expect(events.find((event) => event.event === 'failed')?.error_detail).toEqual(detail);
+    });
+  });
+
+  describe('memory soft limit', () => {
+    it('sheds output of the oldest finished sessions once RSS passes the limit', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const finish = async (at: string) => {
+        const exec = new FakeChildProcess(true);
+        mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+        const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+        exec.stdout?.emit('data', Buffer.from(`${'x'.repeat(1023)}\n`.repeat(4)));
+        jest.setSystemTime(new Date(at));
+        exec.emit('close', 0);
+        return id;
+      };
+      const newest = await finish('2024-01-01T12:02:00.000Z');
+      const oldest = await finish('2024-01-01T12:00:00.000Z');
+      const middle = await finish('2024-01-01T12:01:00.000Z');
+      const running = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => running as unknown as ChildProcess);
+      const live = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      running.stdout?.emit('data', Buffer.from('still going\n'));
+
+      let rss = 100 * 1024 * 1024;
+      const guard = new MemoryGuard(svc, 100, () => rss);
+      expect(await guard.check()).toEqual([]);
+
+      // 6 KiB over the limit takes two 4 KiB sessions, oldest first
+      rss += 6 * 1024;
+      const shed = await guard.check();
+      // Without a data directory the lines are gone, and both the result and the record say so
+      expect(shed).toEqual([
+        { session_id: oldest, lines: 4, bytes: 4092, lost: true },
+        { session_id: middle, lines: 4, bytes: 4092, lost: true },
+      ]);
+      expect(svc.getSessionRecord(oldest)?.output).toEqual([]);
+      expect(svc.getSessionRecord(oldest)?.output_shed).toBe(4);
+      expect(svc.getSessionRecord(oldest)?.output_lost).toBe(true);
+      expect(svc.getSessionRecord(newest)?.output).toHaveLength(4);
+      expect(svc.getSessionRecord(newest)?.output_lost).toBeUndefined();
+      expect(svc.getSessionRecord(live)?.output).toHaveLength(1);
+    });
+  });
+
+  describe('terminating by age', () => {
+    it('cancels only running sessions started before the cutoff', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const start = async (at: string) => {
+        jest.setSystemTime(new Date(at));
+        const child = new FakeChildProcess(true);
+        mockedSpawn.mockImplementation(() => child as unknown as ChildProcess);
+        const id = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+        return { id, child };
+      };
+      const middle = await start('2024-01-01T10:30:00.000Z');
+      const oldest = await start('2024-01-01T09:00:00.000Z');
+      const recent = await start('2024-01-01T11:45:00.000Z');
+      const finished = await start('2024-01-01T08:00:00.000Z');
+      finished.child.emit('close', 0);
+
+      jest.setSystemTime(new Date('2024-01-01T12:00:00.000Z'));
+      await expect(svc.terminateOlderThan(60)).resolves.toEqual([oldest.id, middle.id]);
+      expect(oldest.child.killed).toBe(true);
+      expect(middle.child.killed).toBe(true);
+      expect(recent.child.killed).toBe(false);
+      expect(svc.getSessionRecord(finished.id)?.status).toBe('completed');
+
+      await expect(svc.terminateOlderThan(0)).rejects.toMatchObject({ status: 400 });
+    });
+  });
+
+  describe('capturing the git diff', () => {
+    it('gives git its own timeout and reports one as an error rather than an empty diff', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { version_timeout_ms: 1000 });
+      const session = new FakeChildProcess(true);
+      const gits: FakeChildProcess[] = [];
+      mockedSpawn.mockImplementation((command: string) => {
+        if (command !== 'git') {
+          return session as unknown as ChildProcess;
+        }
+        const git = new FakeChildProcess(true);
+        gits.push(git);
+        return git as unknown as ChildProcess;
+      });
+      const id = await svc.executeClaudeCode({
+        prompt: 'p', model: 'claude-3', project_path: '/tmp/p', capture_git_diff: true,
+      });
+      session.emit('close', 0);
+      await jest.advanceTimersByTimeAsync(0);
+      expect(gits).toHaveLength(2);
+
+      await jest.advanceTimersByTimeAsync(5000);
+      expect(gits.some((git) => git.killed)).toBe(false);
+
+      await jest.advanceTimersByTimeAsync(60 * 1000);
+      expect(gits.every((git) => git.killed)).toBe(true);
+      expect(svc.getSessionRecord(id)?.git_diff).toBeUndefined();
+      expect(() => svc.getGitDiff(id)).toThrow('The git diff could not be captured: git diff timed out after 60000ms');
+    });
+  });
+
+  describe('recording git info', () => {
+    it('starts without git info when git is slower than its own short timeout', async () => {
+      const warn = jest.spyOn(console, 'warn').mockImplementation(() => undefined);
+      const svc = new ClaudeService(CLAUDE_BIN, { record_git_info: true, version_timeout_ms: 60000 });
+      const gits: FakeChildProcess[] = [];
+      mockedSpawn.mockImplementation((command: string) => {
+        const child = new FakeChildProcess(true);
+        if (command === 'git') {
+          gits.push(child);
+        }
+        return child as unknown as ChildProcess;
+      });
+
+      const started = svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+      await jest.advanceTimersByTimeAsync(2000);
+      const id = await started;
+
+      expect(gits).toHaveLength(2);
+      expect(gits.every((git) => git.killed)).toBe(true);
+      expect(svc.getSessionRecord(id)?.info.git_commit).toBeUndefined();
+      expect(warn).toHaveBeenCalledWith('Not recording git info for /tmp/p: git did not answer within 2000ms');
+      warn.mockRestore();
+    });
+  });
+
+  describe('restarting a session', () => {
+    it('resumes the captured Claude session id in the original project and model', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const first = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => first as unknown as ChildProcess);
+      const original = await svc.executeClaudeCode({ prompt: 'p', model: 'opus', project_path: '/tmp/restart' });
+      first.stdout?.emit('data', Buffer.from('{"type":"system","subtype":"init","session_id":"claude-abc"}\n'));
+      first.emit('close', 0);
+      expect(svc.getSessionRecord(original)?.info.claude_session_id).toBe('claude-abc');
+
+      const second = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => second as unknown as ChildProcess);
+      const restarted = await svc.restartSession(original);
+
+      expect(restarted).not.toBe(original);
+      const [, args, options] = mockedSpawn.mock.calls[mockedSpawn.mock.calls.length - 1];
+      expect(args.slice(0, 2)).toEqual(['--resume', 'claude-abc']);
+      expect(args[args.indexOf('--model') + 1]).toBe('opus');
+      expect(options.cwd).toBe('/tmp/restart');
+      expect(svc.getSessionRecord(restarted)?.info.restarted_from).toBe(original);
+    });
+
+    it('refuses a session that never reported a Claude session id', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const child = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => child as unknown as ChildProcess);
+      const id = await svc.executeClaudeCode({ prompt: 'p', model: 'opus', project_path: '/tmp/restart' });
+      child.emit('close', 1);
+
+      await expect(svc.restartSession(id)).rejects.toMatchObject({ code: 'CLAUDE_SESSION_ID_MISSING', status: 409 });
+    });
+  });
+
+  describe('wrapper command', () => {
+    beforeEach(() => {
+      mockedSpawn.mockImplementation(() => new FakeChildProcess(true) as unknown as ChildProcess);
+    });
+
+    it('spawns sessions under the wrapper with the Claude binary as its argument', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { wrapper_command: ['nice', '-n', '10'] });
+
+      const sessionId = await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      const [binary, args] = mockedSpawn.mock.calls[0] as [string, string[]];
+      expect(binary).toBe('nice');
+      expect(args.slice(0, 3)).toEqual(['-n', '10', CLAUDE_BIN]);
+      expect(args.slice(3)).toEqual(expect.arrayContaining(['-p', 'p', '--model', 'claude-3']));
+      expect(svc.getSessionRecord(sessionId)?.launch).toMatchObject({ binary: 'nice', args });
+    });
+
+    it('spawns the Claude binary directly without a wrapper', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      await svc.executeClaudeCode({ prompt: 'p', model: 'claude-3', project_path: '/tmp/p' });
+
+      expect(mockedSpawn.mock.calls[0][0]).toBe(CLAUDE_BIN);
+    });
+
+    it('refuses a wrapper with empty tokens', () => {
+      expect(() => new ClaudeService(CLAUDE_BIN, { wrapper_command: ['nice', ''] })).toThrow(/wrapper_command/);
+    });
+  });
+
+  describe('output retention', () => {
+    const request = { prompt: 'p', model: 'claude-3', project_path: '/tmp/p', output_retention_ms: 5 * 60 * 1000 };
+    let exec: FakeChildProcess;
+
+    beforeEach(() => {
+      exec = new FakeChildProcess(true);
+      mockedSpawn.mockImplementation(() => exec as unknown as ChildProcess);
+    });
+
+    it('drops lines read before the window, also while no new line arrives', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const id = await svc.executeClaudeCode(request);
+      const contents = () => svc.getSessionOutput(id)!.map((line) => line.content);
+
+      exec.stdout?.emit('data', Buffer.from('first\n'));
+      jest.advanceTimersByTime(4 * 60 * 1000);
+      exec.stdout?.emit('data', Buffer.from('second\n'));
+      expect(contents()).toEqual(['first', 'second']);
+
+      jest.advanceTimersByTime(2 * 60 * 1000);
+      expect(contents()).toEqual(['second']);
+
+      jest.advanceTimersByTime(4 * 60 * 1000);
+      exec.stdout?.emit('data', Buffer.from('third\n'));
+      expect(contents()).toEqual(['third']);
+      expect(svc.getSessionRecord(id)?.output_total).toBe(3);
+    });
+
+    it('applies the window to every read of a finished session', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const id = await svc.executeClaudeCode(request);
+
+      exec.stdout?.emit('data', Buffer.from('first\n'));
+      exec.emit('close', 0);
+      expect(svc.getSessionResult(id)).toEqual({ text: 'first', parsed: false });
+
+      // No line arrives after the window has passed
+      jest.advanceTimersByTime(6 * 60 * 1000);
+
+      expect(svc.getOutputBatch([{ session_id: id }])).toEqual([
+        { session_id: id, status: 'completed', lines: [], next: 1, skipped: 1 },
+      ]);
+      expect(svc.getSessionResult(id)).toEqual({ text: '', parsed: false });
+      expect(await svc.readSessionOutput(id)).toEqual([]);
+      expect(svc.getSessionRecord(id)?.output).toEqual([]);
+    });
+
+    it('keeps the line cap when it is tighter than the window', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { max_output_lines: 2 });
+      const id = await svc.executeClaudeCode(request);
+
+      exec.stdout?.emit('data', Buffer.from('one\ntwo\nthree\n'));
+
+      expect(svc.getSessionOutput(id)!.map((line) => line.content)).toEqual(['two', 'three']);
+    });
+
+    it('rejects a window that is not a positive number', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      await expect(svc.executeClaudeCode({ ...request, output_retention_ms: 0 })).rejects.toMatchObject({
+        code: 'VALIDATION_ERROR',
+        status: 400,
+      });
+    });
+  });
+
+  describe('prompt wrapping', () => {
+    const lastPrompt = () => {
+      const args = mockedSpawn.mock.calls[mockedSpawn.mock.calls.length - 1][1] as string[];
+      return args[args.indexOf('-p') + 1];
+    };
+
+    beforeEach(() => {
+      mockedSpawn.mockImplementation(() => new FakeChildProcess(true) as unknown as ChildProcess);
+    });
+
+    it('puts the configured prefix and suffix around the prompt unless the request opts out', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, {
+        prompt_prefix: 'Follow the coding standards.',
+        prompt_suffix: 'Run the tests before finishing.',
+      });
+
+      const sessionId = await svc.executeClaudeCode({ prompt: 'Fix the bug', model: 'claude-3', project_path: '/tmp/p' });
+      expect(lastPrompt()).toBe('Follow the coding standards.\n\nFix the bug\n\nRun the tests before finishing.');
+      expect(svc.getSessionRecord(sessionId)?.info.task).toBe('Fix the bug');
+
+      await svc.executeClaudeCode({
+        prompt: 'Fix the bug',
+        model: 'claude-3',
+        project_path: '/tmp/p',
+        apply_prompt_wrapping: false,
+      });
+      expect(lastPrompt()).toBe('Fix the bug');
+    });
+
+    it('wraps on one side only when only a prefix is configured', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN, { prompt_prefix: 'Be brief.' });
+
+      await svc.executeClaudeCode({ prompt: 'Explain', model: 'claude-3', project_path: '/tmp/p' });
+      expect(lastPrompt()).toBe('Be brief.\n\nExplain');
+    });
+  });
+
+  describe('spawn failures', () => {
+    // Like a real failed spawn: no pid, and the reason follows as an `error` event
+    function failSpawnWith(code: string) {
+      mockedSpawn.mockImplementation(() => {
+        const child = new FakeChildProcess(false);
+        void nextIteration().then(() =>
+          child.emit('error', Object.assign(new Error(`spawn ${CLAUDE_BIN} ${code}`), { code }))
+        );
+        return child as unknown as ChildProcess;
+      });
+    }
+
+    const request = { prompt: 'p', model: 'claude-3', project_path: '/tmp/p' };
+
+    it('reports a missing binary as a failed dependency that may have moved', async () => {
+      failSpawnWith('ENOENT');
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      await expect(svc.executeClaudeCode(request)).rejects.toMatchObject({
+        code: 'CLAUDE_BINARY_NOT_FOUND',
+        status: 424,
+        message: expect.stringMatching(/not found; it may have been moved/),
+        details: { binary: CLAUDE_BIN },
+      });
+      expect(svc.getProcessStats().running).toBe(0);
+    });
+
+    it('suggests chmod when the binary may not be executed', async () => {
+      failSpawnWith('EACCES');
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      await expect(svc.executeClaudeCode(request)).rejects.toMatchObject({
+        code: 'CLAUDE_BINARY_NOT_EXECUTABLE',
+        status: 424,
+        message: expect.stringMatching(/chmod \+x/),
+      });
+    });
+
+    it('emits failed_to_start for a session whose process could not be spawned', async () => {
+      failSpawnWith('ENOENT');
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const events: any[] = [];
+      svc.on('session_event', (event) => events.push(event));
+
+      await expect(svc.executeClaudeCode(request)).rejects.toMatchObject({ code: 'CLAUDE_BINARY_NOT_FOUND' });
+      expect(events).toEqual([
+        expect.objectContaining({
+          event: 'failed_to_start',
+          project_path: '/tmp/p',
+          model: 'claude-3',
+          error: expect.stringMatching(/was not found/),
+        }),
+      ]);
+    });
+
+    it('passes other errors through as a server error', async () => {
+      failSpawnWith('EAGAIN');
+      const svc = new ClaudeService(CLAUDE_BIN);
+
+      await expect(svc.executeClaudeCode(request)).rejects.toMatchObject({
+        code: 'SPAWN_FAILED',
+        status: 500,
+        message: `Failed to start Claude process: spawn ${CLAUDE_BIN} EAGAIN`,
+        details: { code: 'EAGAIN' },
+      });
+    });
+
+    it('names the wrapper when it is the binary that failed', async () => {
+      failSpawnWith('ENOENT');
+      const svc = new ClaudeService(CLAUDE_BIN, { wrapper_command: ['firejail'] });
+
+      await expect(svc.executeClaudeCode(request)).rejects.toMatchObject({ details: { binary: 'firejail' } });
+    });
+  });
+
+  describe('orphaned processes', () => {
+    let dataDir: string;
+
+    beforeEach(async () => {
+      dataDir = await fs.mkdtemp(path.join(os.tmpdir(), 'claudia-orphans-'));
+    });
+
+    afterEach(async () => {
+      await fs.rm(dataDir, { recursive: true, force: true });
+    });
+
+    it('finds nothing without a data directory', async () => {
+      const svc = new ClaudeService(CLAUDE_BIN);
+      const listProcesses = jest.fn(async () => [
+        { pid: 300, ppid: 1, started_at: Date.now(), command: '/usr/local/bin/claude -p x --output-format stream-json' },
+      ]);
+
+      expect(await svc.findOrphans(listProcesses)).toEqual([]);
+      expect(listProcesses).not.toHaveBeenCalled();
+    });
+
+    it('reports processes an earlier run recorded and forgets the ones that are gone', async () => {
+      const earlier = new SessionStore(dataDir);
+      const startedAt = new Date('2026-10-16T07:30:00.000Z');
+      await earlier.recordProcess({ session_id: 'alive', pid: 300, started_at: startedAt.toISOString(), server_pid: 1 });
+      await earlier.recordProcess({ session_id: 'gone', pid: 301, started_at: startedAt.toISOString(), server_pid: 1 });
+
+      const svc = new ClaudeService(CLAUDE_BIN, { data_dir: dataDir });
+      const orphans = await svc.findOrphans(async () => [
+        { pid: 300, ppid: 1, started_at: startedAt.getTime(), command: 'claude -p a' },
+        // The PID of the second session now belongs to something else
+        { pid: 301, ppid: 1, started_at: startedAt.getTime() + 3_600_000, command: 'vim' },
+        { pid: 302, ppid: 1, started_at: startedAt.getTime(), command: 'claude -p desktop --output-format stream-json' },
+      ]);
+
+      expect(orphans).toEqual([expect.objectContaining({ pid: 300, session_id: 'alive' })]);
+      expect((await earlier.listProcesses()).map((entry) => entry.session_id)).toEqual(['alive']);
+    });
+  });
+});
@@ -0,0 +1,61 @@
+import { descendantsOf, findOrphanedProcesses, isRecordedProcess, parsePsOutput } from '../orphans.js';
+import type { RecordedProcess } from '../session-store.js';
+
+describe('orphan detection', () => {
+  const SERVER_PID = 100;
+  const processes = parsePsOutput(
+    [
+      '  100     1 Thu Oct 16 08:00:00 2026 node dist/index.js --port 3000',
+      '  200   100 Thu Oct 16 09:00:00 2026 /usr/local/bin/claude -p hello --output-format stream-json',
+      '  300     1 Thu Oct 16 07:30:00 2026 /usr/local/bin/claude -p old --output-format stream-json',
+      '  400     1 Thu Oct 16 07:45:00 2026 /usr/local/bin/claude -p desktop --output-format stream-json',
+      '  500     1 Thu Oct 16 09:10:00 2026 sh -c wrapper',
+      '  510   500 Thu Oct 16 09:10:01 2026 /usr/local/bin/claude -p wrapped --output-format stream-json',
+      '  600     1 Thu Oct 16 09:20:00 2026 vim notes.md',
+    ].join('\n')
+  );
+  const at = (time: string) => new Date(2026, 9, 16, ...time.split(':').map(Number)).toISOString();
+  const recorded = (session_id: string, pid: number, time: string): RecordedProcess => ({
+    session_id,
+    pid,
+    started_at: at(time),
+    server_pid: 99,
+  });
+
+  it('parses ps output with start times', () => {
+    expect(processes).toHaveLength(7);
+    expect(processes[1]).toEqual({
+      pid: 200,
+      ppid: 100,
+      started_at: new Date(2026, 9, 16, 9, 0, 0).getTime(),
+      command: '/usr/local/bin/claude -p hello --output-format stream-json',
+    });
+  });
+
+  it('matches a recorded process only while its PID belongs to the same process', () => {
+    expect(isRecordedProcess(processes[2], recorded('old', 300, '07:30:00'))).toBe(true);
+    // The PID was reused by a process started an hour later
+    expect(isRecordedProcess(processes[6], recorded('gone', 600, '08:20:00'))).toBe(false);
+  });
+
+  it('collects every process below the given ones', () => {
+    expect(descendantsOf(processes, [500])).toEqual(new Set([500, 510]));
+  });
+
+  it('reports only recorded processes that nothing running now owns', () => {
+    const orphans = findOrphanedProcesses(
+      processes,
+      [
+        recorded('old', 300, '07:30:00'),
+        recorded('reused', 600, '08:20:00'),
+        recorded('child', 200, '09:00:00'),
+        recorded('wrapped', 510, '09:10:01'),
+      ],
+      new Set([500]),
+      SERVER_PID
+    );
+
+    // 400 is Claude, but not one of ours; 200 and 510 run below the server and a tracked wrapper
+    expect(orphans).toEqual([{ ...processes[2], session_id: 'old' }]);
+  });
+});
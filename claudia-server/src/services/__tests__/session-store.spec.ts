@@ -0,0 +1,58 @@
+import { promises as fs } from 'fs';
+import { tmpdir } from 'os';
+import { join } from 'path';
+
+import { SessionStore } from '../session-store.js';
+import type { SessionRecord } from '../../types/index.js';
+
+describe('SessionStore', () => {
+  let dataDir: string;
+
+  beforeEach(async () => {
+    dataDir = await fs.mkdtemp(join(tmpdir(), 'claudia-store-'));
+  });
+
+  afterEach(async () => {
+    await fs.rm(dataDir, { recursive: true, force: true });
+  });
+
+  const record = (status: SessionRecord['status'], output: string[]): SessionRecord => ({
+    info: {
+      run_id: 1,
+      process_type: { ClaudeSession: { session_id: 'abc' } },
+      pid: 4242,
+      started_at: '2024-01-01T12:00:00.000Z',
+      project_path: '/tmp/p',
+      task: 'p',
+      model: 'sonnet',
+    },
+    status,
+    output: output.map((content) => ({ stream: 'stdout', content, timestamp: '2024-01-01T12:00:00.000Z' })),
+  });
+
+  it('lands overlapping saves of one session in call order, leaving no temporary files', async () => {
+    const store = new SessionStore(dataDir);
+
+    // The first record is much larger, so an unordered write would finish last
+    const running = store.save('abc', record('running', Array.from({ length: 5000 }, (_, i) => `line ${i}`)));
+    const completed = store.save('abc', record('completed', ['done']));
+    await Promise.all([running, completed]);
+
+    const saved = JSON.parse(await fs.readFile(join(dataDir, 'sessions', 'abc.json'), 'utf8'));
+    expect(saved.status).toBe('completed');
+    expect(saved.output).toHaveLength(1);
+    expect(await fs.readdir(join(dataDir, 'sessions'))).toEqual(['abc.json']);
+  });
+
+  it('keeps the record as it was when save was called', async () => {
+    const store = new SessionStore(dataDir);
+    const saved = record('running', []);
+
+    const write = store.save('abc', saved);
+    saved.status = 'failed';
+    await write;
+
+    const onDisk = JSON.parse(await fs.readFile(join(dataDir, 'sessions', 'abc.json'), 'utf8'));
+    expect(onDisk.status).toBe('running');
+  });
+});
@@ -0,0 +1,126 @@
+import { promises as fs } from 'fs';
+import { dirname, join } from 'path';
+
+/**
+ * Default parent of the per-session cgroups; the server needs write access to it
+ * (run as root, or with a delegated subtree)
+ */
+export const DEFAULT_CGROUP_ROOT = '/sys/fs/cgroup/claudia';
+
+/**
+ * Per-session cgroup v2 groups with a memory limit, so a runaway session is OOM-killed
+ * instead of exhausting the host.
+ *
+ * Setup is best-effort: on other platforms, without cgroup v2 and its memory controller, or
+ * without permission, a warning is logged once and sessions run without a limit.
+ */
+export class MemoryCgroups {
+  private available?: Promise<boolean>;
+
+  constructor(
+    private root: string = DEFAULT_CGROUP_ROOT,
+    private platform: NodeJS.Platform = process.platform
+  ) {}
+
+  /**
+   * Create the cgroup for a session with `memory.max` set, returning its path, or
+   * undefined when limits are unavailable
+   */
+  async create(sessionId: string, limitMb: number): Promise<string | undefined> {
+    if (!(await this.isAvailable())) {
+      return undefined;
+    }
+
+    const path = join(this.root, `session-${sessionId}`);
+    try {
+      await fs.mkdir(path, { recursive: true });
+      await fs.writeFile(join(path, 'memory.max'), String(Math.floor(limitMb * 1024 * 1024)));
+    } catch (error) {
+      console.warn(`⚠️  Could not set a memory limit for session ${sessionId}: ${messageOf(error)}`);
+      await this.remove(path);
+      return undefined;
+    }
+
+    // Without this, a session at its limit swaps instead of being killed
+    await fs.writeFile(join(path, 'memory.swap.max'), '0').catch(() => undefined);
+    return path;
+  }
+
+  /**
+   * Move a process into a session cgroup; processes it starts later inherit the group
+   */
+  async addProcess(path: string, pid: number): Promise<void> {
+    try {
+      await fs.writeFile(join(path, 'cgroup.procs'), String(pid));
+    } catch (error) {
+      console.warn(`⚠️  Could not move process ${pid} into ${path}: ${messageOf(error)}`);
+    }
+  }
+
+  /**
+   * Whether the kernel OOM-killed a process in the cgroup because of its memory limit
+   */
+  async wasOomKilled(path: string): Promise<boolean> {
+    try {
+      const events = await fs.readFile(join(path, 'memory.events'), 'utf8');
+      const match = events.match(/^oom_kill (\d+)$/m);
+      return match ? parseInt(match[1], 10) > 0 : false;
+    } catch {
+      return false;
+    }
+  }
+
+  /**
+   * Remove an empty session cgroup
+   */
+  async remove(path: string): Promise<void> {
+    await fs.rmdir(path).catch(() => undefined);
+  }
+
+  /**
+   * Check once that cgroup v2 with the memory controller is usable and enable the
+   * controller for the session groups
+   */
+  private isAvailable(): Promise<boolean> {
+    this.available ??= this.setUp().then((reason) => {
+      if (reason) {
+        console.warn(`⚠️  Memory limits unavailable, sessions run without one: ${reason}`);
+      }
+      return !reason;
+    });
+    return this.available;
+  }
+
+  /**
+   * Returns why memory limits cannot be used, or undefined when they can
+   */
+  private async setUp(): Promise<string | undefined> {
+    if (this.platform !== 'linux') {
+      return 'cgroup memory limits need Linux';
+    }
+
+    const parent = dirname(this.root);
+    try {
+      const controllers = await fs.readFile(join(parent, 'cgroup.controllers'), 'utf8');
+      if (!controllers.split(/\s+/).includes('memory')) {
+        return `the memory controller is not available in ${parent}`;
+      }
+    } catch {
+      return `${parent} is not a cgroup v2 hierarchy`;
+    }
+
+    try {
+      await fs.appendFile(join(parent, 'cgroup.subtree_control'), '+memory');
+      await fs.mkdir(this.root, { recursive: true });
+      await fs.appendFile(join(this.root, 'cgroup.subtree_control'), '+memory');
+    } catch (error) {
+      return `cannot set up ${this.root}: ${messageOf(error)}`;
+    }
+
+    return undefined;
+  }
+}
+
+function messageOf(error: unknown): string {
+  return error instanceof Error ? error.message : String(error);
+}
@@ -1,27 +1,420 @@
 import { spawn, ChildProcess } from 'child_process';
 import { EventEmitter } from 'events';
 import { v4 as uuidv4 } from 'uuid';
-import { promises as fs } from 'fs';
-import { join, dirname } from 'path';
-import { homedir } from 'os';
+import { ServiceError } from '../utils/errors.js';
+import { decodeOutput } from '../utils/encoding.js';
+import { contentBlocksOf, textOf } from '../utils/stream-json.js';
+import { parseHelpFlags } from '../utils/cli-help.js';
+import { redactEnv } from '../utils/redact.js';
+import { shellCommand } from '../utils/shell.js';
+import { compareVersions } from '../utils/version.js';
+import type { CharsetFallback, DecodedText } from '../utils/encoding.js';
+import { findOrphanedProcesses, isRecordedProcess, listSystemProcesses } from './orphans.js';
+import type { OrphanedProcess, SystemProcess } from './orphans.js';
+import { SessionStore } from './session-store.js';
+import { DetachedProcess } from './detached.js';
+import { MemoryCgroups } from './cgroups.js';
+import { ThroughputMeter } from './throughput.js';
+import type { DataDirCheck, PersistenceStatus } from './session-store.js';
+import { promises as fs, constants as fsConstants, createReadStream, createWriteStream } from 'fs';
+import type { FileHandle } from 'fs/promises';
+import { Readable } from 'stream';
+import { pipeline } from 'stream/promises';
+import { createInterface } from 'readline';
+import { setImmediate as nextIteration } from 'timers/promises';
+import { join, dirname, resolve, relative, isAbsolute, delimiter } from 'path';
+import { homedir, tmpdir } from 'os';
 import type {
   ClaudeStreamMessage,
   ProcessInfo,
+  ProcessStats,
+  SessionTotals,
+  SessionStatusChange,
+  UsageQuery,
+  UsageGroup,
   ClaudeVersionStatus,
+  ClaudeHelp,
   ExecuteClaudeRequest,
   ContinueClaudeRequest,
   ResumeClaudeRequest,
+  CancelClaudeRequest,
+  RestartSessionRequest,
+  CompareRequest,
+  CompareRun,
+  ClaudeInstallation,
+  ClaudeSelection,
+  SessionEvent,
+  SessionIntervention,
+  InterventionReason,
+  SessionLifecycleEvent,
+  SessionRecord,
+  SessionDebugInfo,
+  SessionCommand,
+  SessionListEntry,
+  SessionListFilter,
+  SessionResult,
+  SessionGitDiff,
+  ShedOutput,
+  SessionStatus,
+  SessionInputResult,
+  SessionThroughput,
+  OutputBatchEntry,
+  OutputLine,
+  ToolUsage,
+  SessionPrecondition,
+  SessionWatchdogUpdate,
+  LabelRule,
+  LabelArgRule,
 } from '../types/index.js';
 
+/**
+ * Runtime options for ClaudeService
+ */
+export interface ClaudeServiceOptions {
+  /** Default for --dangerously-skip-permissions when a request does not say (default: true) */
+  skip_permissions?: boolean;
+  /** Default for --verbose when a request does not say (default: true) */
+  verbose?: boolean;
+  /**
+   * Kill running sessions when the service is cleaned up (default: true).
+   * When false, processes are spawned detached in their own process group, with their output
+   * going to files under data_dir instead of pipes, so they outlive a server restart and are
+   * taken back by adoptDetachedSessions. Requires data_dir.
+   */
+  kill_on_drop?: boolean;
+  /** Number of output lines buffered per session (default: 1000) */
+  max_output_lines?: number;
+  /**
+   * Treat the buffered lines as a window over the session's output log: older lines are read
+   * back from disk by readSessionOutput. Requires data_dir (default: false)
+   */
+  disk_backed_output?: boolean;
+  /** Longest stdout line kept in bytes; the rest of a longer line is dropped (default: 10 MiB) */
+  max_line_bytes?: number;
+  /** How long a soft cancel waits for Claude to exit after closing its stdin (default: 10000) */
+  soft_cancel_grace_ms?: number;
+  /** How long Claude may keep running after closing its stdout before it is killed (default: 10000) */
+  stdout_eof_grace_ms?: number;
+  /**
+   * Cancel a session whose stdout averages more than this many bytes per second over a
+   * whole throughput window, catching runaway output loops (default: no limit)
+   */
+  max_output_bytes_per_sec?: number;
+  /** Shortest output preview refresh interval, used while a session is active (default: 1000) */
+  preview_interval_min_ms?: number;
+  /** Longest output preview refresh interval, reached while a session is quiet (default: 16000) */
+  preview_interval_max_ms?: number;
+  /** How output that is not valid UTF-8 is rendered; such lines are always tagged (default: replace) */
+  output_charset_fallback?: CharsetFallback;
+  /** Also write every output line to the server's stdout, prefixed with the session ID (default: false) */
+  tee_output_to_stdout?: boolean;
+  /** Regular expressions; output lines matching one are dropped before they are buffered or broadcast (default: none) */
+  output_filters?: string[];
+  /** Command sessions are run under, e.g. `['nice', '-n', '10']`; the Claude binary and its arguments follow it (default: none) */
+  wrapper_command?: string[];
+  /** Count the lines output_filters dropped in each session's `output_filtered` (default: false) */
+  count_filtered_output?: boolean;
+  /** Maximum sessions running at once across all projects (default: unlimited) */
+  max_concurrent_sessions?: number;
+  /** Maximum sessions running at once in a single project (default: unlimited) */
+  max_concurrent_per_project?: number;
+  /**
+   * Refuse a session whose project already has one running with 409; otherwise the start
+   * succeeds with a warning (default: false)
+   */
+  reject_concurrent_same_project?: boolean;
+  /**
+   * Maximum sessions running at once that were started with the same API key (default: unlimited).
+   * While set, starts without a key are refused. Keys are not authenticated, so this only limits
+   * clients that cannot invent new keys.
+   */
+  per_key_session_limit?: number;
+  /** Directory session records are written to; persistence is off when unset */
+  data_dir?: string;
+  /** Append persisted output lines to disk at least this often (default: 1000) */
+  output_flush_interval_ms?: number;
+  /** Append a session's persisted output as soon as this many lines are buffered (default: 100) */
+  output_flush_batch_lines?: number;
+  /** Fallback model per requested model, used once when a session fails with an overload error */
+  model_fallback?: Record<string, string>;
+  /** Maximum session records kept; the oldest finished ones are evicted first (default: unlimited) */
+  max_stored_sessions?: number;
+  /**
+   * Flags clients may pass in extra_args. When unset any flag not reserved by the server is
+   * accepted; when set, only the listed flags are.
+   */
+  allowed_extra_args?: string[];
+  /** Maximum Claude processes spawned per second; extra starts wait their turn (default: unlimited) */
+  spawn_rate_limit?: number;
+  /** Starts that may wait for spawn_rate_limit at once; further starts are rejected with 429 (default: unbounded) */
+  max_queue_depth?: number;
+  /** Start in read-only mode, refusing new sessions (default: false) */
+  read_only?: boolean;
+  /** How long a `claude --version` or `--help` probe may run before it is killed (default: 5000) */
+  version_timeout_ms?: number;
+  /** Memory limit per session in MiB, enforced with a cgroup v2 group on Linux (default: none) */
+  memory_limit_mb?: number;
+  /** Parent cgroup the per-session groups are created under (default: /sys/fs/cgroup/claudia) */
+  cgroup_root?: string;
+  /** Record the project's git branch and HEAD commit when a session starts (default: false) */
+  record_git_info?: boolean;
+  /** Directories output_result_to may point into (default: the session's project_path) */
+  result_output_roots?: string[];
+  /** Directories files fed to a session's stdin may come from (default: the session's project_path) */
+  input_file_roots?: string[];
+  /** Largest file that may be fed to a session's stdin (default: 1 MiB) */
+  max_input_file_bytes?: number;
+  /** Directories a session's additional_dirs must lie under (default: any existing directory) */
+  additional_dir_roots?: string[];
+  /**
+   * What resuming a session that is still running does: `reject` with 409, or `attach` by
+   * returning the running session without starting another process (default: reject)
+   */
+  resume_running?: 'reject' | 'attach';
+  /** Claude binaries compareInstallations may run (default: none, which disables it) */
+  compare_binaries?: string[];
+  /** Labels attached to sessions whose prompt matches a rule's pattern (default: none) */
+  label_rules?: LabelRule[];
+  /** Claude arguments added to sessions carrying a rule's labels, after the request's own (default: none) */
+  label_arg_rules?: LabelArgRule[];
+  /** Regular expressions, matched case-insensitively, that refuse a session whose prompt matches (default: none) */
+  prompt_denylist?: string[];
+  /** Text put before every prompt, separated by a blank line, unless the request opts out (default: none) */
+  prompt_prefix?: string;
+  /** Text put after every prompt, separated by a blank line, unless the request opts out (default: none) */
+  prompt_suffix?: string;
+}
+
+/**
+ * Number of trailing stdout lines used as the result when no `result` message was emitted
+ */
+const RESULT_FALLBACK_LINES = 20;
+
+/**
+ * Length of SessionInfo.output_preview and the default bounds of its refresh interval.
+ * The interval doubles while a session is quiet and halves while it produces output.
+ */
+const OUTPUT_PREVIEW_CHARS = 500;
+const OUTPUT_PREVIEW_INTERVAL_MS = 1000;
+const OUTPUT_PREVIEW_MAX_INTERVAL_MS = 16000;
+
+/**
+ * A session's rendered log written to a temporary file by writeSessionLog
+ */
+export interface SessionLogFile {
+  path: string;
+  /** Delete the file; call once it has been served */
+  remove(): Promise<void>;
+}
+
+/**
+ * Live timeout and abort patterns of a running session
+ */
+interface Watchdog {
+  timer?: NodeJS.Timeout;
+  patterns: RegExp[];
+}
+
+/**
+ * The files a detached session's output goes to, opened for its process
+ */
+interface DetachedOutputFiles {
+  stdout: FileHandle;
+  stderr: FileHandle;
+}
+
+/**
+ * What a running session was started with, kept so it can be reset
+ */
+interface SessionLaunchRequest {
+  claudePath: string;
+  args: string[];
+  projectPath: string;
+  request: any;
+}
+
+/**
+ * Flags the server sets itself, which clients may never pass in extra_args
+ */
+const RESERVED_FLAGS = [
+  '-p',
+  '--print',
+  '--model',
+  '--output-format',
+  '--input-format',
+  '--verbose',
+  '-c',
+  '--continue',
+  '-r',
+  '--resume',
+  '--settings',
+  '--add-dir',
+  '--dangerously-skip-permissions',
+];
+
+/**
+ * Errors Claude reports when a model is overloaded or rate limited
+ */
+const OVERLOAD_PATTERN = /overloaded|rate[_ ]limit|\b529\b/i;
+
+/**
+ * Default time allowed for a `claude --version` or `--help` probe
+ */
+const DEFAULT_VERSION_TIMEOUT_MS = 5000;
+
+/**
+ * Time allowed for the `git diff` and `git status` of capture_git_diff, which can be slow in a
+ * large repository
+ */
+const GIT_DIFF_TIMEOUT_MS = 60 * 1000;
+
+/**
+ * Time allowed for each git lookup of record_git_info, which holds up the session's spawn
+ */
+const GIT_INFO_TIMEOUT_MS = 2000;
+
+/**
+ * Default cap on a single stdout line, so one huge tool result cannot exhaust memory
+ */
+const DEFAULT_MAX_LINE_BYTES = 10 * 1024 * 1024;
+
+/**
+ * Session names: URL-safe, starting with a letter or digit
+ */
+const SESSION_NAME = /^[A-Za-z0-9][A-Za-z0-9._-]{0,63}$/;
+
+/**
+ * Names shaped like a session id are refused so a name can never shadow an id
+ */
+const UUID_LIKE = /^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$/i;
+
+/**
+ * Default size limit of a file fed to a session's stdin
+ */
+const DEFAULT_MAX_INPUT_FILE_BYTES = 1024 * 1024;
+
+/**
+ * Most sessions one output batch request may ask for
+ */
+const MAX_OUTPUT_BATCH_SIZE = 100;
+
+/**
+ * Default time a soft cancel waits before killing the process
+ */
+const DEFAULT_SOFT_CANCEL_GRACE_MS = 10000;
+
+/**
+ * Default time a process may outlive its stdout before it is killed
+ */
+const DEFAULT_STDOUT_EOF_GRACE_MS = 10000;
+
+/**
+ * Default time a compare run may take before it is cancelled
+ */
+const DEFAULT_COMPARE_TIMEOUT_MS = 5 * 60 * 1000;
+
+/**
+ * Prompt a restarted session is resumed with when the client gives none
+ */
+const DEFAULT_RESTART_PROMPT = 'Continue from where you left off.';
+
+/**
+ * Maximum characters of precondition output returned to the client
+ */
+const PRECONDITION_OUTPUT_LIMIT = 64 * 1024;
+
 /**
  * Service for managing Claude Code CLI processes
  */
 export class ClaudeService extends EventEmitter {
   private processes: Map<string, ChildProcess> = new Map();
   private processRegistry: Map<string, ProcessInfo> = new Map();
+  private cancelRequested: Set<string> = new Set();
+  private sessions: Map<string, SessionRecord> = new Map();
+  private previewBuffers: Map<string, string> = new Map();
+  private previewTimers: Map<string, NodeJS.Timeout> = new Map();
+  private previewActive: Set<string> = new Set();
+  private runningCount = 0;
+  private runningPerProject: Map<string, number> = new Map();
+  private runningPerKey: Map<string, number> = new Map();
+  private sessionKeys: Map<string, string> = new Map(); // sessionId -> API key that started it
+  private settingsDirs: Map<string, string> = new Map();
+  private overloaded: Set<string> = new Set();
+  private watchdogs: Map<string, Watchdog> = new Map();
+  private throughput: Map<string, ThroughputMeter> = new Map();
+  private helpCache: Map<string, ClaudeHelp> = new Map();
+  private nextSpawnAt = 0;
+  private queuedSpawns = 0;
+  private readOnly: boolean;
+  private cgroups?: MemoryCgroups;
+  private sessionCgroups: Map<string, string> = new Map();
+  private resultTargets: Map<string, string> = new Map();
+  private gitDiffRequested: Set<string> = new Set();
+  private gitDiffPending: Set<string> = new Set();
+  private discardOutput: Set<string> = new Set();
+  private resuming: Set<string> = new Set();
+  private launchRequests: Map<string, SessionLaunchRequest> = new Map();
+  private resetting: Set<string> = new Set();
+  private sessionNames: Map<string, string> = new Map();
+  private feedingInput: Set<string> = new Set();
+  private labelRules: { pattern: RegExp; labels: Record<string, string> }[];
+  private labelArgRules: LabelArgRule[];
+  private promptDenylist: RegExp[];
+  private outputFilters: RegExp[];
+  private store?: SessionStore;
 
-  constructor(private claudeBinaryPath?: string) {
+  constructor(private claudeBinaryPath?: string, private options: ClaudeServiceOptions = {}) {
     super();
+    // Every SSE client attaches its own listener
+    this.setMaxListeners(0);
+    if (options.data_dir) {
+      this.store = new SessionStore(options.data_dir, {
+        interval_ms: options.output_flush_interval_ms,
+        batch_lines: options.output_flush_batch_lines,
+      });
+    }
+    this.readOnly = options.read_only ?? false;
+    if (options.memory_limit_mb) {
+      this.cgroups = new MemoryCgroups(options.cgroup_root);
+    }
+    this.labelRules = compileLabelRules(options.label_rules ?? []);
+    this.labelArgRules = checkLabelArgRules(options.label_arg_rules ?? []);
+    this.promptDenylist = compilePromptDenylist(options.prompt_denylist ?? []);
+    this.outputFilters = compileOutputFilters(options.output_filters ?? []);
+    checkWrapperCommand(options.wrapper_command ?? []);
+    if (options.disk_backed_output && !options.data_dir) {
+      throw new Error('disk_backed_output requires data_dir, where the output logs are kept');
+    }
+    if (options.kill_on_drop === false && !options.data_dir) {
+      throw new Error('kill_on_drop: false requires data_dir, where detached sessions write their output');
+    }
+  }
+
+  /**
+   * Whether new sessions are refused
+   */
+  isReadOnly(): boolean {
+    return this.readOnly;
+  }
+
+  /**
+   * Refuse or accept new sessions. Running sessions are unaffected.
+   */
+  setReadOnly(readOnly: boolean): void {
+    this.readOnly = readOnly;
+  }
+
+  /**
+   * Reject a session start while the server is read-only
+   */
+  private assertAcceptingSessions(): void {
+    if (this.readOnly) {
+      throw new ServiceError(
+        'Server is in read-only mode and not accepting new sessions',
+        'READ_ONLY',
+        503
+      );
+    }
   }
 
   /**
@@ -37,15 +430,7 @@ export class ClaudeService extends EventEmitter {
       }
     }
 
-    const searchPaths = [
-      'claude', // In PATH
-      '/usr/local/bin/claude',
-      '/opt/homebrew/bin/claude',
-      join(homedir(), '.local/bin/claude'),
-      join(homedir(), '.nvm/versions/node/*/bin/claude'),
-    ];
-
-    for (const path of searchPaths) {
+    for (const path of claudeSearchPaths()) {
       try {
         // Test if binary exists and is executable
         await this.testClaudeBinary(path);
@@ -58,12 +443,71 @@ export class ClaudeService extends EventEmitter {
     throw new Error('Claude binary not found. Please install Claude Code CLI.');
   }
 
+  /**
+   * Discover the Claude installations again and switch to the newest one, for picking up an
+   * upgraded Claude without a restart. Every `claude` on PATH, the common install locations
+   * and the current binary are probed with `--version`; on a tie the current binary is kept.
+   * Sessions already running keep the binary they started with.
+   *
+   * @throws ServiceError 424 when no installation answers `--version`.
+   */
+  async reselectClaudeBinary(): Promise<ClaudeSelection> {
+    let previous = await this.findClaudeBinary().catch(() => undefined);
+    const onPath = (process.env.PATH ?? '').split(delimiter).filter(Boolean).map((dir) => join(dir, 'claude'));
+    const candidates = new Set<string>(previous && isAbsolute(previous) ? [previous] : []);
+    for (const path of onPath) {
+      candidates.add(path);
+    }
+    for (const path of claudeSearchPaths().filter(isAbsolute)) {
+      candidates.add(path);
+    }
+
+    const installations: ClaudeInstallation[] = [];
+    for (const path of candidates) {
+      try {
+        await fs.access(path, fsConstants.X_OK);
+        const output = await this.runCommand(path, ['--version']);
+        installations.push({ path, version: output.match(/(\d+\.\d+\.\d+)/)?.[1] });
+      } catch {
+        continue;
+      }
+    }
+
+    // A binary found by name is the first `claude` on PATH
+    if (previous && !isAbsolute(previous)) {
+      previous = installations.find((installation) => onPath.includes(installation.path))?.path;
+    }
+
+    // Unversioned installations only win when none reports a version
+    let best: ClaudeInstallation | undefined;
+    for (const installation of installations) {
+      if (!best || (installation.version && (!best.version || compareVersions(installation.version, best.version) > 0))) {
+        best = installation;
+      }
+    }
+    if (!best) {
+      throw new ServiceError('No Claude installation found', 'CLAUDE_BINARY_NOT_FOUND', 424, {
+        searched: Array.from(candidates),
+      });
+    }
+
+    this.claudeBinaryPath = best.path;
+    return {
+      ...(previous ? { previous } : {}),
+      selected: best.path,
+      ...(best.version ? { version: best.version } : {}),
+      changed: best.path !== previous,
+      installations,
+    };
+  }
+
   /**
    * Test if a Claude binary path is valid
    */
   private async testClaudeBinary(path: string): Promise<void> {
     return new Promise((resolve, reject) => {
       const child = spawn(path, ['--version'], { stdio: 'pipe' });
+      const timer = this.startProbeTimer(child, `${path} --version`, reject);
       let output = '';
 
       child.stdout?.on('data', (data) => {
@@ -75,6 +519,7 @@ export class ClaudeService extends EventEmitter {
       });
 
       child.on('close', (code) => {
+        clearTimeout(timer);
         if (code === 0 && output.includes('claude')) {
           resolve();
         } else {
@@ -82,10 +527,30 @@ export class ClaudeService extends EventEmitter {
         }
       });
 
-      child.on('error', reject);
+      child.on('error', (error) => {
+        clearTimeout(timer);
+        reject(error);
+      });
     });
   }
 
+  /**
+   * Kill a command that has not exited within `timeoutMs` (version_timeout_ms for CLI probes), so
+   * a stalled binary cannot hang discovery, and reject with an `ETIMEDOUT` error
+   */
+  private startProbeTimer(
+    child: ChildProcess,
+    description: string,
+    reject: (error: Error) => void,
+    timeoutMs = this.options.version_timeout_ms ?? DEFAULT_VERSION_TIMEOUT_MS
+  ): NodeJS.Timeout {
+    return setTimeout(() => {
+      console.warn(`⚠️  ${description} did not exit within ${timeoutMs}ms, giving up`);
+      child.kill('SIGKILL');
+      reject(Object.assign(new Error(`${description} timed out after ${timeoutMs}ms`), { code: 'ETIMEDOUT' }));
+    }, timeoutMs);
+  }
+
   /**
    * Get Claude version and installation status
    */
@@ -110,12 +575,61 @@ export class ClaudeService extends EventEmitter {
     }
   }
 
+  /**
+   * Check the Claude binary is at least `minVersion`
+   *
+   * @returns Why the binary does not qualify (it is older, or its version cannot be determined),
+   *   or undefined when it does.
+   */
+  async checkMinimumVersion(minVersion: string): Promise<string | undefined> {
+    const status = await this.checkClaudeVersion();
+    if (!status.is_installed) {
+      return `cannot determine the Claude version (minimum ${minVersion}): ${status.output}`;
+    }
+    if (!status.version) {
+      return `cannot determine the Claude version (minimum ${minVersion}) from "${status.output}"`;
+    }
+    if (compareVersions(status.version, minVersion) < 0) {
+      return `Claude ${status.version} is older than the minimum supported version ${minVersion}`;
+    }
+    return undefined;
+  }
+
+  /**
+   * Get `claude --help` output and the flags it lists. Results are cached per binary
+   * path and version, so upgrading Claude in place is picked up on the next call.
+   */
+  async getClaudeHelp(): Promise<ClaudeHelp> {
+    const claudePath = await this.findClaudeBinary();
+    const versionOutput = await this.runCommand(claudePath, ['--version']);
+    const version = versionOutput.match(/(\d+\.\d+\.\d+)/)?.[1];
+    const cacheKey = `${claudePath}@${version ?? versionOutput.trim()}`;
+
+    const cached = this.helpCache.get(cacheKey);
+    if (cached) {
+      return cached;
+    }
+
+    const raw = await this.runCommand(claudePath, ['--help']);
+    const help: ClaudeHelp = {
+      binary_path: claudePath,
+      version,
+      raw,
+      flags: parseHelpFlags(raw),
+    };
+    this.helpCache.set(cacheKey, help);
+    return help;
+  }
+
   /**
    * Execute a command and return output
+   *
+   * @param timeoutMs - How long the command may run; defaults to version_timeout_ms.
    */
-  private async runCommand(command: string, args: string[], cwd?: string): Promise<string> {
+  private async runCommand(command: string, args: string[], cwd?: string, timeoutMs?: number): Promise<string> {
     return new Promise((resolve, reject) => {
       const child = spawn(command, args, { cwd, stdio: 'pipe' });
+      const timer = this.startProbeTimer(child, [command, ...args].join(' '), reject, timeoutMs);
       let output = '';
       let error = '';
 
@@ -128,6 +642,7 @@ export class ClaudeService extends EventEmitter {
       });
 
       child.on('close', (code) => {
+        clearTimeout(timer);
         if (code === 0) {
           resolve(output);
         } else {
@@ -135,201 +650,2680 @@ export class ClaudeService extends EventEmitter {
         }
       });
 
-      child.on('error', reject);
+      child.on('error', (error) => {
+        clearTimeout(timer);
+        reject(error);
+      });
     });
   }
 
   /**
    * Execute Claude Code with streaming output
+   *
+   * @param apiKey - Key the client identified itself with; counts against per_key_session_limit.
    */
-  async executeClaudeCode(request: ExecuteClaudeRequest): Promise<string> {
+  async executeClaudeCode(request: ExecuteClaudeRequest, apiKey?: string): Promise<string> {
     const sessionId = uuidv4();
+    this.assertAcceptingSessions();
     const claudePath = await this.findClaudeBinary();
 
-    const args = [
-      '-p',
-      request.prompt,
-      '--model',
-      request.model,
-      '--output-format',
-      'stream-json',
-      '--verbose',
-      '--dangerously-skip-permissions',
-    ];
+    const args = this.buildSessionArgs([], request);
 
-    await this.spawnClaudeProcess(sessionId, claudePath, args, request.project_path, request);
+    await this.spawnClaudeProcess(sessionId, claudePath, args, request.project_path, request, undefined, apiKey);
     return sessionId;
   }
 
   /**
    * Continue existing Claude Code conversation
    */
-  async continueClaudeCode(request: ContinueClaudeRequest): Promise<string> {
+  async continueClaudeCode(request: ContinueClaudeRequest, apiKey?: string): Promise<string> {
     const sessionId = uuidv4();
+    this.assertAcceptingSessions();
     const claudePath = await this.findClaudeBinary();
 
-    const args = [
-      '-c', // Continue flag
-      '-p',
-      request.prompt,
-      '--model',
-      request.model,
-      '--output-format',
-      'stream-json',
-      '--verbose',
-      '--dangerously-skip-permissions',
-    ];
+    const args = this.buildSessionArgs(['-c'], request); // Continue flag
 
-    await this.spawnClaudeProcess(sessionId, claudePath, args, request.project_path, request);
+    await this.spawnClaudeProcess(sessionId, claudePath, args, request.project_path, request, undefined, apiKey);
     return sessionId;
   }
 
   /**
    * Resume existing Claude Code session
    */
-  async resumeClaudeCode(request: ResumeClaudeRequest): Promise<string> {
-    const sessionId = request.session_id;
-    const claudePath = await this.findClaudeBinary();
+  async resumeClaudeCode(request: ResumeClaudeRequest, apiKey?: string): Promise<string> {
+    const sessionId = this.resolveSessionId(request.session_id);
+    this.assertAcceptingSessions();
 
-    const args = [
-      '--resume',
-      request.session_id,
-      '-p',
-      request.prompt,
-      '--model',
-      request.model,
-      '--output-format',
-      'stream-json',
-      '--verbose',
-      '--dangerously-skip-permissions',
-    ];
+    // A second `--resume` of a live session would run two processes on one conversation
+    if (this.processes.has(sessionId) || this.resuming.has(sessionId)) {
+      if (this.options.resume_running === 'attach' && this.processes.has(sessionId)) {
+        return sessionId;
+      }
+      throw new ServiceError('Session is still running', 'SESSION_ALREADY_RUNNING', 409, { session_id: sessionId });
+    }
 
-    await this.spawnClaudeProcess(sessionId, claudePath, args, request.project_path, request);
-    return sessionId;
+    this.resuming.add(sessionId);
+    try {
+      const claudePath = await this.findClaudeBinary();
+
+      // A name or tracked id means nothing to Claude; resume the conversation it reported
+      const claudeSessionId = this.sessions.get(sessionId)?.info.claude_session_id ?? sessionId;
+      const args = this.buildSessionArgs(['--resume', claudeSessionId], request);
+
+      await this.spawnClaudeProcess(sessionId, claudePath, args, request.project_path, request, undefined, apiKey);
+      return sessionId;
+    } finally {
+      this.resuming.delete(sessionId);
+    }
   }
 
   /**
-   * Spawn Claude process with streaming output
+   * Start a new session that picks up a finished one's conversation with `--resume`, using the
+   * Claude session id captured from its output and its original project and model. Unlike a
+   * fresh execute, Claude keeps the earlier turns.
    */
-  private async spawnClaudeProcess(
-    sessionId: string,
-    claudePath: string,
-    args: string[],
-    projectPath: string,
-    request: any
-  ): Promise<void> {
-    const child = spawn(claudePath, args, {
-      cwd: projectPath,
-      stdio: 'pipe',
-      env: { ...process.env },
-    });
-
-    if (!child.pid) {
-      throw new Error('Failed to start Claude process');
+  async restartSession(sessionId: string, request: RestartSessionRequest = {}, apiKey?: string): Promise<string> {
+    const record = this.sessions.get(sessionId);
+    if (!record) {
+      throw new ServiceError('Session not found', 'SESSION_NOT_FOUND', 404);
+    }
+    if (record.status === 'running') {
+      throw new ServiceError('Session is still running', 'SESSION_ALREADY_RUNNING', 409, { session_id: sessionId });
+    }
+    const claudeSessionId = record.info.claude_session_id;
+    if (!claudeSessionId) {
+      throw new ServiceError(
+        'Session never reported a Claude session id, so it cannot be resumed',
+        'CLAUDE_SESSION_ID_MISSING',
+        409,
+        { session_id: sessionId }
+      );
     }
+    if (request.prompt !== undefined && (typeof request.prompt !== 'string' || !request.prompt)) {
+      throw new ServiceError('prompt must be a non-empty string', 'VALIDATION_ERROR', 400);
+    }
+    this.assertAcceptingSessions();
 
-    // Register process
-    const processInfo: ProcessInfo = {
-      run_id: Date.now(),
-      process_type: { ClaudeSession: { session_id: sessionId } },
-      pid: child.pid,
-      started_at: new Date().toISOString(),
-      project_path: projectPath,
-      task: request.prompt.substring(0, 100),
-      model: request.model,
+    const newSessionId = uuidv4();
+    const claudePath = await this.findClaudeBinary();
+    const resumeRequest: ResumeClaudeRequest = {
+      project_path: record.info.project_path,
+      session_id: claudeSessionId,
+      prompt: request.prompt ?? DEFAULT_RESTART_PROMPT,
+      model: record.info.model,
+      ...(record.info.working_dir ? { working_dir: record.info.working_dir } : {}),
+      ...(record.info.output_subdir ? { output_subdir: record.info.output_subdir } : {}),
     };
+    const args = this.buildSessionArgs(['--resume', claudeSessionId], resumeRequest);
 
-    this.processes.set(sessionId, child);
-    this.processRegistry.set(sessionId, processInfo);
-
-    // Handle stdout (streaming JSON)
-    child.stdout?.on('data', (data) => {
-      const lines = data.toString().split('\n').filter((line: string) => line.trim());
-      
-      for (const line of lines) {
-        try {
-          const message = JSON.parse(line) as ClaudeStreamMessage;
-          message.session_id = sessionId;
-          message.timestamp = new Date().toISOString();
-          
-          this.emit('claude_stream', {
-            session_id: sessionId,
-            message,
-          });
-        } catch (error) {
-          // Non-JSON line, emit as raw output
-          this.emit('claude_output', {
-            session_id: sessionId,
-            data: line,
-          });
-        }
-      }
-    });
-
-    // Handle stderr
-    child.stderr?.on('data', (data) => {
-      this.emit('claude_error', {
-        session_id: sessionId,
-        error: data.toString(),
-      });
-    });
-
-    // Handle process exit
-    child.on('close', (code) => {
-      this.processes.delete(sessionId);
-      this.processRegistry.delete(sessionId);
-      
-      this.emit('claude_exit', {
-        session_id: sessionId,
-        code,
-      });
-    });
-
-    child.on('error', (error) => {
-      this.processes.delete(sessionId);
-      this.processRegistry.delete(sessionId);
-      
-      this.emit('claude_error', {
-        session_id: sessionId,
-        error: error.message,
-      });
-    });
+    await this.spawnClaudeProcess(
+      newSessionId,
+      claudePath,
+      args,
+      resumeRequest.project_path,
+      resumeRequest,
+      undefined,
+      apiKey,
+      sessionId
+    );
+    return newSessionId;
   }
 
   /**
-   * Cancel a running Claude process
+   * Cancel a running session, wait for its process to exit, then start a new session with the
+   * same binary, arguments and request. The new session gets its own id, so nothing the old
+   * process printed on its way out is attributed to it.
+   *
+   * @returns The new session's id.
    */
-  async cancelClaudeExecution(sessionId: string): Promise<boolean> {
-    const process = this.processes.get(sessionId);
-    
-    if (process) {
-      process.kill('SIGTERM');
-      
-      // Force kill after 5 seconds if not terminated
-      setTimeout(() => {
-        if (!process.killed) {
-          process.kill('SIGKILL');
-        }
-      }, 5000);
-      
-      return true;
+  async resetSession(sessionId: string, apiKey?: string): Promise<string> {
+    const launch = this.launchRequests.get(sessionId);
+    if (!this.processes.has(sessionId) || !launch) {
+      throw new ServiceError('Session not found or not running', 'SESSION_NOT_FOUND', 404);
     }
-    
-    return false;
-  }
+    if (this.resetting.has(sessionId)) {
+      throw new ServiceError('Session is already being reset', 'RESET_IN_PROGRESS', 409, { session_id: sessionId });
+    }
+    this.assertAcceptingSessions();
 
-  /**
-   * Get list of running Claude sessions
-   */
-  getRunningClaudeSessions(): ProcessInfo[] {
-    return Array.from(this.processRegistry.values());
+    this.resetting.add(sessionId);
+    let listener!: (event: SessionEvent) => void;
+    try {
+      // Listen before cancelling so an exit that settles right away is not missed
+      const ended = new Promise<void>((resolve) => {
+        listener = (event) => {
+          if (event.session_id === sessionId && event.event !== 'started') {
+            resolve();
+          }
+        };
+        this.on('session_event', listener);
+      });
+      await this.cancelClaudeExecution(sessionId);
+      await ended;
+    } finally {
+      this.off('session_event', listener);
+      this.resetting.delete(sessionId);
+    }
+
+    const newSessionId = uuidv4();
+    await this.spawnClaudeProcess(
+      newSessionId,
+      launch.claudePath,
+      launch.args,
+      launch.projectPath,
+      launch.request,
+      undefined,
+      apiKey
+    );
+    return newSessionId;
   }
 
   /**
-   * Get session info by ID
+   * Run one prompt against two Claude binaries side by side, each as a regular session in its
+   * own empty temporary directory, and return both outcomes once both have finished.
+   * Only binaries listed in compare_binaries may be run.
+   *
+   * @param apiKey - Key the client identified itself with; both runs count against per_key_session_limit.
    */
-  getSessionInfo(sessionId: string): ProcessInfo | undefined {
-    return this.processRegistry.get(sessionId);
+  async compareInstallations(request: CompareRequest, apiKey?: string): Promise<CompareRun[]> {
+    this.assertAcceptingSessions();
+
+    const binaries = request.binaries;
+    if (!Array.isArray(binaries) || binaries.length !== 2 || binaries.some((path) => typeof path !== 'string')) {
+      throw new ServiceError('binaries must list exactly two Claude binaries', 'VALIDATION_ERROR', 400);
+    }
+    const allowed = this.options.compare_binaries ?? [];
+    const disallowed = binaries.filter((path) => !allowed.includes(path));
+    if (disallowed.length > 0) {
+      throw new ServiceError('Binary is not in compare_binaries', 'BINARY_NOT_ALLOWED', 403, {
+        binaries: disallowed,
+      });
+    }
+    this.validateTimeout(request.timeout_ms);
+
+    return Promise.all(binaries.map((binary) => this.runComparison(binary, request, apiKey)));
+  }
+
+  private async runComparison(binary: string, request: CompareRequest, apiKey?: string): Promise<CompareRun> {
+    const sessionId = uuidv4();
+    const dir = await fs.mkdtemp(join(tmpdir(), 'claudia-compare-'));
+    const sessionRequest: ExecuteClaudeRequest = {
+      project_path: dir,
+      prompt: request.prompt,
+      model: request.model,
+      timeout_ms: request.timeout_ms ?? DEFAULT_COMPARE_TIMEOUT_MS,
+    };
+
+    // Listen before spawning so a binary that exits immediately is not missed
+    let listener!: (event: SessionEvent) => void;
+    const finished = new Promise<void>((resolve) => {
+      listener = (event) => {
+        if (event.session_id === sessionId && event.event !== 'started') {
+          resolve();
+        }
+      };
+      this.on('session_event', listener);
+    });
+
+    const started = Date.now();
+    try {
+      await this.spawnClaudeProcess(
+        sessionId, binary, this.buildSessionArgs([], sessionRequest), dir, sessionRequest, undefined, apiKey
+      );
+      await finished;
+    } finally {
+      this.off('session_event', listener);
+      await fs.rm(dir, { recursive: true, force: true }).catch(() => undefined);
+    }
+
+    const record = this.sessions.get(sessionId);
+    return {
+      binary,
+      session_id: sessionId,
+      status: record?.status ?? 'failed',
+      exit_code: record?.exit_code,
+      duration_ms: Date.now() - started,
+      result: this.getSessionResult(sessionId),
+      output: record ? this.visibleOutput(record) : [],
+    };
+  }
+
+  /**
+   * Build the CLI arguments shared by execute/continue/resume, after any mode-specific leading flags
+   */
+  private buildSessionArgs(leading: string[], request: ExecuteClaudeRequest): string[] {
+    const args = [
+      ...leading,
+      '-p',
+      this.wrapPrompt(request),
+      '--model',
+      request.model,
+      '--output-format',
+      'stream-json',
+    ];
+
+    if (request.verbose ?? this.options.verbose ?? true) {
+      args.push('--verbose');
+    }
+    if (request.skip_permissions ?? this.skipsPermissionsByDefault()) {
+      args.push('--dangerously-skip-permissions');
+    }
+    if (request.extra_args !== undefined) {
+      args.push(...this.checkExtraArgs(request.extra_args));
+    }
+
+    return args;
+  }
+
+  /**
+   * The prompt passed to Claude: the request's prompt between the configured prompt_prefix and
+   * prompt_suffix, unless the request set apply_prompt_wrapping to false
+   */
+  private wrapPrompt(request: ExecuteClaudeRequest): string {
+    if (request.apply_prompt_wrapping !== undefined && typeof request.apply_prompt_wrapping !== 'boolean') {
+      throw new ServiceError('apply_prompt_wrapping must be a boolean', 'VALIDATION_ERROR', 400);
+    }
+    if (request.apply_prompt_wrapping === false) {
+      return request.prompt;
+    }
+    return [this.options.prompt_prefix, request.prompt, this.options.prompt_suffix]
+      .filter((part) => part)
+      .join('\n\n');
+  }
+
+  /**
+   * Validate client-supplied extra CLI arguments. Tokens starting with `-` are flags (a
+   * `--flag=value` form is checked by its name); anything else is a value and passes.
+   */
+  private checkExtraArgs(extraArgs: unknown): string[] {
+    if (!Array.isArray(extraArgs) || extraArgs.some((arg) => typeof arg !== 'string')) {
+      throw new ServiceError('extra_args must be an array of strings', 'VALIDATION_ERROR', 400);
+    }
+
+    const allowed = this.options.allowed_extra_args;
+    for (const arg of extraArgs as string[]) {
+      if (!arg.startsWith('-')) {
+        continue;
+      }
+
+      const flag = arg.split('=')[0];
+      if (RESERVED_FLAGS.includes(flag)) {
+        throw new ServiceError(`Flag ${flag} is set by the server and cannot be passed`, 'ARG_NOT_ALLOWED', 400, { flag });
+      }
+      if (allowed && !allowed.includes(flag)) {
+        throw new ServiceError(`Flag ${flag} is not in the allowed extra arguments`, 'ARG_NOT_ALLOWED', 400, { flag });
+      }
+    }
+
+    return extraArgs as string[];
+  }
+
+  /**
+   * Whether sessions run with --dangerously-skip-permissions unless a request opts out
+   */
+  skipsPermissionsByDefault(): boolean {
+    return this.options.skip_permissions ?? true;
+  }
+
+  /**
+   * Run a precondition command in the project directory and refuse the session
+   * with 412 Precondition Failed unless it exits with the expected code
+   */
+  private async checkPrecondition(precondition: SessionPrecondition, cwd: string): Promise<void> {
+    const expected = precondition.expect_exit_code ?? 0;
+    const timeoutMs = precondition.timeout_ms ?? 30000;
+
+    const { code, output, timedOut } = await new Promise<{
+      code: number | null;
+      output: string;
+      timedOut: boolean;
+    }>((resolve) => {
+      const child = spawn(precondition.command, { cwd, shell: true, stdio: 'pipe' });
+      let output = '';
+      let timedOut = false;
+      const append = (data: Buffer) => {
+        if (output.length < PRECONDITION_OUTPUT_LIMIT) {
+          output = (output + data.toString()).slice(0, PRECONDITION_OUTPUT_LIMIT);
+        }
+      };
+      const timer = setTimeout(() => {
+        timedOut = true;
+        child.kill('SIGKILL');
+      }, timeoutMs);
+
+      child.stdout?.on('data', append);
+      child.stderr?.on('data', append);
+      child.on('close', (exitCode) => {
+        clearTimeout(timer);
+        resolve({ code: exitCode, output, timedOut });
+      });
+      child.on('error', (error) => {
+        clearTimeout(timer);
+        resolve({ code: null, output: error.message, timedOut });
+      });
+    });
+
+    if (timedOut || code !== expected) {
+      throw new ServiceError(
+        timedOut
+          ? `Precondition timed out after ${timeoutMs}ms`
+          : `Precondition exited with ${code}, expected ${expected}`,
+        'PRECONDITION_FAILED',
+        412,
+        { command: precondition.command, exit_code: code, expect_exit_code: expected, output }
+      );
+    }
+  }
+
+  /**
+   * Two sessions in one project can overwrite each other's file edits. Fail with 409 when
+   * reject_concurrent_same_project is set and the project already has a session running;
+   * otherwise return a warning for the start response.
+   */
+  private checkProjectConflict(projectPath: string): string[] {
+    const running = this.runningPerProject.get(projectPath) ?? 0;
+    if (running === 0) {
+      return [];
+    }
+
+    if (this.options.reject_concurrent_same_project) {
+      const sessionIds = Array.from(this.processRegistry.entries())
+        .filter(([, info]) => info.project_path === projectPath)
+        .map(([sessionId]) => sessionId);
+      throw new ServiceError(
+        'Another session is already running in this project',
+        'PROJECT_BUSY',
+        409,
+        { project_path: projectPath, session_ids: sessionIds }
+      );
+    }
+    return [`${running} other session(s) already running in ${projectPath} may edit the same files`];
+  }
+
+  /**
+   * Claim a running-session slot globally, for the project, and for the API key, or fail with 429.
+   * Checking and claiming happen synchronously so concurrent starts cannot overshoot.
+   */
+  private reserveSlot(projectPath: string, apiKey?: string): void {
+    const { max_concurrent_sessions: maxTotal, max_concurrent_per_project: maxPerProject } = this.options;
+    const maxPerKey = this.options.per_key_session_limit;
+    const projectCount = this.runningPerProject.get(projectPath) ?? 0;
+    const keyCount = apiKey ? (this.runningPerKey.get(apiKey) ?? 0) : 0;
+
+    if (maxTotal && this.runningCount >= maxTotal) {
+      throw new ServiceError(
+        `Maximum concurrent sessions reached (${maxTotal})`,
+        'MAX_SESSIONS_REACHED',
+        429
+      );
+    }
+    if (maxPerProject && projectCount >= maxPerProject) {
+      throw new ServiceError(
+        `Maximum concurrent sessions for project reached (${maxPerProject})`,
+        'MAX_PROJECT_SESSIONS_REACHED',
+        429,
+        { project_path: projectPath }
+      );
+    }
+    // Without a key a client could start any number of sessions past the limit
+    if (maxPerKey && !apiKey) {
+      throw new ServiceError(
+        'An X-API-Key header is required while a per-key session limit is set',
+        'API_KEY_REQUIRED',
+        401
+      );
+    }
+    if (apiKey && maxPerKey && keyCount >= maxPerKey) {
+      throw new ServiceError(
+        `Maximum concurrent sessions for API key reached (${maxPerKey})`,
+        'MAX_KEY_SESSIONS_REACHED',
+        429
+      );
+    }
+
+    this.runningCount++;
+    this.runningPerProject.set(projectPath, projectCount + 1);
+    if (apiKey) {
+      this.runningPerKey.set(apiKey, keyCount + 1);
+    }
+  }
+
+  /**
+   * Check that a requested session name is usable: not an id, not a route segment, and
+   * made of characters that need no escaping in a URL
+   */
+  private validateSessionName(name: unknown): void {
+    if (name === undefined) {
+      return;
+    }
+    if (typeof name !== 'string' || !SESSION_NAME.test(name) || UUID_LIKE.test(name) || name === 'running') {
+      throw new ServiceError(
+        'name must be 1-64 letters, digits, ".", "_" or "-", start with a letter or digit, and not look like a session id',
+        'VALIDATION_ERROR',
+        400
+      );
+    }
+  }
+
+  /**
+   * Point a name at a new session. A name stays taken while its session is starting or running;
+   * after that a new session may take it over.
+   */
+  private claimSessionName(name: string | undefined, sessionId: string): void {
+    if (!name) {
+      return;
+    }
+
+    const holder = this.sessionNames.get(name);
+    const holderRecord = holder !== undefined ? this.sessions.get(holder) : undefined;
+    if (holder !== undefined && holder !== sessionId && (!holderRecord || holderRecord.status === 'running')) {
+      throw new ServiceError(
+        `Session name "${name}" is already used by a running session`,
+        'SESSION_NAME_TAKEN',
+        409,
+        { name, session_id: holder }
+      );
+    }
+    this.sessionNames.set(name, sessionId);
+  }
+
+  /**
+   * Free a name if it still points at the given session
+   */
+  private releaseSessionName(name: string | undefined, sessionId: string): void {
+    if (name && this.sessionNames.get(name) === sessionId) {
+      this.sessionNames.delete(name);
+    }
+  }
+
+  /**
+   * Refuse a prompt matching a prompt_denylist pattern with 403, naming the pattern but not
+   * echoing the prompt
+   */
+  private checkPromptAllowed(prompt: unknown): void {
+    if (typeof prompt !== 'string') {
+      return;
+    }
+    const rule = this.promptDenylist.find((pattern) => pattern.test(prompt));
+    if (rule) {
+      throw new ServiceError('Prompt matches a denied pattern', 'PROMPT_DENIED', 403, { rule: rule.source });
+    }
+  }
+
+  /**
+   * The labels of a new session: those of every label rule matching the prompt, in rule order,
+   * overridden by the labels given in the request
+   */
+  private labelsFor(labels: unknown, prompt: unknown): Record<string, string> {
+    if (
+      labels !== undefined &&
+      (typeof labels !== 'object' || labels === null || Array.isArray(labels) ||
+        Object.values(labels).some((value) => typeof value !== 'string'))
+    ) {
+      throw new ServiceError('labels must be an object of string values', 'VALIDATION_ERROR', 400);
+    }
+
+    const derived: Record<string, string> = {};
+    if (typeof prompt === 'string') {
+      for (const rule of this.labelRules) {
+        if (rule.pattern.test(prompt)) {
+          Object.assign(derived, rule.labels);
+        }
+      }
+    }
+    return { ...derived, ...(labels as Record<string, string> | undefined) };
+  }
+
+  /**
+   * Claude arguments of every label_arg_rules entry whose labels the session carries, in rule order
+   */
+  private argsForLabels(labels: Record<string, string>): string[] {
+    return this.labelArgRules
+      .filter((rule) => Object.entries(rule.labels).every(([key, value]) => labels[key] === value))
+      .flatMap((rule) => rule.args);
+  }
+
+  /**
+   * Map a session name to its id; anything that is not a known name is returned unchanged
+   */
+  resolveSessionId(idOrName: string): string {
+    return this.sessionNames.get(idOrName) ?? idOrName;
+  }
+
+  /**
+   * Return a slot claimed by reserveSlot
+   */
+  private releaseSlot(projectPath: string, apiKey?: string): void {
+    this.runningCount = Math.max(0, this.runningCount - 1);
+    const projectCount = (this.runningPerProject.get(projectPath) ?? 1) - 1;
+    if (projectCount > 0) {
+      this.runningPerProject.set(projectPath, projectCount);
+    } else {
+      this.runningPerProject.delete(projectPath);
+    }
+    if (apiKey) {
+      const keyCount = (this.runningPerKey.get(apiKey) ?? 1) - 1;
+      if (keyCount > 0) {
+        this.runningPerKey.set(apiKey, keyCount);
+      } else {
+        this.runningPerKey.delete(apiKey);
+      }
+    }
+  }
+
+  /**
+   * Wait until spawning another process stays within spawn_rate_limit. Each caller claims
+   * the next free spawn time up front, so simultaneous starts are spaced out in call order.
+   * A start that would have to wait is rejected once max_queue_depth starts are already waiting.
+   */
+  private async throttleSpawn(): Promise<void> {
+    const limit = this.options.spawn_rate_limit;
+    if (!limit || limit <= 0) {
+      return;
+    }
+
+    const now = Date.now();
+    const spawnAt = Math.max(now, this.nextSpawnAt);
+    if (spawnAt <= now) {
+      this.nextSpawnAt = now + 1000 / limit;
+      return;
+    }
+
+    const maxDepth = this.options.max_queue_depth;
+    if (maxDepth && this.queuedSpawns >= maxDepth) {
+      throw new ServiceError(
+        `Spawn queue is full (${maxDepth} starts waiting)`,
+        'SPAWN_QUEUE_FULL',
+        429,
+        { max_queue_depth: maxDepth }
+      );
+    }
+
+    this.nextSpawnAt = spawnAt + 1000 / limit;
+    this.queuedSpawns++;
+    try {
+      await new Promise((resolve) => setTimeout(resolve, spawnAt - now));
+    } finally {
+      this.queuedSpawns--;
+    }
+  }
+
+  /**
+   * Running sessions overall and per project, and how many starts wait to be spawned
+   */
+  getProcessStats(): ProcessStats {
+    return {
+      running: this.runningCount,
+      running_per_project: Object.fromEntries(this.runningPerProject),
+      queue_depth: this.queuedSpawns,
+      max_queue_depth: this.options.max_queue_depth ?? 0,
+      max_running: this.options.max_concurrent_sessions ?? 0,
+    };
+  }
+
+  /**
+   * Why a finished session ended, for its status timeline
+   */
+  private finishReason(record: SessionRecord, status: SessionStatus): string | undefined {
+    if (record.terminated_by) {
+      return `terminated by ${record.terminated_by}`;
+    }
+    if (record.failure_reason) {
+      return record.failure_reason;
+    }
+    if (status === 'cancelled') {
+      return 'cancelled on request';
+    }
+    if (record.exit_signal) {
+      return `killed by ${record.exit_signal}`;
+    }
+    if (typeof record.exit_code === 'number' && record.exit_code !== 0) {
+      return `exit code ${record.exit_code}`;
+    }
+    return undefined;
+  }
+
+  /**
+   * Add up the reported tokens and cost of stored sessions started in the query's time range,
+   * grouped by project, model, or a label, most expensive group first. Only sessions that
+   * reported a result contribute tokens and cost, but every matching session is counted.
+   */
+  getUsage(query: UsageQuery): UsageGroup[] {
+    const labelName = query.group_by === 'tenant' ? 'tenant' : query.label;
+    const groups = new Map<string | null, UsageGroup>();
+
+    for (const record of this.sessions.values()) {
+      const startedAt = Date.parse(record.info.started_at);
+      if ((query.from && startedAt < query.from.getTime()) || (query.to && startedAt >= query.to.getTime())) {
+        continue;
+      }
+
+      const key =
+        query.group_by === 'project' ? record.info.project_path
+        : query.group_by === 'model' ? record.info.model
+        : record.info.labels?.[labelName!] ?? null;
+      let group = groups.get(key);
+      if (!group) {
+        group = {
+          key,
+          sessions: 0,
+          input_tokens: 0,
+          output_tokens: 0,
+          cache_creation_input_tokens: 0,
+          cache_read_input_tokens: 0,
+          cost_usd: 0,
+        };
+        groups.set(key, group);
+      }
+
+      const usage = record.result?.usage;
+      group.sessions++;
+      group.input_tokens += usage?.input_tokens ?? 0;
+      group.output_tokens += usage?.output_tokens ?? 0;
+      group.cache_creation_input_tokens += usage?.cache_creation_input_tokens ?? 0;
+      group.cache_read_input_tokens += usage?.cache_read_input_tokens ?? 0;
+      group.cost_usd += record.result?.cost_usd ?? 0;
+    }
+
+    return Array.from(groups.values()).sort((a, b) => b.cost_usd - a.cost_usd);
+  }
+
+  /**
+   * Count stored sessions by status and add up what the finished ones cost
+   */
+  getSessionTotals(): SessionTotals {
+    const totals: SessionTotals = {
+      total: this.sessions.size,
+      by_status: { running: 0, completed: 0, cancelled: 0, failed: 0 },
+      total_cost_usd: 0,
+    };
+    for (const record of this.sessions.values()) {
+      totals.by_status[record.status]++;
+      totals.total_cost_usd += record.result?.cost_usd ?? 0;
+    }
+    return totals;
+  }
+
+  /**
+   * Check output_subdir is a relative path that stays inside the sessions directory: no
+   * absolute paths, no empty, `.` or `..` segments. Returns it with `/` separators.
+   */
+  private validateOutputSubdir(subdir: unknown): string | undefined {
+    if (subdir === undefined) {
+      return undefined;
+    }
+    const segments = typeof subdir === 'string' ? subdir.split(/[\\/]/) : [];
+    if (
+      typeof subdir !== 'string' ||
+      isAbsolute(subdir) ||
+      segments.some((segment) => segment === '' || segment === '.' || segment === '..')
+    ) {
+      throw new ServiceError(
+        'output_subdir must be a relative path without empty, "." or ".." segments',
+        'VALIDATION_ERROR',
+        400,
+        { output_subdir: subdir }
+      );
+    }
+    return segments.join('/');
+  }
+
+  /**
+   * Resolve output_result_to against the project path and check it lies under an allowed root
+   */
+  private resolveResultTarget(target: unknown, projectPath: string): string | undefined {
+    if (target === undefined) {
+      return undefined;
+    }
+    if (typeof target !== 'string' || !target) {
+      throw new ServiceError('output_result_to must be a file path', 'VALIDATION_ERROR', 400);
+    }
+
+    const path = resolve(projectPath, target);
+    const roots = this.options.result_output_roots ?? [projectPath];
+    const allowed = roots.some((root) => {
+      const rel = relative(resolve(root), path);
+      return rel !== '' && !rel.startsWith('..') && !isAbsolute(rel);
+    });
+    if (!allowed) {
+      throw new ServiceError('output_result_to is outside the allowed directories', 'VALIDATION_ERROR', 400, {
+        path,
+        allowed_roots: roots,
+      });
+    }
+    return path;
+  }
+
+  /**
+   * Write a completed session's final result text to its output_result_to file. The text goes
+   * to a temporary file next to the target first and is renamed over it, so readers never see
+   * a partial answer.
+   */
+  private async writeResultFile(sessionId: string, path: string): Promise<void> {
+    const result = this.sessions.get(sessionId)?.result;
+    if (!result) {
+      console.warn(`Session ${sessionId} produced no result message; not writing ${path}`);
+      return;
+    }
+
+    const temp = `${path}.${process.pid}.${sessionId}.tmp`;
+    try {
+      await fs.mkdir(dirname(path), { recursive: true });
+      await fs.writeFile(temp, result.text);
+      await fs.rename(temp, path);
+    } catch (error) {
+      await fs.unlink(temp).catch(() => undefined);
+      console.warn(`Could not write the result of session ${sessionId} to ${path}:`, error);
+    }
+  }
+
+  /**
+   * Record a finished session's `git diff` and `git status` in its directory. A directory
+   * that is not in a git repository is skipped without an error.
+   */
+  private async captureGitDiff(sessionId: string, cwd: string): Promise<void> {
+    this.gitDiffPending.add(sessionId);
+    try {
+      const [diff, status] = await Promise.all([
+        this.runGit(['diff'], cwd, GIT_DIFF_TIMEOUT_MS),
+        this.runGit(['status', '--porcelain'], cwd, GIT_DIFF_TIMEOUT_MS),
+      ]);
+      const record = this.sessions.get(sessionId);
+      if (record) {
+        record.git_diff = { diff, status, captured_at: new Date().toISOString() };
+        this.persistSession(sessionId);
+      }
+    } catch (error) {
+      const message = error instanceof Error ? error.message : String(error);
+      console.warn(`Could not capture the git diff of session ${sessionId} in ${cwd}: ${message}`);
+      // Outside a git repository there is simply nothing to capture; a timeout is a failure to report
+      const record = this.sessions.get(sessionId);
+      if (record && (error as NodeJS.ErrnoException).code === 'ETIMEDOUT') {
+        record.git_diff_error = message;
+        this.persistSession(sessionId);
+      }
+    } finally {
+      this.gitDiffPending.delete(sessionId);
+    }
+  }
+
+  /**
+   * Run git in a project directory with its own timeout rather than version_timeout_ms, which is
+   * sized for `claude --version` probes
+   */
+  private runGit(args: string[], cwd: string, timeoutMs: number): Promise<string> {
+    return this.runCommand('git', args, cwd, timeoutMs);
+  }
+
+  /**
+   * The branch and HEAD commit of the git repository a project is in: nothing for a directory
+   * outside a git repository or one without commits, and no branch for a detached HEAD
+   */
+  private async gitInfoOf(projectPath: string): Promise<Pick<ProcessInfo, 'git_branch' | 'git_commit'>> {
+    try {
+      const [commit, branch] = await Promise.all([
+        this.runGit(['rev-parse', 'HEAD'], projectPath, GIT_INFO_TIMEOUT_MS),
+        this.runGit(['branch', '--show-current'], projectPath, GIT_INFO_TIMEOUT_MS),
+      ]);
+      return { ...(branch.trim() ? { git_branch: branch.trim() } : {}), git_commit: commit.trim() };
+    } catch (error) {
+      // Not being in a git repository is expected; a slow git is worth knowing about
+      if ((error as NodeJS.ErrnoException).code === 'ETIMEDOUT') {
+        console.warn(
+          `Not recording git info for ${projectPath}: git did not answer within ${GIT_INFO_TIMEOUT_MS}ms`
+        );
+      }
+      return {};
+    }
+  }
+
+  /**
+   * The project changes captured when a session ended
+   *
+   * @throws ServiceError 404 when the session is unknown or no diff was captured, 409 while the
+   *   session runs or its diff is still being captured, 504 when git timed out capturing it.
+   */
+  getGitDiff(sessionId: string): SessionGitDiff {
+    const record = this.sessions.get(sessionId);
+    if (!record) {
+      throw new ServiceError('Session not found', 'SESSION_NOT_FOUND', 404);
+    }
+    if (record.status === 'running' || this.gitDiffPending.has(sessionId)) {
+      throw new ServiceError('The git diff is captured once the session ends', 'GIT_DIFF_PENDING', 409);
+    }
+    if (record.git_diff_error) {
+      throw new ServiceError(`The git diff could not be captured: ${record.git_diff_error}`, 'GIT_DIFF_TIMEOUT', 504);
+    }
+    if (!record.git_diff) {
+      throw new ServiceError(
+        'No git diff was captured; start the session with capture_git_diff in a git repository',
+        'GIT_DIFF_UNAVAILABLE',
+        404
+      );
+    }
+    return record.git_diff;
+  }
+
+  /**
+   * Reject a request path that does not exist or is not a directory
+   */
+  private async assertDirectory(path: string, field: string): Promise<void> {
+    await this.assertSymlinkToDirectory(path, field);
+    let isDirectory = false;
+    try {
+      isDirectory = (await fs.stat(path)).isDirectory();
+    } catch {
+      throw new ServiceError(`${field} does not exist: ${path}`, 'VALIDATION_ERROR', 400);
+    }
+    if (!isDirectory) {
+      throw new ServiceError(`${field} is not a directory: ${path}`, 'VALIDATION_ERROR', 400);
+    }
+  }
+
+  /**
+   * When the path is a symlink, check that it resolves to a directory. stat follows links, so
+   * a link to a file or a dangling link would otherwise only fail once Claude is spawned in it.
+   * Paths that are not symlinks are left to the caller.
+   */
+  private async assertSymlinkToDirectory(path: string, field: string): Promise<void> {
+    const link = await fs.lstat(path).catch(() => undefined);
+    if (!link?.isSymbolicLink()) {
+      return;
+    }
+
+    let target: string;
+    try {
+      target = await fs.realpath(path);
+    } catch {
+      const destination = await fs.readlink(path).catch(() => 'unknown');
+      throw new ServiceError(
+        `${field} is a broken symlink: ${path} -> ${destination}`,
+        'VALIDATION_ERROR',
+        400,
+        { path, target: destination }
+      );
+    }
+    if (!(await fs.stat(target)).isDirectory()) {
+      throw new ServiceError(
+        `${field} is a symlink to a file, not a directory: ${path} -> ${target}`,
+        'VALIDATION_ERROR',
+        400,
+        { path, target }
+      );
+    }
+  }
+
+  /**
+   * Resolve the directories a session may access besides its project, relative to project_path.
+   * Each must exist and, when additional_dir_roots is set, lie under one of the roots once
+   * symlinks are resolved.
+   */
+  private async resolveAdditionalDirs(dirs: unknown, projectPath: string): Promise<string[]> {
+    if (!Array.isArray(dirs) || dirs.some((dir) => typeof dir !== 'string' || !dir)) {
+      throw new ServiceError('additional_dirs must be an array of directory paths', 'VALIDATION_ERROR', 400);
+    }
+
+    const configuredRoots = this.options.additional_dir_roots;
+    const roots = configuredRoots && await Promise.all(
+      configuredRoots.map((root) => fs.realpath(root).catch(() => resolve(root)))
+    );
+
+    const resolved: string[] = [];
+    for (const dir of dirs as string[]) {
+      const requested = resolve(projectPath, dir);
+      await this.assertDirectory(requested, 'additional_dirs entry');
+      const path = await fs.realpath(requested);
+
+      const allowed = !roots || roots.some((root) => {
+        const rel = relative(root, path);
+        return !rel.startsWith('..') && !isAbsolute(rel);
+      });
+      if (!allowed) {
+        throw new ServiceError('additional_dirs entry is outside the allowed directories', 'PATH_NOT_ALLOWED', 403, {
+          path,
+          allowed_roots: roots,
+        });
+      }
+      resolved.push(path);
+    }
+    return resolved;
+  }
+
+  /**
+   * Write a session's settings overlay to its own temporary directory, returning the file path
+   */
+  private async writeSessionSettings(sessionId: string, settings: unknown): Promise<string> {
+    if (typeof settings !== 'object' || settings === null || Array.isArray(settings)) {
+      throw new ServiceError('settings must be a JSON object', 'VALIDATION_ERROR', 400);
+    }
+
+    const dir = await fs.mkdtemp(join(tmpdir(), 'claudia-settings-'));
+    this.settingsDirs.set(sessionId, dir);
+    const file = join(dir, 'settings.json');
+    await fs.writeFile(file, JSON.stringify(settings, null, 2), { mode: 0o600 });
+    return file;
+  }
+
+  /**
+   * Delete a session's temporary settings overlay, if it has one
+   */
+  private async removeSessionSettings(sessionId: string): Promise<void> {
+    const dir = this.settingsDirs.get(sessionId);
+    if (!dir) {
+      return;
+    }
+
+    this.settingsDirs.delete(sessionId);
+    try {
+      await fs.rm(dir, { recursive: true, force: true });
+    } catch (error) {
+      console.warn(`Failed to remove session settings ${dir}:`, error);
+    }
+  }
+
+  /**
+   * Whether sessions are spawned as process group leaders so signals reach their subprocesses
+   * (MCP servers, tool commands). Process groups are not available on Windows.
+   */
+  private usesProcessGroups(): boolean {
+    return process.platform !== 'win32';
+  }
+
+  /**
+   * Signal a session's whole process group, falling back to the Claude process alone
+   */
+  private signalSession(child: ChildProcess, signal: NodeJS.Signals): void {
+    if (this.usesProcessGroups() && child.pid) {
+      try {
+        process.kill(-child.pid, signal);
+        return;
+      } catch {
+        // The group is already gone or was never created; signal the process directly
+      }
+    }
+    child.kill(signal);
+  }
+
+  /**
+   * The binary and arguments to spawn for a session, with wrapper_command put in front
+   */
+  private wrapCommand(claudePath: string, args: string[]): [string, string[]] {
+    const [wrapper, ...wrapperArgs] = this.options.wrapper_command ?? [];
+    return wrapper ? [wrapper, [...wrapperArgs, claudePath, ...args]] : [claudePath, args];
+  }
+
+  /**
+   * Create a detached session's output files, truncating those of an earlier attempt
+   */
+  private async openDetachedOutput(sessionId: string): Promise<DetachedOutputFiles> {
+    const paths = this.store!.processOutputPaths(sessionId);
+    let stdout: FileHandle | undefined;
+    try {
+      await fs.mkdir(dirname(paths.stdout), { recursive: true });
+      stdout = await fs.open(paths.stdout, 'w');
+      return { stdout, stderr: await fs.open(paths.stderr, 'w') };
+    } catch (error) {
+      await stdout?.close();
+      const message = error instanceof Error ? error.message : String(error);
+      throw new ServiceError(`Cannot create the output files of a detached session: ${message}`, 'SPAWN_FAILED', 500);
+    }
+  }
+
+  /**
+   * Whether running sessions are killed when the service is cleaned up
+   */
+  private killsOnDrop(): boolean {
+    return this.options.kill_on_drop ?? true;
+  }
+
+  /**
+   * Spawn Claude process with streaming output
+   */
+  private async spawnClaudeProcess(
+    sessionId: string,
+    claudePath: string,
+    args: string[],
+    projectPath: string,
+    request: any,
+    fallbackFrom?: string,
+    apiKey?: string,
+    restartedFrom?: string
+  ): Promise<void> {
+    if (request.working_dir) {
+      await this.assertDirectory(projectPath, 'project_path');
+      await this.assertDirectory(request.working_dir, 'working_dir');
+    } else {
+      await this.assertSymlinkToDirectory(projectPath, 'project_path');
+    }
+    const cwd: string = request.working_dir || projectPath;
+    const requestedArgs = args;
+    const requestedAt = new Date().toISOString();
+
+    this.validateTimeout(request.timeout_ms);
+    this.validateSessionName(request.name);
+    this.checkPromptAllowed(request.prompt);
+    const labels = this.labelsFor(request.labels, request.prompt);
+    const abortPatterns = this.compileAbortPatterns(request.abort_patterns);
+    const resultTarget = this.resolveResultTarget(request.output_result_to, projectPath);
+    const outputSubdir = this.validateOutputSubdir(request.output_subdir);
+    if (request.capture_git_diff !== undefined && typeof request.capture_git_diff !== 'boolean') {
+      throw new ServiceError('capture_git_diff must be a boolean', 'VALIDATION_ERROR', 400);
+    }
+    const retention = request.output_retention_ms;
+    if (retention !== undefined && (typeof retention !== 'number' || !(retention > 0))) {
+      throw new ServiceError('output_retention_ms must be a positive number', 'VALIDATION_ERROR', 400);
+    }
+
+    if (request.precondition) {
+      await this.checkPrecondition(request.precondition, projectPath);
+    }
+
+    // A fallback retry reuses the argv of its first attempt, which already has these
+    if (!fallbackFrom) {
+      args = [...args, ...this.argsForLabels(labels)];
+    }
+
+    if (request.additional_dirs !== undefined) {
+      const dirs = await this.resolveAdditionalDirs(request.additional_dirs, projectPath);
+      args = [...args, ...dirs.flatMap((dir) => ['--add-dir', dir])];
+    }
+
+    if (request.settings !== undefined) {
+      args = [...args, '--settings', await this.writeSessionSettings(sessionId, request.settings)];
+    }
+
+    // A fallback retry keeps the slot of the attempt it replaces
+    let warnings: string[] = [];
+    if (!fallbackFrom) {
+      try {
+        this.claimSessionName(request.name, sessionId);
+        warnings = this.checkProjectConflict(projectPath);
+        this.reserveSlot(projectPath, apiKey);
+      } catch (error) {
+        this.releaseSessionName(request.name, sessionId);
+        await this.removeSessionSettings(sessionId);
+        throw error;
+      }
+    }
+
+    const detached = !this.killsOnDrop();
+    let outputFiles: DetachedOutputFiles | undefined;
+    try {
+      await this.throttleSpawn();
+      outputFiles = detached ? await this.openDetachedOutput(sessionId) : undefined;
+    } catch (error) {
+      if (!fallbackFrom) {
+        this.releaseSlot(projectPath, apiKey);
+        this.releaseSessionName(request.name, sessionId);
+      }
+      await this.removeSessionSettings(sessionId);
+      throw error;
+    }
+    const gitInfo = this.options.record_git_info ? await this.gitInfoOf(projectPath) : {};
+    const cgroup = await this.cgroups?.create(sessionId, this.options.memory_limit_mb!);
+
+    const env = { ...process.env };
+    const [binary, argv] = this.wrapCommand(claudePath, args);
+    // A detached session writes straight to its output files, so it never blocks on or dies
+    // writing to a pipe once this server is gone
+    const spawned = spawn(binary, argv, {
+      cwd,
+      stdio: outputFiles ? ['ignore', outputFiles.stdout.fd, outputFiles.stderr.fd] : 'pipe',
+      env,
+      detached: detached || this.usesProcessGroups(),
+    });
+    if (outputFiles) {
+      // The child has its own copies of the descriptors
+      void outputFiles.stdout.close();
+      void outputFiles.stderr.close();
+    }
+
+    if (!spawned.pid) {
+      // A spawn that fails reports why with an `error` event on the next tick
+      let spawnError: NodeJS.ErrnoException | undefined;
+      spawned.once('error', (error) => {
+        spawnError = error;
+      });
+      await nextIteration();
+
+      if (!fallbackFrom) {
+        this.releaseSlot(projectPath, apiKey);
+        this.releaseSessionName(request.name, sessionId);
+      }
+      await this.removeSessionSettings(sessionId);
+      if (cgroup) {
+        await this.cgroups!.remove(cgroup);
+      }
+      if (outputFiles) {
+        await this.store!.forgetProcess(sessionId);
+      }
+      const failure = spawnFailure(binary, spawnError);
+      this.emitSessionEvent('failed_to_start', sessionId, { project_path: projectPath, model: request.model }, {
+        error: failure.message,
+      });
+      throw failure;
+    }
+
+    let follower: DetachedProcess | undefined;
+    if (outputFiles) {
+      // Do not keep the server's event loop alive for a process meant to outlive it
+      spawned.unref();
+      follower = new DetachedProcess(spawned.pid, this.store!.processOutputPaths(sessionId), spawned);
+    }
+    const child = follower ? (follower as unknown as ChildProcess) : spawned;
+
+    if (cgroup) {
+      this.sessionCgroups.set(sessionId, cgroup);
+      void this.cgroups!.addProcess(cgroup, spawned.pid);
+    }
+    if (apiKey) {
+      this.sessionKeys.set(sessionId, apiKey);
+    }
+
+    // Register process
+    const processInfo: ProcessInfo = {
+      run_id: Date.now(),
+      process_type: { ClaudeSession: { session_id: sessionId } },
+      pid: spawned.pid,
+      started_at: new Date().toISOString(),
+      project_path: projectPath,
+      task: request.prompt.substring(0, 100),
+      model: request.model,
+      ...gitInfo,
+    };
+    if (args.includes('--dangerously-skip-permissions')) {
+      processInfo.permissions_skipped = true;
+    }
+    if (detached) {
+      processInfo.detached = true;
+    }
+    if (cwd !== projectPath) {
+      processInfo.working_dir = cwd;
+    }
+    if (fallbackFrom) {
+      processInfo.fallback_from = fallbackFrom;
+    }
+    if (restartedFrom) {
+      processInfo.restarted_from = restartedFrom;
+    }
+    if (request.name) {
+      processInfo.name = request.name;
+    }
+    if (Object.keys(labels).length > 0) {
+      processInfo.labels = labels;
+    }
+    if (outputSubdir) {
+      processInfo.output_subdir = outputSubdir;
+    }
+    if (retention !== undefined) {
+      processInfo.output_retention_ms = retention;
+    }
+    if (warnings.length > 0) {
+      processInfo.warnings = warnings;
+    }
+
+    this.processes.set(sessionId, child);
+    this.processRegistry.set(sessionId, processInfo);
+    const recorded = this.store?.recordProcess({
+      session_id: sessionId,
+      pid: spawned.pid,
+      started_at: processInfo.started_at,
+      server_pid: process.pid,
+      ...(detached ? { detached: true } : {}),
+      ...(outputSubdir ? { output_subdir: outputSubdir } : {}),
+    });
+    if (!fallbackFrom) {
+      this.launchRequests.set(sessionId, { claudePath, args: requestedArgs, projectPath, request });
+    }
+    if (resultTarget) {
+      this.resultTargets.set(sessionId, resultTarget);
+    }
+    if (request.capture_git_diff) {
+      this.gitDiffRequested.add(sessionId);
+    }
+    const previous = fallbackFrom ? this.sessions.get(sessionId) : undefined;
+    const envOverrides = Object.fromEntries(
+      Object.entries(env).filter(([name, value]) => process.env[name] !== value)
+    );
+    // A fallback retry continues the timeline of the attempt it replaces
+    const statusHistory: SessionStatusChange[] = previous?.status_history
+      ? [
+          ...previous.status_history,
+          { status: 'running', at: processInfo.started_at, reason: `retried with fallback model ${request.model}` },
+        ]
+      : [{ status: 'starting', at: requestedAt }, { status: 'running', at: processInfo.started_at }];
+    this.sessions.set(sessionId, {
+      info: processInfo,
+      status: 'running',
+      output: previous?.output ?? [],
+      output_total: previous?.output_total ?? 0,
+      status_history: statusHistory,
+      launch: {
+        binary,
+        args: argv,
+        cwd,
+        env: redactEnv(env),
+        env_overrides: redactEnv(envOverrides),
+      },
+    });
+    this.startPreviewUpdates(sessionId);
+    this.watchdogs.set(sessionId, { patterns: [] });
+    this.throughput.set(sessionId, new ThroughputMeter());
+    this.applyWatchdogUpdate(sessionId, processInfo, request.timeout_ms, request.abort_patterns, abortPatterns);
+    this.persistSession(sessionId);
+    if (!fallbackFrom) {
+      this.emitSessionEvent('started', sessionId, processInfo);
+    }
+
+    this.attachProcessHandlers(sessionId, child, processInfo, { claudePath, args, projectPath, request });
+    void follower?.follow();
+    // A detached session can be adopted by a later run as soon as its start is reported
+    await recorded;
+  }
+
+  /**
+   * Follow a session's process: buffer and publish its output, and finish the session when it
+   * exits. `launch` is how the process was started, for a fallback retry; an adopted process
+   * has none and is never retried.
+   */
+  private attachProcessHandlers(
+    sessionId: string,
+    child: ChildProcess,
+    processInfo: ProcessInfo,
+    launch?: SessionLaunchRequest
+  ): void {
+    // Handle stdout (streaming JSON), splitting raw bytes on newlines so multi-byte
+    // characters and JSON messages spanning several chunks stay intact. A line longer than
+    // max_line_bytes is cut at the cap and the rest is skipped up to the next newline.
+    const maxLineBytes = this.options.max_line_bytes ?? DEFAULT_MAX_LINE_BYTES;
+    let pending: Buffer = Buffer.alloc(0);
+    let skipping = false;
+    child.stdout?.on('data', this.guardOutputHandler(sessionId, child, 'stdout', (data: Buffer) => {
+      // A line is stamped with the time the chunk completing it was read
+      const readAt = new Date().toISOString();
+      let buffer = pending.length > 0 ? Buffer.concat([pending, data]) : data;
+      let newline: number;
+      while ((newline = buffer.indexOf(0x0a)) !== -1) {
+        if (skipping) {
+          skipping = false;
+        } else if (newline > maxLineBytes) {
+          this.handleStdoutLine(sessionId, buffer.subarray(0, maxLineBytes), readAt, true);
+        } else {
+          this.handleStdoutLine(sessionId, buffer.subarray(0, newline), readAt);
+        }
+        buffer = buffer.subarray(newline + 1);
+      }
+
+      if (skipping) {
+        buffer = Buffer.alloc(0);
+      } else if (buffer.length > maxLineBytes) {
+        this.handleStdoutLine(sessionId, buffer.subarray(0, maxLineBytes), readAt, true);
+        skipping = true;
+        buffer = Buffer.alloc(0);
+      }
+      pending = buffer;
+    }));
+
+    // Claude closing stdout normally means it is about to exit. One that lingers, e.g. stuck
+    // waiting on a subprocess, would never emit 'close' and keep the session running forever.
+    child.stdout?.on('end', () => {
+      const grace = this.options.stdout_eof_grace_ms ?? DEFAULT_STDOUT_EOF_GRACE_MS;
+      const timer = setTimeout(() => {
+        if (this.processes.get(sessionId) === child && child.exitCode === null && child.signalCode === null) {
+          this.terminateSession(sessionId, 'stdout_closed', 'stdout closed but process alive');
+        }
+      }, grace);
+      timer.unref?.();
+      child.once('exit', () => clearTimeout(timer));
+    });
+
+    // Writes to stdin fail with EPIPE once Claude has exited; the exit is handled below
+    child.stdin?.on('error', () => undefined);
+
+    // Handle stderr
+    child.stderr?.on('data', this.guardOutputHandler(sessionId, child, 'stderr', (data: Buffer) => {
+      const readAt = new Date().toISOString();
+      const decoded = decodeOutput(data, this.options.output_charset_fallback);
+      if (this.filterOutput(sessionId, decoded.text)) {
+        return;
+      }
+      this.bufferOutput(sessionId, {
+        stream: 'stderr',
+        content: decoded.text,
+        timestamp: readAt,
+        ...this.encodingTags(decoded),
+      });
+      if (OVERLOAD_PATTERN.test(decoded.text)) {
+        this.overloaded.add(sessionId);
+      }
+      this.emit('claude_error', {
+        session_id: sessionId,
+        error: decoded.text,
+        timestamp: readAt,
+      });
+    }));
+
+    // Handle process exit
+    child.on('close', (code, signal) => {
+      if (pending.length > 0) {
+        this.guardOutputHandler(sessionId, child, 'stdout', (line: Buffer) => {
+          this.handleStdoutLine(sessionId, line, new Date().toISOString());
+        })(pending);
+        pending = Buffer.alloc(0);
+      }
+
+      const wasRegistered = this.processRegistry.has(sessionId);
+      const cancelled = this.cancelRequested.delete(sessionId);
+      const overloaded = this.overloaded.delete(sessionId);
+      this.processes.delete(sessionId);
+      this.processRegistry.delete(sessionId);
+      void this.store?.forgetProcess(sessionId);
+      
+      this.emit('claude_exit', {
+        session_id: sessionId,
+        code,
+      });
+
+      const settle = () => {
+        // A session whose output could not be handled failed, however the process ended
+        const failureReason = this.sessions.get(sessionId)?.failure_reason;
+        const fallbackModel = launch && overloaded && !cancelled && !failureReason && code !== 0
+          ? this.fallbackModelFor(processInfo)
+          : undefined;
+        if (wasRegistered && launch && fallbackModel) {
+          this.retryWithFallback(
+            sessionId, launch.claudePath, launch.args, launch.projectPath, launch.request, processInfo, fallbackModel
+          );
+        } else if (wasRegistered && failureReason) {
+          if (this.finishSession(sessionId, 'failed', code, signal)) {
+            this.emitSessionEvent('failed', sessionId, processInfo, { exit_code: code, error: failureReason });
+          }
+        } else if (wasRegistered) {
+          const status: Exclude<SessionStatus, 'running'> = cancelled
+            ? 'cancelled'
+            : code === 0 || (code === null && signal === null && this.endedWithResult(sessionId))
+              ? 'completed'
+              : 'failed';
+          if (this.finishSession(sessionId, status, code, signal)) {
+            this.emitSessionEvent(status, sessionId, processInfo, { exit_code: code });
+          }
+        }
+      };
+
+      // The cgroup is read for an OOM kill before the session is finished, so the
+      // final record already says why it failed
+      if (this.sessionCgroups.has(sessionId)) {
+        void this.releaseCgroup(sessionId).then(settle);
+      } else {
+        settle();
+      }
+    });
+
+    child.on('error', (error) => {
+      const wasRegistered = this.processRegistry.has(sessionId);
+      this.cancelRequested.delete(sessionId);
+      this.overloaded.delete(sessionId);
+      this.processes.delete(sessionId);
+      this.processRegistry.delete(sessionId);
+      void this.store?.forgetProcess(sessionId);
+      
+      this.emit('claude_error', {
+        session_id: sessionId,
+        error: error.message,
+      });
+      void this.releaseCgroup(sessionId);
+
+      if (wasRegistered && this.finishSession(sessionId, 'failed', null)) {
+        this.emitSessionEvent('failed', sessionId, processInfo, { error: error.message });
+      }
+    });
+  }
+
+  /**
+   * Whether a session emitted a result that is not an error. An adopted process exits with no
+   * known exit code, so this decides whether it completed.
+   */
+  private endedWithResult(sessionId: string): boolean {
+    const result = this.sessions.get(sessionId)?.result;
+    return result !== undefined && !result.is_error;
+  }
+
+  /**
+   * Remove a session's cgroup, first marking the session as terminated by its memory limit
+   * if the kernel OOM-killed it
+   */
+  private async releaseCgroup(sessionId: string): Promise<void> {
+    const cgroup = this.sessionCgroups.get(sessionId);
+    if (!cgroup || !this.cgroups) {
+      return;
+    }
+    this.sessionCgroups.delete(sessionId);
+
+    const record = this.sessions.get(sessionId);
+    if (record && !record.terminated_by && (await this.cgroups.wasOomKilled(cgroup))) {
+      const message = `Session exceeded its memory limit of ${this.options.memory_limit_mb} MiB`;
+      record.terminated_by = 'memory_limit';
+      this.emit('claude_error', { session_id: sessionId, error: message });
+      this.emitIntervention(sessionId, record.info, 'memory_limit', message);
+    }
+    await this.cgroups.remove(cgroup);
+  }
+
+  /**
+   * The configured fallback for a session's model; a session that already fell back gets none
+   */
+  private fallbackModelFor(info: ProcessInfo): string | undefined {
+    if (info.fallback_from) {
+      return undefined;
+    }
+    return this.options.model_fallback?.[info.model];
+  }
+
+  /**
+   * Restart an overloaded session under the same ID with the fallback model.
+   * The precondition is not re-run and the settings overlay from the first attempt is reused.
+   */
+  private retryWithFallback(
+    sessionId: string,
+    claudePath: string,
+    args: string[],
+    projectPath: string,
+    request: any,
+    info: ProcessInfo,
+    fallbackModel: string
+  ): void {
+    console.warn(`Session ${sessionId}: ${info.model} is overloaded, retrying with ${fallbackModel}`);
+    this.stopPreviewUpdates(sessionId);
+    this.stopWatchdog(sessionId);
+
+    const retryArgs = args.map((arg, i) => (args[i - 1] === '--model' ? fallbackModel : arg));
+    const retryRequest = {
+      ...request,
+      model: fallbackModel,
+      precondition: undefined,
+      settings: undefined,
+      additional_dirs: undefined,
+    };
+
+    this.spawnClaudeProcess(
+      sessionId, claudePath, retryArgs, projectPath, retryRequest, info.model, undefined, info.restarted_from
+    ).catch((error) => {
+      if (this.finishSession(sessionId, 'failed', null)) {
+        this.emitSessionEvent('failed', sessionId, info, { error: error instanceof Error ? error.message : String(error) });
+      }
+    });
+  }
+
+  /**
+   * Parse one complete stdout line, buffer it, and emit it as a stream message or raw output,
+   * stamped with `readAt`, the time it was read from the process
+   */
+  private handleStdoutLine(sessionId: string, bytes: Buffer, readAt: string, truncated = false): void {
+    this.meterOutput(sessionId, bytes.length);
+    const decoded = decodeOutput(bytes, this.options.output_charset_fallback);
+    const line = decoded.text;
+    if (!line.trim() || this.filterOutput(sessionId, line)) {
+      return;
+    }
+
+    if (truncated) {
+      // The rest of the line is skipped unread, so its real length is unknown here
+      console.warn(`Session ${sessionId}: output line exceeded the ${bytes.length}-byte cap and was truncated`);
+      this.bufferOutput(sessionId, {
+        stream: 'stdout',
+        content: line,
+        timestamp: readAt,
+        truncated: true,
+        ...this.encodingTags(decoded),
+      });
+      this.appendPreview(sessionId, line);
+      this.emit('claude_output', {
+        session_id: sessionId,
+        data: line,
+        timestamp: readAt,
+        truncated: true,
+        ...this.encodingTags(decoded),
+      });
+      return;
+    }
+
+    this.bufferOutput(sessionId, { stream: 'stdout', content: line, timestamp: readAt, ...this.encodingTags(decoded) });
+
+    try {
+      const message = JSON.parse(line) as ClaudeStreamMessage;
+      this.captureClaudeSessionId(sessionId, message);
+      this.captureResult(sessionId, message);
+      this.captureToolUse(sessionId, message);
+      if ((message.type === 'error' || (message as any).is_error) && OVERLOAD_PATTERN.test(line)) {
+        this.overloaded.add(sessionId);
+      }
+      this.appendPreview(sessionId, textOf(message));
+      message.session_id = sessionId;
+      message.timestamp = readAt;
+      
+      this.emit('claude_stream', {
+        session_id: sessionId,
+        message,
+      });
+    } catch (error) {
+      // Non-JSON line, emit as raw output
+      this.appendPreview(sessionId, line);
+      this.emit('claude_output', {
+        session_id: sessionId,
+        data: line,
+        timestamp: readAt,
+        ...this.encodingTags(decoded),
+      });
+    }
+  }
+
+  /**
+   * The invalid-UTF-8 tag and raw bytes to attach to a decoded line, if any
+   */
+  private encodingTags(decoded: DecodedText): Pick<OutputLine, 'invalid_utf8' | 'raw_base64'> {
+    return decoded.invalid_utf8
+      ? { invalid_utf8: true, raw_base64: decoded.raw_base64 }
+      : {};
+  }
+
+  /**
+   * Append a line to the session's output buffer, dropping the oldest beyond the cap
+   */
+  private bufferOutput(sessionId: string, line: OutputLine): void {
+    const record = this.sessions.get(sessionId);
+    if (!record) {
+      return;
+    }
+
+    if (this.options.tee_output_to_stdout) {
+      this.teeOutput(sessionId, line);
+    }
+
+    record.output.push(line);
+    record.output_total = (record.output_total ?? record.output.length - 1) + 1;
+    this.store?.appendOutput(sessionId, line, record.info.output_subdir);
+    if (this.previewTimers.has(sessionId)) {
+      this.previewActive.add(sessionId);
+    }
+    const maxLines = this.options.max_output_lines ?? 1000;
+    if (record.output.length > maxLines) {
+      record.output.splice(0, record.output.length - maxLines);
+    }
+    this.dropExpiredOutput(record);
+
+    this.checkAbortPatterns(sessionId, line);
+  }
+
+  /**
+   * Whether an output line matches one of the output_filters and is to be dropped
+   */
+  private filterOutput(sessionId: string, line: string): boolean {
+    if (!this.outputFilters.some((pattern) => pattern.test(line))) {
+      return false;
+    }
+    const record = this.sessions.get(sessionId);
+    if (record && this.options.count_filtered_output) {
+      record.output_filtered = (record.output_filtered ?? 0) + 1;
+    }
+    return true;
+  }
+
+  /**
+   * The buffered output lines of a session, without those read before its output_retention_ms
+   * window. Every read of the buffer goes through here, so the window also applies while no new
+   * line arrives, and after the session finished.
+   */
+  visibleOutput(record: SessionRecord): OutputLine[] {
+    this.dropExpiredOutput(record);
+    return record.output;
+  }
+
+  /**
+   * Drop the buffered lines read before the session's output_retention_ms window. Lines are
+   * buffered in read order, so the expired ones are at the front.
+   */
+  private dropExpiredOutput(record: SessionRecord): void {
+    const retention = record.info.output_retention_ms;
+    if (!retention) {
+      return;
+    }
+    const cutoff = Date.now() - retention;
+    let expired = 0;
+    while (expired < record.output.length && Date.parse(record.output[expired].timestamp) < cutoff) {
+      expired++;
+    }
+    if (expired > 0) {
+      record.output.splice(0, expired);
+    }
+  }
+
+  /**
+   * Reject a timeout that is not a positive number of milliseconds
+   */
+  private validateTimeout(timeoutMs: unknown): void {
+    if (timeoutMs !== undefined && (typeof timeoutMs !== 'number' || !(timeoutMs > 0))) {
+      throw new ServiceError('timeout_ms must be a positive number', 'VALIDATION_ERROR', 400);
+    }
+  }
+
+  /**
+   * Compile abort patterns, rejecting anything that is not a valid regular expression
+   */
+  private compileAbortPatterns(patterns: unknown): RegExp[] {
+    if (patterns === undefined) {
+      return [];
+    }
+    if (!Array.isArray(patterns) || patterns.some((pattern) => typeof pattern !== 'string')) {
+      throw new ServiceError('abort_patterns must be an array of strings', 'VALIDATION_ERROR', 400);
+    }
+
+    return patterns.map((pattern: string) => {
+      try {
+        return new RegExp(pattern);
+      } catch (error) {
+        throw new ServiceError(`Invalid abort pattern: ${pattern}`, 'VALIDATION_ERROR', 400, {
+          pattern,
+          message: error instanceof Error ? error.message : String(error),
+        });
+      }
+    });
+  }
+
+  /**
+   * Set a session's timeout and add abort patterns, after they have been validated
+   */
+  private applyWatchdogUpdate(
+    sessionId: string,
+    info: ProcessInfo,
+    timeoutMs: number | undefined,
+    patternSources: string[] | undefined,
+    patterns: RegExp[]
+  ): void {
+    const watchdog = this.watchdogs.get(sessionId);
+    if (!watchdog) {
+      return;
+    }
+
+    if (timeoutMs !== undefined) {
+      clearTimeout(watchdog.timer);
+      const remaining = Math.max(0, timeoutMs - (Date.now() - Date.parse(info.started_at)));
+      watchdog.timer = setTimeout(() => {
+        this.terminateSession(sessionId, 'timeout', `Session exceeded its ${timeoutMs}ms timeout`);
+      }, remaining);
+      watchdog.timer.unref();
+      info.timeout_ms = timeoutMs;
+    }
+
+    if (patternSources && patternSources.length > 0) {
+      watchdog.patterns.push(...patterns);
+      info.abort_patterns = [...(info.abort_patterns ?? []), ...patternSources];
+    }
+  }
+
+  /**
+   * Cancel the session when an output line matches one of its abort patterns
+   */
+  private checkAbortPatterns(sessionId: string, line: OutputLine): void {
+    const watchdog = this.watchdogs.get(sessionId);
+    const match = watchdog?.patterns.find((pattern) => pattern.test(line.content));
+    if (match) {
+      this.terminateSession(sessionId, 'abort_pattern', `Output matched abort pattern ${match.source}`);
+    }
+  }
+
+  /**
+   * Wrap a handler of a session's output so an exception inside it fails that session instead
+   * of escaping into the event loop. The first exception records the session's failure_reason
+   * and kills the process; output arriving after that is dropped.
+   */
+  private guardOutputHandler<T>(
+    sessionId: string,
+    child: ChildProcess,
+    stream: 'stdout' | 'stderr',
+    handler: (data: T) => void
+  ): (data: T) => void {
+    return (data: T) => {
+      const record = this.sessions.get(sessionId);
+      if (record?.failure_reason) {
+        return;
+      }
+      try {
+        handler(data);
+      } catch (error) {
+        const message = `Error handling ${stream} of session ${sessionId}: ${error instanceof Error ? error.message : String(error)}`;
+        console.error(`❌ ${message}`, error);
+        if (record) {
+          record.failure_reason = message;
+          this.persistSession(sessionId);
+        }
+        this.emit('claude_error', { session_id: sessionId, error: message });
+        if (this.processes.get(sessionId) === child) {
+          this.killSession(child);
+        }
+      }
+    };
+  }
+
+  /**
+   * Cancel a session on behalf of a watchdog, recording why
+   */
+  private terminateSession(sessionId: string, reason: InterventionReason, message: string): void {
+    const record = this.sessions.get(sessionId);
+    const child = this.processes.get(sessionId);
+    if (!record || record.status !== 'running' || record.terminated_by || !child || hasExited(child)) {
+      return;
+    }
+
+    this.stopWatchdog(sessionId);
+    record.terminated_by = reason;
+    this.emit('claude_error', { session_id: sessionId, error: message });
+    this.emitIntervention(sessionId, record.info, reason, message);
+    void this.cancelClaudeExecution(sessionId);
+  }
+
+  /**
+   * Count a stdout line towards the session's throughput and cancel the session when its
+   * sustained rate exceeds max_output_bytes_per_sec
+   */
+  private meterOutput(sessionId: string, bytes: number): void {
+    const meter = this.throughput.get(sessionId);
+    if (!meter) {
+      return;
+    }
+
+    const now = Date.now();
+    meter.record(bytes, now);
+
+    const limit = this.options.max_output_bytes_per_sec;
+    if (limit && meter.hasFullWindow(now)) {
+      const { bytes_per_sec: rate } = meter.getThroughput(now);
+      if (rate > limit) {
+        this.terminateSession(
+          sessionId,
+          'output_rate',
+          `Output rate of ${rate} bytes/s exceeded the limit of ${limit} bytes/s`
+        );
+      }
+    }
+  }
+
+  /**
+   * Disarm a session's timeout and abort patterns
+   */
+  private stopWatchdog(sessionId: string): void {
+    const watchdog = this.watchdogs.get(sessionId);
+    if (watchdog) {
+      clearTimeout(watchdog.timer);
+      this.watchdogs.delete(sessionId);
+    }
+  }
+
+  /**
+   * Adjust the timeout and abort patterns of a running session without restarting it.
+   * The update is validated as a whole before anything changes.
+   */
+  updateSession(sessionId: string, update: SessionWatchdogUpdate): ProcessInfo {
+    const info = this.processRegistry.get(sessionId);
+    if (!info) {
+      throw new ServiceError('Session not found or not running', 'SESSION_NOT_FOUND', 404);
+    }
+
+    this.validateTimeout(update.timeout_ms);
+    const patterns = this.compileAbortPatterns(update.abort_patterns);
+    this.applyWatchdogUpdate(sessionId, info, update.timeout_ms, update.abort_patterns, patterns);
+    return info;
+  }
+
+  /**
+   * Write an output line to the server's stdout for container log collectors
+   */
+  private teeOutput(sessionId: string, line: OutputLine): void {
+    const prefix = line.stream === 'stderr' ? `[${sessionId}:stderr]` : `[${sessionId}]`;
+    const text = line.content.replace(/\n$/, '');
+    process.stdout.write(
+      text.split('\n').map((part) => `${prefix} ${part}`).join('\n') + '\n'
+    );
+  }
+
+  /**
+   * Collect meaningful text for the output preview until it is long enough
+   */
+  private appendPreview(sessionId: string, text: string): void {
+    const current = this.previewBuffers.get(sessionId);
+    if (current === undefined || current.length >= OUTPUT_PREVIEW_CHARS || !text.trim()) {
+      return;
+    }
+    const next = current ? `${current}\n${text}` : text;
+    this.previewBuffers.set(sessionId, next.slice(0, OUTPUT_PREVIEW_CHARS));
+  }
+
+  /**
+   * Publish collected preview text to SessionInfo.output_preview on a timer rather than per line
+   */
+  private startPreviewUpdates(sessionId: string): void {
+    this.previewBuffers.set(sessionId, '');
+    this.schedulePreview(sessionId, this.options.preview_interval_min_ms ?? OUTPUT_PREVIEW_INTERVAL_MS);
+  }
+
+  /**
+   * Arm the next preview refresh. Quiet sessions back off towards the maximum interval so
+   * they cause fewer wakeups; output brings the interval back down towards the minimum.
+   */
+  private schedulePreview(sessionId: string, intervalMs: number): void {
+    const timer = setTimeout(() => {
+      this.publishPreview(sessionId);
+
+      const min = this.options.preview_interval_min_ms ?? OUTPUT_PREVIEW_INTERVAL_MS;
+      const max = Math.max(min, this.options.preview_interval_max_ms ?? OUTPUT_PREVIEW_MAX_INTERVAL_MS);
+      const next = this.previewActive.delete(sessionId)
+        ? Math.max(min, intervalMs / 2)
+        : Math.min(max, intervalMs * 2);
+      this.schedulePreview(sessionId, next);
+    }, intervalMs);
+    timer.unref();
+    this.previewTimers.set(sessionId, timer);
+  }
+
+  /**
+   * Copy the collected preview into the session info
+   */
+  private publishPreview(sessionId: string): void {
+    const preview = this.previewBuffers.get(sessionId);
+    const record = this.sessions.get(sessionId);
+    if (record && preview) {
+      record.info.output_preview = preview;
+    }
+  }
+
+  /**
+   * Publish the final preview and stop refreshing it
+   */
+  private stopPreviewUpdates(sessionId: string): void {
+    this.publishPreview(sessionId);
+    clearTimeout(this.previewTimers.get(sessionId));
+    this.previewTimers.delete(sessionId);
+    this.previewBuffers.delete(sessionId);
+    this.previewActive.delete(sessionId);
+  }
+
+  /**
+   * Remember Claude's own session id, which every stream-json message carries, before it is
+   * replaced by the server's id for clients
+   */
+  private captureClaudeSessionId(sessionId: string, message: any): void {
+    const record = this.sessions.get(sessionId);
+    if (record && !record.info.claude_session_id && typeof message?.session_id === 'string' && message.session_id) {
+      record.info.claude_session_id = message.session_id;
+    }
+  }
+
+  /**
+   * Count the `tool_use` blocks of an assistant message by tool name
+   */
+  private captureToolUse(sessionId: string, message: any): void {
+    const record = this.sessions.get(sessionId);
+    if (!record || message?.type !== 'assistant') {
+      return;
+    }
+
+    for (const block of contentBlocksOf(message)) {
+      if (block.type !== 'tool_use' || typeof block.name !== 'string') {
+        continue;
+      }
+      record.tools_used ??= [];
+      const usage = record.tools_used.find((tool) => tool.name === block.name);
+      if (usage) {
+        usage.count++;
+      } else {
+        record.tools_used.push({ name: block.name, count: 1 });
+      }
+    }
+  }
+
+  /**
+   * Record the stream-json `result` message as the session's final answer
+   */
+  private captureResult(sessionId: string, message: any): void {
+    const record = this.sessions.get(sessionId);
+    if (!record || message?.type !== 'result') {
+      return;
+    }
+
+    record.result = {
+      text: typeof message.result === 'string' ? message.result : '',
+      parsed: true,
+      is_error: message.is_error,
+      cost_usd: message.total_cost_usd ?? message.cost_usd,
+      usage: message.usage,
+      num_turns: message.num_turns,
+      duration_ms: message.duration_ms,
+    };
+
+    // The exit code alone cannot tell "hit max turns" from "crashed"
+    const subtype = typeof message.subtype === 'string' ? message.subtype : undefined;
+    if (message.is_error || subtype?.startsWith('error')) {
+      const text = [message.result, message.error].find((value) => typeof value === 'string' && value);
+      record.info.error_detail = { subtype: subtype ?? 'error', ...(text ? { message: text } : {}) };
+    }
+  }
+
+  /**
+   * Move a session record into its terminal status, unless it has already reached one
+   *
+   * @returns Whether the session was finished now; callers only announce it when it was.
+   */
+  private finishSession(
+    sessionId: string,
+    status: SessionStatus,
+    exitCode: number | null,
+    signal: NodeJS.Signals | null = null
+  ): boolean {
+    const record = this.sessions.get(sessionId);
+    // A session reaches its terminal status once; a late second report must not release its
+    // slot again or overwrite how it ended
+    if (!record || record.status !== 'running') {
+      return false;
+    }
+
+    this.stopPreviewUpdates(sessionId);
+    this.stopWatchdog(sessionId);
+    this.throughput.delete(sessionId);
+    this.releaseSlot(record.info.project_path, this.sessionKeys.get(sessionId));
+    this.sessionKeys.delete(sessionId);
+    this.launchRequests.delete(sessionId);
+    void this.removeSessionSettings(sessionId);
+    record.status = status;
+    record.exit_code = exitCode;
+    record.exit_signal = signal;
+    record.ended_at = new Date().toISOString();
+    const reason = this.finishReason(record, status);
+    record.status_history?.push({ status, at: record.ended_at, ...(reason ? { reason } : {}) });
+    const resultTarget = this.resultTargets.get(sessionId);
+    this.resultTargets.delete(sessionId);
+    if (resultTarget && status === 'completed') {
+      void this.writeResultFile(sessionId, resultTarget);
+    }
+    if (this.gitDiffRequested.delete(sessionId)) {
+      void this.captureGitDiff(sessionId, record.info.working_dir ?? record.info.project_path);
+    }
+    if (this.discardOutput.delete(sessionId)) {
+      record.output = [];
+      record.output_discarded = true;
+      void this.store?.removeOutput(sessionId, record.info.output_subdir);
+    } else {
+      void this.store?.flushOutput(sessionId);
+    }
+    this.persistSession(sessionId);
+    this.evictStoredSessions();
+    return true;
+  }
+
+  /**
+   * Drop the buffered output of the sessions that finished longest ago until about `bytes` of
+   * output were released. With persistence on, each session's output log is flushed to disk
+   * before its lines are dropped; without it, or with the store degraded, the lines are lost and
+   * the session is marked `output_lost`. Running sessions are never touched, and records are kept.
+   *
+   * @returns The sessions whose output was dropped, oldest first.
+   */
+  async shedFinishedOutput(bytes: number): Promise<ShedOutput[]> {
+    const finished = Array.from(this.sessions.entries())
+      .filter(([, record]) => record.status !== 'running' && record.output.length > 0)
+      .sort(([, a], [, b]) => (a.ended_at ?? '').localeCompare(b.ended_at ?? ''));
+
+    const shed: ShedOutput[] = [];
+    let released = 0;
+    for (const [sessionId, record] of finished) {
+      if (released >= bytes) {
+        break;
+      }
+      await this.store?.flushOutput(sessionId);
+      // A degraded store may have failed to write these very lines
+      const lost = !this.store || this.store.getStatus().degraded;
+
+      const lines = record.output.length;
+      const lineBytes = record.output.reduce((sum, line) => sum + Buffer.byteLength(line.content), 0);
+      record.output = [];
+      record.output_shed = (record.output_shed ?? 0) + lines;
+      if (lost) {
+        record.output_lost = true;
+      }
+      released += lineBytes;
+      shed.push({ session_id: sessionId, lines, bytes: lineBytes, lost });
+    }
+    return shed;
+  }
+
+  /**
+   * Drop the records that finished longest ago until at most max_stored_sessions remain.
+   * Running sessions are never evicted, so the cap can be exceeded while they run.
+   */
+  private evictStoredSessions(): void {
+    const max = this.options.max_stored_sessions;
+    if (!max || this.sessions.size <= max) {
+      return;
+    }
+
+    const finished = Array.from(this.sessions.entries())
+      .filter(([, record]) => record.status !== 'running')
+      .sort(([, a], [, b]) => (a.ended_at ?? '').localeCompare(b.ended_at ?? ''));
+
+    for (const [sessionId, record] of finished.slice(0, this.sessions.size - max)) {
+      this.sessions.delete(sessionId);
+      this.releaseSessionName(record.info.name, sessionId);
+    }
+  }
+
+  /**
+   * Write a session record to the data directory in the background; failures only degrade persistence
+   */
+  private persistSession(sessionId: string): void {
+    const record = this.sessions.get(sessionId);
+    if (this.store && record) {
+      void this.store.save(sessionId, record);
+    }
+  }
+
+  /**
+   * Whether session records are persisted, and whether writes have started failing
+   */
+  getPersistenceStatus(): PersistenceStatus {
+    return this.store ? this.store.getStatus() : { enabled: false, degraded: false };
+  }
+
+  /**
+   * Check that the data directory is writable right now; undefined when persistence is off
+   */
+  async checkDataDir(): Promise<DataDirCheck | undefined> {
+    return this.store?.checkWritable();
+  }
+
+  /**
+   * Publish a lifecycle transition on the server-wide `session_event` channel. A session whose
+   * process could not be spawned has no ProcessInfo, only its project and model.
+   */
+  private emitSessionEvent(
+    event: SessionLifecycleEvent,
+    sessionId: string,
+    info: Pick<ProcessInfo, 'project_path' | 'model' | 'error_detail'>,
+    extra: Pick<SessionEvent, 'exit_code' | 'error'> = {}
+  ): void {
+    const sessionEvent: SessionEvent = {
+      event,
+      session_id: sessionId,
+      project_path: info.project_path,
+      model: info.model,
+      ...extra,
+      ...(info.error_detail ? { error_detail: info.error_detail } : {}),
+      timestamp: new Date().toISOString(),
+    };
+    this.emit('session_event', sessionEvent);
+  }
+
+  /**
+   * Publish on the `session_intervention` channel that a session is being killed
+   * rather than finishing on its own
+   */
+  private emitIntervention(
+    sessionId: string,
+    info: ProcessInfo,
+    reason: InterventionReason,
+    message: string
+  ): void {
+    const intervention: SessionIntervention = {
+      event: 'intervention',
+      session_id: sessionId,
+      project_path: info.project_path,
+      model: info.model,
+      reason,
+      message,
+      timestamp: new Date().toISOString(),
+    };
+    this.emit('session_intervention', intervention);
+  }
+
+  /**
+   * Cancel a running Claude process
+   */
+  async cancelClaudeExecution(sessionId: string, options: CancelClaudeRequest = {}): Promise<boolean> {
+    const child = this.processes.get(sessionId);
+    
+    // A process that already exited on its own completes with its exit code, even if its
+    // close event, and so its terminal status, is still on the way
+    if (child && !hasExited(child)) {
+      this.cancelRequested.add(sessionId);
+      if (options.keep_output === false) {
+        this.discardOutput.add(sessionId);
+      }
+
+      // A soft cancel closes stdin so Claude can wrap up the current turn, and only
+      // falls back to signals when it is still running after the grace period
+      if (options.soft && child.stdin && !child.stdin.destroyed) {
+        const grace = options.grace_ms ?? this.options.soft_cancel_grace_ms ?? DEFAULT_SOFT_CANCEL_GRACE_MS;
+        child.stdin.end();
+        setTimeout(() => {
+          if (child.exitCode === null && child.signalCode === null) {
+            this.killSession(child);
+          }
+        }, grace);
+        return true;
+      }
+
+      this.killSession(child);
+      return true;
+    }
+    
+    return false;
+  }
+
+  /**
+   * Cancel every running session started more than `minutes` ago, whatever it is doing.
+   * Finished session records are left alone.
+   *
+   * @returns The IDs of the cancelled sessions, oldest first.
+   */
+  async terminateOlderThan(minutes: unknown): Promise<string[]> {
+    if (typeof minutes !== 'number' || !(minutes > 0)) {
+      throw new ServiceError('minutes must be a positive number', 'VALIDATION_ERROR', 400);
+    }
+
+    const cutoff = Date.now() - minutes * 60_000;
+    const expired = Array.from(this.processRegistry.entries())
+      .filter(([, info]) => Date.parse(info.started_at) < cutoff)
+      .sort(([, a], [, b]) => Date.parse(a.started_at) - Date.parse(b.started_at));
+
+    const terminated: string[] = [];
+    for (const [sessionId] of expired) {
+      if (await this.cancelClaudeExecution(sessionId)) {
+        terminated.push(sessionId);
+      }
+    }
+    return terminated;
+  }
+
+  /**
+   * Stream a file into a running session's stdin line by line, waiting for the pipe to drain
+   * between lines. The file must lie under input_file_roots (symlinks resolved) and fit in
+   * max_input_file_bytes; one file at a time is fed to a session.
+   */
+  async streamFileToSession(sessionId: string, file: unknown): Promise<SessionInputResult> {
+    const child = this.processes.get(sessionId);
+    const info = this.processRegistry.get(sessionId);
+    if (!child || !info) {
+      throw new ServiceError('Session not found or not running', 'SESSION_NOT_FOUND', 404);
+    }
+    if (typeof file !== 'string' || !file) {
+      throw new ServiceError('path must be a file path', 'VALIDATION_ERROR', 400);
+    }
+    const stdin = child.stdin;
+    if (!stdin || !stdin.writable) {
+      throw new ServiceError('Session stdin is closed', 'STDIN_CLOSED', 409);
+    }
+    if (this.feedingInput.has(sessionId)) {
+      throw new ServiceError('Another file is still being fed to this session', 'INPUT_IN_PROGRESS', 409);
+    }
+
+    const requested = resolve(info.project_path, file);
+    let path: string;
+    try {
+      path = await fs.realpath(requested);
+    } catch {
+      throw new ServiceError('Input file not found', 'FILE_NOT_FOUND', 404, { path: requested });
+    }
+
+    const configuredRoots = this.options.input_file_roots ?? [info.project_path];
+    const roots = await Promise.all(
+      configuredRoots.map((root) => fs.realpath(root).catch(() => resolve(root)))
+    );
+    const allowed = roots.some((root) => {
+      const rel = relative(root, path);
+      return rel !== '' && !rel.startsWith('..') && !isAbsolute(rel);
+    });
+    if (!allowed) {
+      throw new ServiceError('Input file is outside the allowed directories', 'PATH_NOT_ALLOWED', 403, {
+        path,
+        allowed_roots: roots,
+      });
+    }
+
+    const stats = await fs.stat(path);
+    if (!stats.isFile()) {
+      throw new ServiceError('Input path is not a file', 'VALIDATION_ERROR', 400, { path });
+    }
+    const maxBytes = this.options.max_input_file_bytes ?? DEFAULT_MAX_INPUT_FILE_BYTES;
+    if (stats.size > maxBytes) {
+      throw new ServiceError(`Input file is larger than ${maxBytes} bytes`, 'FILE_TOO_LARGE', 413, {
+        size: stats.size,
+        max_input_file_bytes: maxBytes,
+      });
+    }
+
+    this.feedingInput.add(sessionId);
+    const reader = createInterface({ input: createReadStream(path), crlfDelay: Infinity });
+    let lines = 0;
+    let bytes = 0;
+    try {
+      for await (const line of reader) {
+        if (!stdin.writable) {
+          throw new ServiceError('Session stdin closed while the file was being fed', 'STDIN_CLOSED', 409, {
+            lines_written: lines,
+          });
+        }
+
+        const chunk = `${line}\n`;
+        lines++;
+        bytes += Buffer.byteLength(chunk);
+        if (!stdin.write(chunk)) {
+          await new Promise<void>((resume) => {
+            const done = () => {
+              stdin.off('drain', done);
+              stdin.off('close', done);
+              resume();
+            };
+            stdin.on('drain', done);
+            stdin.on('close', done);
+          });
+        }
+      }
+    } finally {
+      reader.close();
+      this.feedingInput.delete(sessionId);
+    }
+
+    return { session_id: sessionId, path, lines, bytes };
+  }
+
+  /**
+   * Send SIGTERM to a session, then SIGKILL if it has not exited after 5 seconds
+   */
+  private killSession(child: ChildProcess): void {
+    this.signalSession(child, 'SIGTERM');
+
+    // Force kill after 5 seconds if not terminated
+    setTimeout(() => {
+      if (child.exitCode === null && child.signalCode === null) {
+        this.signalSession(child, 'SIGKILL');
+      }
+    }, 5000);
+  }
+
+  /**
+   * Get list of running Claude sessions
+   */
+  getRunningClaudeSessions(): ProcessInfo[] {
+    return Array.from(this.processRegistry.values());
+  }
+
+  /**
+   * Running and finished sessions matching a filter, oldest first. Entries are produced
+   * lazily so a long history can be streamed without building the whole list.
+   */
+  *listSessions(filter: SessionListFilter = {}): Generator<SessionListEntry> {
+    let skipped = 0;
+    let listed = 0;
+
+    for (const record of this.sessions.values()) {
+      if (filter.limit !== undefined && listed >= filter.limit) {
+        return;
+      }
+      if (filter.status && record.status !== filter.status) {
+        continue;
+      }
+      if (filter.project_path && record.info.project_path !== filter.project_path) {
+        continue;
+      }
+      if (skipped < (filter.offset ?? 0)) {
+        skipped++;
+        continue;
+      }
+
+      listed++;
+      yield {
+        ...record.info,
+        status: record.status,
+        ...(record.exit_code !== undefined ? { exit_code: record.exit_code } : {}),
+        ...(record.ended_at ? { ended_at: record.ended_at } : {}),
+        ...(record.terminated_by ? { terminated_by: record.terminated_by } : {}),
+      };
+    }
+  }
+
+  /**
+   * Get the output rate of a running session
+   */
+  getSessionThroughput(sessionId: string): SessionThroughput | undefined {
+    return this.throughput.get(sessionId)?.getThroughput();
+  }
+
+  /**
+   * Get session info by ID
+   */
+  getSessionInfo(sessionId: string): ProcessInfo | undefined {
+    return this.processRegistry.get(sessionId);
+  }
+
+  /**
+   * Get the record of a running or finished session, its output already cut to visibleOutput
+   */
+  getSessionRecord(sessionId: string): SessionRecord | undefined {
+    const record = this.sessions.get(sessionId);
+    if (record) {
+      this.visibleOutput(record);
+    }
+    return record;
+  }
+
+  /**
+   * Get how a session was launched and how it ended, with the first and last `lines` output lines
+   */
+  getSessionDebug(sessionId: string, lines = 5): SessionDebugInfo | undefined {
+    const record = this.sessions.get(sessionId);
+    if (!record?.launch) {
+      return undefined;
+    }
+
+    const output = this.visibleOutput(record);
+    return {
+      session_id: sessionId,
+      status: record.status,
+      pid: record.info.pid,
+      started_at: record.info.started_at,
+      ended_at: record.ended_at,
+      ...record.launch,
+      exit_code: record.exit_code,
+      exit_signal: record.exit_signal,
+      output_head: output.slice(0, lines),
+      output_tail: output.slice(-lines),
+    };
+  }
+
+  /**
+   * Get the command that launched a session, quoted so it can be rerun from a shell
+   */
+  getSessionCommand(sessionId: string): SessionCommand | undefined {
+    const launch = this.sessions.get(sessionId)?.launch;
+    if (!launch) {
+      return undefined;
+    }
+
+    const argv = [launch.binary, ...launch.args];
+    const env = launch.env_overrides ?? {};
+    return {
+      session_id: sessionId,
+      cwd: launch.cwd,
+      argv,
+      env,
+      command: shellCommand(argv, launch.cwd, env),
+    };
+  }
+
+  /**
+   * Get the tools a running or finished session called, with call counts
+   */
+  getSessionTools(sessionId: string): ToolUsage[] | undefined {
+    const record = this.sessions.get(sessionId);
+    return record ? record.tools_used ?? [] : undefined;
+  }
+
+  /**
+   * Get the buffered output lines of a running or finished session
+   */
+  getSessionOutput(sessionId: string): OutputLine[] | undefined {
+    const record = this.sessions.get(sessionId);
+    return record ? this.visibleOutput(record) : undefined;
+  }
+
+  /**
+   * Get a session's output. With disk_backed_output, the lines that have scrolled out of the
+   * in-memory buffer are read back from the session's output log, so the whole output is
+   * returned; otherwise, and for sessions with output_retention_ms, this is the buffered lines.
+   */
+  async readSessionOutput(sessionId: string): Promise<OutputLine[] | undefined> {
+    const record = this.sessions.get(sessionId);
+    if (!record) {
+      return undefined;
+    }
+
+    const buffered = this.visibleOutput(record);
+    const onDiskOnly = (record.output_total ?? buffered.length) - buffered.length;
+    if (
+      !this.options.disk_backed_output ||
+      !this.store ||
+      onDiskOnly <= 0 ||
+      record.output_discarded ||
+      record.info.output_retention_ms
+    ) {
+      return buffered;
+    }
+    // Copy the window first: lines read while the log is being read must not be counted twice
+    const recent = [...buffered];
+    const older = await this.store.readOutput(sessionId, onDiskOnly, record.info.output_subdir);
+    return [...older, ...recent];
+  }
+
+  /**
+   * The output several sessions produced after a cursor each, for clients following many
+   * sessions at once. `since` counts the lines already seen (0 or omitted for all); unknown
+   * sessions are reported in their entry instead of failing the batch.
+   */
+  getOutputBatch(requests: unknown): OutputBatchEntry[] {
+    const valid = Array.isArray(requests) && requests.every((item) =>
+      typeof item?.session_id === 'string' &&
+      (item.since === undefined || (Number.isInteger(item.since) && item.since >= 0))
+    );
+    if (!valid) {
+      throw new ServiceError(
+        'sessions must be an array of { session_id, since } with since a non-negative integer',
+        'VALIDATION_ERROR',
+        400
+      );
+    }
+    if (requests.length > MAX_OUTPUT_BATCH_SIZE) {
+      throw new ServiceError(`At most ${MAX_OUTPUT_BATCH_SIZE} sessions may be requested at once`, 'VALIDATION_ERROR', 400);
+    }
+
+    return requests.map(({ session_id: idOrName, since = 0 }: { session_id: string; since?: number }) => {
+      const record = this.sessions.get(this.resolveSessionId(idOrName));
+      if (!record) {
+        return { session_id: idOrName, error: { code: 'SESSION_NOT_FOUND', message: 'Session not found' } };
+      }
+
+      const buffered = this.visibleOutput(record);
+      const total = record.output_total ?? buffered.length;
+      const firstBuffered = total - buffered.length;
+      const from = Math.min(Math.max(since, firstBuffered), total);
+      return {
+        session_id: idOrName,
+        status: record.status,
+        lines: buffered.slice(from - firstBuffered),
+        next: total,
+        skipped: Math.max(0, firstBuffered - since),
+      };
+    });
+  }
+
+  /**
+   * Render a session's output as a plain log file, one chunk per line, preserving the original
+   * bytes of lines that were not valid UTF-8. The persisted output log is read when there is one,
+   * since the buffer only keeps the most recent lines. A session with output_retention_ms only
+   * shows the lines within its window.
+   *
+   * @returns undefined for an unknown session.
+   */
+  async getSessionLog(sessionId: string): Promise<AsyncIterable<Buffer> | undefined> {
+    const record = this.sessions.get(sessionId);
+    if (!record) {
+      return undefined;
+    }
+
+    const lines = record.info.output_retention_ms
+      ? this.visibleOutput(record)
+      : (await this.store?.streamOutput(sessionId, record.info.output_subdir)) ?? record.output;
+    return (async function* () {
+      for await (const line of lines) {
+        const bytes = line.raw_base64 ? Buffer.from(line.raw_base64, 'base64') : Buffer.from(line.content);
+        yield bytes[bytes.length - 1] === 0x0a ? bytes : Buffer.concat([bytes, Buffer.from('\n')]);
+      }
+    })();
+  }
+
+  /**
+   * Write a session's rendered log, as getSessionLog yields it, to a temporary file, so byte
+   * ranges of it can be served from disk instead of holding the whole log in memory
+   *
+   * @returns undefined for an unknown session.
+   */
+  async writeSessionLog(sessionId: string): Promise<SessionLogFile | undefined> {
+    const chunks = await this.getSessionLog(sessionId);
+    if (!chunks) {
+      return undefined;
+    }
+
+    const dir = await fs.mkdtemp(join(tmpdir(), 'claudia-log-'));
+    const remove = () => fs.rm(dir, { recursive: true, force: true });
+    const path = join(dir, `${sessionId}.log`);
+    try {
+      await pipeline(Readable.from(chunks), createWriteStream(path));
+    } catch (error) {
+      await remove();
+      throw error;
+    }
+    return { path, remove };
+  }
+
+  /**
+   * Get the final result of a finished session.
+   * Falls back to the last stdout lines when no stream-json `result` message was emitted.
+   */
+  getSessionResult(sessionId: string): SessionResult | undefined {
+    const record = this.sessions.get(sessionId);
+    if (!record) {
+      return undefined;
+    }
+    if (record.result) {
+      return record.result;
+    }
+
+    const lastLines = this.visibleOutput(record)
+      .filter((line) => line.stream === 'stdout')
+      .slice(-RESULT_FALLBACK_LINES)
+      .map((line) => line.content);
+    return { text: lastLines.join('\n'), parsed: false };
+  }
+
+  /**
+   * Find session processes an earlier server run recorded in the data directory that are still
+   * running but not tracked. Records of processes that are gone are removed. Without a data
+   * directory nothing is recorded, so nothing is found.
+   */
+  async findOrphans(
+    listProcesses: () => Promise<SystemProcess[]> = listSystemProcesses
+  ): Promise<OrphanedProcess[]> {
+    if (!this.store) {
+      return [];
+    }
+
+    const processes = await listProcesses();
+    const recorded = (await this.store.listProcesses()).filter(
+      (entry) => !this.processRegistry.has(entry.session_id)
+    );
+    const gone = recorded.filter((entry) => !processes.some((proc) => isRecordedProcess(proc, entry)));
+    await Promise.all(gone.map((entry) => this.store!.forgetProcess(entry.session_id)));
+    const trackedPids = new Set(Array.from(this.processRegistry.values()).map((info) => info.pid));
+    return findOrphanedProcesses(processes, recorded, trackedPids);
+  }
+
+  /**
+   * Terminate orphaned Claude processes, returning the ones signalled
+   */
+  async reapOrphans(
+    listProcesses: () => Promise<SystemProcess[]> = listSystemProcesses
+  ): Promise<OrphanedProcess[]> {
+    const reaped: OrphanedProcess[] = [];
+    for (const orphan of await this.findOrphans(listProcesses)) {
+      try {
+        process.kill(orphan.pid, 'SIGTERM');
+        reaped.push(orphan);
+      } catch (error) {
+        console.warn(`Failed to reap orphaned Claude process ${orphan.pid}:`, error);
+      }
+    }
+    return reaped;
+  }
+
+  /**
+   * Take back the detached sessions an earlier run left running when kill_on_drop is off. A
+   * recorded process still running as the same process, by PID and start time, is followed
+   * again, its output replayed from the start of its output files. One that exited meanwhile
+   * is finished from the output it left. Sessions without a readable record are left to
+   * findOrphans.
+   *
+   * @returns The IDs of the sessions taken back
+   */
+  async adoptDetachedSessions(
+    listProcesses: () => Promise<SystemProcess[]> = listSystemProcesses
+  ): Promise<string[]> {
+    if (!this.store || this.killsOnDrop()) {
+      return [];
+    }
+
+    const recorded = (await this.store.listProcesses()).filter(
+      (entry) => entry.detached && !this.processRegistry.has(entry.session_id)
+    );
+    if (recorded.length === 0) {
+      return [];
+    }
+    const processes = await listProcesses();
+
+    const adopted: string[] = [];
+    for (const entry of recorded) {
+      const saved = await this.store.load(entry.session_id, entry.output_subdir);
+      if (!saved) {
+        continue;
+      }
+      const running = processes.some((proc) => isRecordedProcess(proc, entry));
+      await this.adoptSession(entry.session_id, saved, running);
+      adopted.push(entry.session_id);
+    }
+    return adopted;
+  }
+
+  /**
+   * Register a detached session from its saved record and replay its output files into it
+   */
+  private async adoptSession(sessionId: string, saved: SessionRecord, running: boolean): Promise<void> {
+    const info: ProcessInfo = { ...saved.info, detached: true };
+    // The output log is rebuilt from the output files, which hold everything the process wrote
+    await this.store!.removeOutput(sessionId, info.output_subdir);
+    const at = new Date().toISOString();
+    this.sessions.set(sessionId, {
+      info,
+      status: 'running',
+      output: [],
+      output_total: 0,
+      launch: saved.launch,
+      status_history: [...(saved.status_history ?? []), { status: 'running', at, reason: 'adopted after a server restart' }],
+    });
+
+    const follower = new DetachedProcess(info.pid, this.store!.processOutputPaths(sessionId));
+    this.processes.set(sessionId, follower as unknown as ChildProcess);
+    this.processRegistry.set(sessionId, info);
+    if (info.name) {
+      this.sessionNames.set(info.name, sessionId);
+    }
+    this.runningCount++;
+    this.runningPerProject.set(info.project_path, (this.runningPerProject.get(info.project_path) ?? 0) + 1);
+    this.startPreviewUpdates(sessionId);
+    this.watchdogs.set(sessionId, { patterns: [] });
+    this.throughput.set(sessionId, new ThroughputMeter());
+    this.attachProcessHandlers(sessionId, follower as unknown as ChildProcess, info);
+    this.persistSession(sessionId);
+
+    if (running) {
+      await follower.follow();
+    } else {
+      await follower.replayExited();
+    }
   }
 
   /**
@@ -340,13 +3334,172 @@ export class ClaudeService extends EventEmitter {
   }
 
   /**
-   * Cleanup all processes
+   * Cleanup all processes (detached processes and their settings overlays are left in place when kill_on_drop is off)
    */
   cleanup(): void {
-    for (const [sessionId, process] of this.processes) {
-      process.kill('SIGTERM');
+    if (this.killsOnDrop()) {
+      for (const child of this.processes.values()) {
+        this.signalSession(child, 'SIGTERM');
+      }
+      for (const sessionId of Array.from(this.settingsDirs.keys())) {
+        void this.removeSessionSettings(sessionId);
+      }
+    } else if (this.processRegistry.size > 0) {
+      // With kill_on_drop off every session process is followed through its output files
+      for (const child of this.processes.values()) {
+        (child as unknown as DetachedProcess).release();
+      }
+      const pids = Array.from(this.processRegistry.values()).map((info) => info.pid);
+      console.log(`Leaving ${pids.length} detached Claude process(es) running: ${pids.join(', ')}`);
     }
     this.processes.clear();
     this.processRegistry.clear();
+    this.cancelRequested.clear();
+    this.overloaded.clear();
+    for (const timer of this.previewTimers.values()) {
+      clearTimeout(timer);
+    }
+    this.previewTimers.clear();
+    this.previewBuffers.clear();
+    this.previewActive.clear();
+    for (const watchdog of this.watchdogs.values()) {
+      clearTimeout(watchdog.timer);
+    }
+    this.watchdogs.clear();
+    this.throughput.clear();
+    this.resultTargets.clear();
+    this.gitDiffRequested.clear();
+    this.discardOutput.clear();
+    this.sessions.clear();
+    this.sessionNames.clear();
+    this.feedingInput.clear();
+    this.runningCount = 0;
+    this.runningPerProject.clear();
+    this.runningPerKey.clear();
+    this.sessionKeys.clear();
+    void this.store?.flushAllOutput();
+  }
+}
+
+/**
+ * Compile the prompt denylist, failing startup on a pattern that is not a valid regular expression
+ */
+function compilePromptDenylist(patterns: string[]): RegExp[] {
+  return patterns.map((pattern) => {
+    try {
+      return new RegExp(pattern, 'i');
+    } catch (error) {
+      const message = error instanceof Error ? error.message : String(error);
+      throw new Error(`Invalid prompt_denylist pattern ${pattern}: ${message}`);
+    }
+  });
+}
+
+/**
+ * Whether a child process has exited, whether or not its close event has been emitted yet
+ */
+function hasExited(child: ChildProcess): boolean {
+  return child.exitCode !== null || child.signalCode !== null;
+}
+
+/**
+ * Where findClaudeBinary looks for Claude when no binary is configured, in order
+ */
+function claudeSearchPaths(): string[] {
+  return [
+    'claude', // In PATH
+    '/usr/local/bin/claude',
+    '/opt/homebrew/bin/claude',
+    join(homedir(), '.local/bin/claude'),
+    join(homedir(), '.nvm/versions/node/*/bin/claude'),
+  ];
+}
+
+/**
+ * Turn the error a failed spawn reported into an actionable one: a missing binary or one that
+ * may not be executed is a problem with the server's setup (424), anything else is passed on
+ */
+function spawnFailure(binary: string, error?: NodeJS.ErrnoException): Error {
+  switch (error?.code) {
+    case 'ENOENT':
+      return new ServiceError(
+        `Failed to start Claude process: ${binary} was not found; it may have been moved or uninstalled`,
+        'CLAUDE_BINARY_NOT_FOUND',
+        424,
+        { binary }
+      );
+    case 'EACCES':
+      return new ServiceError(
+        `Failed to start Claude process: permission denied running ${binary}; make it executable (chmod +x)`,
+        'CLAUDE_BINARY_NOT_EXECUTABLE',
+        424,
+        { binary }
+      );
+    case undefined:
+      return new ServiceError('Failed to start Claude process', 'SPAWN_FAILED', 500, { binary });
+    default:
+      return new ServiceError(`Failed to start Claude process: ${error.message}`, 'SPAWN_FAILED', 500, {
+        binary,
+        code: error.code,
+      });
+  }
+}
+
+/**
+ * Compile the output filters, failing startup on a pattern that is not a valid regular expression
+ */
+function compileOutputFilters(patterns: string[]): RegExp[] {
+  return patterns.map((pattern) => {
+    try {
+      return new RegExp(pattern);
+    } catch (error) {
+      const message = error instanceof Error ? error.message : String(error);
+      throw new Error(`Invalid output_filters pattern ${pattern}: ${message}`);
+    }
+  });
+}
+
+/**
+ * Check configured label argument rules, failing startup on one that cannot be used
+ */
+function checkLabelArgRules(rules: LabelArgRule[]): LabelArgRule[] {
+  for (const rule of rules) {
+    if (typeof rule?.labels !== 'object' || rule.labels === null) {
+      throw new Error('Invalid label_arg_rules entry: labels must be an object');
+    }
+    if (!Array.isArray(rule.args) || rule.args.some((arg) => typeof arg !== 'string')) {
+      throw new Error(`Invalid label_arg_rules entry for ${JSON.stringify(rule.labels)}: args must be an array of strings`);
+    }
+    const reserved = rule.args.find((arg) => RESERVED_FLAGS.includes(arg.split('=')[0]));
+    if (reserved) {
+      throw new Error(`Invalid label_arg_rules entry for ${JSON.stringify(rule.labels)}: ${reserved} is set by the server`);
+    }
+  }
+  return rules;
+}
+
+/**
+ * Check the configured wrapper command, failing startup on one that cannot be spawned
+ */
+function checkWrapperCommand(command: string[]): void {
+  if (!Array.isArray(command) || command.some((token) => typeof token !== 'string' || !token)) {
+    throw new Error('Invalid wrapper_command: expected an array of non-empty strings');
   }
-}
\ No newline at end of file
+}
+
+/**
+ * Compile configured label rules, failing startup on a rule that cannot be used
+ */
+function compileLabelRules(rules: LabelRule[]): { pattern: RegExp; labels: Record<string, string> }[] {
+  return rules.map((rule) => {
+    if (typeof rule?.labels !== 'object' || rule.labels === null) {
+      throw new Error(`Invalid label rule for ${rule?.pattern}: labels must be an object`);
+    }
+    try {
+      return { pattern: new RegExp(rule.pattern, rule.flags), labels: rule.labels };
+    } catch (error) {
+      const message = error instanceof Error ? error.message : String(error);
+      throw new Error(`Invalid label rule pattern ${rule.pattern}: ${message}`);
+    }
+  });
+}
@@ -0,0 +1,177 @@
+import type { ChildProcess } from 'child_process';
+import { EventEmitter } from 'events';
+import { promises as fs } from 'fs';
+import type { FileHandle } from 'fs/promises';
+
+/**
+ * How often a detached session's output files are read for new output, and an adopted
+ * process is checked for having exited
+ */
+const POLL_INTERVAL_MS = 200;
+
+/**
+ * Bytes read from an output file at a time
+ */
+const READ_CHUNK_BYTES = 64 * 1024;
+
+/**
+ * Where a detached session's process writes its stdout and stderr
+ */
+export interface DetachedOutputPaths {
+  stdout: string;
+  stderr: string;
+}
+
+/**
+ * A session process spawned to survive server shutdown. Its output goes to files instead of
+ * pipes, so it never blocks on, or dies writing to, a pipe nobody reads once the server is gone.
+ * This follows the files and stands in for the ChildProcess: `stdout` and `stderr` emit `data`
+ * and `end`, and `exit` and `close` are emitted once the process is gone and its output read.
+ *
+ * A process spawned by this server run reports its exit code and signal. One adopted from an
+ * earlier run is not a child of this one, so it is polled for and exits with both null.
+ */
+export class DetachedProcess extends EventEmitter {
+  readonly stdout = new EventEmitter();
+  readonly stderr = new EventEmitter();
+  readonly stdin = null;
+  exitCode: number | null = null;
+  signalCode: NodeJS.Signals | null = null;
+  private offsets = { stdout: 0, stderr: 0 };
+  private reading: Promise<void> = Promise.resolve();
+  private polling = false;
+  private timer?: NodeJS.Timeout;
+  private exited = false;
+  private released = false;
+
+  /**
+   * @param pid The process ID
+   * @param paths The files the process writes its output to
+   * @param child The process, when this server run spawned it
+   */
+  constructor(readonly pid: number, private paths: DetachedOutputPaths, private child?: ChildProcess) {
+    super();
+    child?.once('exit', (code, signal) => void this.finish(code, signal));
+    child?.on('error', (error) => this.emit('error', error));
+  }
+
+  /**
+   * Emit the output written so far, then keep following it until the process exits
+   */
+  async follow(): Promise<void> {
+    await this.read();
+    if (this.exited || this.released) {
+      return;
+    }
+
+    this.timer = setInterval(() => {
+      if (this.polling) {
+        return;
+      }
+      this.polling = true;
+      void this.read().then(() => {
+        this.polling = false;
+        if (!this.child && !isAlive(this.pid)) {
+          void this.finish(null, null);
+        }
+      });
+    }, POLL_INTERVAL_MS);
+    this.timer.unref?.();
+  }
+
+  /**
+   * Emit the output of a process that is already gone, and report that it exited
+   */
+  replayExited(): Promise<void> {
+    return this.finish(null, null);
+  }
+
+  /**
+   * Stop following the process and leave it running. No further events are emitted.
+   */
+  release(): void {
+    this.released = true;
+    clearInterval(this.timer);
+  }
+
+  /**
+   * Signal the process
+   */
+  kill(signal: NodeJS.Signals | number = 'SIGTERM'): boolean {
+    try {
+      process.kill(this.pid, signal);
+      return true;
+    } catch {
+      return false;
+    }
+  }
+
+  private async finish(code: number | null, signal: NodeJS.Signals | null): Promise<void> {
+    if (this.exited) {
+      return;
+    }
+    this.exited = true;
+    clearInterval(this.timer);
+
+    await this.read();
+    if (this.released) {
+      return;
+    }
+    this.stdout.emit('end');
+    this.stderr.emit('end');
+    this.exitCode = code;
+    this.signalCode = signal;
+    this.emit('exit', code, signal);
+    this.emit('close', code, signal);
+  }
+
+  /**
+   * Emit what was appended to the output files since the last read. Reads are chained so
+   * output is emitted in order and exactly once.
+   */
+  private read(): Promise<void> {
+    this.reading = this.reading.then(async () => {
+      await this.readNew('stdout');
+      await this.readNew('stderr');
+    });
+    return this.reading;
+  }
+
+  private async readNew(stream: 'stdout' | 'stderr'): Promise<void> {
+    let handle: FileHandle;
+    try {
+      handle = await fs.open(this.paths[stream], 'r');
+    } catch {
+      // Not created yet, or removed along with the process record
+      return;
+    }
+
+    try {
+      for (;;) {
+        const buffer = Buffer.alloc(READ_CHUNK_BYTES);
+        const { bytesRead } = await handle.read(buffer, 0, READ_CHUNK_BYTES, this.offsets[stream]);
+        if (bytesRead === 0 || this.released) {
+          return;
+        }
+        this.offsets[stream] += bytesRead;
+        this[stream].emit('data', buffer.subarray(0, bytesRead));
+      }
+    } catch {
+      // Retried on the next read from the same offset
+    } finally {
+      await handle.close().catch(() => undefined);
+    }
+  }
+}
+
+/**
+ * Whether a process with the PID exists; one owned by another user still counts
+ */
+function isAlive(pid: number): boolean {
+  try {
+    process.kill(pid, 0);
+    return true;
+  } catch (error) {
+    return (error as NodeJS.ErrnoException).code === 'EPERM';
+  }
+}
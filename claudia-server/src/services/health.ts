@@ -0,0 +1,77 @@
+import type { ClaudeService } from './claude.js';
+import type { WebhookService } from './webhooks.js';
+import type { HealthCheck, HealthCheckStatus } from '../types/index.js';
+
+/**
+ * Check statuses from best to worst
+ */
+const SEVERITY: HealthCheckStatus[] = ['ok', 'degraded', 'fail'];
+
+function check(status: HealthCheckStatus, message: string, details: Record<string, unknown> = {}): HealthCheck {
+  return { status, ok: status === 'ok', message, ...details };
+}
+
+/**
+ * The worst status among a set of checks
+ */
+export function worstStatus(checks: HealthCheck[]): HealthCheckStatus {
+  return checks.reduce<HealthCheckStatus>(
+    (worst, { status }) => (SEVERITY.indexOf(status) > SEVERITY.indexOf(worst) ? status : worst),
+    'ok'
+  );
+}
+
+/**
+ * Run every subsystem check of the deep health report, concurrently:
+ * - `claude_binary` — the Claude binary answers `--version` (`fail` otherwise)
+ * - `data_dir` — the data directory accepts writes, when persistence is on
+ * - `persistence` — no session write has failed so far
+ * - `concurrency` — running sessions are below max_concurrent_sessions
+ * - `webhooks` — every configured webhook URL answers, when any are configured
+ */
+export async function runHealthChecks(
+  claudeService: ClaudeService,
+  webhookService?: WebhookService
+): Promise<Record<string, HealthCheck>> {
+  const [version, dataDir, webhooks] = await Promise.all([
+    claudeService.checkClaudeVersion(),
+    claudeService.checkDataDir(),
+    webhookService?.checkReachable() ?? Promise.resolve([]),
+  ]);
+
+  const checks: Record<string, HealthCheck> = {};
+
+  checks.claude_binary = version.is_installed
+    ? check('ok', `Claude ${version.version ?? 'of unknown version'} is runnable`, { version: version.version })
+    : check('fail', `Claude binary is not runnable: ${version.output}`);
+
+  if (!dataDir) {
+    checks.data_dir = check('ok', 'Persistence is disabled', { skipped: 'persistence disabled' });
+  } else if (dataDir.ok) {
+    checks.data_dir = check('ok', 'Data directory is writable', { path: dataDir.path });
+  } else {
+    checks.data_dir = check('degraded', `data directory not writable: ${dataDir.error}`, {
+      path: dataDir.path,
+      error: dataDir.error,
+    });
+  }
+
+  const persistence = claudeService.getPersistenceStatus();
+  checks.persistence = persistence.degraded
+    ? check('degraded', `persistence writes failing: ${persistence.last_error}`)
+    : check('ok', persistence.enabled ? 'Session writes are succeeding' : 'Persistence is disabled');
+
+  const { running, max_running: maxRunning } = claudeService.getProcessStats();
+  checks.concurrency = maxRunning > 0 && running >= maxRunning
+    ? check('degraded', `At capacity: ${running} of ${maxRunning} sessions running`, { running, max: maxRunning })
+    : check('ok', `${running} of ${maxRunning || 'unlimited'} sessions running`, { running, max: maxRunning });
+
+  if (webhooks.length > 0) {
+    const unreachable = webhooks.filter((webhook) => !webhook.reachable);
+    checks.webhooks = unreachable.length > 0
+      ? check('degraded', `Unreachable webhooks: ${unreachable.map((webhook) => webhook.url).join(', ')}`, { endpoints: webhooks })
+      : check('ok', `All ${webhooks.length} webhooks are reachable`, { endpoints: webhooks });
+  }
+
+  return checks;
+}
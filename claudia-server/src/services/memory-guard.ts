@@ -0,0 +1,75 @@
+import type { ClaudeService } from './claude.js';
+import type { ShedOutput } from '../types/index.js';
+
+/**
+ * How often the server's RSS is compared against the soft limit
+ */
+export const MEMORY_CHECK_INTERVAL_MS = 10000;
+
+/**
+ * Keeps the server under a soft memory limit by dropping the buffered output of the sessions
+ * that finished longest ago whenever its resident set size exceeds the limit.
+ *
+ * RSS does not shrink as soon as buffers are released, so each check sheds about as much output
+ * as the RSS is over the limit and leaves the rest to the next check.
+ */
+export class MemoryGuard {
+  private timer?: NodeJS.Timeout;
+  private checking = false;
+
+  constructor(
+    private claudeService: ClaudeService,
+    private softLimitMb: number,
+    private readRss: () => number = () => process.memoryUsage().rss
+  ) {}
+
+  start(intervalMs: number = MEMORY_CHECK_INTERVAL_MS): void {
+    this.stop();
+    this.timer = setInterval(() => void this.check(), intervalMs);
+    this.timer.unref();
+  }
+
+  stop(): void {
+    if (this.timer) {
+      clearInterval(this.timer);
+      this.timer = undefined;
+    }
+  }
+
+  /**
+   * Compare the RSS against the soft limit once, shedding output when it is exceeded
+   *
+   * @returns The sessions whose output was dropped by this check.
+   */
+  async check(): Promise<ShedOutput[]> {
+    if (this.checking) {
+      return [];
+    }
+    this.checking = true;
+    try {
+      const rss = this.readRss();
+      const limit = this.softLimitMb * 1024 * 1024;
+      if (rss <= limit) {
+        return [];
+      }
+
+      const shed = await this.claudeService.shedFinishedOutput(rss - limit);
+      if (shed.length > 0) {
+        const sessions = shed
+          .map(
+            (entry) =>
+              `${entry.session_id} (${entry.lines} lines, ${Math.ceil(entry.bytes / 1024)} KiB` +
+              `${entry.lost ? ', not persisted' : ''})`
+          )
+          .join(', ');
+        console.warn(
+          `🧹 RSS ${Math.round(rss / 1024 / 1024)} MiB is over the ${this.softLimitMb} MiB soft limit; ` +
+            `dropped buffered output of ${sessions}`
+        );
+      }
+      return shed;
+    } finally {
+      this.checking = false;
+    }
+  }
+}
@@ -0,0 +1,137 @@
+import { spawn } from 'child_process';
+import type { RecordedProcess } from './session-store.js';
+
+/**
+ * A process as reported by the operating system
+ */
+export interface SystemProcess {
+  pid: number;
+  ppid: number;
+  /** When the process started, in milliseconds since the epoch to the second, when ps reports it */
+  started_at?: number;
+  command: string;
+}
+
+/**
+ * A running process an earlier server run recorded for one of its sessions
+ */
+export interface OrphanedProcess extends SystemProcess {
+  session_id: string;
+}
+
+/**
+ * How far the OS start time of a process may be from the spawn time recorded for it: ps reports
+ * whole seconds, and a spawn can lag under load
+ */
+const START_TIME_TOLERANCE_MS = 5000;
+
+const MONTHS = ['Jan', 'Feb', 'Mar', 'Apr', 'May', 'Jun', 'Jul', 'Aug', 'Sep', 'Oct', 'Nov', 'Dec'];
+
+/**
+ * List running processes via `ps`. Returns an empty list on platforms without `ps`.
+ */
+export async function listSystemProcesses(): Promise<SystemProcess[]> {
+  if (process.platform === 'win32') {
+    return [];
+  }
+
+  const output = await new Promise<string>((resolve) => {
+    // The C locale keeps lstart in the English format parsePsOutput reads
+    const child = spawn('ps', ['-axo', 'pid=,ppid=,lstart=,args='], {
+      stdio: ['ignore', 'pipe', 'ignore'],
+      env: { ...process.env, LC_ALL: 'C' },
+    });
+    let stdout = '';
+    child.stdout?.on('data', (data) => {
+      stdout += data.toString();
+    });
+    child.on('close', () => resolve(stdout));
+    child.on('error', () => resolve(''));
+  });
+
+  return parsePsOutput(output);
+}
+
+/**
+ * Parse `ps -o pid=,ppid=,lstart=,args=` output into process entries. lstart is local time, as
+ * in `Thu Oct 16 08:26:06 2026`.
+ */
+export function parsePsOutput(output: string): SystemProcess[] {
+  const processes: SystemProcess[] = [];
+  for (const line of output.split('\n')) {
+    const match = line
+      .trim()
+      .match(/^(\d+)\s+(\d+)\s+\w{3}\s+(\w{3})\s+(\d{1,2})\s+(\d{1,2}):(\d{2}):(\d{2})\s+(\d{4})\s+(.*)$/);
+    if (match) {
+      const [, pid, ppid, month, day, hours, minutes, seconds, year, command] = match;
+      const monthIndex = MONTHS.indexOf(month);
+      processes.push({
+        pid: parseInt(pid, 10),
+        ppid: parseInt(ppid, 10),
+        ...(monthIndex >= 0
+          ? { started_at: new Date(+year, monthIndex, +day, +hours, +minutes, +seconds).getTime() }
+          : {}),
+        command,
+      });
+    }
+  }
+  return processes;
+}
+
+/**
+ * Whether a running process is the recorded one: the same PID, started when it was spawned, so
+ * a PID reused by an unrelated process does not count
+ */
+export function isRecordedProcess(proc: SystemProcess, recorded: RecordedProcess): boolean {
+  return (
+    proc.pid === recorded.pid &&
+    proc.started_at !== undefined &&
+    Math.abs(proc.started_at - Date.parse(recorded.started_at)) <= START_TIME_TOLERANCE_MS
+  );
+}
+
+/**
+ * The given PIDs and the PIDs of every process running below them
+ */
+export function descendantsOf(processes: SystemProcess[], roots: Iterable<number>): Set<number> {
+  const found = new Set(roots);
+  let grew = true;
+  while (grew) {
+    grew = false;
+    for (const proc of processes) {
+      if (!found.has(proc.pid) && found.has(proc.ppid)) {
+        found.add(proc.pid);
+        grew = true;
+      }
+    }
+  }
+  return found;
+}
+
+/**
+ * Find session processes an earlier server run recorded that are still running but no longer
+ * tracked. Only recorded processes qualify, so Claude sessions of the desktop app or another
+ * server on the host are never reported, and anything running below the server or a tracked
+ * session (such as Claude under a wrapper_command) is excluded.
+ *
+ * @param processes The system process list
+ * @param recorded Session processes recorded in the data directory
+ * @param trackedPids PIDs of sessions the server currently manages
+ * @param serverPid The server's own PID
+ */
+export function findOrphanedProcesses(
+  processes: SystemProcess[],
+  recorded: RecordedProcess[],
+  trackedPids: Set<number>,
+  serverPid: number = process.pid
+): OrphanedProcess[] {
+  const ours = descendantsOf(processes, [serverPid, ...trackedPids]);
+  const orphans: OrphanedProcess[] = [];
+  for (const proc of processes) {
+    const entry = recorded.find((candidate) => isRecordedProcess(proc, candidate));
+    if (entry && !ours.has(proc.pid)) {
+      orphans.push({ ...proc, session_id: entry.session_id });
+    }
+  }
+  return orphans;
+}
@@ -0,0 +1,411 @@
+import { promises as fs, createReadStream } from 'fs';
+import { join } from 'path';
+import { createInterface } from 'readline';
+import type { OutputLine, SessionRecord } from '../types/index.js';
+import type { DetachedOutputPaths } from './detached.js';
+
+/**
+ * How long buffered output lines wait before being appended to disk by default
+ */
+const DEFAULT_OUTPUT_FLUSH_INTERVAL_MS = 1000;
+
+/**
+ * Buffered lines of one session that trigger an immediate append by default
+ */
+const DEFAULT_OUTPUT_FLUSH_BATCH_LINES = 100;
+
+/**
+ * When buffered output is appended to disk
+ */
+export interface OutputFlushOptions {
+  /** Append buffered lines at least this often (default: 1000) */
+  interval_ms?: number;
+  /** Append a session's lines as soon as this many are buffered (default: 100) */
+  batch_lines?: number;
+}
+
+/**
+ * Health of the on-disk session store
+ */
+export interface PersistenceStatus {
+  enabled: boolean;
+  /** True once a write has failed; sessions keep running in memory */
+  degraded: boolean;
+  last_error?: string;
+}
+
+/**
+ * Result of probing the data directory for writability
+ */
+export interface DataDirCheck {
+  ok: boolean;
+  path: string;
+  error?: string;
+}
+
+/**
+ * A session process recorded in `<data_dir>/processes/` while it runs, so a later server run can
+ * tell the processes of its predecessors from anyone else's
+ */
+export interface RecordedProcess {
+  session_id: string;
+  pid: number;
+  /** When the process was spawned (ISO 8601), compared with the OS start time to rule out PID reuse */
+  started_at: string;
+  /** PID of the server that spawned it */
+  server_pid: number;
+  /** Present and true when the process was spawned to survive server shutdown */
+  detached?: boolean;
+  output_subdir?: string;
+}
+
+/**
+ * Writes session records to `<data_dir>/sessions/<session_id>.json`, and every output line to
+ * `<data_dir>/sessions/<session_id>.output.jsonl`. A session started with an `output_subdir`
+ * has both files nested under `<data_dir>/sessions/<output_subdir>/` instead.
+ *
+ * Output lines are buffered in memory and appended in batches, by size or on an interval, so
+ * disk IO never runs in the path that reads the process output. Appends for one session are
+ * chained so they land in order.
+ *
+ * Writes are best-effort: a full or read-only data directory is logged and marks
+ * the store degraded instead of failing the session that triggered the write.
+ */
+export class SessionStore {
+  private degraded = false;
+  private lastError?: string;
+  private pendingOutput: Map<string, OutputLine[]> = new Map();
+  private outputSubdirs: Map<string, string> = new Map();
+  private outputWrites: Map<string, Promise<void>> = new Map();
+  private recordWrites: Map<string, Promise<void>> = new Map();
+  private processWrites: Map<string, Promise<void>> = new Map();
+  private flushTimer?: NodeJS.Timeout;
+  private flushIntervalMs: number;
+  private flushBatchLines: number;
+
+  constructor(private dataDir: string, outputFlush: OutputFlushOptions = {}) {
+    this.flushIntervalMs = outputFlush.interval_ms ?? DEFAULT_OUTPUT_FLUSH_INTERVAL_MS;
+    this.flushBatchLines = outputFlush.batch_lines ?? DEFAULT_OUTPUT_FLUSH_BATCH_LINES;
+  }
+
+  /**
+   * Directory holding one JSON file per session
+   */
+  getSessionsDir(): string {
+    return join(this.dataDir, 'sessions');
+  }
+
+  /**
+   * Directory holding one file per running session process
+   */
+  private getProcessesDir(): string {
+    return join(this.dataDir, 'processes');
+  }
+
+  /**
+   * Files a detached session's process writes its raw stdout and stderr to, next to its process
+   * record
+   */
+  processOutputPaths(sessionId: string): DetachedOutputPaths {
+    const base = join(this.getProcessesDir(), sessionId);
+    return { stdout: `${base}.stdout`, stderr: `${base}.stderr` };
+  }
+
+  /**
+   * Directory holding a session's files: the sessions directory, or the requested subdirectory
+   * of it. The subdirectory must already be checked to be a safe relative path.
+   */
+  private dirFor(subdir?: string): string {
+    return subdir ? join(this.getSessionsDir(), subdir) : this.getSessionsDir();
+  }
+
+  /**
+   * Persist a session record, never throwing. Saves of one session are chained so a later
+   * record is never overwritten by an earlier one, and each is written to a temporary file
+   * renamed into place, so a crash mid-write leaves the previous record intact.
+   *
+   * @returns A promise that settles once this record is on disk.
+   */
+  save(sessionId: string, record: SessionRecord): Promise<void> {
+    // Serialised now, so the file holds the record as it was at this call
+    const text = JSON.stringify(record);
+    const dir = this.dirFor(record.info.output_subdir);
+    const previous = this.recordWrites.get(sessionId) ?? Promise.resolve();
+    const write = previous.then(() => this.writeRecord(sessionId, dir, text));
+    this.recordWrites.set(sessionId, write);
+    void write.then(() => {
+      if (this.recordWrites.get(sessionId) === write) {
+        this.recordWrites.delete(sessionId);
+      }
+    });
+    return write;
+  }
+
+  /**
+   * Read back a session record written by save, or undefined when there is none or it cannot
+   * be read
+   */
+  async load(sessionId: string, subdir?: string): Promise<SessionRecord | undefined> {
+    try {
+      return JSON.parse(await fs.readFile(join(this.dirFor(subdir), `${sessionId}.json`), 'utf8')) as SessionRecord;
+    } catch {
+      return undefined;
+    }
+  }
+
+  private async writeRecord(sessionId: string, dir: string, text: string): Promise<void> {
+    const path = join(dir, `${sessionId}.json`);
+    const temp = `${path}.${process.pid}.tmp`;
+    try {
+      await fs.mkdir(dir, { recursive: true });
+      await fs.writeFile(temp, text);
+      await fs.rename(temp, path);
+    } catch (error) {
+      await fs.unlink(temp).catch(() => undefined);
+      this.markDegraded(error);
+    }
+  }
+
+  /**
+   * Buffer an output line for the session's output log; it is appended with the next batch
+   */
+  appendOutput(sessionId: string, line: OutputLine, subdir?: string): void {
+    if (subdir) {
+      this.outputSubdirs.set(sessionId, subdir);
+    }
+    let pending = this.pendingOutput.get(sessionId);
+    if (!pending) {
+      pending = [];
+      this.pendingOutput.set(sessionId, pending);
+    }
+    pending.push(line);
+
+    if (pending.length >= this.flushBatchLines) {
+      void this.flushOutput(sessionId);
+    } else if (!this.flushTimer) {
+      this.flushTimer = setTimeout(() => {
+        this.flushTimer = undefined;
+        void this.flushAllOutput();
+      }, this.flushIntervalMs);
+      this.flushTimer.unref();
+    }
+  }
+
+  /**
+   * Append the session's buffered output lines now, never throwing
+   *
+   * @returns A promise that settles once every line buffered so far is on disk.
+   */
+  flushOutput(sessionId: string): Promise<void> {
+    const lines = this.pendingOutput.get(sessionId);
+    this.pendingOutput.delete(sessionId);
+    const previous = this.outputWrites.get(sessionId) ?? Promise.resolve();
+    if (!lines || lines.length === 0) {
+      return previous;
+    }
+
+    const write = previous.then(() => this.writeOutput(sessionId, lines));
+    this.outputWrites.set(sessionId, write);
+    void write.then(() => {
+      if (this.outputWrites.get(sessionId) === write) {
+        this.outputWrites.delete(sessionId);
+        if (!this.pendingOutput.has(sessionId)) {
+          this.outputSubdirs.delete(sessionId);
+        }
+      }
+    });
+    return write;
+  }
+
+  /**
+   * Append the buffered output lines of every session now
+   */
+  async flushAllOutput(): Promise<void> {
+    if (this.flushTimer) {
+      clearTimeout(this.flushTimer);
+      this.flushTimer = undefined;
+    }
+    const sessionIds = new Set([...this.pendingOutput.keys(), ...this.outputWrites.keys()]);
+    await Promise.all(Array.from(sessionIds, (sessionId) => this.flushOutput(sessionId)));
+  }
+
+  /**
+   * Read the first `count` lines of a session's output log, once every line buffered so far is
+   * on disk. A missing or unreadable log gives the lines that could be read.
+   */
+  async readOutput(sessionId: string, count: number, subdir?: string): Promise<OutputLine[]> {
+    await this.flushOutput(sessionId);
+
+    const lines: OutputLine[] = [];
+    try {
+      for await (const line of this.outputLinesOf(join(this.dirFor(subdir), `${sessionId}.output.jsonl`))) {
+        if (lines.length >= count) {
+          break;
+        }
+        lines.push(line);
+      }
+    } catch (error) {
+      console.warn(`⚠️  Cannot read the output log of session ${sessionId}: ${error instanceof Error ? error.message : error}`);
+    }
+    return lines;
+  }
+
+  /**
+   * Stream every line of a session's output log, once every line buffered so far is on disk
+   *
+   * @returns undefined when the session has no output log.
+   */
+  async streamOutput(sessionId: string, subdir?: string): Promise<AsyncIterable<OutputLine> | undefined> {
+    await this.flushOutput(sessionId);
+
+    const path = join(this.dirFor(subdir), `${sessionId}.output.jsonl`);
+    try {
+      await fs.access(path);
+    } catch {
+      return undefined;
+    }
+    return this.outputLinesOf(path);
+  }
+
+  private async *outputLinesOf(path: string): AsyncGenerator<OutputLine> {
+    const stream = createReadStream(path, { encoding: 'utf8' });
+    try {
+      for await (const text of createInterface({ input: stream, crlfDelay: Infinity })) {
+        if (text) {
+          yield JSON.parse(text) as OutputLine;
+        }
+      }
+    } finally {
+      stream.destroy();
+    }
+  }
+
+  /**
+   * Drop the session's buffered output lines and delete its output log, once any append in
+   * flight has landed. Never throws.
+   */
+  async removeOutput(sessionId: string, subdir?: string): Promise<void> {
+    this.pendingOutput.delete(sessionId);
+    await this.outputWrites.get(sessionId);
+    try {
+      await fs.rm(join(this.dirFor(subdir), `${sessionId}.output.jsonl`), { force: true });
+    } catch (error) {
+      this.markDegraded(error);
+    }
+  }
+
+  private async writeOutput(sessionId: string, lines: OutputLine[]): Promise<void> {
+    try {
+      const dir = this.dirFor(this.outputSubdirs.get(sessionId));
+      await fs.mkdir(dir, { recursive: true });
+      const text = lines.map((line) => `${JSON.stringify(line)}\n`).join('');
+      await fs.appendFile(join(dir, `${sessionId}.output.jsonl`), text);
+    } catch (error) {
+      this.markDegraded(error);
+    }
+  }
+
+  /**
+   * Record a session's process while it runs, never throwing. Records and removals of one
+   * session land in call order.
+   */
+  recordProcess(entry: RecordedProcess): Promise<void> {
+    return this.chainProcessWrite(entry.session_id, async () => {
+      const dir = this.getProcessesDir();
+      const path = join(dir, `${entry.session_id}.json`);
+      const temp = `${path}.${process.pid}.tmp`;
+      try {
+        await fs.mkdir(dir, { recursive: true });
+        await fs.writeFile(temp, JSON.stringify(entry));
+        await fs.rename(temp, path);
+      } catch (error) {
+        await fs.unlink(temp).catch(() => undefined);
+        this.markDegraded(error);
+      }
+    });
+  }
+
+  /**
+   * Remove a session's process record, and the output files of a detached one, once the
+   * process is gone, never throwing
+   */
+  forgetProcess(sessionId: string): Promise<void> {
+    return this.chainProcessWrite(sessionId, async () => {
+      const { stdout, stderr } = this.processOutputPaths(sessionId);
+      try {
+        await fs.rm(join(this.getProcessesDir(), `${sessionId}.json`), { force: true });
+        await fs.rm(stdout, { force: true });
+        await fs.rm(stderr, { force: true });
+      } catch (error) {
+        this.markDegraded(error);
+      }
+    });
+  }
+
+  /**
+   * The session processes recorded by this and earlier server runs, skipping unreadable entries
+   */
+  async listProcesses(): Promise<RecordedProcess[]> {
+    const dir = this.getProcessesDir();
+    let names: string[];
+    try {
+      names = await fs.readdir(dir);
+    } catch {
+      return [];
+    }
+
+    const entries: RecordedProcess[] = [];
+    for (const name of names.filter((file) => file.endsWith('.json'))) {
+      try {
+        entries.push(JSON.parse(await fs.readFile(join(dir, name), 'utf8')) as RecordedProcess);
+      } catch {
+        // Removed since the listing, or not a process record
+      }
+    }
+    return entries;
+  }
+
+  private chainProcessWrite(sessionId: string, task: () => Promise<void>): Promise<void> {
+    const write = (this.processWrites.get(sessionId) ?? Promise.resolve()).then(task);
+    this.processWrites.set(sessionId, write);
+    void write.then(() => {
+      if (this.processWrites.get(sessionId) === write) {
+        this.processWrites.delete(sessionId);
+      }
+    });
+    return write;
+  }
+
+  private markDegraded(error: unknown): void {
+    const message = error instanceof Error ? error.message : String(error);
+    if (!this.degraded) {
+      console.warn(`⚠️  Session persistence degraded, keeping sessions in memory only: ${message}`);
+    }
+    this.degraded = true;
+    this.lastError = message;
+  }
+
+  /**
+   * Probe that the sessions directory accepts writes by creating and deleting a temp file
+   */
+  async checkWritable(): Promise<DataDirCheck> {
+    const dir = this.getSessionsDir();
+    const probe = join(dir, `.health-${process.pid}-${Date.now()}`);
+    try {
+      await fs.mkdir(dir, { recursive: true });
+      await fs.writeFile(probe, 'ok');
+      await fs.unlink(probe);
+      return { ok: true, path: dir };
+    } catch (error) {
+      return { ok: false, path: dir, error: error instanceof Error ? error.message : String(error) };
+    }
+  }
+
+  getStatus(): PersistenceStatus {
+    return {
+      enabled: true,
+      degraded: this.degraded,
+      ...(this.lastError ? { last_error: this.lastError } : {}),
+    };
+  }
+}
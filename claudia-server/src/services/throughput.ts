@@ -0,0 +1,78 @@
+import type { SessionThroughput } from '../types/index.js';
+
+/**
+ * Length of the rolling window output rates are averaged over
+ */
+export const THROUGHPUT_WINDOW_MS = 10000;
+
+const BUCKET_MS = 1000;
+
+interface Bucket {
+  start: number;
+  lines: number;
+  bytes: number;
+}
+
+/**
+ * Rolling lines/sec and bytes/sec of one session's stdout, kept in one-second buckets
+ * over the last THROUGHPUT_WINDOW_MS
+ */
+export class ThroughputMeter {
+  private buckets: Bucket[] = [];
+  private totalLines = 0;
+  private totalBytes = 0;
+
+  constructor(private startedAt: number = Date.now()) {}
+
+  record(bytes: number, now: number = Date.now()): void {
+    const start = now - (now % BUCKET_MS);
+    let bucket = this.buckets[this.buckets.length - 1];
+    if (!bucket || bucket.start !== start) {
+      bucket = { start, lines: 0, bytes: 0 };
+      this.buckets.push(bucket);
+    }
+    bucket.lines++;
+    bucket.bytes += bytes;
+    this.totalLines++;
+    this.totalBytes += bytes;
+    this.prune(now);
+  }
+
+  /**
+   * Whether the meter has run for a whole window, so its rates describe sustained output
+   * rather than a short burst
+   */
+  hasFullWindow(now: number = Date.now()): boolean {
+    return now - this.startedAt >= THROUGHPUT_WINDOW_MS;
+  }
+
+  getThroughput(now: number = Date.now()): SessionThroughput {
+    this.prune(now);
+    let lines = 0;
+    let bytes = 0;
+    for (const bucket of this.buckets) {
+      lines += bucket.lines;
+      bytes += bucket.bytes;
+    }
+
+    const seconds = THROUGHPUT_WINDOW_MS / 1000;
+    return {
+      lines_per_sec: round(lines / seconds),
+      bytes_per_sec: round(bytes / seconds),
+      total_lines: this.totalLines,
+      total_bytes: this.totalBytes,
+      window_ms: THROUGHPUT_WINDOW_MS,
+    };
+  }
+
+  private prune(now: number): void {
+    const oldest = now - THROUGHPUT_WINDOW_MS;
+    while (this.buckets.length > 0 && this.buckets[0].start <= oldest) {
+      this.buckets.shift();
+    }
+  }
+}
+
+function round(value: number): number {
+  return Math.round(value * 100) / 100;
+}
@@ -0,0 +1,95 @@
+import type { EventEmitter } from 'events';
+import type {
+  SessionEvent,
+  SessionIntervention,
+  SessionLifecycleEvent,
+  WebhookConfig,
+  WebhookEvent,
+} from '../types/index.js';
+
+/**
+ * Events a webhook receives when its config does not list any
+ */
+const DEFAULT_EVENTS: SessionLifecycleEvent[] = ['started', 'completed', 'cancelled', 'failed'];
+
+/**
+ * How long one delivery may take before it is abandoned
+ */
+const DELIVERY_TIMEOUT_MS = 10000;
+
+/**
+ * How long a reachability probe may take before the endpoint counts as unreachable
+ */
+const PROBE_TIMEOUT_MS = 3000;
+
+/**
+ * Whether a webhook endpoint answered a probe
+ */
+export interface WebhookReachability {
+  url: string;
+  reachable: boolean;
+  /** HTTP status of the answer; any status counts as reachable */
+  status?: number;
+  error?: string;
+}
+
+/**
+ * POSTs session lifecycle events and interventions to the configured webhook URLs.
+ *
+ * Delivery is best-effort: each event is sent once, and failures are logged without
+ * affecting the session.
+ */
+export class WebhookService {
+  constructor(private webhooks: WebhookConfig[]) {}
+
+  /**
+   * Deliver the `session_event` and `session_intervention` events of a Claude service
+   */
+  attach(source: EventEmitter): void {
+    if (this.webhooks.length === 0) {
+      return;
+    }
+
+    source.on('session_event', (event: SessionEvent) => {
+      void this.deliver(event.event, event);
+    });
+    source.on('session_intervention', (intervention: SessionIntervention) => {
+      void this.deliver('intervention', intervention);
+    });
+  }
+
+  /**
+   * Send a HEAD request to every configured webhook URL. Any HTTP answer, even an error
+   * status, shows the endpoint is reachable; only network failures and timeouts do not.
+   */
+  async checkReachable(): Promise<WebhookReachability[]> {
+    return Promise.all(this.webhooks.map(async ({ url }) => {
+      try {
+        const response = await fetch(url, { method: 'HEAD', signal: AbortSignal.timeout(PROBE_TIMEOUT_MS) });
+        return { url, reachable: true, status: response.status };
+      } catch (error) {
+        return { url, reachable: false, error: error instanceof Error ? error.message : String(error) };
+      }
+    }));
+  }
+
+  private async deliver(event: WebhookEvent, payload: SessionEvent | SessionIntervention): Promise<void> {
+    const targets = this.webhooks.filter((webhook) => (webhook.events ?? DEFAULT_EVENTS).includes(event));
+    await Promise.all(targets.map(async (webhook) => {
+      try {
+        const response = await fetch(webhook.url, {
+          method: 'POST',
+          headers: { 'Content-Type': 'application/json' },
+          body: JSON.stringify(payload),
+          signal: AbortSignal.timeout(DELIVERY_TIMEOUT_MS),
+        });
+        if (!response.ok) {
+          console.warn(`⚠️  Webhook ${webhook.url} answered ${response.status} to ${event} for session ${payload.session_id}`);
+        }
+      } catch (error) {
+        const message = error instanceof Error ? error.message : String(error);
+        console.warn(`⚠️  Webhook ${webhook.url} failed for ${event} of session ${payload.session_id}: ${message}`);
+      }
+    }));
+  }
+}
@@ -1,6 +1,26 @@
 import { WebSocketServer, WebSocket } from 'ws';
 import { EventEmitter } from 'events';
-import type { WebSocketMessage } from '../types/index.js';
+import type { Server, IncomingMessage } from 'http';
+import type { Duplex } from 'stream';
+import type {
+  WebSocketMessage,
+  SessionEvent,
+  SessionWatchdogUpdate,
+  ProcessInfo,
+  SessionRecord,
+  OutputLine,
+  StatsSnapshot,
+} from '../types/index.js';
+
+/**
+ * Shortest interval a client may ask for with subscribe_stats
+ */
+const MIN_STATS_INTERVAL_SECONDS = 0.1;
+
+/**
+ * Applies an `update_session` message; throws to report the update as rejected
+ */
+export type SessionUpdateHandler = (sessionId: string, update: SessionWatchdogUpdate) => ProcessInfo;
 
 /**
  * Service for managing WebSocket connections and real-time communication
@@ -9,16 +29,153 @@ export class WebSocketService extends EventEmitter {
   private wss: WebSocketServer;
   private clients: Map<string, any> = new Map();
   private subscriptions: Map<string, Set<string>> = new Map(); // clientId -> sessionIds
+  private globalSubscribers: Set<string> = new Set(); // clientIds receiving every session_event
+  private sessionUpdateHandler?: SessionUpdateHandler;
+  private sessionListProvider?: () => ProcessInfo[];
+  private sessionRecordProvider?: (sessionId: string) => SessionRecord | undefined;
+  private sessionIdResolver?: (idOrName: string) => string;
+  private statsProvider?: () => StatsSnapshot;
+  private statsTimers: Map<string, NodeJS.Timeout> = new Map(); // clientId -> stats emitter
+  private draining = false;
+  private maxConnections = 0;
+  private lastInbound: Map<string, number> = new Map(); // clientId -> time of the last message
+  private idleTimer?: NodeJS.Timeout;
 
-  constructor(server: any) {
+  constructor(server?: Server) {
     super();
     
-    this.wss = new WebSocketServer({ 
-      server,
-      path: '/ws'
-    });
+    this.wss = new WebSocketServer({ noServer: true });
 
     this.setupWebSocketServer();
+
+    if (server) {
+      this.attach(server);
+    }
+  }
+
+  /**
+   * Accept WebSocket upgrades on `/ws` from the given HTTP server.
+   * Can be called once per listener so every bound address serves the same endpoint.
+   */
+  attach(server: Server): void {
+    server.on('upgrade', (request: IncomingMessage, socket: Duplex, head: Buffer) => {
+      const { pathname } = new URL(request.url || '/', 'http://localhost');
+      if (pathname !== '/ws') {
+        this.rejectUpgrade(socket, 400, 'Bad Request');
+        return;
+      }
+      if (this.draining) {
+        this.rejectUpgrade(socket, 503, 'Service Unavailable');
+        return;
+      }
+      // Clients are registered synchronously once the upgrade completes and removed on close,
+      // so the client count is exact here
+      if (this.maxConnections > 0 && this.clients.size >= this.maxConnections) {
+        this.rejectUpgrade(socket, 503, 'Service Unavailable');
+        return;
+      }
+
+      this.wss.handleUpgrade(request, socket, head, (ws) => {
+        this.wss.emit('connection', ws, request);
+      });
+    });
+  }
+
+  /**
+   * Whether new `/ws` upgrades are currently refused
+   */
+  isDraining(): boolean {
+    return this.draining;
+  }
+
+  /**
+   * Refuse (or accept again) new `/ws` upgrades with a 503; connected clients are unaffected
+   */
+  setDraining(draining: boolean): void {
+    this.draining = draining;
+  }
+
+  /**
+   * Refuse `/ws` upgrades with a 503 while this many clients are connected (0 = unlimited)
+   */
+  setMaxConnections(limit: number): void {
+    this.maxConnections = limit;
+  }
+
+  /**
+   * Close connections that have sent nothing for this many seconds and have no session
+   * subscriptions (0 = never). Clients streaming a session's output or stats are never closed.
+   */
+  setIdleTimeout(seconds: number): void {
+    if (this.idleTimer) {
+      clearInterval(this.idleTimer);
+      this.idleTimer = undefined;
+    }
+    if (seconds <= 0) {
+      return;
+    }
+
+    const timeoutMs = seconds * 1000;
+    this.idleTimer = setInterval(() => this.closeIdleClients(timeoutMs), Math.min(timeoutMs / 2, 5000));
+    this.idleTimer.unref();
+  }
+
+  private closeIdleClients(timeoutMs: number): void {
+    const now = Date.now();
+    for (const [clientId, ws] of this.clients) {
+      const subscribed =
+        (this.subscriptions.get(clientId)?.size ?? 0) > 0 ||
+        this.globalSubscribers.has(clientId) ||
+        this.statsTimers.has(clientId);
+      if (!subscribed && now - (this.lastInbound.get(clientId) ?? now) >= timeoutMs) {
+        console.log(`Closing idle WebSocket client: ${clientId}`);
+        ws.close(4000, `idle for ${timeoutMs / 1000}s with no messages or subscriptions`);
+      }
+    }
+  }
+
+  /**
+   * Set the handler that applies `update_session` messages to running sessions
+   */
+  setSessionUpdateHandler(handler: SessionUpdateHandler): void {
+    this.sessionUpdateHandler = handler;
+  }
+
+  /**
+   * Set the source of the running-session list returned for `get_sessions`
+   */
+  setSessionListProvider(provider: () => ProcessInfo[]): void {
+    this.sessionListProvider = provider;
+  }
+
+  /**
+   * Set the lookup used to replay sessions that already finished when a client subscribes
+   */
+  setSessionRecordProvider(provider: (sessionId: string) => SessionRecord | undefined): void {
+    this.sessionRecordProvider = provider;
+  }
+
+  /**
+   * Set the lookup that turns a session name into its ID, so clients may name sessions in
+   * subscriptions and updates
+   */
+  setSessionIdResolver(resolver: (idOrName: string) => string): void {
+    this.sessionIdResolver = resolver;
+  }
+
+  /**
+   * Set the source of the snapshots sent to `subscribe_stats` clients
+   */
+  setStatsProvider(provider: () => StatsSnapshot): void {
+    this.statsProvider = provider;
+  }
+
+  /**
+   * Refuse an upgrade request with a plain HTTP status line
+   */
+  private rejectUpgrade(socket: Duplex, status: number, reason: string): void {
+    socket.write(`HTTP/1.1 ${status} ${reason}\r\nConnection: close\r\n\r\n`);
+    socket.destroy();
   }
 
   private setupWebSocketServer(): void {
@@ -26,6 +183,7 @@ export class WebSocketService extends EventEmitter {
       const clientId = this.generateClientId();
       this.clients.set(clientId, ws);
       this.subscriptions.set(clientId, new Set());
+      this.lastInbound.set(clientId, Date.now());
 
       console.log(`WebSocket client connected: ${clientId}`);
 
@@ -37,6 +195,7 @@ export class WebSocketService extends EventEmitter {
       });
 
       ws.on('message', (data: Buffer) => {
+        this.lastInbound.set(clientId, Date.now());
         try {
           const message = JSON.parse(data.toString()) as WebSocketMessage;
           this.handleClientMessage(clientId, message);
@@ -49,16 +208,30 @@ export class WebSocketService extends EventEmitter {
         console.log(`WebSocket client disconnected: ${clientId}`);
         this.clients.delete(clientId);
         this.subscriptions.delete(clientId);
+        this.globalSubscribers.delete(clientId);
+        this.lastInbound.delete(clientId);
+        this.stopStats(clientId);
       });
 
       ws.on('error', (error: Error) => {
         console.error(`WebSocket error for client ${clientId}:`, error);
         this.clients.delete(clientId);
         this.subscriptions.delete(clientId);
+        this.globalSubscribers.delete(clientId);
+        this.lastInbound.delete(clientId);
+        this.stopStats(clientId);
       });
     });
   }
 
+  /**
+   * The ID of the session a client named, by ID or by name. Output is published under IDs,
+   * so subscriptions are always stored under the ID.
+   */
+  private resolveSessionId(idOrName: string): string {
+    return this.sessionIdResolver?.(idOrName) ?? idOrName;
+  }
+
   private generateClientId(): string {
     return `client_${Date.now()}_${Math.random().toString(36).substr(2, 9)}`;
   }
@@ -71,54 +244,277 @@ export class WebSocketService extends EventEmitter {
       case 'unsubscribe':
         this.handleUnsubscribe(clientId, message);
         break;
+      case 'subscribe_many':
+        this.handleSubscribeMany(clientId, message, true);
+        break;
+      case 'unsubscribe_many':
+        this.handleSubscribeMany(clientId, message, false);
+        break;
+      case 'subscribe_all':
+        this.globalSubscribers.add(clientId);
+        this.reply(clientId, message, {
+          type: 'status',
+          data: { status: 'subscribed_all' },
+          timestamp: new Date().toISOString(),
+        });
+        break;
+      case 'unsubscribe_all':
+        this.globalSubscribers.delete(clientId);
+        this.reply(clientId, message, {
+          type: 'status',
+          data: { status: 'unsubscribed_all' },
+          timestamp: new Date().toISOString(),
+        });
+        break;
+      case 'update_session':
+        this.handleUpdateSession(clientId, message);
+        break;
+      case 'get_sessions':
+        this.reply(clientId, message, {
+          type: 'sessions',
+          data: this.sessionListProvider?.() ?? [],
+          timestamp: new Date().toISOString(),
+        });
+        break;
+      case 'subscribe_stats':
+        this.handleSubscribeStats(clientId, message);
+        break;
+      case 'unsubscribe_stats':
+        this.stopStats(clientId);
+        this.reply(clientId, message, {
+          type: 'status',
+          data: { status: 'unsubscribed_stats' },
+          timestamp: new Date().toISOString(),
+        });
+        break;
       default:
-        this.sendError(clientId, 'Unknown message type', { type: message.type });
+        this.sendError(clientId, 'Unknown message type', { type: message.type }, message.request_id);
     }
   }
 
   private handleSubscribe(clientId: string, message: WebSocketMessage): void {
     if (!message.session_id) {
-      this.sendError(clientId, 'session_id required for subscribe');
+      this.sendError(clientId, 'session_id required for subscribe', undefined, message.request_id);
       return;
     }
 
+    const sessionId = this.resolveSessionId(message.session_id);
     const subscriptions = this.subscriptions.get(clientId);
     if (subscriptions) {
-      subscriptions.add(message.session_id);
-      console.log(`Client ${clientId} subscribed to session ${message.session_id}`);
+      subscriptions.add(sessionId);
+      console.log(`Client ${clientId} subscribed to session ${sessionId}`);
       
-      this.sendToClient(clientId, {
+      this.reply(clientId, message, {
         type: 'status',
         data: { 
           status: 'subscribed', 
-          session_id: message.session_id,
+          session_id: sessionId,
           subscriptions: Array.from(subscriptions)
         },
         timestamp: new Date().toISOString(),
       });
+      this.replayFinishedSession(clientId, sessionId);
     }
   }
 
+  /**
+   * A session that already finished has no live output left to stream. Send its buffered
+   * output and the completion message instead, so subscribing just after a fast session
+   * ended looks the same as having subscribed before it started.
+   */
+  private replayFinishedSession(clientId: string, sessionId: string): void {
+    const record = this.sessionRecordProvider?.(sessionId);
+    if (!record || record.status === 'running') {
+      return;
+    }
+
+    // The provider hands out records with output already cut to the retention window
+    for (const line of record.output) {
+      this.sendToClient(clientId, {
+        type: 'claude_stream',
+        data: this.replayedMessage(line),
+        session_id: sessionId,
+        timestamp: line.timestamp,
+      });
+    }
+    this.sendToClient(clientId, {
+      type: 'claude_stream',
+      data: {
+        type: 'complete',
+        content: `Process exited with code ${record.exit_code ?? null}`,
+        timestamp: record.ended_at ?? new Date().toISOString(),
+      },
+      session_id: sessionId,
+      timestamp: new Date().toISOString(),
+    });
+  }
+
+  /**
+   * Rebuild the claude_stream payload a buffered output line was originally sent as
+   */
+  private replayedMessage(line: OutputLine): any {
+    const { content, timestamp } = line;
+    if (line.stream === 'stderr') {
+      return { type: 'error', content, timestamp };
+    }
+    try {
+      const message = JSON.parse(content);
+      if (message && typeof message === 'object') {
+        return { ...message, timestamp };
+      }
+    } catch {
+      // Not JSON; sent as plain output
+    }
+    return { type: 'output', content, timestamp };
+  }
+
   private handleUnsubscribe(clientId: string, message: WebSocketMessage): void {
     if (!message.session_id) {
-      this.sendError(clientId, 'session_id required for unsubscribe');
+      this.sendError(clientId, 'session_id required for unsubscribe', undefined, message.request_id);
       return;
     }
 
+    const sessionId = this.resolveSessionId(message.session_id);
     const subscriptions = this.subscriptions.get(clientId);
     if (subscriptions) {
-      subscriptions.delete(message.session_id);
-      console.log(`Client ${clientId} unsubscribed from session ${message.session_id}`);
+      subscriptions.delete(sessionId);
+      console.log(`Client ${clientId} unsubscribed from session ${sessionId}`);
       
-      this.sendToClient(clientId, {
+      this.reply(clientId, message, {
         type: 'status',
         data: { 
           status: 'unsubscribed', 
-          session_id: message.session_id,
+          session_id: sessionId,
+          subscriptions: Array.from(subscriptions)
+        },
+        timestamp: new Date().toISOString(),
+      });
+    }
+  }
+
+  /**
+   * Add or remove several session subscriptions at once, so one connection can follow many sessions
+   */
+  private handleSubscribeMany(clientId: string, message: WebSocketMessage, subscribe: boolean): void {
+    const requested = message.session_ids;
+    if (!Array.isArray(requested) || requested.some((id) => typeof id !== 'string')) {
+      this.sendError(clientId, `session_ids array required for ${message.type}`, undefined, message.request_id);
+      return;
+    }
+    const sessionIds = requested.map((idOrName) => this.resolveSessionId(idOrName));
+
+    const subscriptions = this.subscriptions.get(clientId);
+    if (subscriptions) {
+      for (const sessionId of sessionIds) {
+        if (subscribe) {
+          subscriptions.add(sessionId);
+        } else {
+          subscriptions.delete(sessionId);
+        }
+      }
+      console.log(`Client ${clientId} ${subscribe ? 'subscribed to' : 'unsubscribed from'} ${sessionIds.length} session(s)`);
+
+      this.reply(clientId, message, {
+        type: 'status',
+        data: {
+          status: subscribe ? 'subscribed' : 'unsubscribed',
+          session_ids: sessionIds,
           subscriptions: Array.from(subscriptions)
         },
         timestamp: new Date().toISOString(),
       });
+      if (subscribe) {
+        for (const sessionId of sessionIds) {
+          this.replayFinishedSession(clientId, sessionId);
+        }
+      }
+    }
+  }
+
+  /**
+   * Start sending this client a `stats` snapshot right away and then every interval_seconds,
+   * replacing any cadence it asked for earlier
+   */
+  private handleSubscribeStats(clientId: string, message: WebSocketMessage): void {
+    const interval = message.interval_seconds;
+    if (typeof interval !== 'number' || !Number.isFinite(interval) || interval < MIN_STATS_INTERVAL_SECONDS) {
+      this.sendError(
+        clientId,
+        `interval_seconds must be a number of at least ${MIN_STATS_INTERVAL_SECONDS}`,
+        { interval_seconds: interval },
+        message.request_id
+      );
+      return;
+    }
+    if (!this.statsProvider) {
+      this.sendError(clientId, 'Stats are not supported', undefined, message.request_id);
+      return;
+    }
+
+    this.stopStats(clientId);
+    const timer = setInterval(() => this.sendStats(clientId), interval * 1000);
+    timer.unref();
+    this.statsTimers.set(clientId, timer);
+
+    this.reply(clientId, message, {
+      type: 'status',
+      data: { status: 'subscribed_stats', interval_seconds: interval },
+      timestamp: new Date().toISOString(),
+    });
+    this.sendStats(clientId);
+  }
+
+  private sendStats(clientId: string): void {
+    if (!this.statsProvider) {
+      return;
+    }
+    this.sendToClient(clientId, {
+      type: 'stats',
+      data: this.statsProvider(),
+      timestamp: new Date().toISOString(),
+    });
+  }
+
+  private stopStats(clientId: string): void {
+    const timer = this.statsTimers.get(clientId);
+    if (timer) {
+      clearInterval(timer);
+      this.statsTimers.delete(clientId);
+    }
+  }
+
+  /**
+   * Change a running session's timeout or abort patterns
+   */
+  private handleUpdateSession(clientId: string, message: WebSocketMessage): void {
+    if (!message.session_id) {
+      this.sendError(clientId, 'session_id required for update_session', undefined, message.request_id);
+      return;
+    }
+    if (!this.sessionUpdateHandler) {
+      this.sendError(clientId, 'Session updates are not supported', undefined, message.request_id);
+      return;
+    }
+
+    const sessionId = this.resolveSessionId(message.session_id);
+    try {
+      const info = this.sessionUpdateHandler(sessionId, message.data ?? {});
+      this.reply(clientId, message, {
+        type: 'status',
+        data: {
+          status: 'session_updated',
+          session_id: sessionId,
+          timeout_ms: info.timeout_ms,
+          abort_patterns: info.abort_patterns ?? [],
+        },
+        session_id: sessionId,
+        timestamp: new Date().toISOString(),
+      });
+    } catch (error) {
+      this.sendError(clientId, error instanceof Error ? error.message : 'Session update failed', {
+        session_id: sessionId,
+        code: (error as { code?: string }).code,
+      }, message.request_id);
     }
   }
 
@@ -136,13 +532,21 @@ export class WebSocketService extends EventEmitter {
     }
   }
 
+  /**
+   * Answer a client message, echoing its request_id so RPC-style clients can match the reply
+   */
+  private reply(clientId: string, request: WebSocketMessage, message: WebSocketMessage): void {
+    this.sendToClient(clientId, request.request_id === undefined ? message : { ...message, request_id: request.request_id });
+  }
+
   /**
    * Send error message to client
    */
-  private sendError(clientId: string, error: string, details?: any): void {
+  private sendError(clientId: string, error: string, details?: any, requestId?: string): void {
     this.sendToClient(clientId, {
       type: 'error',
       data: { error, details },
+      ...(requestId === undefined ? {} : { request_id: requestId }),
       timestamp: new Date().toISOString(),
     });
   }
@@ -151,11 +555,12 @@ export class WebSocketService extends EventEmitter {
    * Broadcast Claude stream message to subscribed clients
    */
   broadcastClaudeStream(sessionId: string, message: any): void {
+    // Output keeps the time it was read from the process rather than the time it is sent
     const wsMessage: WebSocketMessage = {
       type: 'claude_stream',
       data: message,
       session_id: sessionId,
-      timestamp: new Date().toISOString(),
+      timestamp: typeof message?.timestamp === 'string' ? message.timestamp : new Date().toISOString(),
     };
 
     for (const [clientId, subscriptions] of this.subscriptions.entries()) {
@@ -165,6 +570,22 @@ export class WebSocketService extends EventEmitter {
     }
   }
 
+  /**
+   * Broadcast a session lifecycle event to clients subscribed to all sessions
+   */
+  broadcastSessionEvent(event: SessionEvent): void {
+    const message: WebSocketMessage = {
+      type: 'session_event',
+      data: event,
+      session_id: event.session_id,
+      timestamp: new Date().toISOString(),
+    };
+
+    for (const clientId of this.globalSubscribers) {
+      this.sendToClient(clientId, message);
+    }
+  }
+
   /**
    * Broadcast status message to all clients
    */
@@ -199,14 +620,22 @@ export class WebSocketService extends EventEmitter {
   }
 
   /**
-   * Close all connections and cleanup
+   * Close all connections and cleanup.
+   * Each client receives a close frame with the Going Away code and the given reason
+   * so it can tell a shutdown apart from a dropped connection.
    */
-  close(): void {
+  close(reason = 'server shutting down'): void {
+    this.setIdleTimeout(0);
+    for (const clientId of [...this.statsTimers.keys()]) {
+      this.stopStats(clientId);
+    }
     for (const client of this.clients.values()) {
-      client.close();
+      client.close(1001, reason);
     }
     this.clients.clear();
     this.subscriptions.clear();
+    this.globalSubscribers.clear();
+    this.lastInbound.clear();
     this.wss.close();
   }
 }
\ No newline at end of file
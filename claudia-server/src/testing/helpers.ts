@@ -0,0 +1,101 @@
+import { promises as fs } from 'fs';
+import { tmpdir } from 'os';
+import { join } from 'path';
+import WebSocket from 'ws';
+
+import { ClaudiaServer } from '../server.js';
+import type { ServerConfig } from '../types/index.js';
+
+/**
+ * Write an executable shell script that stands in for the Claude CLI.
+ * The script body receives the same argv the server would pass to `claude`.
+ */
+export async function createFakeClaude(body: string): Promise<string> {
+  const dir = await fs.mkdtemp(join(tmpdir(), 'claudia-fake-'));
+  const path = join(dir, 'claude');
+  await fs.writeFile(path, `#!/bin/sh\n${body}\n`, { mode: 0o755 });
+  return path;
+}
+
+/**
+ * Create an empty temporary project directory
+ */
+export async function createTempProject(): Promise<string> {
+  return fs.mkdtemp(join(tmpdir(), 'claudia-project-'));
+}
+
+/**
+ * Start a server on an ephemeral loopback port and return it with its base URL
+ */
+export async function startTestServer(
+  config: Partial<ServerConfig> = {}
+): Promise<{ server: ClaudiaServer; baseUrl: string; wsUrl: string }> {
+  const server = new ClaudiaServer({ listen: [{ host: '127.0.0.1', port: 0 }], ...config });
+  await server.start();
+  const [{ host, port }] = server.getAddresses();
+  const [ws] = server.getWebSocketAddresses();
+  return { server, baseUrl: `http://${host}:${port}`, wsUrl: `ws://${ws.host}:${ws.port}/ws` };
+}
+
+/**
+ * POST a JSON body and return the status plus parsed response
+ */
+export async function postJson(url: string, body: unknown): Promise<{ status: number; body: any }> {
+  const res = await fetch(url, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify(body),
+  });
+  return { status: res.status, body: await res.json() };
+}
+
+/**
+ * GET a URL repeatedly until the response status satisfies the predicate, then return it parsed
+ */
+export async function fetchUntil(
+  url: string,
+  accept: (status: number) => boolean,
+  timeoutMs = 5000
+): Promise<{ status: number; body: any }> {
+  const deadline = Date.now() + timeoutMs;
+  for (;;) {
+    const res = await fetch(url);
+    if (accept(res.status)) {
+      return { status: res.status, body: await res.json() };
+    }
+    if (Date.now() > deadline) {
+      throw new Error(`${url} still returned ${res.status} after ${timeoutMs}ms`);
+    }
+    await new Promise((resolve) => setTimeout(resolve, 20));
+  }
+}
+
+/**
+ * Open a WebSocket and collect every parsed message it receives
+ */
+export async function connectWebSocket(url: string): Promise<{ ws: WebSocket; messages: any[] }> {
+  const ws = new WebSocket(url);
+  const messages: any[] = [];
+  ws.on('message', (raw) => messages.push(JSON.parse(raw.toString())));
+  await new Promise<void>((resolve, reject) => {
+    ws.once('open', () => resolve());
+    ws.once('error', reject);
+  });
+  return { ws, messages };
+}
+
+/**
+ * Poll until the predicate holds or the timeout elapses
+ */
+export async function waitFor(
+  predicate: () => boolean | Promise<boolean>,
+  timeoutMs = 5000
+): Promise<void> {
+  const deadline = Date.now() + timeoutMs;
+  while (!(await predicate())) {
+    if (Date.now() > deadline) {
+      throw new Error(`Condition not met within ${timeoutMs}ms`);
+    }
+    await new Promise((resolve) => setTimeout(resolve, 20));
+  }
+}
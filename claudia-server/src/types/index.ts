@@ -15,6 +15,367 @@ export interface ProcessInfo {
   project_path: string;
   task: string;
   model: string;
+  /** Present and true when the session was launched with --dangerously-skip-permissions */
+  permissions_skipped?: boolean;
+  /** Present and true when the process was spawned detached and survives server shutdown */
+  detached?: boolean;
+  /** The process working directory, when it differs from project_path */
+  working_dir?: string;
+  /** The first ~500 characters of meaningful output, refreshed periodically while running */
+  output_preview?: string;
+  /** The originally requested model, when the session was retried on its fallback after an overload */
+  fallback_from?: string;
+  /** Total runtime allowed before the session is cancelled */
+  timeout_ms?: number;
+  /** Regular expressions that cancel the session when an output line matches */
+  abort_patterns?: string[];
+  /** How long buffered output lines are kept, by their read time */
+  output_retention_ms?: number;
+  /** Friendly name given at start, usable in place of the session id */
+  name?: string;
+  /** Labels given at start, merged over those derived from the prompt by label_rules */
+  labels?: Record<string, string>;
+  /** Problems noticed at start that did not stop the session, e.g. another session in the project */
+  warnings?: string[];
+  /** Claude's own report of what went wrong, from a `result` message flagged `is_error` */
+  error_detail?: SessionErrorDetail;
+  /** Directory under `<data_dir>/sessions` holding the session's files, when one was requested */
+  output_subdir?: string;
+  /** Claude's own id for the conversation, from its stream-json output; what `--resume` takes */
+  claude_session_id?: string;
+  /** The session this one restarted with `--resume`, for sessions started by the restart endpoint */
+  restarted_from?: string;
+  /** The project's current git branch at start, with record_git_info; unset for a detached HEAD */
+  git_branch?: string;
+  /** The project's git HEAD commit at start, with record_git_info, when the project is a git repository */
+  git_commit?: string;
+}
+
+/**
+ * Why Claude reported a session as failed, taken from an error `result` message
+ */
+export interface SessionErrorDetail {
+  /** The result subtype, e.g. `error_max_turns` or `error_during_execution` */
+  subtype: string;
+  /** Claude's message for the error, when it gave one */
+  message?: string;
+}
+
+/**
+ * Status of a session tracked by the server
+ */
+export type SessionStatus = 'running' | 'completed' | 'cancelled' | 'failed';
+
+/**
+ * A single line of buffered process output
+ */
+export interface OutputLine {
+  stream: 'stdout' | 'stderr';
+  content: string;
+  /** When the line was read from the process (ISO 8601), not when it was sent to a client */
+  timestamp: string;
+  /** Present when the bytes were not valid UTF-8; `content` is then a lossy rendering */
+  invalid_utf8?: true;
+  /** The original bytes, base64-encoded, for lines tagged `invalid_utf8` */
+  raw_base64?: string;
+  /** Present when the line exceeded max_line_bytes; `content` holds only its beginning */
+  truncated?: true;
+}
+
+/**
+ * The final answer of a session, taken from the stream-json `result` message
+ */
+export interface SessionResult {
+  /** The result text, or the last output lines when no `result` message was seen */
+  text: string;
+  /** Whether the text came from a parsed `result` message rather than the output fallback */
+  parsed: boolean;
+  is_error?: boolean;
+  cost_usd?: number;
+  usage?: {
+    input_tokens?: number;
+    output_tokens?: number;
+    cache_creation_input_tokens?: number;
+    cache_read_input_tokens?: number;
+  };
+  num_turns?: number;
+  duration_ms?: number;
+}
+
+/**
+ * Everything the server keeps about a session, running or finished
+ */
+export interface SessionRecord {
+  info: ProcessInfo;
+  status: SessionStatus;
+  exit_code?: number | null;
+  ended_at?: string;
+  /** Most recent output lines, capped at the service's output buffer size */
+  output: OutputLine[];
+  /** Parsed from the stream-json `result` message, when one was emitted */
+  result?: SessionResult;
+  /** Set when a watchdog cancelled the session or it was killed for exceeding its memory limit */
+  terminated_by?: InterventionReason;
+  /** Set when the server failed the session because handling its output threw */
+  failure_reason?: string;
+  /** Signal that terminated the process, if it did not exit on its own */
+  exit_signal?: string | null;
+  /** How the process was started */
+  launch?: SessionLaunch;
+  /** Tools Claude called, in order of first use */
+  tools_used?: ToolUsage[];
+  /** Output lines produced so far, including those dropped from the buffer; the cursor for `since` */
+  output_total?: number;
+  /** Buffered lines dropped after the session finished because the server was over its memory soft limit */
+  output_shed?: number;
+  /** Present and true when shed output was not persisted to the data directory and cannot be read again */
+  output_lost?: boolean;
+  /** Output lines dropped by output_filters, when count_filtered_output is on */
+  output_filtered?: number;
+  /** What changed in the project, when the session was started with capture_git_diff */
+  git_diff?: SessionGitDiff;
+  /** Why capture_git_diff failed, when git timed out instead of reporting the changes */
+  git_diff_error?: string;
+  /** Present and true when the session was cancelled with `keep_output: false` and its output cleared */
+  output_discarded?: boolean;
+  /** Every status the session went through, oldest first */
+  status_history?: SessionStatusChange[];
+}
+
+/**
+ * One entry of a session's status timeline. `starting` covers the time between the request and
+ * the process being spawned, e.g. waiting for spawn_rate_limit.
+ */
+export interface SessionStatusChange {
+  status: 'starting' | SessionStatus;
+  at: string;
+  /** Why the session moved to this status, when there is more to say than the status itself */
+  reason?: string;
+}
+
+/**
+ * A project's uncommitted changes, captured with git when a session ended
+ */
+export interface SessionGitDiff {
+  /** Output of `git diff`: unstaged changes to tracked files */
+  diff: string;
+  /** Output of `git status --porcelain`, which also lists staged and untracked files */
+  status: string;
+  captured_at: string;
+}
+
+/**
+ * Output released from one finished session to bring the server under its memory soft limit
+ */
+export interface ShedOutput {
+  session_id: string;
+  lines: number;
+  bytes: number;
+  /** True when the lines were not persisted to the data directory, so they are gone for good */
+  lost: boolean;
+}
+
+/**
+ * How many times a session called one tool
+ */
+export interface ToolUsage {
+  name: string;
+  count: number;
+}
+
+/**
+ * The exact command a session was started with
+ */
+export interface SessionLaunch {
+  binary: string;
+  args: string[];
+  cwd: string;
+  /** Environment passed to the process, with secret values redacted */
+  env: Record<string, string>;
+  /** Variables that differ from the server's own environment, with secret values redacted */
+  env_overrides?: Record<string, string>;
+}
+
+/**
+ * A session's launch as a command line that can be pasted into a shell
+ */
+export interface SessionCommand {
+  session_id: string;
+  cwd: string;
+  /** The binary followed by its arguments */
+  argv: string[];
+  /** Environment overrides, with secret values redacted */
+  env: Record<string, string>;
+  /** `cd <cwd> && VAR=value <binary> <args...>`, shell-quoted */
+  command: string;
+}
+
+/**
+ * A running session's stdout rate, averaged over the last `window_ms`
+ */
+export interface SessionThroughput {
+  lines_per_sec: number;
+  bytes_per_sec: number;
+  total_lines: number;
+  total_bytes: number;
+  window_ms: number;
+}
+
+/**
+ * What POST /api/claude/sessions/:id/input/file wrote to a session's stdin
+ */
+export interface SessionInputResult {
+  session_id: string;
+  /** The file that was read, with symlinks resolved */
+  path: string;
+  lines: number;
+  bytes: number;
+}
+
+/**
+ * One session's new output in a POST /api/claude/sessions/output/batch response
+ */
+export interface OutputBatchEntry {
+  /** The id or name as requested */
+  session_id: string;
+  status?: SessionStatus;
+  /** Lines after the requested `since` cursor that are still buffered */
+  lines?: OutputLine[];
+  /** Cursor to pass as `since` next time */
+  next?: number;
+  /** New lines that were already dropped from the buffer and could not be returned */
+  skipped?: number;
+  /** Set instead of the fields above when the session is unknown */
+  error?: { code: string; message: string };
+}
+
+/**
+ * Process counts reported by GET /api/claude/processes/stats
+ */
+export interface ProcessStats {
+  running: number;
+  running_per_project: Record<string, number>;
+  /** Starts waiting for their turn under spawn_rate_limit */
+  queue_depth: number;
+  /** 0 when the queue is unbounded */
+  max_queue_depth: number;
+  /** max_concurrent_sessions; 0 when unlimited */
+  max_running: number;
+}
+
+/**
+ * Counts over every stored session, running or finished
+ */
+export interface SessionTotals {
+  total: number;
+  by_status: Record<SessionStatus, number>;
+  /** Sum of the reported cost of finished sessions */
+  total_cost_usd: number;
+}
+
+/**
+ * What GET /api/usage groups sessions by. `tenant` is shorthand for the `tenant` label.
+ */
+export type UsageGroupBy = 'project' | 'model' | 'label' | 'tenant';
+
+/**
+ * Which sessions GET /api/usage adds up, and how
+ */
+export interface UsageQuery {
+  group_by: UsageGroupBy;
+  /** Label name to group by, for `group_by=label` */
+  label?: string;
+  /** Only sessions started at or after this time */
+  from?: Date;
+  /** Only sessions started before this time */
+  to?: Date;
+}
+
+/**
+ * Token and cost totals of the sessions sharing one value of the grouping dimension
+ */
+export interface UsageGroup {
+  /** The project path, model, or label value; null for sessions without the label */
+  key: string | null;
+  sessions: number;
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_input_tokens: number;
+  cache_read_input_tokens: number;
+  cost_usd: number;
+}
+
+/**
+ * Periodic snapshot sent to WebSocket clients that sent `subscribe_stats`
+ */
+export interface StatsSnapshot extends ProcessStats {
+  sessions: SessionTotals;
+  memory: NodeJS.MemoryUsage;
+}
+
+/**
+ * Outcome of one `GET /api/status/health?deep=true` check
+ */
+export type HealthCheckStatus = 'ok' | 'degraded' | 'fail';
+
+/**
+ * One subsystem check of the deep health report
+ */
+export interface HealthCheck {
+  status: HealthCheckStatus;
+  /** Kept for older clients: true unless status is `fail` or `degraded` */
+  ok: boolean;
+  message: string;
+  [detail: string]: unknown;
+}
+
+/**
+ * A running or finished session as listed by GET /api/claude/sessions
+ */
+export interface SessionListEntry extends ProcessInfo {
+  status: SessionStatus;
+  exit_code?: number | null;
+  ended_at?: string;
+  terminated_by?: InterventionReason;
+}
+
+/**
+ * Which sessions GET /api/claude/sessions returns
+ */
+export interface SessionListFilter {
+  status?: SessionStatus;
+  project_path?: string;
+  /** Matching sessions to skip, oldest first */
+  offset?: number;
+  /** Most sessions to return */
+  limit?: number;
+}
+
+/**
+ * Everything needed to see how a session was launched and how it ended
+ */
+export interface SessionDebugInfo extends SessionLaunch {
+  session_id: string;
+  status: SessionStatus;
+  pid: number;
+  started_at: string;
+  ended_at?: string;
+  exit_code?: number | null;
+  exit_signal?: string | null;
+  /** First buffered output lines (the buffer drops the oldest lines once full) */
+  output_head: OutputLine[];
+  /** Last buffered output lines */
+  output_tail: OutputLine[];
+}
+
+/**
+ * Changes to a running session's watchdogs; omitted fields are left as they are
+ */
+export interface SessionWatchdogUpdate {
+  /** New total runtime limit, measured from the session start */
+  timeout_ms?: number;
+  /** Patterns added to the session's abort patterns; they apply to later output only */
+  abort_patterns?: string[];
 }
 
 /**
@@ -60,6 +421,31 @@ export interface ClaudeSettings {
   [key: string]: any;
 }
 
+/**
+ * One flag listed by `claude --help`
+ */
+export interface ClaudeCliFlag {
+  /** Long flag name, e.g. `--output-format` */
+  name: string;
+  /** Single-letter alias, e.g. `-p` */
+  short?: string;
+  /** Argument placeholder, e.g. `<format>` or `[filter]` */
+  argument?: string;
+  description: string;
+}
+
+/**
+ * `claude --help` output for the installed binary
+ */
+export interface ClaudeHelp {
+  binary_path: string;
+  version?: string;
+  /** The full help text */
+  raw: string;
+  /** Flags recognized in the help text */
+  flags: ClaudeCliFlag[];
+}
+
 /**
  * Represents the Claude Code version status
  */
@@ -72,6 +458,30 @@ export interface ClaudeVersionStatus {
   output: string;
 }
 
+/**
+ * A Claude binary found by POST /api/claude/reselect
+ */
+export interface ClaudeInstallation {
+  path: string;
+  /** The version `--version` reported, when it printed one */
+  version?: string;
+}
+
+/**
+ * The outcome of discovering the Claude installations again
+ */
+export interface ClaudeSelection {
+  /** The binary in use before, when there was a working one */
+  previous?: string;
+  /** The binary new sessions now use */
+  selected: string;
+  version?: string;
+  /** Whether the binary in use changed */
+  changed: boolean;
+  /** Every installation that answered `--version` */
+  installations: ClaudeInstallation[];
+}
+
 /**
  * Represents a CLAUDE.md file found in the project
  */
@@ -114,6 +524,94 @@ export interface ClaudeStreamMessage {
   session_id?: string;
 }
 
+/**
+ * Lifecycle transitions published on the server-wide event stream
+ */
+export type SessionLifecycleEvent = 'started' | 'completed' | 'cancelled' | 'failed' | 'failed_to_start';
+
+/**
+ * A session lifecycle event (output lines are not included)
+ */
+export interface SessionEvent {
+  event: SessionLifecycleEvent;
+  session_id: string;
+  project_path: string;
+  model: string;
+  /** Exit code for terminal events, when the process exited normally */
+  exit_code?: number | null;
+  /** Error message for `failed` and `failed_to_start` events caused by a spawn/process error */
+  error?: string;
+  /** Claude's own error report for terminal events, when its `result` message flagged one */
+  error_detail?: SessionErrorDetail;
+  timestamp: string;
+}
+
+/**
+ * Why a session was killed instead of finishing on its own
+ */
+export type InterventionReason = 'timeout' | 'abort_pattern' | 'memory_limit' | 'output_rate' | 'stdout_closed';
+
+/**
+ * Published when a watchdog or the memory limit kills a session, ahead of its
+ * terminal lifecycle event
+ */
+export interface SessionIntervention {
+  event: 'intervention';
+  session_id: string;
+  project_path: string;
+  model: string;
+  reason: InterventionReason;
+  /** Human-readable description, e.g. the abort pattern that matched */
+  message: string;
+  timestamp: string;
+}
+
+/**
+ * Events a webhook can subscribe to
+ */
+export type WebhookEvent = SessionLifecycleEvent | 'intervention';
+
+/**
+ * A URL that receives session events as JSON POSTs
+ */
+export interface WebhookConfig {
+  url: string;
+  /** Events to deliver (default: the lifecycle events, without interventions) */
+  events?: WebhookEvent[];
+}
+
+/**
+ * Labels attached to every session whose prompt matches a regular expression
+ */
+export interface LabelRule {
+  pattern: string;
+  /** RegExp flags for pattern, e.g. "i" (default: none) */
+  flags?: string;
+  labels: Record<string, string>;
+}
+
+/**
+ * Claude arguments added to every session carrying all of the given labels
+ */
+export interface LabelArgRule {
+  /** Labels the session must have, e.g. `{ "env": "prod" }`; a rule with none matches every session */
+  labels: Record<string, string>;
+  /** Arguments appended to the argv, e.g. `["--max-turns", "3"]`; server-set flags are not allowed */
+  args: string[];
+}
+
+/**
+ * A command that must exit with the expected code before a session is started
+ */
+export interface SessionPrecondition {
+  /** Shell command run in the project directory */
+  command: string;
+  /** Exit code that lets the session start (default: 0) */
+  expect_exit_code?: number;
+  /** Kill the command and fail the precondition after this long (default: 30000) */
+  timeout_ms?: number;
+}
+
 /**
  * API Request types
  */
@@ -121,12 +619,76 @@ export interface ExecuteClaudeRequest {
   project_path: string;
   prompt: string;
   model: string;
+  /** Override the server's skip_permissions default for this session */
+  skip_permissions?: boolean;
+  /** Directory Claude runs in; defaults to project_path */
+  working_dir?: string;
+  /** Only start the session if this command exits with the expected code */
+  precondition?: SessionPrecondition;
+  /** Settings overlay written to a temporary settings.json and passed with --settings */
+  settings?: Record<string, any>;
+  /** Cancel the session after this many milliseconds */
+  timeout_ms?: number;
+  /** Cancel the session when an output line matches one of these regular expressions */
+  abort_patterns?: string[];
+  /** Drop buffered output lines read longer ago than this many milliseconds */
+  output_retention_ms?: number;
+  /** Override the server's verbose default (passes or omits --verbose) */
+  verbose?: boolean;
+  /** Additional Claude CLI arguments, appended after the ones the server sets */
+  extra_args?: string[];
+  /** File the final result text is written to when the session completes, relative to project_path */
+  output_result_to?: string;
+  /** Friendly name usable instead of the session id in every endpoint; unique among running sessions */
+  name?: string;
+  /** Labels attached to the session; they override labels derived by the server's label_rules */
+  labels?: Record<string, string>;
+  /** Directories outside the project Claude may access, passed as --add-dir; relative to project_path */
+  additional_dirs?: string[];
+  /** Relative directory under `<data_dir>/sessions` the session's record and output log are written to */
+  output_subdir?: string;
+  /** Record `git diff` and `git status` of the project when the session ends */
+  capture_git_diff?: boolean;
+  /** Set to false to send the prompt without the server's prompt_prefix and prompt_suffix */
+  apply_prompt_wrapping?: boolean;
 }
 
 export interface ContinueClaudeRequest {
   project_path: string;
   prompt: string;
   model: string;
+  /** Override the server's skip_permissions default for this session */
+  skip_permissions?: boolean;
+  /** Directory Claude runs in; defaults to project_path */
+  working_dir?: string;
+  /** Only start the session if this command exits with the expected code */
+  precondition?: SessionPrecondition;
+  /** Settings overlay written to a temporary settings.json and passed with --settings */
+  settings?: Record<string, any>;
+  /** Cancel the session after this many milliseconds */
+  timeout_ms?: number;
+  /** Cancel the session when an output line matches one of these regular expressions */
+  abort_patterns?: string[];
+  /** Drop buffered output lines read longer ago than this many milliseconds */
+  output_retention_ms?: number;
+  /** Override the server's verbose default (passes or omits --verbose) */
+  verbose?: boolean;
+  /** Additional Claude CLI arguments, appended after the ones the server sets */
+  extra_args?: string[];
+  /** File the final result text is written to when the session completes, relative to project_path */
+  output_result_to?: string;
+  /** Friendly name usable instead of the session id in every endpoint; unique among running sessions */
+  name?: string;
+  /** Labels attached to the session; they override labels derived by the server's label_rules */
+  labels?: Record<string, string>;
+  /** Directories outside the project Claude may access, passed as --add-dir; relative to project_path */
+  additional_dirs?: string[];
+  /** Relative directory under `<data_dir>/sessions` the session's record and output log are written to */
+  output_subdir?: string;
+  /** Record `git diff` and `git status` of the project when the session ends */
+  capture_git_diff?: boolean;
+  /** Set to false to send the prompt without the server's prompt_prefix and prompt_suffix */
+  apply_prompt_wrapping?: boolean;
 }
 
 export interface ResumeClaudeRequest {
@@ -134,29 +696,300 @@ export interface ResumeClaudeRequest {
   session_id: string;
   prompt: string;
   model: string;
+  /** Override the server's skip_permissions default for this session */
+  skip_permissions?: boolean;
+  /** Directory Claude runs in; defaults to project_path */
+  working_dir?: string;
+  /** Only start the session if this command exits with the expected code */
+  precondition?: SessionPrecondition;
+  /** Settings overlay written to a temporary settings.json and passed with --settings */
+  settings?: Record<string, any>;
+  /** Cancel the session after this many milliseconds */
+  timeout_ms?: number;
+  /** Cancel the session when an output line matches one of these regular expressions */
+  abort_patterns?: string[];
+  /** Drop buffered output lines read longer ago than this many milliseconds */
+  output_retention_ms?: number;
+  /** Override the server's verbose default (passes or omits --verbose) */
+  verbose?: boolean;
+  /** Additional Claude CLI arguments, appended after the ones the server sets */
+  extra_args?: string[];
+  /** File the final result text is written to when the session completes, relative to project_path */
+  output_result_to?: string;
+  /** Friendly name usable instead of the session id in every endpoint; unique among running sessions */
+  name?: string;
+  /** Labels attached to the session; they override labels derived by the server's label_rules */
+  labels?: Record<string, string>;
+  /** Directories outside the project Claude may access, passed as --add-dir; relative to project_path */
+  additional_dirs?: string[];
+  /** Relative directory under `<data_dir>/sessions` the session's record and output log are written to */
+  output_subdir?: string;
+  /** Record `git diff` and `git status` of the project when the session ends */
+  capture_git_diff?: boolean;
+  /** Set to false to send the prompt without the server's prompt_prefix and prompt_suffix */
+  apply_prompt_wrapping?: boolean;
+}
+
+/**
+ * Price of a model in USD per million tokens
+ */
+export interface ModelPricing {
+  input_per_mtok: number;
+  output_per_mtok: number;
+}
+
+export interface EstimateRequest {
+  prompt: string;
+  model: string;
+  /** Files whose content will be part of the context, resolved against project_path */
+  context_files?: string[];
+  project_path?: string;
+  /** Output tokens to include in the cost estimate (default: 0) */
+  expected_output_tokens?: number;
+}
+
+/**
+ * Body of POST /api/claude/cancel/:sessionId; empty for an immediate cancel
+ */
+export interface CancelClaudeRequest {
+  /** Close Claude's stdin so it can finish the current turn, and kill it only after grace_ms */
+  soft?: boolean;
+  /** How long a soft cancel waits before killing (default: soft_cancel_grace_ms) */
+  grace_ms?: number;
+  /** Keep the partial output (default: true); false clears the buffer and the on-disk log once cancelled */
+  keep_output?: boolean;
+}
+
+/**
+ * Body of POST /api/claude/sessions/:sessionId/restart
+ */
+export interface RestartSessionRequest {
+  /** Prompt sent with `--resume` (default: ask Claude to continue where it left off) */
+  prompt?: string;
+}
+
+export interface CompareRequest {
+  /** The two Claude binaries to run the prompt against; each must be listed in compare_binaries */
+  binaries: string[];
+  prompt: string;
+  model: string;
+  /** Cancel a run after this many milliseconds (default: 300000) */
+  timeout_ms?: number;
+}
+
+/**
+ * One side of a comparison: how a binary ran the prompt
+ */
+export interface CompareRun {
+  binary: string;
+  session_id: string;
+  status: SessionStatus;
+  exit_code?: number | null;
+  duration_ms: number;
+  result?: SessionResult;
+  output: OutputLine[];
 }
 
 /**
  * WebSocket message types
  */
 export interface WebSocketMessage {
-  type: 'subscribe' | 'unsubscribe' | 'claude_stream' | 'error' | 'status';
+  type:
+    | 'subscribe'
+    | 'unsubscribe'
+    | 'subscribe_many'
+    | 'unsubscribe_many'
+    | 'subscribe_all'
+    | 'unsubscribe_all'
+    | 'update_session'
+    | 'get_sessions'
+    | 'subscribe_stats'
+    | 'unsubscribe_stats'
+    | 'sessions'
+    | 'stats'
+    | 'claude_stream'
+    | 'session_event'
+    | 'error'
+    | 'status';
   data?: any;
   session_id?: string;
+  /** Sessions for subscribe_many / unsubscribe_many */
+  session_ids?: string[];
+  /** Seconds between `stats` messages for subscribe_stats */
+  interval_seconds?: number;
+  /** Optional client-chosen ID, echoed on the reply (or error) to a client message */
+  request_id?: string;
   timestamp: string;
 }
 
+/**
+ * A single host/port pair the server listens on
+ */
+export interface ListenAddress {
+  host: string;
+  port: number;
+}
+
 /**
  * Server configuration
  */
 export interface ServerConfig {
   port: number;
   host: string;
+  /** Every address the server binds; defaults to the single `host`/`port` pair */
+  listen: ListenAddress[];
+  /**
+   * Addresses serving only the `/ws` WebSocket endpoint. When any are set, the `listen`
+   * addresses serve only the REST API (default: none, `/ws` is served on every `listen` address)
+   */
+  ws_listen: ListenAddress[];
   cors_origin: string[];
   max_concurrent_sessions: number;
+  /** Maximum sessions running at once in a single project (0 = unlimited) */
+  max_concurrent_per_project: number;
+  /** Refuse a session whose project already has one running, instead of warning (default: false) */
+  reject_concurrent_same_project: boolean;
+  /**
+   * Maximum sessions running at once per `X-API-Key` header value (0 = unlimited). While set,
+   * starts without the header are refused; keys are not authenticated.
+   */
+  per_key_session_limit: number;
+  /** Maximum WebSocket clients connected at once; further upgrades get 503 (0 = unlimited) */
+  max_ws_connections: number;
+  /** Close WebSocket clients with no messages or subscriptions for this long (0 = never) */
+  ws_idle_timeout_seconds: number;
   session_timeout_ms: number;
   claude_binary_path?: string;
   claude_home_dir?: string;
+  /** Command sessions are run under, followed by the Claude binary and its arguments (default: none) */
+  wrapper_command: string[];
+  /** Pass --dangerously-skip-permissions unless a request opts out (default: true) */
+  skip_permissions: boolean;
+  /**
+   * Kill running Claude processes when the server stops (default: true). When false, sessions
+   * write their output to files under data_dir and the next run adopts them; requires data_dir.
+   */
+  kill_on_drop: boolean;
+  /** Terminate untracked Claude session processes found at startup (default: false) */
+  reap_orphans_on_start: boolean;
+  /** How output that is not valid UTF-8 is rendered: `replace` or `latin1` (default: replace) */
+  output_charset_fallback: 'replace' | 'latin1';
+  /** Mirror session output to the server's stdout, prefixed with the session ID (default: false) */
+  tee_output_to_stdout: boolean;
+  /** Regular expressions; matching output lines are dropped before they are buffered or broadcast */
+  output_filters: string[];
+  /** Count the lines output_filters dropped per session (default: false) */
+  count_filtered_output: boolean;
+  /** Time allowed to receive and answer a non-streaming API request, 0 to disable (default: 30000) */
+  request_timeout_ms: number;
+  /**
+   * Drop a connection that has not sent complete request headers, including a WebSocket
+   * upgrade on /ws, within this long; 0 to disable (default: 10000)
+   */
+  handshake_timeout_ms: number;
+  /** Largest accepted request body in bytes (default: 10 MiB) */
+  max_body_bytes: number;
+  /** Directory session records are persisted to; persistence is off when unset */
+  data_dir?: string;
+  /** Append persisted output lines to disk at least this often (default: 1000) */
+  output_flush_interval_ms: number;
+  /** Append a session's persisted output as soon as this many lines are buffered (default: 100) */
+  output_flush_batch_lines: number;
+  /** Number of output lines kept in memory per session (default: 1000) */
+  max_output_lines: number;
+  /** Read output lines older than the in-memory ones back from the output log; requires data_dir (default: false) */
+  disk_backed_output: boolean;
+  /** Model to retry a session with, once, when its requested model reports overload (default: none) */
+  model_fallback: Record<string, string>;
+  /** Pricing used by /api/estimate, keyed by model name or family (default: list prices) */
+  model_pricing: Record<string, ModelPricing>;
+  /** Maximum session records kept in memory; oldest finished ones are evicted (0 = unlimited) */
+  max_stored_sessions: number;
+  /** Pass --verbose unless a request opts out (default: true) */
+  verbose: boolean;
+  /** Output preview refresh interval while a session produces output (default: 1000) */
+  preview_interval_min_ms: number;
+  /** Output preview refresh interval a quiet session backs off to (default: 16000) */
+  preview_interval_max_ms: number;
+  /** Only these flags may be passed in extra_args; unset allows any flag the server does not reserve */
+  allowed_extra_args?: string[];
+  /** Maximum Claude processes spawned per second; batch starts queue behind it (0 = unlimited) */
+  spawn_rate_limit: number;
+  /** Starts that may wait for spawn_rate_limit at once; further starts get 429 (0 = unbounded) */
+  max_queue_depth: number;
+  /** Refuse new sessions while serving queries and cancels; toggled at runtime via /api/admin/readonly */
+  read_only: boolean;
+  /** How long a `claude --version` or `--help` probe may run before it is killed (default: 5000) */
+  version_timeout_ms: number;
+  /** Memory limit per session in MiB via a cgroup v2 group on Linux; 0 = unlimited */
+  memory_limit_mb: number;
+  /**
+   * Server RSS in MiB above which the buffered output of the oldest finished sessions is
+   * dropped until back under it; 0 = off
+   */
+  memory_soft_limit_mb: number;
+  /** Parent cgroup the per-session groups are created under (default: /sys/fs/cgroup/claudia) */
+  cgroup_root?: string;
+  /** Record each session's project git branch and HEAD commit in its info (default: false) */
+  record_git_info: boolean;
+  /** Directories output_result_to may point into; unset allows only the session's project_path */
+  result_output_roots?: string[];
+  /** Directories POST /sessions/:id/input/file may read from; unset allows only the session's project_path */
+  input_file_roots?: string[];
+  /** Largest file POST /sessions/:id/input/file accepts (default: 1 MiB) */
+  max_input_file_bytes: number;
+  /** Directories a session's additional_dirs must lie under; unset allows any existing directory */
+  additional_dir_roots?: string[];
+  /** Resuming a session that is still running: `reject` with 409 or `attach` to it (default: reject) */
+  resume_running: 'reject' | 'attach';
+  /** Claude binaries POST /api/claude/compare may run; the endpoint is disabled when empty */
+  compare_binaries: string[];
+  /** Oldest Claude version the server works with, checked at startup (default: none) */
+  min_claude_version?: string;
+  /** What happens when Claude is older than min_claude_version or its version is unknown (default: refuse) */
+  min_claude_version_action: 'refuse' | 'warn';
+  /** Longest stdout line kept in bytes; longer lines are truncated (default: 10 MiB) */
+  max_line_bytes: number;
+  /** How long a soft cancel waits for Claude to exit before killing it (default: 10000) */
+  soft_cancel_grace_ms: number;
+  /** How long Claude may keep running after closing its stdout before it is killed (default: 10000) */
+  stdout_eof_grace_ms: number;
+  /** Cancel a session whose stdout averages more bytes/sec than this over 10 seconds; 0 = no limit */
+  max_output_bytes_per_sec: number;
+  /** URLs notified of session events; see WebhookConfig */
+  webhooks: WebhookConfig[];
+  /** Rules deriving session labels from the prompt; see LabelRule */
+  label_rules: LabelRule[];
+  /** Rules adding Claude arguments to sessions by their labels; see LabelArgRule */
+  label_arg_rules: LabelArgRule[];
+  /** Regular expressions (case-insensitive) that refuse a session start with 403 when the prompt matches */
+  prompt_denylist: string[];
+  /** Text put before every prompt, unless a request sets apply_prompt_wrapping to false */
+  prompt_prefix?: string;
+  /** Text put after every prompt, unless a request sets apply_prompt_wrapping to false */
+  prompt_suffix?: string;
+  /** File the server's log is written to, followed by GET /api/logs/tail; unset disables both */
+  log_file?: string;
+  /**
+   * Bearer token required by GET /api/logs/tail; unset keeps that endpoint off. Best set with
+   * CLAUDIA_ADMIN_TOKEN, which other users cannot read from the process list.
+   */
+  admin_token?: string;
+  /**
+   * File written once every listener is bound and removed on shutdown, for supervisors to poll
+   * (default: `<data_dir>/ready` when data_dir is set, otherwise none)
+   */
+  ready_file?: string;
+}
+
+/**
+ * A usage example served by GET /api/examples, rendered for the server's address
+ */
+export interface ApiExample {
+  name: string;
+  description: string;
+  curl: string;
+  javascript: string;
 }
 
 /**
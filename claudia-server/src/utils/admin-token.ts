@@ -0,0 +1,40 @@
+import { createHash, timingSafeEqual } from 'crypto';
+import type { RequestHandler } from 'express';
+import type { ErrorResponse } from '../types/index.js';
+
+/**
+ * Guard a route with the configured admin_token, sent as `Authorization: Bearer <token>`.
+ *
+ * Without an admin_token the route stays off and answers 403 with code
+ * `ADMIN_TOKEN_NOT_CONFIGURED`; a missing or wrong token answers 401 with code `ADMIN_TOKEN_INVALID`.
+ */
+export function requireAdminToken(adminToken: string | undefined): RequestHandler {
+  const expected = adminToken ? digest(adminToken) : undefined;
+
+  return (req, res, next) => {
+    if (!expected) {
+      const errorResponse: ErrorResponse = {
+        error: 'This endpoint is disabled until an admin token is configured (admin_token or CLAUDIA_ADMIN_TOKEN)',
+        code: 'ADMIN_TOKEN_NOT_CONFIGURED',
+        timestamp: new Date().toISOString(),
+      };
+      return res.status(403).json(errorResponse);
+    }
+
+    const match = /^Bearer (.+)$/.exec(req.get('authorization') ?? '');
+    // Comparing fixed-length digests keeps the comparison time independent of the token
+    if (!match || !timingSafeEqual(digest(match[1]), expected)) {
+      const errorResponse: ErrorResponse = {
+        error: 'A valid admin token is required',
+        code: 'ADMIN_TOKEN_INVALID',
+        timestamp: new Date().toISOString(),
+      };
+      return res.status(401).set('WWW-Authenticate', 'Bearer').json(errorResponse);
+    }
+    next();
+  };
+}
+
+function digest(token: string): Buffer {
+  return createHash('sha256').update(token).digest();
+}
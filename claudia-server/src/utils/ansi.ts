@@ -0,0 +1,45 @@
+/**
+ * Removing ANSI terminal escape sequences from process output
+ */
+
+const ESC = '\x1b';
+const BEL = '\x07';
+
+/**
+ * Remove ANSI escape sequences: CSI sequences such as colors and cursor movement
+ * (`ESC [ ... final`), OSC sequences such as window titles and hyperlinks (`ESC ] ... BEL` or
+ * `ESC ] ... ESC \`), and two-character escapes. An unterminated sequence at the end of the
+ * text is dropped.
+ */
+export function stripAnsi(text: string): string {
+  if (!text.includes(ESC) && !text.includes('\x9b')) {
+    return text;
+  }
+
+  let result = '';
+  let i = 0;
+  while (i < text.length) {
+    const char = text[i];
+    if (char === '\x9b' || (char === ESC && text[i + 1] === '[')) {
+      // CSI: parameter and intermediate bytes, then one final byte in @ to ~
+      i += char === ESC ? 2 : 1;
+      while (i < text.length && !(text[i] >= '@' && text[i] <= '~')) {
+        i++;
+      }
+      i++;
+    } else if (char === ESC && text[i + 1] === ']') {
+      // OSC: runs to BEL or to the string terminator ESC \
+      i += 2;
+      while (i < text.length && text[i] !== BEL && !(text[i] === ESC && text[i + 1] === '\\')) {
+        i++;
+      }
+      i += text[i] === BEL ? 1 : 2;
+    } else if (char === ESC) {
+      i += 2;
+    } else {
+      result += char;
+      i++;
+    }
+  }
+  return result;
+}
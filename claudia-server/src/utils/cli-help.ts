@@ -0,0 +1,37 @@
+import type { ClaudeCliFlag } from '../types/index.js';
+
+/**
+ * An option line in commander-style help output:
+ * `  -p, --print                 Print response and exit` or
+ * `  --output-format <format>    Output format ...`
+ */
+const FLAG_LINE = /^\s+(?:(-[A-Za-z0-9]),\s+)?(--[A-Za-z0-9][\w-]*)((?:\s+(?:<[^>]+>|\[[^\]]+\]))*)(?:\s{2,}(.*))?$/;
+
+/**
+ * Extract the flags listed in `claude --help` output. Description lines that wrap
+ * onto the next line are joined; subcommands and section headers are ignored.
+ */
+export function parseHelpFlags(helpText: string): ClaudeCliFlag[] {
+  const flags: ClaudeCliFlag[] = [];
+  let current: ClaudeCliFlag | undefined;
+
+  for (const line of helpText.split(/\r?\n/)) {
+    const match = line.match(FLAG_LINE);
+    if (match) {
+      const [, short, name, argument, description] = match;
+      current = {
+        name,
+        ...(short ? { short } : {}),
+        ...(argument.trim() ? { argument: argument.trim() } : {}),
+        description: description?.trim() ?? '',
+      };
+      flags.push(current);
+    } else if (current && /^\s{4,}\S/.test(line)) {
+      current.description = `${current.description} ${line.trim()}`.trim();
+    } else {
+      current = undefined;
+    }
+  }
+
+  return flags;
+}
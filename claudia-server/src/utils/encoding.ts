@@ -0,0 +1,34 @@
+/**
+ * How bytes that are not valid UTF-8 are rendered as text
+ * - `replace`: decode as UTF-8 with U+FFFD replacement characters
+ * - `latin1`: decode every byte as ISO-8859-1 so no byte is lost
+ */
+export type CharsetFallback = 'replace' | 'latin1';
+
+/**
+ * A decoded chunk of process output
+ */
+export interface DecodedText {
+  text: string;
+  /** Set when the bytes were not valid UTF-8 */
+  invalid_utf8?: true;
+  /** The original bytes, base64-encoded, when they were not valid UTF-8 */
+  raw_base64?: string;
+}
+
+const strictUtf8 = new TextDecoder('utf-8', { fatal: true });
+
+/**
+ * Decode process output, tagging invalid UTF-8 instead of silently mangling it
+ */
+export function decodeOutput(bytes: Buffer, fallback: CharsetFallback = 'replace'): DecodedText {
+  try {
+    return { text: strictUtf8.decode(bytes) };
+  } catch {
+    return {
+      text: bytes.toString(fallback === 'latin1' ? 'latin1' : 'utf8'),
+      invalid_utf8: true,
+      raw_base64: bytes.toString('base64'),
+    };
+  }
+}
@@ -0,0 +1,47 @@
+import type { ErrorResponse } from '../types/index.js';
+
+/**
+ * An error raised by a service that maps to a specific HTTP status and error code
+ */
+export class ServiceError extends Error {
+  constructor(
+    message: string,
+    public readonly code: string,
+    public readonly status: number,
+    public readonly details?: any
+  ) {
+    super(message);
+    this.name = 'ServiceError';
+  }
+}
+
+/**
+ * Build the HTTP status and ErrorResponse body for an error caught in a route.
+ * ServiceErrors keep their own status and code; anything else becomes a 500 with `fallbackCode`.
+ */
+export function toErrorResponse(
+  error: unknown,
+  fallbackCode: string,
+  fallbackStatus = 500
+): { status: number; body: ErrorResponse } {
+  if (error instanceof ServiceError) {
+    return {
+      status: error.status,
+      body: {
+        error: error.message,
+        code: error.code,
+        timestamp: new Date().toISOString(),
+        details: error.details,
+      },
+    };
+  }
+
+  return {
+    status: fallbackStatus,
+    body: {
+      error: error instanceof Error ? error.message : 'Unknown error',
+      code: fallbackCode,
+      timestamp: new Date().toISOString(),
+    },
+  };
+}
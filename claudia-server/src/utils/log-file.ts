@@ -0,0 +1,98 @@
+/**
+ * Helpers for the server's own log file
+ */
+
+import { createWriteStream, promises as fs } from 'fs';
+import { format } from 'util';
+import { StringDecoder } from 'string_decoder';
+
+/**
+ * How often a followed file is checked for appended data
+ */
+const FOLLOW_INTERVAL_MS = 250;
+
+/**
+ * Largest chunk read from a followed file at once
+ */
+const FOLLOW_CHUNK_BYTES = 64 * 1024;
+
+/**
+ * Append everything written through console.log, info, warn and error to a file as well,
+ * each line prefixed with a timestamp
+ */
+export function teeConsoleToFile(path: string): void {
+  const file = createWriteStream(path, { flags: 'a' });
+  file.on('error', (error) => {
+    process.stderr.write(`⚠️  Cannot write log file ${path}: ${error.message}\n`);
+  });
+
+  for (const method of ['log', 'info', 'warn', 'error'] as const) {
+    const original = console[method].bind(console);
+    console[method] = (...args: unknown[]) => {
+      original(...args);
+      const timestamp = new Date().toISOString();
+      const text = format(...args).split('\n').map((line) => `${timestamp} ${line}`).join('\n');
+      file.write(`${text}\n`);
+    };
+  }
+}
+
+/**
+ * Call `onLine` for every complete line appended to a file from now on. A file that shrinks
+ * (truncated or rotated) is read again from its start.
+ *
+ * @returns A function that stops following the file.
+ */
+export async function followFile(path: string, onLine: (line: string) => void): Promise<() => void> {
+  let offset = await fs.stat(path).then((stats) => stats.size, () => 0);
+  let decoder = new StringDecoder('utf8');
+  let pending = '';
+  let reading = false;
+
+  const poll = async () => {
+    if (reading) {
+      return;
+    }
+    reading = true;
+    try {
+      const { size } = await fs.stat(path);
+      if (size < offset) {
+        offset = 0;
+        decoder = new StringDecoder('utf8');
+        pending = '';
+      }
+      if (size === offset) {
+        return;
+      }
+
+      const handle = await fs.open(path, 'r');
+      try {
+        const buffer = Buffer.alloc(FOLLOW_CHUNK_BYTES);
+        while (offset < size) {
+          const { bytesRead } = await handle.read(buffer, 0, Math.min(buffer.length, size - offset), offset);
+          if (bytesRead === 0) {
+            break;
+          }
+          offset += bytesRead;
+          pending += decoder.write(buffer.subarray(0, bytesRead));
+        }
+      } finally {
+        await handle.close();
+      }
+
+      const lines = pending.split('\n');
+      pending = lines.pop() ?? '';
+      for (const line of lines) {
+        onLine(line.replace(/\r$/, ''));
+      }
+    } catch {
+      // The file is missing for now, e.g. mid-rotation; try again on the next poll
+    } finally {
+      reading = false;
+    }
+  };
+
+  const timer = setInterval(() => void poll(), FOLLOW_INTERVAL_MS);
+  timer.unref();
+  return () => clearInterval(timer);
+}
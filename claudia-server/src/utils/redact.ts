@@ -0,0 +1,17 @@
+/**
+ * Environment variable names whose values are treated as secrets
+ */
+const SECRET_NAME = /KEY|TOKEN|SECRET|PASSW(OR)?D|CREDENTIAL|AUTH|COOKIE|PRIVATE/i;
+
+/**
+ * Copy of an environment with the values of secret-looking variables replaced
+ */
+export function redactEnv(env: NodeJS.ProcessEnv): Record<string, string> {
+  const redacted: Record<string, string> = {};
+  for (const [name, value] of Object.entries(env)) {
+    if (value !== undefined) {
+      redacted[name] = SECRET_NAME.test(name) ? '[REDACTED]' : value;
+    }
+  }
+  return redacted;
+}
@@ -0,0 +1,23 @@
+/**
+ * Characters that never need quoting in a POSIX shell word
+ */
+const SAFE_WORD = /^[A-Za-z0-9_\-+=.,/:@%]+$/;
+
+/**
+ * Quote one argument for a POSIX shell, leaving plain words as they are
+ */
+export function shellQuote(arg: string): string {
+  if (SAFE_WORD.test(arg)) {
+    return arg;
+  }
+  return `'${arg.replace(/'/g, `'\\''`)}'`;
+}
+
+/**
+ * Render a command as one line that can be pasted into a shell: change to `cwd`, then run
+ * `argv` with `env` set as `VAR=value` prefixes
+ */
+export function shellCommand(argv: string[], cwd: string, env: Record<string, string> = {}): string {
+  const assignments = Object.entries(env).map(([name, value]) => `${name}=${shellQuote(value)}`);
+  return `cd ${shellQuote(cwd)} && ${[...assignments, ...argv.map(shellQuote)].join(' ')}`;
+}
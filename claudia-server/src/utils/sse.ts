@@ -0,0 +1,51 @@
+import type { Request, Response } from 'express';
+
+/**
+ * Writer for a single Server-Sent Events response
+ */
+export interface EventStream {
+  /** Send one event; `data` is JSON-encoded */
+  send(event: string, data: unknown): void;
+  /** End the response */
+  close(): void;
+}
+
+/**
+ * Switch a response into `text/event-stream` mode.
+ *
+ * Every event is flushed immediately so the compression middleware does not hold it back.
+ * `onClose` runs once when the client disconnects, which is where listeners should be removed.
+ */
+export function openEventStream(req: Request, res: Response, onClose: () => void): EventStream {
+  res.status(200);
+  res.setHeader('Content-Type', 'text/event-stream');
+  res.setHeader('Cache-Control', 'no-cache');
+  res.setHeader('Connection', 'keep-alive');
+  res.setHeader('X-Accel-Buffering', 'no');
+  res.flushHeaders();
+
+  let closed = false;
+  req.on('close', () => {
+    if (!closed) {
+      closed = true;
+      onClose();
+    }
+  });
+
+  return {
+    send(event: string, data: unknown): void {
+      if (closed) {
+        return;
+      }
+      res.write(`event: ${event}\ndata: ${JSON.stringify(data)}\n\n`);
+      res.flush();
+    },
+    close(): void {
+      if (!closed) {
+        closed = true;
+        onClose();
+        res.end();
+      }
+    },
+  };
+}
@@ -0,0 +1,194 @@
+/**
+ * Helpers for reading Claude's `--output-format stream-json` messages
+ */
+
+import type { OutputLine } from '../types/index.js';
+
+/**
+ * A content block inside an assistant or user message
+ */
+export interface ContentBlock {
+  type: string;
+  text?: string;
+  id?: string;
+  name?: string;
+  input?: any;
+  tool_use_id?: string;
+  content?: any;
+  is_error?: boolean;
+}
+
+/**
+ * The content blocks of an `assistant` or `user` message, or an empty list
+ */
+export function contentBlocksOf(message: any): ContentBlock[] {
+  const content = message?.message?.content;
+  if (Array.isArray(content)) {
+    return content.filter((block) => block && typeof block.type === 'string');
+  }
+  if (typeof content === 'string') {
+    return [{ type: 'text', text: content }];
+  }
+  return [];
+}
+
+/**
+ * The human-readable text of a message: assistant text blocks or the final result
+ */
+export function textOf(message: any): string {
+  if (message?.type === 'assistant') {
+    return contentBlocksOf(message)
+      .filter((block) => block.type === 'text' && typeof block.text === 'string')
+      .map((block) => block.text as string)
+      .join('\n');
+  }
+  if (message?.type === 'result' && typeof message.result === 'string') {
+    return message.result;
+  }
+  return '';
+}
+
+/**
+ * One typed event derived from a stream-json message
+ */
+export interface TypedStreamEvent {
+  event: string;
+  data: unknown;
+}
+
+/**
+ * Split a stream-json message into typed events: the text of an `assistant` message, each of
+ * its `tool_use` blocks, the `tool_result` blocks of a `user` message, and the final `result`.
+ * Other message types (such as `system`) pass through under their own type.
+ */
+export function typedEventsOf(message: any): TypedStreamEvent[] {
+  const type = typeof message?.type === 'string' ? message.type : 'unknown';
+  const blocks = contentBlocksOf(message);
+
+  if (type === 'assistant') {
+    const events: TypedStreamEvent[] = [];
+    const content = blocks.filter((block) => block.type !== 'tool_use');
+    if (content.length > 0) {
+      events.push({ event: 'assistant', data: { ...message, message: { ...message.message, content } } });
+    }
+    for (const block of blocks.filter((block) => block.type === 'tool_use')) {
+      events.push({ event: 'tool_use', data: { id: block.id, name: block.name, input: block.input } });
+    }
+    return events;
+  }
+
+  if (type === 'user') {
+    const results = blocks.filter((block) => block.type === 'tool_result');
+    if (results.length > 0) {
+      return results.map((block) => ({
+        event: 'tool_result',
+        data: { tool_use_id: block.tool_use_id, content: block.content, is_error: block.is_error ?? false },
+      }));
+    }
+  }
+
+  return [{ event: type, data: message }];
+}
+
+/**
+ * Rebuild the typed events of buffered output lines. Stdout lines that are not JSON objects and
+ * all stderr lines become `raw` events carrying their stream, content and read time; messages
+ * carry their read time as `timestamp`, as they did when first streamed.
+ */
+export function typedEventsOfOutput(lines: OutputLine[]): TypedStreamEvent[] {
+  const events: TypedStreamEvent[] = [];
+  for (const line of lines) {
+    const { stream, content, timestamp } = line;
+    let message: unknown;
+    try {
+      message = stream === 'stdout' ? JSON.parse(content) : undefined;
+    } catch {
+      // Not JSON; sent as raw output below
+    }
+    if (!message || typeof message !== 'object') {
+      events.push({ event: 'raw', data: { stream, content, timestamp } });
+      continue;
+    }
+    events.push(...typedEventsOf({ ...message, timestamp }));
+  }
+  return events;
+}
+
+/**
+ * Render buffered output lines as a Markdown transcript: assistant text and the final result as
+ * prose, tool calls and their results as fenced blocks, and output that is not stream-json as
+ * fenced text. System messages are left out.
+ */
+export function markdownOfOutput(lines: OutputLine[]): string {
+  const sections: string[] = [];
+  let raw: string[] = [];
+  const flushRaw = () => {
+    if (raw.length > 0) {
+      sections.push(fenced(raw.join('\n'), 'text'));
+      raw = [];
+    }
+  };
+
+  for (const { event, data } of typedEventsOfOutput(lines)) {
+    const message = data as any;
+    if (event === 'raw') {
+      raw.push(message.content);
+      continue;
+    }
+    flushRaw();
+
+    switch (event) {
+      case 'assistant':
+      case 'user': {
+        const text = contentBlocksOf(message)
+          .filter((block) => block.type === 'text' && typeof block.text === 'string')
+          .map((block) => block.text as string)
+          .join('\n');
+        if (text) {
+          sections.push(`**${event === 'assistant' ? 'Assistant' : 'User'}:**\n\n${text}`);
+        }
+        break;
+      }
+      case 'tool_use':
+        sections.push(`**Tool call: ${message.name}**\n\n${fenced(JSON.stringify(message.input ?? {}, null, 2), 'json')}`);
+        break;
+      case 'tool_result': {
+        const label = message.is_error ? '**Tool error:**' : '**Tool result:**';
+        sections.push(`${label}\n\n${fenced(toolResultText(message.content), '')}`);
+        break;
+      }
+      case 'result':
+        if (typeof message.result === 'string' && message.result) {
+          sections.push(`**Result:**\n\n${message.result}`);
+        }
+        break;
+    }
+  }
+  flushRaw();
+
+  return sections.length > 0 ? `${sections.join('\n\n')}\n` : '';
+}
+
+/**
+ * The text of a tool_result block's content, which is a string or a list of content blocks
+ */
+function toolResultText(content: unknown): string {
+  if (typeof content === 'string') {
+    return content;
+  }
+  if (Array.isArray(content)) {
+    return content
+      .map((block) => (block?.type === 'text' && typeof block.text === 'string' ? block.text : JSON.stringify(block)))
+      .join('\n');
+  }
+  return content === undefined ? '' : JSON.stringify(content, null, 2);
+}
+
+/**
+ * Wrap text in a code fence longer than any backtick run inside it
+ */
+function fenced(text: string, language: string): string {
+  const longestRun = Math.max(0, ...(text.match(/`+/g) ?? []).map((run) => run.length));
+  const fence = '`'.repeat(Math.max(3, longestRun + 1));
+  return `${fence}${language}\n${text}\n${fence}`;
+}
@@ -0,0 +1,43 @@
+import type { ModelPricing } from '../types/index.js';
+
+/**
+ * Average characters per token for English prose and code with Claude's tokenizer
+ */
+const CHARS_PER_TOKEN = 3.5;
+
+/**
+ * Published list prices (USD per million tokens), matched by model family
+ */
+export const DEFAULT_MODEL_PRICING: Record<string, ModelPricing> = {
+  opus: { input_per_mtok: 15, output_per_mtok: 75 },
+  sonnet: { input_per_mtok: 3, output_per_mtok: 15 },
+  haiku: { input_per_mtok: 0.8, output_per_mtok: 4 },
+};
+
+/**
+ * Rough token count for a piece of text. This is a character-based heuristic,
+ * not Claude's tokenizer, and is only meant for budgeting.
+ */
+export function estimateTokens(text: string): number {
+  if (!text) {
+    return 0;
+  }
+  return Math.ceil(text.length / CHARS_PER_TOKEN);
+}
+
+/**
+ * Find the pricing for a model: an exact entry in `pricing` first, then the first
+ * family name (`opus`, `sonnet`, ...) contained in the model name.
+ */
+export function pricingFor(
+  model: string,
+  pricing: Record<string, ModelPricing> = DEFAULT_MODEL_PRICING
+): ModelPricing | undefined {
+  if (pricing[model]) {
+    return pricing[model];
+  }
+
+  const name = model.toLowerCase();
+  const family = Object.keys(pricing).find((key) => name.includes(key.toLowerCase()));
+  return family ? pricing[family] : undefined;
+}
@@ -0,0 +1,28 @@
+/**
+ * Numeric parts of a dotted version; a part like `17-beta` counts as its leading digits
+ */
+function versionParts(version: string): number[] {
+  return version
+    .split('.')
+    .map((part) => /^\d+/.exec(part)?.[0])
+    .filter((digits): digits is string => digits !== undefined)
+    .map(Number);
+}
+
+/**
+ * Compare two dotted versions part by part, so `1.0.10` is newer than `1.0.9`. Missing parts
+ * count as 0.
+ *
+ * @returns A negative number, 0, or a positive number as `a` is older than, equal to, or newer than `b`.
+ */
+export function compareVersions(a: string, b: string): number {
+  const aParts = versionParts(a);
+  const bParts = versionParts(b);
+  for (let i = 0; i < Math.max(aParts.length, bParts.length); i++) {
+    const diff = (aParts[i] ?? 0) - (bParts[i] ?? 0);
+    if (diff !== 0) {
+      return diff;
+    }
+  }
+  return 0;
+}
@@ -6,8 +6,19 @@ use std::cmp::Ordering;
 /// Supports NVM installations, aliased paths, and version-based selection
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tauri::Manager;
 
+/// How long a `which claude` result is reused before the lookup runs again
+const WHICH_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Environment variable overriding WHICH_CACHE_TTL, in seconds (0 disables the cache)
+const WHICH_CACHE_TTL_ENV: &str = "CLAUDIA_WHICH_CACHE_TTL_SECS";
+
+/// The last `which claude` result, shared by every discovery
+static WHICH_CACHE: Mutex<WhichCache> = Mutex::new(WhichCache::new());
+
 /// Type of Claude installation
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum InstallationType {
@@ -147,7 +158,7 @@ fn discover_system_installations() -> Vec<ClaudeInstallation> {
     let mut installations = Vec::new();
 
     // 1. Try 'which' command first (now works in production)
-    if let Some(installation) = try_which_command() {
+    if let Some(installation) = cached_which_command() {
         installations.push(installation);
     }
 
@@ -164,27 +175,91 @@ fn discover_system_installations() -> Vec<ClaudeInstallation> {
     installations
 }
 
+/// A cached `which claude` lookup, so that an alias is not resolved again on every discovery
+/// but a changed alias or install is still picked up. Only a found installation is cached, so a
+/// Claude installed after a failed lookup is found on the next discovery.
+struct WhichCache {
+    entry: Option<(Instant, ClaudeInstallation)>,
+}
+
+impl WhichCache {
+    const fn new() -> Self {
+        Self { entry: None }
+    }
+
+    /// Return the cached installation while it is younger than `ttl` and its path still
+    /// exists; otherwise run `resolve` and cache what it finds
+    fn get_or_resolve(
+        &mut self,
+        now: Instant,
+        ttl: Duration,
+        resolve: impl FnOnce() -> Option<ClaudeInstallation>,
+    ) -> Option<ClaudeInstallation> {
+        if let Some((resolved_at, installation)) = &self.entry {
+            let fresh = now.saturating_duration_since(*resolved_at) < ttl;
+            if fresh && PathBuf::from(&installation.path).exists() {
+                return Some(installation.clone());
+            }
+            debug!("Cached 'which claude' result is stale, resolving again");
+        }
+
+        let installation = resolve();
+        self.entry = installation.clone().map(|installation| (now, installation));
+        installation
+    }
+
+    fn invalidate(&mut self) {
+        self.entry = None;
+    }
+}
+
+/// Forget the cached `which claude` result, so the next discovery resolves the alias again
+pub fn invalidate_which_cache() {
+    WHICH_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .invalidate();
+}
+
+/// The `which claude` result, reused for WHICH_CACHE_TTL (or CLAUDIA_WHICH_CACHE_TTL_SECS)
+fn cached_which_command() -> Option<ClaudeInstallation> {
+    let ttl = std::env::var(WHICH_CACHE_TTL_ENV)
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(WHICH_CACHE_TTL);
+
+    WHICH_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get_or_resolve(Instant::now(), ttl, try_which_command)
+}
+
+/// Extract the binary path from `which claude` output, following a shell alias
+fn parse_which_output(output: &str) -> Option<String> {
+    let output = output.trim();
+    if output.is_empty() {
+        return None;
+    }
+
+    // Parse aliased output: "claude: aliased to /path/to/claude"
+    if output.starts_with("claude:") && output.contains("aliased to") {
+        output
+            .split("aliased to")
+            .nth(1)
+            .map(|s| s.trim().to_string())
+    } else {
+        Some(output.to_string())
+    }
+}
+
 /// Try using the 'which' command to find Claude
 fn try_which_command() -> Option<ClaudeInstallation> {
     debug!("Trying 'which claude' to find binary...");
 
     match Command::new("which").arg("claude").output() {
         Ok(output) if output.status.success() => {
-            let output_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-
-            if output_str.is_empty() {
-                return None;
-            }
-
-            // Parse aliased output: "claude: aliased to /path/to/claude"
-            let path = if output_str.starts_with("claude:") && output_str.contains("aliased to") {
-                output_str
-                    .split("aliased to")
-                    .nth(1)
-                    .map(|s| s.trim().to_string())
-            } else {
-                Some(output_str)
-            }?;
+            let path = parse_which_output(&String::from_utf8_lossy(&output.stdout))?;
 
             debug!("'which' found claude at: {}", path);
 
@@ -519,3 +594,89 @@ pub fn create_command_with_env(program: &str) -> Command {
 
     cmd
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn which_installation(path: &std::path::Path) -> ClaudeInstallation {
+        ClaudeInstallation {
+            path: path.to_string_lossy().to_string(),
+            version: None,
+            source: "which".to_string(),
+            installation_type: InstallationType::System,
+        }
+    }
+
+    #[test]
+    fn test_parse_which_output_follows_alias() {
+        assert_eq!(
+            parse_which_output("claude: aliased to /opt/claude/bin/claude\n"),
+            Some("/opt/claude/bin/claude".to_string())
+        );
+        assert_eq!(
+            parse_which_output("/usr/local/bin/claude\n"),
+            Some("/usr/local/bin/claude".to_string())
+        );
+        assert_eq!(parse_which_output("  \n"), None);
+    }
+
+    #[test]
+    fn test_which_cache_picks_up_changed_alias_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_target = temp_dir.path().join("old-claude");
+        let new_target = temp_dir.path().join("new-claude");
+        std::fs::write(&old_target, "").unwrap();
+        std::fs::write(&new_target, "").unwrap();
+
+        let ttl = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut cache = WhichCache::new();
+
+        let first = cache.get_or_resolve(start, ttl, || Some(which_installation(&old_target)));
+        assert_eq!(first.unwrap().path, old_target.to_string_lossy());
+
+        // The alias now points elsewhere, but the cached result is still fresh
+        let cached = cache.get_or_resolve(start + Duration::from_secs(10), ttl, || {
+            Some(which_installation(&new_target))
+        });
+        assert_eq!(cached.unwrap().path, old_target.to_string_lossy());
+
+        // Once the TTL has passed the alias is resolved again
+        let expired =
+            cache.get_or_resolve(start + ttl, ttl, || Some(which_installation(&new_target)));
+        assert_eq!(expired.unwrap().path, new_target.to_string_lossy());
+
+        // An explicit refresh resolves again immediately
+        cache.invalidate();
+        let refreshed =
+            cache.get_or_resolve(start + ttl, ttl, || Some(which_installation(&old_target)));
+        assert_eq!(refreshed.unwrap().path, old_target.to_string_lossy());
+
+        // So does a cached target that was removed
+        std::fs::remove_file(&old_target).unwrap();
+        let revalidated =
+            cache.get_or_resolve(start + ttl, ttl, || Some(which_installation(&new_target)));
+        assert_eq!(revalidated.unwrap().path, new_target.to_string_lossy());
+    }
+
+    #[test]
+    fn test_which_cache_does_not_cache_a_failed_lookup() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("claude");
+        std::fs::write(&target, "").unwrap();
+
+        let ttl = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut cache = WhichCache::new();
+
+        assert!(cache.get_or_resolve(start, ttl, || None).is_none());
+
+        // Claude installed right after the failed lookup is found without waiting for the TTL
+        let found = cache.get_or_resolve(start + Duration::from_secs(1), ttl, || {
+            Some(which_installation(&target))
+        });
+        assert_eq!(found.unwrap().path, target.to_string_lossy());
+    }
+}
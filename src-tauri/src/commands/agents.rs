@@ -16,9 +16,14 @@ use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
 use tokio::process::Command;
 
 /// Finds the full path to the claude binary
-/// This is necessary because macOS apps have a limited PATH environment
-fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
-    crate::claude_binary::find_claude_binary(app_handle)
+/// This is necessary because macOS apps have a limited PATH environment.
+/// Discovery runs `which` and `--version` probes with blocking commands, so it runs on
+/// the blocking thread pool instead of stalling an async worker.
+async fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
+    let app_handle = app_handle.clone();
+    tokio::task::spawn_blocking(move || crate::claude_binary::find_claude_binary(&app_handle))
+        .await
+        .map_err(|e| format!("Claude binary discovery failed: {}", e))?
 }
 
 /// Represents a CC Agent stored in the database
@@ -744,7 +749,7 @@ pub async fn execute_agent(
 
     // Find Claude binary
     info!("Running agent '{}'", agent.name);
-    let claude_path = match find_claude_binary(&app) {
+    let claude_path = match find_claude_binary(&app).await {
         Ok(path) => path,
         Err(e) => {
             error!("Failed to find claude binary: {}", e);
@@ -1824,7 +1829,14 @@ pub async fn set_claude_binary_path(db: State<'_, AgentDb>, path: String) -> Res
 pub async fn list_claude_installations(
     _app: AppHandle,
 ) -> Result<Vec<crate::claude_binary::ClaudeInstallation>, String> {
-    let installations = crate::claude_binary::discover_claude_installations();
+    // Discovery runs blocking `which` and `--version` commands, so keep it off the async
+    // workers. Listing is the user's refresh, so re-resolve a `claude` alias that may have changed
+    let installations = tokio::task::spawn_blocking(|| {
+        crate::claude_binary::invalidate_which_cache();
+        crate::claude_binary::discover_claude_installations()
+    })
+    .await
+    .map_err(|e| format!("Claude installation discovery failed: {}", e))?;
 
     if installations.is_empty() {
         return Err("No Claude Code installations found on the system".to_string());
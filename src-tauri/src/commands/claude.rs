@@ -130,9 +130,14 @@ pub struct FileEntry {
 }
 
 /// Finds the full path to the claude binary
-/// This is necessary because macOS apps have a limited PATH environment
-fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
-    crate::claude_binary::find_claude_binary(app_handle)
+/// This is necessary because macOS apps have a limited PATH environment.
+/// Discovery runs `which` and `--version` probes with blocking commands, so it runs on
+/// the blocking thread pool instead of stalling an async worker.
+async fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
+    let app_handle = app_handle.clone();
+    tokio::task::spawn_blocking(move || crate::claude_binary::find_claude_binary(&app_handle))
+        .await
+        .map_err(|e| format!("Claude binary discovery failed: {}", e))?
 }
 
 /// Gets the path to the ~/.claude directory
@@ -580,10 +585,10 @@ pub async fn open_new_session(app: AppHandle, path: Option<String>) -> Result<St
     log::info!("Opening new Claude Code session at path: {:?}", path);
 
     #[cfg(not(debug_assertions))]
-    let _claude_path = find_claude_binary(&app)?;
+    let _claude_path = find_claude_binary(&app).await?;
 
     #[cfg(debug_assertions)]
-    let claude_path = find_claude_binary(&app)?;
+    let claude_path = find_claude_binary(&app).await?;
 
     // In production, we can't use std::process::Command directly
     // The user should launch Claude Code through other means or use the execute_claude_code command
@@ -637,7 +642,7 @@ pub async fn get_system_prompt() -> Result<String, String> {
 pub async fn check_claude_version(app: AppHandle) -> Result<ClaudeVersionStatus, String> {
     log::info!("Checking Claude Code version");
 
-    let claude_path = match find_claude_binary(&app) {
+    let claude_path = match find_claude_binary(&app).await {
         Ok(path) => path,
         Err(e) => {
             return Ok(ClaudeVersionStatus {
@@ -923,7 +928,7 @@ pub async fn execute_claude_code(
         model
     );
 
-    let claude_path = find_claude_binary(&app)?;
+    let claude_path = find_claude_binary(&app).await?;
     
     let args = vec![
         "-p".to_string(),
@@ -954,7 +959,7 @@ pub async fn continue_claude_code(
         model
     );
 
-    let claude_path = find_claude_binary(&app)?;
+    let claude_path = find_claude_binary(&app).await?;
     
     let args = vec![
         "-c".to_string(), // Continue flag
@@ -988,7 +993,7 @@ pub async fn resume_claude_code(
         model
     );
 
-    let claude_path = find_claude_binary(&app)?;
+    let claude_path = find_claude_binary(&app).await?;
     
     let args = vec![
         "--resume".to_string(),
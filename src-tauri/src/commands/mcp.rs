@@ -615,8 +615,13 @@ pub async fn mcp_add_from_claude_desktop(
 pub async fn mcp_serve(app: AppHandle) -> Result<String, String> {
     info!("Starting Claude Code as MCP server");
 
-    // Start the server in a separate process
-    let claude_path = match find_claude_binary(&app) {
+    // Start the server in a separate process; discovery blocks, so run it off the async workers
+    let lookup_app = app.clone();
+    let claude_path = match tokio::task::spawn_blocking(move || find_claude_binary(&lookup_app))
+        .await
+        .map_err(|e| anyhow::anyhow!("Claude binary discovery failed: {}", e))
+        .and_then(|found| found)
+    {
         Ok(path) => path,
         Err(e) => {
             error!("Failed to find claude binary: {}", e);